@@ -0,0 +1,91 @@
+//! Prints the squares of 0.5, 1.0, ..., 3.0 as 8.8 fixed-point hex values, computed on the
+//! computer with the fixed-point routines.
+
+use assembler::{
+    fixed::{emit_fixadd, emit_fixmul},
+    Instruction,
+    Register::{R0, R1, R2},
+};
+
+const X: u8 = 0x00;
+const STEP: u8 = 0x02;
+const ROWS: u8 = 0x04;
+const SQUARE: u8 = 0x06;
+const NIBBLE: u8 = 0x08;
+
+/// Prints the hex digit of the value in `R0`, clobbers `R0` to `R2`
+fn print_digit(program: &mut Vec<Instruction>, start: u8) {
+    // The digit is printed at `start + 12`, letters start at `start + 10`
+    program.extend([
+        Instruction::LoadConstant(R1, 10),
+        Instruction::Move(R2, R0),
+        Instruction::Sub(R2, R1),
+        Instruction::JumpIfOverflow(start + 10),
+        Instruction::LoadConstant(R1, b'0'),
+        Instruction::Jump(start + 12),
+        Instruction::LoadConstant(R1, b'A' - 10),
+        Instruction::Add(R0, R1),
+        Instruction::StoreMemory(R0, 255),
+    ]);
+}
+
+/// Prints the byte at the address as two hex digits
+fn print_byte(program: &mut Vec<Instruction>, address: u8) {
+    program.extend([
+        Instruction::LoadMemory(R0, address),
+        Instruction::StoreMemory(R0, NIBBLE),
+        Instruction::ShiftRightMemory(NIBBLE),
+        Instruction::ShiftRightMemory(NIBBLE),
+        Instruction::ShiftRightMemory(NIBBLE),
+        Instruction::ShiftRightMemory(NIBBLE),
+        Instruction::LoadMemory(R0, NIBBLE),
+    ]);
+    print_digit(program, length(program));
+    program.extend([
+        Instruction::LoadMemory(R0, address),
+        Instruction::LoadConstant(R1, 0x0F),
+        Instruction::And(R0, R1),
+    ]);
+    print_digit(program, length(program));
+}
+
+/// Returns the number of bytes the program takes
+fn length(program: &[Instruction]) -> u8 {
+    Instruction::to_bytes(program.iter().copied())
+        .len()
+        .try_into()
+        .unwrap()
+}
+
+fn main() {
+    let mut program = vec![
+        Instruction::LoadConstant(R0, 0x80),
+        Instruction::StoreMemory(R0, X),
+        Instruction::StoreMemory(R0, STEP),
+        Instruction::LoadConstant(R0, 6),
+        Instruction::StoreMemory(R0, ROWS),
+    ];
+    let row = length(&program);
+    emit_fixmul(&mut program, X, X, SQUARE);
+    print_byte(&mut program, SQUARE + 1);
+    print_byte(&mut program, SQUARE);
+    program.extend([
+        Instruction::LoadConstant(R0, b'\n'),
+        Instruction::StoreMemory(R0, 255),
+    ]);
+    emit_fixadd(&mut program, X, STEP, X);
+    let end = length(&program) + 11;
+    program.extend([
+        Instruction::LoadMemory(R0, ROWS),
+        Instruction::LoadConstant(R1, 1),
+        Instruction::Sub(R0, R1),
+        Instruction::StoreMemory(R0, ROWS),
+        Instruction::JumpIfZero(R0, end),
+        Instruction::Jump(row),
+    ]);
+
+    let bytes = Instruction::to_bytes(program);
+    let mut output = String::new();
+    michael_computer::alu(bytes, [], &mut output).unwrap();
+    print!("{output}");
+}
@@ -0,0 +1,116 @@
+; A monitor reading commands from the input at 254 and answering on the console at 255.
+;
+;   r           prints the registers the monitor started with as hex, like `00 11 22 33`
+;   m<addr>     prints the byte at the address, given as 2 hex digits
+;   w<addr><v>  writes the byte v at the address, both as 2 hex digits
+;
+; Newlines are skipped, any other byte prints `?`. The monitor halts once the input is exhausted.
+
+start:  st r0, [save0]          ; keep the registers for the r command
+        st r1, [save1]
+        st r2, [save2]
+        st r3, [save3]
+
+loop:   ld r0, [254]
+        jz r0, done
+        ldi r1, 0x72            ; r
+        mov r2, r0
+        sub r2, r1
+        jz r2, regs
+        ldi r1, 0x6D            ; m
+        mov r2, r0
+        sub r2, r1
+        jz r2, mem
+        ldi r1, 0x77            ; w
+        mov r2, r0
+        sub r2, r1
+        jz r2, write
+        ldi r1, 0x0A            ; newline
+        sub r0, r1
+        jz r0, loop
+        ldi r0, 0x3F            ; ?
+        st r0, [255]
+
+newline: ldi r0, 0x0A
+        st r0, [255]
+        jmp loop
+
+regs:   ldi r3, save0
+next:   ld r0, [r3]
+        call hex
+        inc r3
+        ldi r1, end
+        mov r2, r3
+        sub r2, r1
+        jz r2, newline
+        ldi r0, 0x20            ; space
+        st r0, [255]
+        jmp next
+
+mem:    call byte
+        ld r0, [r0]
+        call hex
+        jmp newline
+
+write:  call byte
+        push r0
+        call byte
+        pop r1
+        st r0, [r1]
+        jmp loop
+
+done:   hlt
+
+; Reads 2 hex digits into r0, clobbers r1 to r3
+byte:   ld r0, [254]
+        call digit
+        ldi r1, 16
+        mul r0, r1
+        mov r3, r0
+        ld r0, [254]
+        call digit
+        or r0, r3
+        ret
+
+; Converts the hex digit in r0 to its value, letters can be lowercase. Clobbers r1 and r2
+digit:  ldi r1, 0x3A            ; one past 9
+        mov r2, r0
+        sub r2, r1              ; no borrow for letters
+        jo letter
+        ldi r1, 0x30            ; 0
+        sub r0, r1
+        ret
+letter: ldi r1, 0xDF            ; clears the lowercase bit
+        and r0, r1
+        ldi r1, 0x37            ; A - 10
+        sub r0, r1
+        ret
+
+; Prints r0 as 2 hex digits, clobbers r0 to r2
+hex:    push r0
+        shr r0
+        shr r0
+        shr r0
+        shr r0
+        call nibble
+        pop r0
+        ldi r1, 0x0F
+        and r0, r1              ; falls through to print the low digit
+
+; Prints the hex digit of the value in r0, clobbers r0 to r2
+nibble: ldi r1, 10
+        mov r2, r0
+        sub r2, r1              ; no borrow from 10 on
+        jo letters
+        ldi r1, 0x30            ; 0
+        jmp print
+letters: ldi r1, 0x37           ; A - 10
+print:  add r0, r1
+        st r0, [255]
+        ret
+
+save0:  .byte 0
+save1:  .byte 0
+save2:  .byte 0
+save3:  .byte 0
+end:
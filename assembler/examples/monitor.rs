@@ -0,0 +1,16 @@
+//! Runs the monitor in `monitor.asm` on the commands read from the standard input.
+
+use std::io::{self, Read};
+
+use assembler::parser::Parser;
+
+fn main() {
+    let program = Parser::new()
+        .assemble(include_str!("monitor.asm"))
+        .unwrap_or_else(|error| panic!("monitor.asm: {error}"));
+    let mut input = Vec::new();
+    io::stdin().read_to_end(&mut input).unwrap();
+    let mut output = String::new();
+    michael_computer::alu_or_panic(program, input, &mut output);
+    print!("{output}");
+}
@@ -0,0 +1,327 @@
+//! Analysis of the memory addresses a program accesses, to keep memory optimizations safe.
+//!
+//! Optimizations like dead store elimination assume every memory access is visible in the literal
+//! address operands. That breaks as soon as a program addresses memory in any other way: a store
+//! that no literal load reads can still be read through a register. [`address_taken`] finds the
+//! first instruction that accesses memory at an address computed at run time, memory
+//! optimizations are disabled when it finds one. Optimizations that only involve the registers
+//! stay valid.
+//!
+//! ```
+//! use assembler::{addresses::dead_stores, Instruction, Register};
+//!
+//! let program = [
+//!     Instruction::StoreMemory(Register::R0, 0x40),
+//!     Instruction::StoreMemory(Register::R1, 0x40),
+//! ];
+//! assert!(dead_stores(&program).unwrap().eq([0]));
+//! ```
+
+use core::{
+    fmt::{self, Display, Formatter},
+    ops::RangeInclusive,
+};
+
+use crate::Instruction;
+
+/// How an instruction accesses memory at an address that isn't a literal operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressTaken {
+    /// A load, store or copy through an address held by a register
+    Indirect,
+
+    /// A push, pop, call or return accessing memory at the stack pointer
+    Stack,
+
+    /// A store or memory shift changing the bytes of the program, which can change the addresses
+    /// of other instructions
+    SelfModifying,
+}
+
+/// The reason memory optimizations are disabled for a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryDiagnostic {
+    /// The index of the first instruction accessing a computed address
+    pub index: usize,
+
+    /// How the instruction accesses memory
+    pub reason: AddressTaken,
+}
+
+impl Display for MemoryDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            AddressTaken::Indirect => "accesses memory through a register",
+            AddressTaken::Stack => "accesses memory at the stack pointer",
+            AddressTaken::SelfModifying => "modifies the program",
+        };
+        write!(
+            f,
+            "instruction {} {reason}, memory optimizations are disabled",
+            self.index
+        )
+    }
+}
+
+/// The addresses mapped to the input and the console by default, accesses of them are never
+/// removed
+const DEVICES: RangeInclusive<u8> = 254..=255;
+
+/// Returns the address the instruction writes with a literal operand
+const fn written(instruction: Instruction) -> Option<u8> {
+    match instruction {
+        Instruction::StoreMemory(_, address)
+        | Instruction::ShiftLeftMemory(address)
+        | Instruction::ShiftRightMemory(address)
+        | Instruction::RotateLeftMemory(address)
+        | Instruction::RotateRightMemory(address) => Some(address),
+        _ => None,
+    }
+}
+
+/// Returns the address the instruction reads or writes with a literal operand, if it isn't one of
+/// the devices
+#[cfg(feature = "alloc")]
+pub(crate) fn literal_address(instruction: Instruction) -> Option<u8> {
+    match instruction {
+        Instruction::LoadMemory(_, address) => Some(address),
+        instruction => written(instruction),
+    }
+    .filter(|address| !DEVICES.contains(address))
+}
+
+/// Returns the first instruction accessing memory at an address that isn't a literal operand, or
+/// `None` if every access of the program is visible in its operands
+#[must_use]
+pub fn address_taken(program: &[Instruction]) -> Option<MemoryDiagnostic> {
+    let length: usize = program
+        .iter()
+        .map(|instruction| instruction.encode().1)
+        .sum();
+    program
+        .iter()
+        .enumerate()
+        .find_map(|(index, &instruction)| {
+            let reason = match instruction {
+                Instruction::LoadIndirect(..)
+                | Instruction::StoreIndirect(..)
+                | Instruction::Copy(..) => AddressTaken::Indirect,
+                Instruction::Push(_)
+                | Instruction::Pop(_)
+                | Instruction::Call(_)
+                | Instruction::Return => AddressTaken::Stack,
+                _ => match written(instruction) {
+                    Some(address) if usize::from(address) < length => AddressTaken::SelfModifying,
+                    _ => return None,
+                },
+            };
+            Some(MemoryDiagnostic { index, reason })
+        })
+}
+
+/// Returns whether the store at the index is overwritten before anything can read it.
+///
+/// Only the literal operands are considered, so this is only correct for programs without
+/// computed addresses. The straight-line code after the store is scanned, the store stays live at
+/// any jump, call, return or halt and at the end of the program. It also stays live at a bank
+/// switch, since the same literal address then names a cell of another bank.
+fn overwritten(program: &[Instruction], index: usize) -> bool {
+    let Some(&Instruction::StoreMemory(_, address)) = program.get(index) else {
+        return false;
+    };
+    if DEVICES.contains(&address) {
+        return false;
+    }
+    for &instruction in &program[index + 1..] {
+        match instruction {
+            Instruction::StoreMemory(_, target) if target == address => return true,
+            Instruction::LoadMemory(_, source) if source == address => return false,
+            Instruction::Jump(_)
+            | Instruction::JumpIfOverflow(_)
+            | Instruction::JumpIfZero(..)
+            | Instruction::Call(_)
+            | Instruction::Return
+            | Instruction::SetBank(_)
+            | Instruction::Halt => return false,
+            _ if written(instruction) == Some(address) => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Returns the indices of the stores that can be removed because they are overwritten before
+/// anything reads them.
+///
+/// # Errors
+/// Returns the reason if the program accesses computed addresses, then no store is known to be
+/// dead
+pub fn dead_stores(
+    program: &[Instruction],
+) -> Result<impl Iterator<Item = usize> + '_, MemoryDiagnostic> {
+    if let Some(diagnostic) = address_taken(program) {
+        return Err(diagnostic);
+    }
+    Ok((0..program.len()).filter(|&index| overwritten(program, index)))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use heapless::String;
+    use michael_computer::equivalence::EquivalenceChecker;
+
+    use super::{address_taken, dead_stores, overwritten, AddressTaken, MemoryDiagnostic};
+    use crate::{Instruction, Register};
+
+    /// Encodes the program without the instructions at the indices
+    fn without(program: &[Instruction], removed: &[usize]) -> ([u8; 64], usize) {
+        let kept = program
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !removed.contains(index))
+            .map(|(_, &instruction)| instruction);
+        let mut buffer = [0; 64];
+        let length = Instruction::encode_into(kept, &mut buffer).unwrap();
+        (buffer, length)
+    }
+
+    #[test]
+    fn literal_dead_stores() {
+        let program = [
+            Instruction::LoadConstant(Register::R0, b'A'),
+            Instruction::StoreMemory(Register::R0, 0x40),
+            Instruction::StoreMemory(Register::R0, 0x41),
+            Instruction::LoadMemory(Register::R1, 0x41),
+            Instruction::StoreMemory(Register::R1, 0x40),
+            Instruction::StoreMemory(Register::R1, 0x41),
+            Instruction::StoreMemory(Register::R1, 255),
+            Instruction::StoreMemory(Register::R1, 255),
+        ];
+        assert_eq!(address_taken(&program), None);
+        // The store at 2 is read, the output is never dead
+        let mut dead = [0; 8];
+        let count = dead_stores(&program)
+            .unwrap()
+            .zip(&mut dead)
+            .map(|(index, slot)| *slot = index)
+            .count();
+        assert_eq!(dead[..count], [1]);
+
+        let (original, length) = without(&program, &[]);
+        let (optimized, optimized_length) = without(&program, &dead[..count]);
+        let checker = EquivalenceChecker::new();
+        assert_eq!(
+            checker.check(&original[..length], &optimized[..optimized_length]),
+            None
+        );
+    }
+
+    #[test]
+    fn indirect_read_disables_dead_stores() {
+        let program = [
+            Instruction::LoadConstant(Register::R0, 0x40),
+            Instruction::LoadConstant(Register::R1, b'A'),
+            Instruction::StoreMemory(Register::R1, 0x40),
+            Instruction::LoadIndirect(Register::R2, Register::R0),
+            Instruction::StoreMemory(Register::R2, 255),
+            Instruction::LoadConstant(Register::R1, b'B'),
+            Instruction::StoreMemory(Register::R1, 0x40),
+        ];
+        // Looking only at the literal addresses, the store at 2 seems dead
+        assert!(overwritten(&program, 2));
+        let (original, length) = without(&program, &[]);
+        let (naive, naive_length) = without(&program, &[2]);
+        let divergence =
+            EquivalenceChecker::new().check(&original[..length], &naive[..naive_length]);
+        assert!(
+            divergence.is_some(),
+            "removing the store changes the output"
+        );
+
+        let diagnostic = MemoryDiagnostic {
+            index: 3,
+            reason: AddressTaken::Indirect,
+        };
+        assert_eq!(dead_stores(&program).err(), Some(diagnostic));
+        let mut text = String::<96>::new();
+        write!(text, "{diagnostic}").unwrap();
+        assert_eq!(
+            text,
+            "instruction 3 accesses memory through a register, memory optimizations are disabled"
+        );
+    }
+
+    #[test]
+    fn bank_switch_keeps_stores() {
+        let program = [
+            Instruction::LoadConstant(Register::R0, b'A'),
+            Instruction::StoreMemory(Register::R0, 0x40),
+            Instruction::LoadConstant(Register::R1, 1),
+            Instruction::SetBank(Register::R1),
+            Instruction::LoadConstant(Register::R0, b'B'),
+            Instruction::StoreMemory(Register::R0, 0x40),
+            Instruction::LoadConstant(Register::R1, 0),
+            Instruction::SetBank(Register::R1),
+            Instruction::LoadMemory(Register::R2, 0x40),
+            Instruction::StoreMemory(Register::R2, 255),
+        ];
+        // The store at 5 is in bank 1, so it doesn't overwrite the one at 1, which is read back
+        assert_eq!(address_taken(&program), None);
+        assert!(!overwritten(&program, 1));
+        assert_eq!(dead_stores(&program).unwrap().next(), None);
+    }
+
+    #[test]
+    fn copy_disables_dead_stores() {
+        let program = [
+            Instruction::LoadConstant(Register::R0, b'A'),
+            Instruction::StoreMemory(Register::R0, 0x40),
+            Instruction::LoadConstant(Register::R1, 0x40),
+            Instruction::LoadConstant(Register::R2, 255),
+            Instruction::Copy(Register::R2, Register::R1),
+            Instruction::LoadConstant(Register::R0, b'B'),
+            Instruction::StoreMemory(Register::R0, 0x40),
+        ];
+        // The copy prints the stored byte, which the literal addresses don't show
+        assert!(overwritten(&program, 1));
+        let (original, length) = without(&program, &[]);
+        let (naive, naive_length) = without(&program, &[1]);
+        assert!(EquivalenceChecker::new()
+            .check(&original[..length], &naive[..naive_length])
+            .is_some());
+
+        assert_eq!(
+            dead_stores(&program).err(),
+            Some(MemoryDiagnostic {
+                index: 4,
+                reason: AddressTaken::Indirect
+            })
+        );
+    }
+
+    #[test]
+    fn computed_addresses() {
+        let stack = [
+            Instruction::StoreMemory(Register::R0, 0xF0),
+            Instruction::Pop(Register::R1),
+        ];
+        assert_eq!(
+            address_taken(&stack).map(|diagnostic| diagnostic.reason),
+            Some(AddressTaken::Stack)
+        );
+        // The store patches the address of the load
+        let patched = [
+            Instruction::StoreMemory(Register::R0, 3),
+            Instruction::LoadMemory(Register::R1, 0x40),
+        ];
+        assert_eq!(
+            address_taken(&patched),
+            Some(MemoryDiagnostic {
+                index: 0,
+                reason: AddressTaken::SelfModifying
+            })
+        );
+    }
+}
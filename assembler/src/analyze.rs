@@ -0,0 +1,403 @@
+//! A static analyzer, warning about instructions that are likely bugs.
+//!
+//! The analysis follows every path through the program, jumps, calls and returns included, and
+//! never rejects a program: registers start at 0, so even the flagged programs run. It warns
+//! about:
+//! - reading a register that isn't written on every path before it
+//! - storing a register that isn't written on every path to the console output at 255
+//! - `adc` and `sbc` where no path can have set the overflow, so they add or subtract no carry
+//! - literal addresses of the devices used for something else than reading the input at 254 or
+//!   writing the output at 255
+//!
+//! ```
+//! use assembler::{
+//!     analyze::{analyze, DiagnosticKind},
+//!     Instruction, Register,
+//! };
+//!
+//! let program = [
+//!     Instruction::LoadConstant(Register::R0, 1),
+//!     Instruction::AddOverflow(Register::R0, Register::R1),
+//!     Instruction::Halt,
+//! ];
+//! let kinds: Vec<_> = analyze(&program).iter().map(|diagnostic| diagnostic.kind).collect();
+//! assert_eq!(
+//!     kinds,
+//!     [
+//!         DiagnosticKind::UninitializedRead(Register::R1),
+//!         DiagnosticKind::CarryNeverSet,
+//!     ]
+//! );
+//! ```
+
+use alloc::{vec, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+
+use crate::{
+    optimize::{reads, target, writes, Place},
+    Instruction, Register,
+};
+
+/// The address the input device is mapped to by default
+const INPUT: u8 = 254;
+
+/// The address the console output is mapped to by default
+const OUTPUT: u8 = 255;
+
+/// How likely a diagnostic is a bug
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The program does what it says, but likely not what was meant
+    Note,
+
+    /// The program likely misbehaves
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Note => write!(f, "note"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// What the analyzer found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// The register is read, but some path to the instruction doesn't write it
+    UninitializedRead(Register),
+
+    /// The register is stored to the output, but some path to the store doesn't write it
+    UninitializedOutput(Register),
+
+    /// An addition or subtraction with overflow that no path can have set the overflow for
+    CarryNeverSet,
+
+    /// The literal address is mapped to a device, but the instruction doesn't read the input or
+    /// write the output
+    DeviceAddress(u8),
+}
+
+impl DiagnosticKind {
+    /// Returns how likely the diagnostic is a bug
+    #[must_use]
+    pub const fn severity(self) -> Severity {
+        match self {
+            Self::CarryNeverSet => Severity::Note,
+            Self::UninitializedRead(_) | Self::UninitializedOutput(_) | Self::DeviceAddress(_) => {
+                Severity::Warning
+            }
+        }
+    }
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UninitializedRead(register) => {
+                write!(f, "r{} is read before it's written", u8::from(*register))
+            }
+            Self::UninitializedOutput(register) => write!(
+                f,
+                "r{} is written to the output before it's loaded",
+                u8::from(*register)
+            ),
+            Self::CarryNeverSet => write!(f, "the overflow is never set, so no carry is used"),
+            Self::DeviceAddress(address) => write!(
+                f,
+                "address {address:#04X} is mapped to a device, but isn't used as one"
+            ),
+        }
+    }
+}
+
+/// A likely bug at an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The index of the instruction in the program
+    pub index: usize,
+
+    /// How likely this is a bug
+    pub severity: Severity,
+
+    /// What was found, its `Display` is the message
+    pub kind: DiagnosticKind,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: instruction {}: {}",
+            self.severity, self.index, self.kind
+        )
+    }
+}
+
+/// What is known before an instruction, over all paths reaching it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    /// The registers written on every path, as a bit per register
+    written: u8,
+
+    /// Whether any path can have set the overflow
+    carry: bool,
+}
+
+impl State {
+    /// Combines the knowledge of 2 paths
+    const fn join(self, other: Self) -> Self {
+        Self {
+            written: self.written & other.written,
+            carry: self.carry || other.carry,
+        }
+    }
+
+    /// Returns the state after the instruction
+    fn after(self, instruction: Instruction) -> Self {
+        let written = Register::ALL
+            .iter()
+            .filter(|&&register| writes(instruction, Place::Register(register)))
+            .fold(self.written, |written, &register| {
+                written | 1 << u8::from(register)
+            });
+        let carry = match instruction {
+            Instruction::ClearCarry => false,
+            _ => self.carry || writes(instruction, Place::Overflow),
+        };
+        Self { written, carry }
+    }
+
+    /// Returns whether the register is written on every path
+    fn is_written(self, register: Register) -> bool {
+        self.written & 1 << u8::from(register) != 0
+    }
+}
+
+/// Returns whether the instruction reads the register only to compute a constant, like `xor r, r`
+fn is_cleared(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Xor(left, right) | Instruction::Sub(left, right) | Instruction::Xnor(left, right)
+            if left == right
+    )
+}
+
+/// Returns the indices of the instructions that can execute after the one at the index
+fn successors(program: &[Instruction], offsets: &[usize], index: usize) -> Vec<usize> {
+    let index_of = |address: u8| offsets.binary_search(&usize::from(address)).ok();
+    let instruction = program[index];
+    let mut next = match instruction {
+        Instruction::Jump(_) | Instruction::Call(_) | Instruction::Halt => Vec::new(),
+        // A return continues after any of the calls
+        Instruction::Return => program
+            .iter()
+            .enumerate()
+            .filter(|(_, instruction)| matches!(instruction, Instruction::Call(_)))
+            .map(|(call, _)| call + 1)
+            .collect(),
+        _ => vec![index + 1],
+    };
+    next.extend(target(instruction).and_then(index_of));
+    next.retain(|&next| next < program.len());
+    next
+}
+
+/// Returns the state before every instruction, `None` for the ones that are never executed
+fn states(program: &[Instruction]) -> Vec<Option<State>> {
+    let mut offsets = Vec::with_capacity(program.len() + 1);
+    let mut offset = 0;
+    for instruction in program {
+        offsets.push(offset);
+        offset += instruction.encode().1;
+    }
+    offsets.push(offset);
+
+    let mut states = vec![None; program.len()];
+    if program.is_empty() {
+        return states;
+    }
+    states[0] = Some(State {
+        written: 0,
+        carry: false,
+    });
+    let mut pending = vec![0];
+    while let Some(index) = pending.pop() {
+        let Some(state) = states[index] else {
+            continue;
+        };
+        let after = state.after(program[index]);
+        for next in successors(program, &offsets, index) {
+            let joined = states[next].map_or(after, |state: State| state.join(after));
+            if states[next] != Some(joined) {
+                states[next] = Some(joined);
+                pending.push(next);
+            }
+        }
+    }
+    states
+}
+
+/// Returns the literal address of a device the instruction uses for something else than reading
+/// the input or writing the output
+const fn device_address(instruction: Instruction) -> Option<u8> {
+    match instruction {
+        Instruction::LoadMemory(_, INPUT) | Instruction::StoreMemory(_, OUTPUT) => None,
+        Instruction::LoadMemory(_, address)
+        | Instruction::StoreMemory(_, address)
+        | Instruction::ShiftLeftMemory(address)
+        | Instruction::ShiftRightMemory(address)
+        | Instruction::RotateLeftMemory(address)
+        | Instruction::RotateRightMemory(address)
+        | Instruction::Jump(address)
+        | Instruction::JumpIfOverflow(address)
+        | Instruction::Call(address)
+        | Instruction::JumpIfZero(_, address)
+            if address == INPUT || address == OUTPUT =>
+        {
+            Some(address)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the diagnostics of the program, ordered by the index of their instruction. Instructions
+/// that are never executed only get diagnostics for their addresses.
+#[must_use]
+pub fn analyze(program: &[Instruction]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut report = |index, kind: DiagnosticKind| {
+        diagnostics.push(Diagnostic {
+            index,
+            severity: kind.severity(),
+            kind,
+        });
+    };
+    for (index, (&instruction, state)) in program.iter().zip(states(program)).enumerate() {
+        if let Some(state) = state {
+            let unwritten = Register::ALL.into_iter().filter(|&register| {
+                reads(instruction, Place::Register(register))
+                    && !state.is_written(register)
+                    && !is_cleared(instruction)
+            });
+            for register in unwritten {
+                let kind = if instruction == Instruction::StoreMemory(register, OUTPUT) {
+                    DiagnosticKind::UninitializedOutput(register)
+                } else {
+                    DiagnosticKind::UninitializedRead(register)
+                };
+                report(index, kind);
+            }
+            let uses_carry = matches!(
+                instruction,
+                Instruction::AddOverflow(..) | Instruction::SubOverflow(..)
+            );
+            if uses_carry && !state.carry {
+                report(index, DiagnosticKind::CarryNeverSet);
+            }
+        }
+        if let Some(address) = device_address(instruction) {
+            report(index, DiagnosticKind::DeviceAddress(address));
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+
+    use super::{analyze, Diagnostic, DiagnosticKind, Severity};
+    use crate::{disassemble::disassemble, parser::Parser, Instruction, Register};
+
+    /// Returns the instructions of the code segment of the source
+    fn code(source: &str) -> Vec<Instruction> {
+        let image = Parser::new().assemble_image(source).unwrap();
+        disassemble(&image.code)
+            .unwrap()
+            .into_iter()
+            .map(|(_, instruction)| instruction)
+            .collect()
+    }
+
+    /// Returns the index and kind of the diagnostics of the source
+    fn diagnostics(source: &str) -> Vec<(usize, DiagnosticKind)> {
+        analyze(&code(source))
+            .into_iter()
+            .map(|diagnostic| (diagnostic.index, diagnostic.kind))
+            .collect()
+    }
+
+    #[test]
+    fn clean() {
+        let source = include_str!("../tests/fixtures/analyze/clean.s");
+        assert_eq!(diagnostics(source), []);
+        assert_eq!(analyze(&[]), []);
+    }
+
+    #[test]
+    fn uninitialized_read() {
+        let source = include_str!("../tests/fixtures/analyze/uninitialized.s");
+        assert_eq!(
+            diagnostics(source),
+            [
+                (1, DiagnosticKind::UninitializedRead(Register::R1)),
+                (5, DiagnosticKind::UninitializedRead(Register::R2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn uninitialized_output() {
+        let source = include_str!("../tests/fixtures/analyze/output.s");
+        assert_eq!(
+            diagnostics(source),
+            [(4, DiagnosticKind::UninitializedOutput(Register::R1))]
+        );
+    }
+
+    #[test]
+    fn carry_never_set() {
+        let source = include_str!("../tests/fixtures/analyze/carry.s");
+        assert_eq!(
+            diagnostics(source),
+            [
+                (2, DiagnosticKind::CarryNeverSet),
+                (7, DiagnosticKind::CarryNeverSet)
+            ]
+        );
+    }
+
+    #[test]
+    fn device_address() {
+        let source = include_str!("../tests/fixtures/analyze/device.s");
+        assert_eq!(
+            diagnostics(source),
+            [
+                (2, DiagnosticKind::DeviceAddress(255)),
+                (3, DiagnosticKind::DeviceAddress(254)),
+                (4, DiagnosticKind::DeviceAddress(255)),
+            ]
+        );
+    }
+
+    #[test]
+    fn messages() {
+        let diagnostic = Diagnostic {
+            index: 3,
+            severity: Severity::Warning,
+            kind: DiagnosticKind::UninitializedOutput(Register::R2),
+        };
+        assert_eq!(
+            diagnostic.to_string(),
+            "warning: instruction 3: r2 is written to the output before it's loaded"
+        );
+        assert_eq!(DiagnosticKind::CarryNeverSet.severity(), Severity::Note);
+        assert_eq!(
+            DiagnosticKind::DeviceAddress(254).to_string(),
+            "address 0xFE is mapped to a device, but isn't used as one"
+        );
+    }
+}
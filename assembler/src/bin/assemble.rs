@@ -0,0 +1,132 @@
+//! Assembles a text source file into the bytes of the program.
+//!
+//! ```text
+//! assemble <source.s> [-o <out.bin>] [--listing]
+//! ```
+//!
+//! Without `-o` the program is written next to the source, with the extension `bin`. `--listing`
+//! prints every source line with its address and bytes to the standard output. Errors are printed
+//! as `file:line:column: message` with the text they refer to.
+
+#![warn(
+    clippy::pedantic,
+    clippy::nursery,
+    clippy::missing_const_for_fn,
+    missing_docs
+)]
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    ops::Range,
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use assembler::parser::Parser;
+use michael_computer::error::McError;
+
+const USAGE: &str = "usage: assemble <source.s> [-o <out.bin>] [--listing]";
+
+/// The number of bytes shown on a line of the listing
+const LISTING_BYTES: usize = 4;
+
+/// The options given on the command line
+#[derive(Debug, Default)]
+struct Options {
+    source: PathBuf,
+    output: Option<PathBuf>,
+    listing: bool,
+}
+
+impl Options {
+    /// Parses the arguments after the name of the binary
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut source = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" => options.output = Some(args.next().ok_or("-o needs a file")?.into()),
+                "--listing" => options.listing = true,
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ if source.is_some() => return Err(format!("unexpected argument `{arg}`")),
+                _ => source = Some(arg),
+            }
+        }
+        options.source = source.ok_or("missing the source file")?.into();
+        Ok(options)
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("assemble: {message}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let name = options.source.display();
+    let source = match fs::read_to_string(&options.source) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("assemble: {name}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let (bytes, lines) = match Parser::new().assemble_lines(&source) {
+        Ok(assembled) => assembled,
+        Err(error) => {
+            let (line, column) = error.line_column(&source);
+            eprintln!(
+                "{name}:{line}:{column}: {} `{}`",
+                error.kind,
+                error.text(&source)
+            );
+            return ExitCode::from(error.exit_code());
+        }
+    };
+    let output = options
+        .output
+        .unwrap_or_else(|| options.source.with_extension("bin"));
+    if let Err(error) = fs::write(&output, &bytes) {
+        eprintln!("assemble: {}: {error}", output.display());
+        return ExitCode::FAILURE;
+    }
+    if options.listing {
+        let listing = listing(&source, &bytes, &lines);
+        if io::stdout().lock().write_all(listing.as_bytes()).is_err() {
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Renders every source line with the address and the bytes it emitted. Lines with more bytes
+/// than fit continue on the next lines, without the source.
+fn listing(source: &str, bytes: &[u8], lines: &[Range<usize>]) -> String {
+    let mut listing = String::new();
+    let source = source.strip_suffix('\n').unwrap_or(source);
+    for (line, range) in source.split('\n').zip(lines) {
+        let mut chunks = bytes[range.clone()].chunks(LISTING_BYTES);
+        let first = chunks.next().unwrap_or_default();
+        let row = format!("{:02X}  {:<11}  {line}", range.start, hex(first));
+        let _ = writeln!(listing, "{}", row.trim_end());
+        for (index, chunk) in chunks.enumerate() {
+            let offset = range.start + (index + 1) * LISTING_BYTES;
+            let _ = writeln!(listing, "{offset:02X}  {}", hex(chunk));
+        }
+    }
+    listing
+}
+
+/// Formats the bytes as hex separated by spaces
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,330 @@
+//! Building programs from instructions in code, with constant tables in a data segment.
+//!
+//! The data segment is placed right after the code like the `.data` directive of the text
+//! assembler does, so the addresses of its labels are only known once the program is built.
+//! Instructions take them with [`Program::push_with_label`]:
+//!
+//! ```
+//! use assembler::{builder::Program, Instruction, Register};
+//!
+//! let mut program = Program::new();
+//! let message = program.string("message", "Hi");
+//! program
+//!     .push_with_label(Instruction::LoadMemory(Register::R0, 0), message)
+//!     .push(Instruction::StoreMemory(Register::R0, 255))
+//!     .push(Instruction::Halt);
+//! let image = program.build().unwrap();
+//! assert_eq!(image.code, [0x04, 0x05, 0x08, 0xFF, 0xFF]);
+//! assert_eq!((image.data_address, &image.data[..]), (5, &b"Hi"[..]));
+//! ```
+
+use alloc::{string::String, vec::Vec};
+
+use michael_computer::opcode::{decode_opcode, decode_prefix, Operand};
+
+use crate::{
+    image::{ImageError, ProgramImage},
+    parser::{ParseError, Token},
+    Instruction,
+};
+
+/// The number of bytes of memory the code and data segment are loaded into
+const MEMORY: usize = 256;
+
+/// Returns the position of the byte operand in the encoding of the instruction, after the opcode
+/// and its register prefix
+///
+/// # Panics
+/// Panics if the instruction doesn't have a byte operand to fill in
+fn byte_operand(instruction: Instruction) -> usize {
+    let (bytes, _) = instruction.encode();
+    let opcode = usize::from(decode_prefix(bytes[0]).is_some());
+    assert!(
+        decode_opcode(bytes[opcode]).is_ok_and(|op| op.operand() == Operand::Byte),
+        "{instruction} doesn't take a byte operand"
+    );
+    opcode + 1
+}
+
+/// A position in the data segment, its address is filled in when the program is built
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataLabel {
+    offset: usize,
+}
+
+impl DataLabel {
+    /// Returns the label of the byte at the offset from this one, like an element of a table
+    #[must_use]
+    pub const fn offset(self, offset: usize) -> Self {
+        Self {
+            offset: self.offset + offset,
+        }
+    }
+}
+
+/// A program built from instructions and the data they address
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    code: Vec<u8>,
+    fixups: Vec<(usize, DataLabel)>,
+    data: Vec<u8>,
+    labels: Vec<(String, DataLabel)>,
+}
+
+impl Program {
+    /// Creates an empty program
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            code: Vec::new(),
+            fixups: Vec::new(),
+            data: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// Appends the instruction to the code
+    pub fn push(&mut self, instruction: Instruction) -> &mut Self {
+        let (bytes, length) = instruction.encode();
+        self.code.extend_from_slice(&bytes[..length]);
+        self
+    }
+
+    /// Appends the instruction with the address of the label as its operand, like the address of
+    /// a load or the value of a load constant
+    ///
+    /// # Panics
+    /// Panics if the instruction doesn't take a byte operand
+    pub fn push_with_label(&mut self, instruction: Instruction, label: DataLabel) -> &mut Self {
+        let operand = self.code.len() + byte_operand(instruction);
+        self.fixups.push((operand, label));
+        self.push(instruction)
+    }
+
+    /// Appends the instruction with the literal as its operand, in any form the text assembler
+    /// takes like `'H'`, `0b0100_1000` or `-1`
+    ///
+    /// # Errors
+    /// Returns the error of the literal as if it started the source, like
+    /// [`ParseErrorKind::ValueOutOfRange`](crate::parser::ParseErrorKind::ValueOutOfRange)
+    ///
+    /// # Panics
+    /// Panics if the instruction doesn't take a byte operand
+    pub fn push_literal(
+        &mut self,
+        instruction: Instruction,
+        literal: &str,
+    ) -> Result<&mut Self, ParseError> {
+        let operand = self.code.len() + byte_operand(instruction);
+        let value = Token {
+            text: literal,
+            start: 0,
+        }
+        .number()?;
+        self.push(instruction);
+        self.code[operand] = value;
+        Ok(self)
+    }
+
+    /// Appends the bytes to the data segment, like the `.byte` directive
+    ///
+    /// # Panics
+    /// Panics if the name is already defined
+    pub fn byte(&mut self, name: &str, bytes: &[u8]) -> DataLabel {
+        let label = self.define(name);
+        self.data.extend_from_slice(bytes);
+        label
+    }
+
+    /// Appends the bytes of the string to the data segment, like the `.string` directive. No
+    /// terminating 0 is added.
+    ///
+    /// # Panics
+    /// Panics if the name is already defined
+    pub fn string(&mut self, name: &str, text: &str) -> DataLabel {
+        self.byte(name, text.as_bytes())
+    }
+
+    /// Appends the number of zero bytes to the data segment, like the `.zero` directive
+    ///
+    /// # Panics
+    /// Panics if the name is already defined
+    pub fn zero(&mut self, name: &str, count: usize) -> DataLabel {
+        let label = self.define(name);
+        self.data.resize(self.data.len() + count, 0);
+        label
+    }
+
+    /// Returns the label of the data with the name
+    #[must_use]
+    pub fn data_label(&self, name: &str) -> Option<DataLabel> {
+        self.labels
+            .iter()
+            .find(|(label, _)| label == name)
+            .map(|&(_, label)| label)
+    }
+
+    /// Places the data segment after the code and fills in the addresses of the labels
+    ///
+    /// # Errors
+    /// Returns [`ImageError::TooLarge`] if the code and the data don't fit in memory together
+    pub fn build(&self) -> Result<ProgramImage, ImageError> {
+        let address = u8::try_from(self.code.len()).map_err(|_| ImageError::TooLarge)?;
+        if self.code.len() + self.data.len() > MEMORY {
+            return Err(ImageError::TooLarge);
+        }
+        let mut code = self.code.clone();
+        for &(offset, label) in &self.fixups {
+            code[offset] =
+                u8::try_from(self.code.len() + label.offset).map_err(|_| ImageError::TooLarge)?;
+        }
+        Ok(ProgramImage::new(code).with_data(address, self.data.clone()))
+    }
+
+    /// Returns a label at the end of the data segment
+    fn define(&mut self, name: &str) -> DataLabel {
+        assert!(
+            self.data_label(name).is_none(),
+            "the label `{name}` is already defined"
+        );
+        let label = DataLabel {
+            offset: self.data.len(),
+        };
+        self.labels.push((name.into(), label));
+        label
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use michael_computer::{
+        bus::{Bus, ConsoleDevice},
+        StopReason,
+    };
+
+    use super::Program;
+    use crate::{
+        image::ImageError,
+        parser::{ParseErrorKind, Parser},
+        Instruction, Register,
+    };
+
+    /// Prints the string at `message` by walking it with indirect loads until the terminating 0
+    fn print_program() -> Program {
+        let mut program = Program::new();
+        let message = program.string("message", "Hello, world!\0");
+        program
+            .push_with_label(Instruction::LoadConstant(Register::R3, 0), message)
+            .push(Instruction::LoadIndirect(Register::R0, Register::R3))
+            .push(Instruction::JumpIfZero(Register::R0, 11))
+            .push(Instruction::StoreMemory(Register::R0, 255))
+            .push(Instruction::Inc(Register::R3))
+            .push(Instruction::Jump(2))
+            .push(Instruction::Halt);
+        program
+    }
+
+    #[test]
+    fn prints_a_table() {
+        let image = print_program().build().unwrap();
+        assert_eq!(image.data_address, 12);
+        assert_eq!(image.code[1], 12);
+
+        let mut console = ConsoleDevice::new(String::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let mut computer = image.load(bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(console.output(), "Hello, world!");
+
+        // The text assembler places the same data at the same address
+        let source = "
+                ldi r3, message
+        loop:   ld r0, [r3]
+                jz r0, done
+                st r0, [255]
+                inc r3
+                jmp loop
+        done:   hlt
+                .data
+        message: .string \"Hello, world!\\0\"
+        ";
+        assert_eq!(Parser::new().assemble_image(source), Ok(image));
+    }
+
+    #[test]
+    fn labels() {
+        let mut program = Program::new();
+        let table = program.byte("table", &[1, 2, 3]);
+        let buffer = program.zero("buffer", 4);
+        assert_eq!(program.data_label("table"), Some(table));
+        assert_eq!(program.data_label("buffer"), Some(buffer));
+        assert_eq!(program.data_label("missing"), None);
+        program
+            .push_with_label(Instruction::LoadMemory(Register::R0, 0), table.offset(2))
+            .push_with_label(Instruction::StoreMemory(Register::R0, 0), buffer)
+            .push_with_label(Instruction::JumpIfZero(Register::R5, 0), table)
+            .push(Instruction::Halt);
+        let image = program.build().unwrap();
+        assert_eq!(image.code, [0x04, 10, 0x08, 11, 0xF9, 0xC5, 8, 0xFF]);
+        assert_eq!(image.data, [1, 2, 3, 0, 0, 0, 0]);
+
+        let mut large = Program::new();
+        large.zero("large", 252);
+        large.push(Instruction::LoadConstant(Register::R0, 0));
+        large.push(Instruction::LoadConstant(Register::R0, 0));
+        assert!(large.build().is_ok());
+        large.push(Instruction::Halt);
+        assert_eq!(large.build(), Err(ImageError::TooLarge));
+    }
+
+    #[test]
+    fn literals() {
+        let mut program = Program::new();
+        program
+            .push_literal(Instruction::LoadConstant(Register::R0, 0), "'H'")
+            .unwrap()
+            .push_literal(Instruction::LoadConstant(Register::R1, 0), "0b0100_1000")
+            .unwrap()
+            .push_literal(Instruction::StoreMemory(Register::R0, 0), "-1")
+            .unwrap()
+            .push_literal(Instruction::LoadConstant(Register::R2, 0), "'\\n'")
+            .unwrap()
+            .push_literal(Instruction::LoadConstant(Register::R6, 0), "7")
+            .unwrap();
+        let image = program.build().unwrap();
+        assert_eq!(
+            image.code,
+            [0x00, b'H', 0x01, 0x48, 0x08, 0xFF, 0x02, b'\n', 0xF9, 0x02, 7]
+        );
+
+        let error = program
+            .push_literal(Instruction::LoadConstant(Register::R0, 0), "-129")
+            .unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::ValueOutOfRange("-129".into()));
+        assert_eq!((error.start, error.end), (0, 4));
+        let error = program
+            .push_literal(Instruction::LoadConstant(Register::R0, 0), "'ab'")
+            .unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::InvalidString);
+        // Failed literals don't leave their instruction behind
+        assert_eq!(program.build().unwrap().code.len(), 11);
+    }
+
+    #[test]
+    #[should_panic = "already defined"]
+    fn duplicate_label() {
+        let mut program = Program::new();
+        program.zero("buffer", 1);
+        program.zero("buffer", 1);
+    }
+
+    #[test]
+    #[should_panic = "doesn't take a byte operand"]
+    fn label_without_operand() {
+        let mut program = Program::new();
+        let label = program.zero("buffer", 1);
+        program.push_with_label(Instruction::Halt, label);
+    }
+}
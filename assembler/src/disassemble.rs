@@ -0,0 +1,479 @@
+//! Disassembly of program bytes, including dumps with corrupt bytes.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::{self, Display, Formatter, Write};
+
+use michael_computer::error::{ErrorKind, Location, McError};
+
+use crate::{image::ProgramImage, metadata::ENCODING_VERSION, Instruction};
+
+/// The bytes at the offset don't decode as an instruction: the opcode is invalid, the operand is
+/// missing or its reserved bits are set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    /// The offset of the first byte that didn't decode
+    pub offset: usize,
+
+    /// The byte at the offset
+    pub byte: u8,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:#04X} at offset {} doesn't decode as an instruction",
+            self.byte, self.offset
+        )
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+impl McError for DecodeError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Format
+    }
+
+    fn location(&self) -> Option<Location> {
+        Some(Location::Offset(self.offset))
+    }
+}
+
+/// Disassembles the bytes into the instructions with their offsets.
+///
+/// # Errors
+/// Returns an error at the first bytes that don't decode, see [`disassemble_lossy`] to continue
+/// after them
+pub fn disassemble(bytes: &[u8]) -> Result<Vec<(usize, Instruction)>, DecodeError> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while let Some(&byte) = bytes.get(offset) {
+        let (instruction, length) =
+            Instruction::decode(&bytes[offset..]).ok_or(DecodeError { offset, byte })?;
+        instructions.push((offset, instruction));
+        offset += length;
+    }
+    Ok(instructions)
+}
+
+/// Renders the bytes as a listing like `0004: 00 48    ldi r0, 0x48`, with the offset and the
+/// bytes of every instruction.
+///
+/// A byte that doesn't decode is shown as `.byte 0xc5` and the listing continues after it, the
+/// first of those is returned next to the listing. The text after the bytes assembles back into
+/// the same program.
+#[must_use]
+pub fn fmt_listing(bytes: &[u8]) -> (String, Option<DecodeError>) {
+    let mut listing = String::new();
+    let mut error = None;
+    let mut offset = 0;
+    while let Some(&byte) = bytes.get(offset) {
+        let instruction = Instruction::decode(&bytes[offset..]);
+        let length = instruction.map_or(1, |(_, length)| length);
+        // Writing to a string can't fail
+        let _ = match bytes[offset..offset + length] {
+            [prefix, opcode, operand] => write!(
+                listing,
+                "{offset:04X}: {prefix:02X} {opcode:02X} {operand:02X} "
+            ),
+            [opcode, operand] => write!(listing, "{offset:04X}: {opcode:02X} {operand:02X}    "),
+            _ => write!(listing, "{offset:04X}: {byte:02X}       "),
+        };
+        let _ = if let Some((instruction, _)) = instruction {
+            writeln!(listing, "{instruction}")
+        } else {
+            error.get_or_insert(DecodeError { offset, byte });
+            writeln!(listing, ".byte {byte:#04x}")
+        };
+        offset += length;
+    }
+    (listing, error)
+}
+
+/// Renders the code of an image like [`fmt_listing`], after a comment line with its metadata.
+///
+/// The comment holds the metadata of the toolchain that built the image and the encoding version
+/// of its instructions, like `; assembler 0.1.0, ISA level 14, encoding version 1`.
+#[must_use]
+pub fn fmt_image_listing(image: &ProgramImage) -> (String, Option<DecodeError>) {
+    let (listing, error) = fmt_listing(&image.code);
+    // Decoding only accepts images of the current encoding version
+    let header = format!(
+        "; {}, encoding version {ENCODING_VERSION}\n",
+        image.metadata
+    );
+    (header + &listing, error)
+}
+
+/// How much a disassembled line can be trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// Decoded after resynchronizing on a guess that didn't decode cleanly for the whole lookahead
+    Low,
+
+    /// Decoded after skipping bytes, with the lookahead decoding cleanly
+    Medium,
+
+    /// Decoded before any corruption, or after resynchronizing at a known entry
+    High,
+}
+
+/// A line of the disassembly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line {
+    /// The offset of the first byte of the line
+    pub offset: usize,
+
+    /// The number of bytes the line covers
+    pub length: usize,
+
+    /// The bytes the line covers, only the first `length` are used
+    pub bytes: [u8; 3],
+
+    /// The decoded instruction, or `None` for a byte that was skipped to resynchronize
+    pub instruction: Option<Instruction>,
+
+    /// Whether the skipped byte doesn't decode, the other skipped bytes decode but were passed
+    /// over to resynchronize at a known entry
+    pub undecodable: bool,
+
+    /// How much the line can be trusted
+    pub confidence: Confidence,
+}
+
+/// Configures how [`disassemble_lossy`] resynchronizes after an undecodable byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryOptions<'a> {
+    entries: &'a [usize],
+    lookahead: usize,
+}
+
+impl Default for RecoveryOptions<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> RecoveryOptions<'a> {
+    /// Creates options without known entries, looking ahead 8 instructions
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            entries: &[],
+            lookahead: 8,
+        }
+    }
+
+    /// Sets the offsets known to start an instruction, like jump targets and labels
+    #[must_use]
+    pub const fn with_entries(mut self, entries: &'a [usize]) -> Self {
+        self.entries = entries;
+        self
+    }
+
+    /// Sets the number of instructions that have to decode after a resynchronization point
+    #[must_use]
+    pub const fn with_lookahead(mut self, lookahead: usize) -> Self {
+        self.lookahead = lookahead;
+        self
+    }
+
+    /// Returns the first known entry after the offset
+    fn next_entry(&self, offset: usize) -> Option<usize> {
+        self.entries
+            .iter()
+            .copied()
+            .filter(|&entry| entry > offset)
+            .min()
+    }
+}
+
+/// The lines of a disassembly.
+///
+/// Formats one line per instruction with its offset in hex like [`fmt_listing`], lines below
+/// [`Confidence::High`] are marked with `?` or `??` and skipped bytes are shown as `.byte`, with a
+/// comment telling undecodable bytes from bytes skipped to resynchronize.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    /// The lines in the order of their offsets
+    pub lines: Vec<Line>,
+}
+
+impl Display for Disassembly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            let marker = match line.confidence {
+                Confidence::High => "  ",
+                Confidence::Medium => "? ",
+                Confidence::Low => "??",
+            };
+            write!(f, "{:04X}: {marker} ", line.offset)?;
+            match line.instruction {
+                Some(instruction) => writeln!(f, "{instruction}")?,
+                None if line.undecodable => {
+                    writeln!(f, ".byte {:#04x} ; undecodable", line.bytes[0])?;
+                }
+                None => writeln!(f, ".byte {:#04x} ; skipped to resync", line.bytes[0])?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the number of instructions decoding cleanly from the offset, up to `limit`, and
+/// whether no undecodable byte was found before the limit or the end
+fn score(bytes: &[u8], mut offset: usize, limit: usize) -> (usize, bool) {
+    for count in 0..limit {
+        if offset >= bytes.len() {
+            return (count, true);
+        }
+        let Some((_, length)) = Instruction::decode(&bytes[offset..]) else {
+            return (count, false);
+        };
+        offset += length;
+    }
+    (limit, true)
+}
+
+/// Disassembles the bytes, resynchronizing after undecodable bytes instead of stopping.
+///
+/// After an undecodable byte both skipping it and continuing at the next known entry are tried.
+/// The candidate with more instructions decoding cleanly in the lookahead wins, the known entry on
+/// a tie. The bytes skipped are reported as lines without an instruction.
+#[must_use]
+pub fn disassemble_lossy(bytes: &[u8], options: &RecoveryOptions) -> Disassembly {
+    let mut lines = Vec::new();
+    let mut confidence = Confidence::High;
+    let mut offset = 0;
+    while offset < bytes.len() {
+        if options.entries.contains(&offset) {
+            confidence = Confidence::High;
+        }
+        if let Some((instruction, length)) = Instruction::decode(&bytes[offset..]) {
+            lines.push(Line {
+                offset,
+                length,
+                bytes: instruction.encode().0,
+                instruction: Some(instruction),
+                undecodable: false,
+                confidence,
+            });
+            offset += length;
+            continue;
+        }
+        let skip = offset + 1;
+        let (skip_score, skip_clean) = score(bytes, skip, options.lookahead);
+        let (resume, resume_confidence) = match options.next_entry(offset) {
+            Some(entry) if score(bytes, entry, options.lookahead).0 >= skip_score => {
+                (entry.min(bytes.len()), Confidence::High)
+            }
+            _ if skip_clean => (skip, Confidence::Medium),
+            _ => (skip, Confidence::Low),
+        };
+        lines.extend((offset..resume).map(|offset| Line {
+            offset,
+            length: 1,
+            bytes: [bytes[offset], 0, 0],
+            instruction: None,
+            undecodable: Instruction::decode(&bytes[offset..]).is_none(),
+            confidence: Confidence::Low,
+        }));
+        offset = resume;
+        confidence = resume_confidence;
+    }
+    Disassembly { lines }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::ToString, vec::Vec};
+
+    use michael_computer::error::{Location, McError};
+
+    use super::{
+        disassemble, disassemble_lossy, fmt_image_listing, fmt_listing, Confidence, DecodeError,
+        RecoveryOptions,
+    };
+    use crate::{
+        image::ProgramImage,
+        metadata::{Metadata, ENCODING_VERSION, ISA_LEVEL},
+        parser::Parser,
+        Instruction, Register,
+    };
+
+    /// The original program, the opcode of `ldi r1, 0x05` at offset 2 is corrupted
+    const CORRUPT: [u8; 9] = [
+        0x00, 0x41, // ldi r0, 'A'
+        0xFE, 0x05, // was 0x01, 0x05: ldi r1, 0x05
+        0x02, 0xFE, // ldi r2, 0xfe
+        0x08, 0xFF, // st r0, [0xff]
+        0xFF, // hlt
+    ];
+
+    #[test]
+    fn clean_program() {
+        let bytes = [0x00, 0x41, 0x08, 0xFF, 0xFF];
+        let disassembly = disassemble_lossy(&bytes, &RecoveryOptions::new());
+        assert!(disassembly
+            .lines
+            .iter()
+            .all(|line| line.confidence == Confidence::High));
+        assert_eq!(
+            disassembly.to_string(),
+            "0000:    ldi r0, 0x41\n0002:    st r0, [0xff]\n0004:    hlt\n"
+        );
+    }
+
+    #[test]
+    fn skip_one_byte() {
+        // Skipping the corrupt opcode decodes its operand as `ld r1, [0x02]`, which swallows the
+        // next opcode, the tail is recovered after skipping the orphaned operand
+        let disassembly = disassemble_lossy(&CORRUPT, &RecoveryOptions::new());
+        let lines: Vec<_> = disassembly
+            .lines
+            .iter()
+            .map(|line| (line.offset, line.instruction, line.confidence))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (
+                    0,
+                    Some(Instruction::LoadConstant(Register::R0, 0x41)),
+                    Confidence::High
+                ),
+                (2, None, Confidence::Low),
+                (
+                    3,
+                    Some(Instruction::LoadMemory(Register::R1, 0x02)),
+                    Confidence::Low
+                ),
+                (5, None, Confidence::Low),
+                (
+                    6,
+                    Some(Instruction::StoreMemory(Register::R0, 0xFF)),
+                    Confidence::Medium
+                ),
+                (8, Some(Instruction::Halt), Confidence::Medium),
+            ]
+        );
+        assert!(disassembly.to_string().contains("0006: ?  st r0, [0xff]\n"));
+    }
+
+    #[test]
+    fn resume_at_entry() {
+        let entries = [0, 4];
+        let options = RecoveryOptions::new().with_entries(&entries);
+        let disassembly = disassemble_lossy(&CORRUPT, &options);
+        let tail = &disassembly.lines[3..];
+        assert_eq!(disassembly.lines[1].instruction, None);
+        assert!(disassembly.lines[1].undecodable);
+        assert_eq!(disassembly.lines[2].instruction, None);
+        assert!(!disassembly.lines[2].undecodable);
+        assert!(tail.iter().all(|line| line.confidence == Confidence::High));
+        assert!(tail.iter().map(|line| line.instruction).eq([
+            Some(Instruction::LoadConstant(Register::R2, 0xFE)),
+            Some(Instruction::StoreMemory(Register::R0, 0xFF)),
+            Some(Instruction::Halt),
+        ]));
+        assert_eq!(
+            disassembly.to_string(),
+            "0000:    ldi r0, 0x41\n0002: ?? .byte 0xfe ; undecodable\n\
+             0003: ?? .byte 0x05 ; skipped to resync\n0004:    ldi r2, 0xfe\n\
+             0006:    st r0, [0xff]\n0008:    hlt\n"
+        );
+    }
+
+    #[test]
+    fn strict() {
+        assert_eq!(
+            disassemble(&[0x00, 0x41, 0xE4, 0xFF]),
+            Ok([
+                (0, Instruction::LoadConstant(Register::R0, 0x41)),
+                (2, Instruction::Inc(Register::R0)),
+                (3, Instruction::Halt)
+            ]
+            .to_vec())
+        );
+        let error = disassemble(&CORRUPT).unwrap_err();
+        assert_eq!(
+            error,
+            DecodeError {
+                offset: 2,
+                byte: 0xFE
+            }
+        );
+        assert_eq!(error.location(), Some(Location::Offset(2)));
+        // A missing operand and reserved bits of a register byte don't decode either
+        assert_eq!(disassemble(&[0xFF, 0x00]).unwrap_err().offset, 1);
+        assert_eq!(disassemble(&[0xF4, 0x10]).unwrap_err().offset, 0);
+    }
+
+    #[test]
+    fn listing_round_trips() {
+        let bytes = include_bytes!("../tests/fixtures/hello.bin");
+        let (listing, error) = fmt_listing(bytes);
+        let lines: Vec<_> = listing.lines().collect();
+        assert_eq!(lines[0], "0000: 03 0C    ldi r3, 0x0c");
+        assert_eq!(lines[4], "0008: E7       inc r3");
+        // The text is decoded as instructions as well, the terminating 0 misses the operand of a
+        // load constant
+        assert_eq!(lines.last(), Some(&"0019: 00       .byte 0x00"));
+        assert_eq!(
+            error,
+            Some(DecodeError {
+                offset: 0x19,
+                byte: 0
+            })
+        );
+        assert_eq!(disassemble(bytes), Err(error.unwrap()));
+
+        let source: Vec<_> = lines.iter().map(|line| &line[15..]).collect();
+        let reassembled = Parser::new().assemble(&source.join("\n")).unwrap();
+        assert_eq!(reassembled, bytes);
+
+        let (listing, error) = fmt_listing(&CORRUPT);
+        assert!(listing.starts_with("0000: 00 41    ldi r0, 0x41\n0002: FE       .byte 0xfe\n"));
+        assert_eq!(error.map(|error| error.offset), Some(2));
+
+        // A register prefix fits in the same columns
+        let (listing, error) = fmt_listing(&[0xF9, 0x01, 0x07, 0xFA, 0x19, 0xFF]);
+        assert_eq!(
+            listing,
+            "0000: F9 01 07 ldi r5, 0x07\n0003: FA 19    mov r6, r1\n0005: FF       hlt\n"
+        );
+        assert_eq!(error, None);
+    }
+
+    #[test]
+    fn image_listing() {
+        let code = [0x00, 0x41, 0x08, 0xFF, 0xFF];
+        let mut image = ProgramImage::new(code.into());
+        let (listing, error) = fmt_image_listing(&image);
+        let [major, minor, patch] = Metadata::CURRENT.assembler_version;
+        let (body, _) = fmt_listing(&code);
+        assert_eq!(
+            listing,
+            format!(
+                "; assembler {major}.{minor}.{patch}, ISA level {ISA_LEVEL}, encoding version \
+                 {ENCODING_VERSION}\n{body}"
+            )
+        );
+        assert_eq!(error, None);
+
+        // The metadata of the image is listed, not the one of the current toolchain
+        image.metadata = Metadata::from_bytes([0, 0, 1, 9]);
+        let (listing, _) = fmt_image_listing(&image);
+        assert_eq!(
+            listing.lines().next(),
+            Some("; assembler 0.0.1, ISA level 9, encoding version 1")
+        );
+        // The comment assembles to nothing, the instruction column still assembles back
+        let source: Vec<_> = listing
+            .lines()
+            .map(|line| line.strip_prefix(';').map_or(&line[15..], |_| line))
+            .collect();
+        assert_eq!(Parser::new().assemble(&source.join("\n")).unwrap(), code);
+    }
+}
@@ -0,0 +1,224 @@
+//! The error of the pipeline from the source text to the execution of the program.
+
+use core::fmt::{self, Display, Formatter};
+
+use michael_computer::{
+    error::{ErrorKind, Location, McError},
+    ExecutionError,
+};
+
+use crate::metadata::{EncodingMismatch, VersionMismatch};
+#[cfg(feature = "alloc")]
+use crate::{image::ImageError, parser::ParseError};
+
+/// An error from any stage of loading and running a program.
+///
+/// The kind and location are the ones of the error it wraps, so `?` can be used across the stages
+/// without losing them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The source text isn't a valid program
+    #[cfg(feature = "alloc")]
+    Parse(ParseError),
+
+    /// The bytes aren't a valid program image
+    #[cfg(feature = "alloc")]
+    Image(ImageError),
+
+    /// The program was built by a different toolchain
+    Version(VersionMismatch),
+
+    /// The program uses a different instruction encoding
+    Encoding(EncodingMismatch),
+
+    /// Loading or executing the program failed
+    Execution(ExecutionError),
+}
+
+#[cfg(feature = "alloc")]
+impl From<ParseError> for Error {
+    fn from(value: ParseError) -> Self {
+        Self::Parse(value)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<ImageError> for Error {
+    fn from(value: ImageError) -> Self {
+        Self::Image(value)
+    }
+}
+
+impl From<VersionMismatch> for Error {
+    fn from(value: VersionMismatch) -> Self {
+        Self::Version(value)
+    }
+}
+
+impl From<EncodingMismatch> for Error {
+    fn from(value: EncodingMismatch) -> Self {
+        Self::Encoding(value)
+    }
+}
+
+impl From<ExecutionError> for Error {
+    fn from(value: ExecutionError) -> Self {
+        Self::Execution(value)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "alloc")]
+            Self::Parse(error) => error.fmt(f),
+            #[cfg(feature = "alloc")]
+            Self::Image(error) => error.fmt(f),
+            Self::Version(error) => error.fmt(f),
+            Self::Encoding(error) => error.fmt(f),
+            Self::Execution(error) => error.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "alloc")]
+            Self::Parse(error) => Some(error),
+            #[cfg(feature = "alloc")]
+            Self::Image(error) => Some(error),
+            Self::Version(error) => Some(error),
+            Self::Encoding(error) => Some(error),
+            Self::Execution(error) => Some(error),
+        }
+    }
+}
+
+impl McError for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            #[cfg(feature = "alloc")]
+            Self::Parse(error) => error.kind(),
+            #[cfg(feature = "alloc")]
+            Self::Image(error) => error.kind(),
+            Self::Version(error) => error.kind(),
+            Self::Encoding(error) => error.kind(),
+            Self::Execution(error) => error.kind(),
+        }
+    }
+
+    fn location(&self) -> Option<Location> {
+        match self {
+            #[cfg(feature = "alloc")]
+            Self::Parse(error) => error.location(),
+            #[cfg(feature = "alloc")]
+            Self::Image(error) => error.location(),
+            Self::Version(error) => error.location(),
+            Self::Encoding(error) => error.location(),
+            Self::Execution(error) => error.location(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{error::Error as _, fmt::Write};
+
+    use michael_computer::{
+        alu,
+        error::{ErrorKind, Location, McError},
+        ExecutionError, StopReason,
+    };
+
+    use super::Error;
+    use crate::metadata::{check_encoding, Metadata};
+    #[cfg(feature = "alloc")]
+    use crate::{
+        image::{ImageError, ProgramImage},
+        parser::{ParseErrorKind, Parser},
+    };
+
+    /// Checks the metadata and runs the program, discarding the output
+    fn load_and_run(metadata: [u8; Metadata::LENGTH], program: &[u8]) -> Result<StopReason, Error> {
+        struct Discard;
+        impl Write for Discard {
+            fn write_str(&mut self, _: &str) -> core::fmt::Result {
+                Ok(())
+            }
+        }
+        Metadata::from_bytes(metadata).check(true)?;
+        Ok(alu(program.iter().copied(), [], &mut Discard)?)
+    }
+
+    #[test]
+    fn pipeline() {
+        let current = Metadata::CURRENT.to_bytes();
+        assert_eq!(load_and_run(current, &[0xFF]), Ok(StopReason::Halted));
+
+        let error = load_and_run(current, &[0, 1, 0xFE]).unwrap_err();
+        assert_eq!(
+            error,
+            Error::Execution(ExecutionError::InvalidInstruction {
+                offset: 2,
+                byte: 0xFE
+            })
+        );
+        assert_eq!(error.kind(), ErrorKind::Execution);
+        assert_eq!(error.location(), Some(Location::Offset(2)));
+        assert_eq!(error.exit_code(), 6);
+        assert!(error.source().is_some());
+
+        let error = load_and_run([0, 0, 1, 0], &[0xFF]).unwrap_err();
+        assert!(matches!(error, Error::Version(_)));
+        assert_eq!(error.exit_code(), 5);
+        assert_eq!(error.location(), None);
+
+        #[cfg(feature = "alloc")]
+        {
+            let error = Error::from(ProgramImage::decode(&[0xFF]).unwrap_err());
+            assert_eq!(error, Error::Image(ImageError::Magic));
+            assert_eq!(error.exit_code(), 4);
+        }
+
+        let error = Error::from(check_encoding(0).unwrap_err());
+        assert_eq!(error.kind(), ErrorKind::Format);
+        assert_eq!(error.exit_code(), 4);
+
+        #[cfg(feature = "alloc")]
+        {
+            let error = Error::from(ProgramImage::decode(&[0xFF]).unwrap_err());
+            assert_eq!(error, Error::Image(ImageError::Magic));
+            assert_eq!(error.exit_code(), 4);
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn parse_error() {
+        /// Assembles and runs the source, discarding the output
+        fn assemble_and_run(source: &str) -> Result<StopReason, Error> {
+            let program = Parser::new().assemble(source)?;
+            load_and_run(Metadata::CURRENT.to_bytes(), &program)
+        }
+
+        assert_eq!(assemble_and_run("hlt"), Ok(StopReason::Halted));
+        let source = "ldi r0, 1\njmp nowhere";
+        let error = assemble_and_run(source).unwrap_err();
+        let Error::Parse(parse_error) = &error else {
+            panic!("{error:?} isn't a parse error");
+        };
+        assert_eq!(parse_error.kind, ParseErrorKind::UndefinedLabel);
+        assert_eq!(parse_error.text(source), "nowhere");
+        assert_eq!(
+            error.location(),
+            Some(Location::Span { start: 14, end: 21 })
+        );
+        assert_eq!(error.exit_code(), 3);
+        assert!(error.source().is_some());
+
+        let error = assemble_and_run("frobnicate r0").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Parse);
+        assert_eq!(error.location(), Some(Location::Span { start: 0, end: 10 }));
+    }
+}
@@ -0,0 +1,525 @@
+//! Annotated walkthroughs of the execution of a program, for teaching.
+
+use alloc::string::String;
+use core::fmt::{self, Write};
+
+use michael_computer::{
+    bus::{Bus, ConsoleDevice},
+    mux::byte::REGISTER_COUNT,
+    Computer, ExecutionError, StepOutcome, StopReason, WatchKind,
+};
+
+use crate::{image::ProgramImage, Instruction, Register};
+
+/// The state visible to the walkthrough before or after an instruction
+struct Snapshot {
+    registers: [u8; REGISTER_COUNT],
+    overflow: bool,
+    half_carry: bool,
+    stack_pointer: u8,
+    bank: u8,
+    memory: Option<u8>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot, including the memory cell the instruction accesses
+    fn take(computer: &Computer, address: Option<u8>) -> Self {
+        Self {
+            registers: computer.registers(),
+            overflow: computer.overflow(),
+            half_carry: computer.half_carry(),
+            stack_pointer: computer.stack_pointer(),
+            bank: computer.bank(),
+            memory: address.map(|address| computer.memory(address)),
+        }
+    }
+
+    fn register(&self, register: Register) -> u8 {
+        self.registers[usize::from(u8::from(register))]
+    }
+}
+
+/// Returns the memory cell the instruction reads or writes, given the registers and stack pointer
+/// before it
+fn accessed_address(
+    instruction: Instruction,
+    registers: [u8; REGISTER_COUNT],
+    stack_pointer: u8,
+) -> Option<u8> {
+    match instruction {
+        Instruction::Push(_) | Instruction::Call(_) => Some(stack_pointer),
+        Instruction::Pop(_) | Instruction::Return => Some(stack_pointer.wrapping_sub(1)),
+        Instruction::LoadIndirect(_, address) | Instruction::StoreIndirect(_, address) => {
+            Some(registers[usize::from(u8::from(address))])
+        }
+        Instruction::LoadMemory(_, address)
+        | Instruction::StoreMemory(_, address)
+        | Instruction::ShiftLeftMemory(address)
+        | Instruction::ShiftRightMemory(address)
+        | Instruction::RotateLeftMemory(address)
+        | Instruction::RotateRightMemory(address) => Some(address),
+        _ => None,
+    }
+}
+
+/// Describes the values the instruction reads
+fn describe_reads(f: &mut impl Write, instruction: Instruction, before: &Snapshot) -> fmt::Result {
+    let register = |register: Register| (u8::from(register), before.register(register));
+    match instruction {
+        Instruction::LoadConstant(_, value) => writeln!(f, "  reads the constant {value:#04x}"),
+        Instruction::LoadMemory(_, 254) => writeln!(f, "  reads the input port"),
+        Instruction::LoadIndirect(_, address) | Instruction::StoreIndirect(_, address)
+            if before.register(address) == 254 =>
+        {
+            let (index, _) = register(address);
+            writeln!(f, "  reads r{index} = 0xfe, the input port")
+        }
+        Instruction::LoadIndirect(_, address) => {
+            let (index, value) = register(address);
+            writeln!(
+                f,
+                "  reads r{index} = {value:#04x}, [{value:#04x}] = {:#04x}",
+                before.memory.unwrap_or_default()
+            )
+        }
+        Instruction::StoreIndirect(source, address) => {
+            let ((source, value), (address, target)) = (register(source), register(address));
+            writeln!(
+                f,
+                "  reads r{source} = {value:#04x}, r{address} = {target:#04x}"
+            )
+        }
+        Instruction::Copy(destination, source) => {
+            let ((destination, to), (source, from)) = (register(destination), register(source));
+            writeln!(
+                f,
+                "  reads r{destination} = {to:#04x}, r{source} = {from:#04x}, r0 = {:#04x}",
+                before.register(Register::R0)
+            )
+        }
+        Instruction::LoadMemory(_, address)
+        | Instruction::ShiftLeftMemory(address)
+        | Instruction::ShiftRightMemory(address)
+        | Instruction::RotateLeftMemory(address)
+        | Instruction::RotateRightMemory(address) => writeln!(
+            f,
+            "  reads [{address:#04x}] = {:#04x}",
+            before.memory.unwrap_or_default()
+        ),
+        Instruction::Pop(_) | Instruction::Return => writeln!(
+            f,
+            "  reads [{:#04x}] = {:#04x}",
+            before.stack_pointer.wrapping_sub(1),
+            before.memory.unwrap_or_default()
+        ),
+        Instruction::StoreMemory(source, _)
+        | Instruction::Push(source)
+        | Instruction::Not(source)
+        | Instruction::ShiftLeft(source)
+        | Instruction::ShiftRight(source)
+        | Instruction::RotateLeft(source)
+        | Instruction::RotateRight(source)
+        | Instruction::Inc(source)
+        | Instruction::Dec(source)
+        | Instruction::SetBank(source)
+        | Instruction::Move(_, source)
+        | Instruction::JumpIfZero(source, _) => {
+            let (index, value) = register(source);
+            writeln!(f, "  reads r{index} = {value:#04x}")
+        }
+        Instruction::Nand(left, right)
+        | Instruction::And(left, right)
+        | Instruction::Nor(left, right)
+        | Instruction::Or(left, right)
+        | Instruction::Xnor(left, right)
+        | Instruction::Xor(left, right)
+        | Instruction::Add(left, right)
+        | Instruction::AddOverflow(left, right)
+        | Instruction::Sub(left, right)
+        | Instruction::SubOverflow(left, right)
+        | Instruction::Mul(left, right)
+        | Instruction::Div(left, right)
+        | Instruction::Rem(left, right) => {
+            let ((left, a), (right, b)) = (register(left), register(right));
+            writeln!(f, "  reads r{left} = {a:#04x}, r{right} = {b:#04x}")
+        }
+        Instruction::JumpIfOverflow(_) => {
+            writeln!(f, "  reads overflow = {}", u8::from(before.overflow))
+        }
+        Instruction::DecimalAdjust(source) => {
+            let (index, value) = register(source);
+            writeln!(
+                f,
+                "  reads r{index} = {value:#04x}, overflow = {}, half carry = {}",
+                u8::from(before.overflow),
+                u8::from(before.half_carry)
+            )
+        }
+        Instruction::Jump(_)
+        | Instruction::Call(_)
+        | Instruction::SetCarry
+        | Instruction::ClearCarry
+        | Instruction::Halt => Ok(()),
+    }
+}
+
+/// Describes the circuit performing the instruction
+fn describe_operation(
+    f: &mut impl Write,
+    instruction: Instruction,
+    before: &Snapshot,
+    after: &Snapshot,
+    counter: u8,
+) -> fmt::Result {
+    let carry_in = u8::from(before.overflow);
+    let carry_out = u8::from(after.overflow);
+    match instruction {
+        Instruction::LoadConstant(..)
+        | Instruction::LoadMemory(..)
+        | Instruction::LoadIndirect(..)
+        | Instruction::Move(..) => {
+            writeln!(f, "  routes the byte into the register through the demux")
+        }
+        Instruction::StoreMemory(..) | Instruction::StoreIndirect(..) => {
+            writeln!(f, "  routes the byte into memory through the 256 way demux")
+        }
+        Instruction::Push(_) => writeln!(
+            f,
+            "  routes the byte onto the stack, increments the stack pointer with the adder"
+        ),
+        Instruction::Pop(_) => writeln!(
+            f,
+            "  decrements the stack pointer with the adder, routes the byte into the register"
+        ),
+        Instruction::Not(_) => writeln!(f, "  inverts every bit with a nand gate"),
+        Instruction::Nand(..)
+        | Instruction::And(..)
+        | Instruction::Nor(..)
+        | Instruction::Or(..)
+        | Instruction::Xnor(..)
+        | Instruction::Xor(..) => {
+            writeln!(f, "  bitwise {} of the 8 bit pairs", instruction.mnemonic())
+        }
+        Instruction::Add(..) => writeln!(f, "  ripple add, carry out = {carry_out}"),
+        Instruction::AddOverflow(..) => writeln!(
+            f,
+            "  ripple add, carry in = {carry_in}, carry out = {carry_out}"
+        ),
+        Instruction::Sub(..) => writeln!(f, "  ripple subtract, carry out = {carry_out}"),
+        Instruction::SubOverflow(..) => writeln!(
+            f,
+            "  ripple subtract, carry in = {carry_in}, carry out = {carry_out}"
+        ),
+        Instruction::Inc(_) => writeln!(f, "  half adder chain, carry out = {carry_out}"),
+        Instruction::Dec(_) => writeln!(f, "  half subtractor chain, borrow out = {carry_out}"),
+        Instruction::DecimalAdjust(_) => writeln!(f, "  BCD correction, carry out = {carry_out}"),
+        Instruction::SetCarry | Instruction::ClearCarry => {
+            writeln!(f, "  drives the overflow to {carry_out}")
+        }
+        Instruction::SetBank(_) => writeln!(
+            f,
+            "  latches the low 4 bits into the bank select, bank = {}",
+            after.bank
+        ),
+        Instruction::Mul(..) => writeln!(
+            f,
+            "  adds the shifted partial products, high byte nonzero = {carry_out}"
+        ),
+        Instruction::Div(..) | Instruction::Rem(..) => writeln!(
+            f,
+            "  restoring division through the subtractor, division by zero = {carry_out}"
+        ),
+        Instruction::Copy(destination, source) => {
+            let count = before.register(Register::R0);
+            let distance = before
+                .register(destination)
+                .wrapping_sub(before.register(source));
+            let direction = if distance < count {
+                "backwards"
+            } else {
+                "forwards"
+            };
+            writeln!(
+                f,
+                "  loads and stores {count} bytes {direction}, stepping the addresses with the incrementer"
+            )
+        }
+        Instruction::Jump(_) | Instruction::JumpIfOverflow(_) | Instruction::JumpIfZero(..) => {
+            writeln!(f, "  muxes the program counter, continues at {counter}")
+        }
+        Instruction::ShiftLeft(_)
+        | Instruction::ShiftRight(_)
+        | Instruction::ShiftLeftMemory(_)
+        | Instruction::ShiftRightMemory(_) => {
+            writeln!(f, "  shifts the byte, bit out = {carry_out}")
+        }
+        Instruction::RotateLeft(_) | Instruction::RotateRight(_) => {
+            writeln!(f, "  rotates the byte, bit out = {carry_out}")
+        }
+        Instruction::RotateLeftMemory(_) | Instruction::RotateRightMemory(_) => writeln!(
+            f,
+            "  rotates the byte through carry, carry in = {carry_in}, carry out = {carry_out}"
+        ),
+        Instruction::Call(_) => writeln!(
+            f,
+            "  pushes the return address, muxes the program counter, continues at {counter}"
+        ),
+        Instruction::Return => writeln!(
+            f,
+            "  pops the return address, muxes the program counter, continues at {counter}"
+        ),
+        Instruction::Halt => writeln!(f, "  stops the clock"),
+    }
+}
+
+/// Describes the changes to the registers, overflow and memory
+fn describe_changes(
+    f: &mut impl Write,
+    instruction: Instruction,
+    before: &Snapshot,
+    after: &Snapshot,
+) -> fmt::Result {
+    for ((index, old), new) in (0..).zip(before.registers).zip(after.registers) {
+        if old != new {
+            writeln!(f, "  r{index}: {old:#04x} -> {new:#04x}")?;
+        }
+    }
+    if before.overflow != after.overflow {
+        writeln!(
+            f,
+            "  overflow: {} -> {}",
+            u8::from(before.overflow),
+            u8::from(after.overflow)
+        )?;
+    }
+    if before.stack_pointer != after.stack_pointer {
+        writeln!(
+            f,
+            "  sp: {:#04x} -> {:#04x}",
+            before.stack_pointer, after.stack_pointer
+        )?;
+    }
+    if before.bank != after.bank {
+        writeln!(f, "  bank: {} -> {}", before.bank, after.bank)?;
+    }
+    if let (Some(address), Some(old), Some(new)) = (
+        accessed_address(instruction, before.registers, before.stack_pointer),
+        before.memory,
+        after.memory,
+    ) {
+        if old != new {
+            writeln!(f, "  [{address:#04x}]: {old:#04x} -> {new:#04x}")?;
+        }
+        if let (Instruction::StoreMemory(..) | Instruction::StoreIndirect(..), 255) =
+            (instruction, address)
+        {
+            writeln!(f, "  outputs {:?}", char::from(new))?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the reason the program stopped
+fn describe_stop(f: &mut impl Write, reason: StopReason) -> fmt::Result {
+    match reason {
+        StopReason::Halted => writeln!(f, "halted"),
+        StopReason::EndOfProgram => writeln!(f, "reached the end of the program"),
+        StopReason::OutOfFuel { executed } => {
+            writeln!(f, "ran out of fuel after {executed} instructions")
+        }
+        StopReason::Breakpoint { pc } => writeln!(f, "stopped at the breakpoint at {pc:#04x}"),
+        StopReason::Watchpoint { addr, kind, value } => {
+            let access = if kind == WatchKind::Read {
+                "read"
+            } else {
+                "wrote"
+            };
+            writeln!(
+                f,
+                "stopped at the watchpoint, the program {access} {value:#04x} at {addr:#04x}"
+            )
+        }
+    }
+}
+
+/// Executes up to `max_steps` instructions of the program and describes every one of them: the
+/// mnemonic, the values it read, the circuit performing it and the state it changed.
+///
+/// The program runs with the console at address 255 and no input.
+#[must_use]
+pub fn explain(program: &[u8], max_steps: usize) -> String {
+    explain_loaded(|bus| Computer::new(program.iter().copied(), bus), max_steps)
+}
+
+/// Describes the execution of the image like [`explain`], starting at its entry with its data
+/// segment loaded
+#[must_use]
+pub fn explain_image(image: &ProgramImage, max_steps: usize) -> String {
+    explain_loaded(|bus| image.load(bus), max_steps)
+}
+
+/// Describes the execution of the computer the closure loads onto the bus
+fn explain_loaded(
+    load: impl FnOnce(Bus<'_>) -> Result<Computer<'_>, ExecutionError>,
+    max_steps: usize,
+) -> String {
+    let mut text = String::new();
+    let mut console = ConsoleDevice::new(String::new());
+    let bus = Bus::new().with_device(255..=255, &mut console);
+    // Writing to a string can't fail
+    let _ = match load(bus) {
+        Ok(mut computer) => walk(&mut text, &mut computer, max_steps),
+        Err(error) => writeln!(text, "error: {error}"),
+    };
+    text
+}
+
+/// Writes the walkthrough of `explain`
+fn walk(f: &mut String, computer: &mut Computer, max_steps: usize) -> fmt::Result {
+    for step in 1..=max_steps {
+        let offset = computer.program_counter();
+        // The instructions are decoded from memory, where the computer fetches them
+        let bytes = [0, 1, 2].map(|index| computer.memory(offset.wrapping_add(index)));
+        let instruction = Instruction::decode(&bytes);
+        let (registers, stack_pointer) = (computer.registers(), computer.stack_pointer());
+        let address = instruction
+            .and_then(|(instruction, _)| accessed_address(instruction, registers, stack_pointer));
+        let before = Snapshot::take(computer, address);
+        let outcome = computer.step();
+        let after = Snapshot::take(computer, address);
+        if let (
+            Some((instruction, _)),
+            Ok(StepOutcome::Executed | StepOutcome::Stopped(StopReason::Halted)),
+        ) = (instruction, &outcome)
+        {
+            writeln!(f, "step {step}, offset {offset}: {instruction}")?;
+            describe_reads(f, instruction, &before)?;
+            describe_operation(f, instruction, &before, &after, computer.program_counter())?;
+            describe_changes(f, instruction, &before, &after)?;
+        }
+        match outcome {
+            Ok(StepOutcome::Executed) => {}
+            Ok(StepOutcome::Stopped(reason)) => return describe_stop(f, reason),
+            Err(error) => return writeln!(f, "error: {error}"),
+        }
+    }
+    writeln!(f, "stopped after {max_steps} steps")
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::{explain, explain_image};
+    use crate::{image::ProgramImage, Instruction, Register};
+
+    #[test]
+    fn golden() {
+        let program = Instruction::to_bytes([
+            Instruction::LoadConstant(Register::R0, 0xF0),
+            Instruction::LoadConstant(Register::R1, 0x51),
+            Instruction::Add(Register::R0, Register::R1),
+            Instruction::JumpIfOverflow(8),
+            Instruction::Halt,
+            Instruction::StoreMemory(Register::R0, 255),
+        ]);
+        let expected = "\
+step 1, offset 0: ldi r0, 0xf0
+  reads the constant 0xf0
+  routes the byte into the register through the demux
+  r0: 0x00 -> 0xf0
+step 2, offset 2: ldi r1, 0x51
+  reads the constant 0x51
+  routes the byte into the register through the demux
+  r1: 0x00 -> 0x51
+step 3, offset 4: add r0, r1
+  reads r0 = 0xf0, r1 = 0x51
+  ripple add, carry out = 1
+  r0: 0xf0 -> 0x41
+  overflow: 0 -> 1
+step 4, offset 5: jo 8
+  reads overflow = 1
+  muxes the program counter, continues at 8
+step 5, offset 8: st r0, [0xff]
+  reads r0 = 0x41
+  routes the byte into memory through the 256 way demux
+  [0xff]: 0x00 -> 0x41
+  outputs 'A'
+reached the end of the program
+";
+        assert_eq!(explain(&program, 10), expected);
+        assert!(explain(&program, 2).ends_with("stopped after 2 steps\n"));
+    }
+
+    #[test]
+    fn stack() {
+        let program = [0x01, 0x2A, 0xC9, 0xCE];
+        let expected = "\
+step 1, offset 0: ldi r1, 0x2a
+  reads the constant 0x2a
+  routes the byte into the register through the demux
+  r1: 0x00 -> 0x2a
+step 2, offset 2: push r1
+  reads r1 = 0x2a
+  routes the byte onto the stack, increments the stack pointer with the adder
+  sp: 0xf0 -> 0xf1
+  [0xf0]: 0x00 -> 0x2a
+step 3, offset 3: pop r2
+  reads [0xf0] = 0x2a
+  decrements the stack pointer with the adder, routes the byte into the register
+  r2: 0x00 -> 0x2a
+  sp: 0xf1 -> 0xf0
+reached the end of the program
+";
+        assert_eq!(explain(&program, 10), expected);
+
+        let program = [0xC2, 0x03, 0xFF, 0xC3];
+        let text = explain(&program, 10);
+        assert!(text.contains(
+            "step 1, offset 0: call 3\n  \
+             pushes the return address, muxes the program counter, continues at 3\n  \
+             sp: 0xf0 -> 0xf1\n  [0xf0]: 0x00 -> 0x02\n"
+        ));
+        assert!(text.contains(
+            "step 2, offset 3: ret\n  reads [0xf0] = 0x02\n  \
+             pops the return address, muxes the program counter, continues at 2\n"
+        ));
+        assert!(text.ends_with("halted\n"));
+    }
+
+    #[test]
+    fn halt_and_errors() {
+        let text = explain(&[0xFF], 5);
+        assert_eq!(text, "step 1, offset 0: hlt\n  stops the clock\nhalted\n");
+        let text = explain(&[0x10, 0xFE], 5);
+        assert!(text.ends_with("error: invalid instruction 0xFE at offset 1\n"));
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        // Stores a halt over the invalid instruction at offset 4 before reaching it
+        let text = explain(&[0x00, 0xFF, 0x08, 0x04, 0xFE], 5);
+        assert!(text.contains("step 3, offset 4: hlt\n"), "{text}");
+        assert!(text.ends_with("halted\n"));
+    }
+
+    #[test]
+    fn image() {
+        // Starts at the entry and loads the byte of the data segment
+        let image = ProgramImage::new(vec![0xFF, 0x04, 0x80, 0xFF])
+            .with_entry(1)
+            .with_data(0x80, vec![0x2A]);
+        assert_eq!(
+            explain_image(&image, 5),
+            "\
+step 1, offset 1: ld r0, [0x80]
+  reads [0x80] = 0x2a
+  routes the byte into the register through the demux
+  r0: 0x00 -> 0x2a
+step 2, offset 3: hlt
+  stops the clock
+halted
+"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Routine generators for unsigned 8.8 fixed-point math.
+//!
+//! A fixed-point value takes two bytes of memory: the fraction at the address of the value and the
+//! integer part at the address after it. Results wrap around when they don't fit in 8.8.
+//!
+//! The routines are appended to a program and compute their jump targets from its length, so the
+//! program has to be loaded at the start of memory.
+
+use alloc::vec::Vec;
+
+use michael_computer::computer::STACK_BASE;
+
+use crate::{
+    Instruction,
+    Register::{R0, R1, R2, R3},
+};
+
+/// The number of memory cells used as scratch space by [`emit_fixmul`]
+pub const SCRATCH_SIZE: u8 = 8;
+
+/// The first of the memory cells used as scratch space by [`emit_fixmul`], 0xE8 to 0xEF right
+/// below the stack, so a routine reached with a call doesn't overwrite the return address
+pub const SCRATCH: u8 = STACK_BASE - SCRATCH_SIZE;
+
+/// The multiplicand, shifted left every iteration, takes 3 bytes
+const MULTIPLICAND: u8 = SCRATCH;
+
+/// The accumulated product takes 3 bytes, the lowest byte is only kept for its carry
+const PRODUCT: u8 = SCRATCH + 3;
+
+/// The copy of the multiplier, shifted right every iteration, takes 2 bytes
+const MULTIPLIER: u8 = SCRATCH + 6;
+
+/// Returns the offset the next instruction is placed at
+fn position(program: &[Instruction]) -> u8 {
+    let length: usize = program
+        .iter()
+        .map(|instruction| instruction.encode().1)
+        .sum();
+    u8::try_from(length).expect("program doesn't fit in memory")
+}
+
+/// Appends a routine storing the sum of the values at `a_addr` and `b_addr` at `dst_addr`.
+///
+/// Clobbers `R0`, `R1` and the overflow flag, takes 14 bytes and executes 8 instructions in 14
+/// cycles.
+/// The overflow flag is set if the sum wrapped around.
+pub fn emit_fixadd(program: &mut Vec<Instruction>, a_addr: u8, b_addr: u8, dst_addr: u8) {
+    program.extend([
+        Instruction::LoadMemory(R0, a_addr),
+        Instruction::LoadMemory(R1, b_addr),
+        Instruction::Add(R0, R1),
+        Instruction::StoreMemory(R0, dst_addr),
+        Instruction::LoadMemory(R0, a_addr.wrapping_add(1)),
+        Instruction::LoadMemory(R1, b_addr.wrapping_add(1)),
+        Instruction::AddOverflow(R0, R1),
+        Instruction::StoreMemory(R0, dst_addr.wrapping_add(1)),
+    ]);
+}
+
+/// Appends a routine storing the product of the values at `a_addr` and `b_addr` at `dst_addr`.
+///
+/// The product is computed by shifting and adding over the 16 bits of the multiplier, using the
+/// [`SCRATCH_SIZE`] bytes of memory from [`SCRATCH`]. The operands and the destination may
+/// overlap, but not the scratch memory.
+///
+/// Clobbers all registers and the overflow flag, takes 78 bytes and executes
+/// `178 + 11 * b.count_ones()` instructions, between 178 and 354, in `340 + 19 * b.count_ones()`
+/// cycles, between 340 and 644.
+///
+/// # Panics
+/// Panics if the program is already longer than 255 bytes
+pub fn emit_fixmul(program: &mut Vec<Instruction>, a_addr: u8, b_addr: u8, dst_addr: u8) {
+    program.extend([
+        Instruction::LoadConstant(R0, 0),
+        Instruction::StoreMemory(R0, MULTIPLICAND + 2),
+        Instruction::StoreMemory(R0, PRODUCT),
+        Instruction::StoreMemory(R0, PRODUCT + 1),
+        Instruction::StoreMemory(R0, PRODUCT + 2),
+        Instruction::LoadMemory(R0, a_addr),
+        Instruction::StoreMemory(R0, MULTIPLICAND),
+        Instruction::LoadMemory(R0, a_addr.wrapping_add(1)),
+        Instruction::StoreMemory(R0, MULTIPLICAND + 1),
+        Instruction::LoadMemory(R0, b_addr),
+        Instruction::StoreMemory(R0, MULTIPLIER),
+        Instruction::LoadMemory(R0, b_addr.wrapping_add(1)),
+        Instruction::StoreMemory(R0, MULTIPLIER + 1),
+        Instruction::LoadConstant(R2, 1),
+        Instruction::LoadConstant(R3, 16),
+    ]);
+
+    let loop_start = position(program);
+    program.extend([
+        // Shift the lowest bit of the multiplier into the overflow flag
+        Instruction::ShiftRightMemory(MULTIPLIER + 1),
+        Instruction::RotateRightMemory(MULTIPLIER),
+    ]);
+    // Both jumps are only 2 bytes, so the addition starts 4 bytes after the first one
+    let add = position(program) + 4;
+    program.push(Instruction::JumpIfOverflow(add));
+    let skip = program.len();
+    program.push(Instruction::Jump(0));
+    for i in 0..3 {
+        program.extend([
+            Instruction::LoadMemory(R0, PRODUCT + i),
+            Instruction::LoadMemory(R1, MULTIPLICAND + i),
+            if i == 0 {
+                Instruction::Add(R0, R1)
+            } else {
+                Instruction::AddOverflow(R0, R1)
+            },
+            Instruction::StoreMemory(R0, PRODUCT + i),
+        ]);
+    }
+    program[skip] = Instruction::Jump(position(program));
+    program.extend([
+        Instruction::ShiftLeftMemory(MULTIPLICAND),
+        Instruction::RotateLeftMemory(MULTIPLICAND + 1),
+        Instruction::RotateLeftMemory(MULTIPLICAND + 2),
+        Instruction::Sub(R3, R2),
+    ]);
+    // The conditional and the unconditional jump take 4 bytes together
+    let done = position(program) + 4;
+    program.extend([
+        Instruction::JumpIfZero(R3, done),
+        Instruction::Jump(loop_start),
+        Instruction::LoadMemory(R0, PRODUCT + 1),
+        Instruction::StoreMemory(R0, dst_addr),
+        Instruction::LoadMemory(R0, PRODUCT + 2),
+        Instruction::StoreMemory(R0, dst_addr.wrapping_add(1)),
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use michael_computer::{
+        bus::Bus, computer::STACK_BASE, state::InitialState, Computer, StepOutcome, StopReason,
+    };
+
+    use super::{emit_fixadd, emit_fixmul};
+    use crate::{Instruction, Register::R0};
+
+    /// Operands covering the integer and fraction bytes, including the wrap around
+    const OPERANDS: [u16; 6] = [0x0000, 0x0001, 0x0080, 0x0100, 0x0A40, 0xFFFF];
+
+    /// Runs the routine with the operands at 0xA0 and 0xA2, after the code in memory, returns the
+    /// value at 0xA4, the number of executed instructions and the cycles they took
+    fn run(routine: &[Instruction], a: u16, b: u16) -> (u16, usize, u64) {
+        let [a_low, a_high] = a.to_le_bytes();
+        let [b_low, b_high] = b.to_le_bytes();
+        let initial = InitialState::new()
+            .with_memory(0xA0, a_low)
+            .with_memory(0xA1, a_high)
+            .with_memory(0xA2, b_low)
+            .with_memory(0xA3, b_high);
+        let program = Instruction::to_bytes(routine.iter().copied());
+        let mut computer = Computer::with_initial_state(program, &initial, Bus::new()).unwrap();
+        let mut steps = 0;
+        while computer.step().unwrap() == StepOutcome::Executed {
+            steps += 1;
+        }
+        let result = u16::from_le_bytes([computer.memory(0xA4), computer.memory(0xA5)]);
+        (result, steps, computer.cycles())
+    }
+
+    #[test]
+    fn fixadd() {
+        let mut routine = Vec::new();
+        emit_fixadd(&mut routine, 0xA0, 0xA2, 0xA4);
+        assert_eq!(Instruction::to_bytes(routine.iter().copied()).len(), 14);
+        for a in OPERANDS {
+            for b in OPERANDS {
+                assert_eq!(run(&routine, a, b), (a.wrapping_add(b), 8, 14));
+            }
+        }
+    }
+
+    #[test]
+    fn fixmul() {
+        let mut routine = Vec::new();
+        emit_fixmul(&mut routine, 0xA0, 0xA2, 0xA4);
+        assert_eq!(Instruction::to_bytes(routine.iter().copied()).len(), 78);
+        for a in OPERANDS {
+            for b in OPERANDS {
+                let expected = ((u32::from(a) * u32::from(b)) >> 8).to_le_bytes();
+                let expected = u16::from_le_bytes([expected[0], expected[1]]);
+                let instructions = 178 + 11 * usize::try_from(b.count_ones()).unwrap();
+                let cycles = 340 + 19 * u64::from(b.count_ones());
+                assert_eq!(
+                    run(&routine, a, b),
+                    (expected, instructions, cycles),
+                    "{a:#06X} * {b:#06X}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fixmul_after_code() {
+        // The jump targets follow the position of the routine in the program
+        let mut routine = Vec::new();
+        emit_fixadd(&mut routine, 0xA0, 0xA2, 0xA0);
+        emit_fixmul(&mut routine, 0xA0, 0xA0, 0xA4);
+        assert_eq!(run(&routine, 0x0180, 0x0100).0, 0x0640);
+    }
+
+    #[test]
+    fn fixmul_subroutine() {
+        // call mul; ldi r0, 1; st r0, [0xA6]; hlt; mul: the routine and ret
+        let mut program = vec![
+            Instruction::Call(7),
+            Instruction::LoadConstant(R0, 1),
+            Instruction::StoreMemory(R0, 0xA6),
+            Instruction::Halt,
+        ];
+        emit_fixmul(&mut program, 0xA0, 0xA2, 0xA4);
+        program.push(Instruction::Return);
+        let initial = InitialState::new()
+            .with_memory(0xA1, 3)
+            .with_memory(0xA2, 0x80);
+        let bytes = Instruction::to_bytes(program);
+        let mut computer = Computer::with_initial_state(bytes, &initial, Bus::new())
+            .unwrap()
+            .with_fuel(1000);
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        // 3.0 * 0.5, and the return came back to the caller
+        assert_eq!([computer.memory(0xA4), computer.memory(0xA5)], [0x80, 1]);
+        assert_eq!(computer.memory(0xA6), 1);
+        assert_eq!(computer.state().stack_pointer, STACK_BASE);
+    }
+}
@@ -0,0 +1,178 @@
+//! The golden wire format of the instructions.
+//!
+//! The bytes are written out by hand instead of being produced by the encoder, so a change to the
+//! encoding fails here. They must never change without bumping
+//! [`ENCODING_VERSION`](crate::metadata::ENCODING_VERSION) and adding a migration note.
+
+use crate::{metadata::ENCODING_VERSION, Instruction, Register};
+
+/// Every instruction with its bytes in encoding version 1.
+/// Instructions added on unused opcodes are appended, the existing entries never change.
+const GOLDEN: [(Instruction, &[u8]); 55] = [
+    (Instruction::LoadConstant(Register::R0, 0x48), &[0x00, 0x48]),
+    (Instruction::LoadConstant(Register::R3, 0xFF), &[0x03, 0xFF]),
+    (Instruction::LoadMemory(Register::R1, 0x10), &[0x05, 0x10]),
+    (Instruction::StoreMemory(Register::R2, 0xFF), &[0x0A, 0xFF]),
+    (Instruction::Not(Register::R3), &[0x0F]),
+    (Instruction::Move(Register::R1, Register::R2), &[0x16]),
+    (Instruction::Nand(Register::R0, Register::R3), &[0x23]),
+    (Instruction::And(Register::R2, Register::R1), &[0x39]),
+    (Instruction::Nor(Register::R3, Register::R0), &[0x4C]),
+    (Instruction::Or(Register::R1, Register::R1), &[0x55]),
+    (Instruction::Xnor(Register::R2, Register::R3), &[0x6B]),
+    (Instruction::Xor(Register::R3, Register::R2), &[0x7E]),
+    (Instruction::Add(Register::R0, Register::R1), &[0x81]),
+    (Instruction::Add(Register::R3, Register::R3), &[0x8F]),
+    (
+        Instruction::AddOverflow(Register::R1, Register::R0),
+        &[0x94],
+    ),
+    (Instruction::Sub(Register::R2, Register::R0), &[0xA8]),
+    (
+        Instruction::SubOverflow(Register::R0, Register::R2),
+        &[0xB2],
+    ),
+    (Instruction::Jump(0x20), &[0xC0, 0x20]),
+    (Instruction::JumpIfOverflow(0x08), &[0xC1, 0x08]),
+    (Instruction::JumpIfZero(Register::R0, 0x00), &[0xC4, 0x00]),
+    (Instruction::JumpIfZero(Register::R3, 0x0C), &[0xC7, 0x0C]),
+    (Instruction::ShiftLeft(Register::R0), &[0xD0]),
+    (Instruction::ShiftRight(Register::R1), &[0xD5]),
+    (Instruction::RotateLeft(Register::R2), &[0xDA]),
+    (Instruction::RotateRight(Register::R3), &[0xDF]),
+    (Instruction::ShiftLeftMemory(0xF0), &[0xE0, 0xF0]),
+    (Instruction::ShiftRightMemory(0xF1), &[0xE1, 0xF1]),
+    (Instruction::RotateLeftMemory(0xF2), &[0xE2, 0xF2]),
+    (Instruction::RotateRightMemory(0xF3), &[0xE3, 0xF3]),
+    (Instruction::Halt, &[0xFF]),
+    (
+        Instruction::LoadIndirect(Register::R1, Register::R2),
+        &[0xF2, 0x06],
+    ),
+    (
+        Instruction::StoreIndirect(Register::R3, Register::R0),
+        &[0xF3, 0x0C],
+    ),
+    (Instruction::Push(Register::R2), &[0xCA]),
+    (Instruction::Pop(Register::R1), &[0xCD]),
+    (Instruction::Call(0x30), &[0xC2, 0x30]),
+    (Instruction::Return, &[0xC3]),
+    (Instruction::Mul(Register::R2, Register::R1), &[0xF4, 0x09]),
+    (Instruction::Div(Register::R0, Register::R3), &[0xF5, 0x03]),
+    (Instruction::Rem(Register::R3, Register::R1), &[0xF6, 0x0D]),
+    (Instruction::Inc(Register::R1), &[0xE5]),
+    (Instruction::Dec(Register::R3), &[0xEB]),
+    (Instruction::SetCarry, &[0xF0]),
+    (Instruction::ClearCarry, &[0xF1]),
+    (
+        Instruction::LoadConstant(Register::R4, 0x48),
+        &[0xF9, 0x00, 0x48],
+    ),
+    (Instruction::Not(Register::R7), &[0xF9, 0x0F]),
+    (Instruction::Add(Register::R5, Register::R2), &[0xFA, 0x86]),
+    (Instruction::Sub(Register::R6, Register::R7), &[0xFB, 0xAB]),
+    (
+        Instruction::JumpIfZero(Register::R5, 0x0C),
+        &[0xF9, 0xC5, 0x0C],
+    ),
+    (
+        Instruction::LoadIndirect(Register::R0, Register::R6),
+        &[0xF9, 0xF2, 0x02],
+    ),
+    (Instruction::SetBank(Register::R1), &[0xED]),
+    (Instruction::SetBank(Register::R6), &[0xF9, 0xEE]),
+    (Instruction::Copy(Register::R1, Register::R2), &[0xF7, 0x06]),
+    (
+        Instruction::Copy(Register::R4, Register::R3),
+        &[0xFA, 0xF7, 0x03],
+    ),
+    (Instruction::DecimalAdjust(Register::R3), &[0xFC, 0x03]),
+    (
+        Instruction::DecimalAdjust(Register::R6),
+        &[0xF9, 0xFC, 0x02],
+    ),
+];
+
+/// The position of the variant in the declaration, the match fails to compile when an instruction
+/// is added without golden bytes being considered
+const fn variant(instruction: Instruction) -> usize {
+    match instruction {
+        Instruction::LoadConstant(..) => 0,
+        Instruction::LoadMemory(..) => 1,
+        Instruction::StoreMemory(..) => 2,
+        Instruction::Not(_) => 3,
+        Instruction::Move(..) => 4,
+        Instruction::Nand(..) => 5,
+        Instruction::And(..) => 6,
+        Instruction::Nor(..) => 7,
+        Instruction::Or(..) => 8,
+        Instruction::Xnor(..) => 9,
+        Instruction::Xor(..) => 10,
+        Instruction::Add(..) => 11,
+        Instruction::AddOverflow(..) => 12,
+        Instruction::Sub(..) => 13,
+        Instruction::SubOverflow(..) => 14,
+        Instruction::Jump(_) => 15,
+        Instruction::JumpIfOverflow(_) => 16,
+        Instruction::Call(_) => 17,
+        Instruction::Return => 18,
+        Instruction::JumpIfZero(..) => 19,
+        Instruction::Push(_) => 20,
+        Instruction::Pop(_) => 21,
+        Instruction::ShiftLeft(_) => 22,
+        Instruction::ShiftRight(_) => 23,
+        Instruction::RotateLeft(_) => 24,
+        Instruction::RotateRight(_) => 25,
+        Instruction::ShiftLeftMemory(_) => 26,
+        Instruction::ShiftRightMemory(_) => 27,
+        Instruction::RotateLeftMemory(_) => 28,
+        Instruction::RotateRightMemory(_) => 29,
+        Instruction::Inc(_) => 30,
+        Instruction::Dec(_) => 31,
+        Instruction::SetBank(_) => 32,
+        Instruction::SetCarry => 33,
+        Instruction::ClearCarry => 34,
+        Instruction::LoadIndirect(..) => 35,
+        Instruction::StoreIndirect(..) => 36,
+        Instruction::Mul(..) => 37,
+        Instruction::Div(..) => 38,
+        Instruction::Rem(..) => 39,
+        Instruction::Copy(..) => 40,
+        Instruction::DecimalAdjust(_) => 41,
+        Instruction::Halt => 42,
+    }
+}
+
+#[test]
+fn encoding_version() {
+    // Changing the golden bytes requires bumping the version, and the other way around
+    assert_eq!(ENCODING_VERSION, 1);
+}
+
+#[test]
+fn every_instruction() {
+    let mut covered = [false; 43];
+    for (instruction, _) in GOLDEN {
+        covered[variant(instruction)] = true;
+    }
+    assert!(covered.iter().all(|&covered| covered));
+}
+
+#[test]
+fn encoder() {
+    for (instruction, bytes) in GOLDEN {
+        let (encoded, length) = instruction.encode();
+        assert_eq!(&encoded[..length], bytes, "{instruction:?}");
+    }
+}
+
+#[test]
+fn decoder() {
+    for (instruction, bytes) in GOLDEN {
+        assert_eq!(
+            Instruction::decode(bytes),
+            Some((instruction, bytes.len())),
+            "{bytes:02X?}"
+        );
+    }
+}
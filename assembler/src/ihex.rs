@@ -0,0 +1,334 @@
+//! Intel HEX text for programs, the format many programmers and hardware tools read.
+//!
+//! Every line is a record: a `:`, the number of data bytes, a 16-bit address, the record type, the
+//! data and a checksum, all as hex digits. Programs only use the 256 bytes of memory, so data
+//! records beyond them are rejected.
+//!
+//! ```
+//! use assembler::ihex::{from_ihex, to_ihex};
+//!
+//! let text = to_ihex(&[0x00, 0x21, 0x08, 0xFF, 0xFF], 0);
+//! assert_eq!(text, ":05000000002108FFFFD4\n:00000001FF\n");
+//! assert_eq!(from_ihex(&text).unwrap(), [0x00, 0x21, 0x08, 0xFF, 0xFF]);
+//! ```
+
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::{self, Display, Formatter, Write};
+
+use michael_computer::error::{ErrorKind, McError};
+
+/// The number of bytes of memory the records can address
+const MEMORY: usize = 256;
+
+/// The number of data bytes [`to_ihex`] puts in a record
+const RECORD_BYTES: usize = 16;
+
+/// A record holding data bytes
+const DATA: u8 = 0x00;
+
+/// The record ending the file
+const END_OF_FILE: u8 = 0x01;
+
+/// The records setting the upper bits of the addresses
+const EXTENDED_ADDRESS: [u8; 2] = [0x02, 0x04];
+
+/// The records holding the start address, used by other processors
+const START_ADDRESS: [u8; 2] = [0x03, 0x05];
+
+/// What is wrong with a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IhexErrorKind {
+    /// The line doesn't start with a `:`
+    MissingColon,
+
+    /// The record has characters that aren't hex digits, or a different length than it declares
+    InvalidRecord,
+
+    /// The checksum doesn't match the bytes of the record
+    Checksum {
+        /// The checksum of the bytes
+        expected: u8,
+
+        /// The checksum stored in the record
+        found: u8,
+    },
+
+    /// The record type isn't known
+    UnsupportedRecord(u8),
+
+    /// The data doesn't fit in the 256 bytes of memory
+    Overflow,
+
+    /// The text ends without an end of file record
+    MissingEnd,
+
+    /// A record follows the end of file record
+    AfterEnd,
+}
+
+impl Display for IhexErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingColon => write!(f, "record doesn't start with `:`"),
+            Self::InvalidRecord => write!(f, "invalid record"),
+            Self::Checksum { expected, found } => write!(
+                f,
+                "checksum is {found:#04X}, but the record sums to {expected:#04X}"
+            ),
+            Self::UnsupportedRecord(kind) => write!(f, "unsupported record type {kind:#04X}"),
+            Self::Overflow => write!(f, "data doesn't fit in memory"),
+            Self::MissingEnd => write!(f, "missing end of file record"),
+            Self::AfterEnd => write!(f, "record after the end of file"),
+        }
+    }
+}
+
+/// An error in Intel HEX text, with the line it was found on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IhexError {
+    /// What is wrong
+    pub kind: IhexErrorKind,
+
+    /// The line of the record, counted from 1
+    pub line: usize,
+}
+
+impl Display for IhexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on line {}", self.kind, self.line)
+    }
+}
+
+impl core::error::Error for IhexError {}
+
+impl McError for IhexError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Format
+    }
+}
+
+/// Returns the checksum of the bytes of a record: the two's complement of their sum
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+        .wrapping_neg()
+}
+
+/// Appends a record with the address, type and data
+fn record(text: &mut String, address: usize, kind: u8, data: &[u8]) {
+    let [high, low] = u16::try_from(address).unwrap_or(u16::MAX).to_be_bytes();
+    let length = u8::try_from(data.len()).unwrap_or(u8::MAX);
+    let header = [length, high, low, kind];
+    text.push(':');
+    for byte in header.iter().chain(data) {
+        // Writing to a string can't fail
+        let _ = write!(text, "{byte:02X}");
+    }
+    let sum = checksum(&header).wrapping_add(checksum(data));
+    let _ = writeln!(text, "{sum:02X}");
+}
+
+/// Converts the program to Intel HEX, with the first byte at the origin.
+///
+/// The data records hold 16 bytes each, followed by the end of file record.
+///
+/// # Panics
+/// Panics if the bytes don't fit in memory after the origin
+#[must_use]
+pub fn to_ihex(bytes: &[u8], origin: u8) -> String {
+    let origin = usize::from(origin);
+    assert!(
+        origin + bytes.len() <= MEMORY,
+        "the program doesn't fit in memory after the origin"
+    );
+    let mut text = String::new();
+    for (index, chunk) in bytes.chunks(RECORD_BYTES).enumerate() {
+        record(&mut text, origin + index * RECORD_BYTES, DATA, chunk);
+    }
+    record(&mut text, 0, END_OF_FILE, &[]);
+    text
+}
+
+/// Parses the hex digits of a record into its bytes
+fn parse_record(line: &str) -> Result<Vec<u8>, IhexErrorKind> {
+    let digits = line.strip_prefix(':').ok_or(IhexErrorKind::MissingColon)?;
+    if digits.len() % 2 != 0 || !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(IhexErrorKind::InvalidRecord);
+    }
+    let bytes: Vec<u8> = (0..digits.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&digits[index..index + 2], 16))
+        .collect::<Result<_, _>>()
+        .map_err(|_| IhexErrorKind::InvalidRecord)?;
+    let Some((&found, record)) = bytes.split_last() else {
+        return Err(IhexErrorKind::InvalidRecord);
+    };
+    if record.len() < 4 || usize::from(record[0]) != record.len() - 4 {
+        return Err(IhexErrorKind::InvalidRecord);
+    }
+    let expected = checksum(record);
+    if expected != found {
+        return Err(IhexErrorKind::Checksum { expected, found });
+    }
+    Ok(bytes)
+}
+
+/// Reads the program from Intel HEX, as the memory from address 0 up to the last byte of data.
+///
+/// Addresses without data read as 0. Extended address records are accepted if they keep the
+/// addresses in memory, and start address records are ignored since programs start at 0. Line
+/// endings can be `\n` or `\r\n`.
+///
+/// # Errors
+/// Returns the first invalid record, data outside the 256 bytes of memory, or a missing end of
+/// file record
+pub fn from_ihex(text: &str) -> Result<Vec<u8>, IhexError> {
+    let mut memory = vec![0; MEMORY];
+    let mut length = 0;
+    let mut ended = false;
+    for (index, line) in text.lines().enumerate() {
+        let error = |kind| IhexError {
+            kind,
+            line: index + 1,
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if ended {
+            return Err(error(IhexErrorKind::AfterEnd));
+        }
+        let record = parse_record(line).map_err(error)?;
+        let address = usize::from(u16::from_be_bytes([record[1], record[2]]));
+        let data = &record[4..record.len() - 1];
+        match record[3] {
+            DATA => {
+                let end = address + data.len();
+                if end > MEMORY {
+                    return Err(error(IhexErrorKind::Overflow));
+                }
+                memory[address..end].copy_from_slice(data);
+                length = length.max(end);
+            }
+            END_OF_FILE => ended = true,
+            kind if EXTENDED_ADDRESS.contains(&kind) => {
+                if data.iter().any(|&byte| byte != 0) {
+                    return Err(error(IhexErrorKind::Overflow));
+                }
+            }
+            kind if START_ADDRESS.contains(&kind) => {}
+            kind => return Err(error(IhexErrorKind::UnsupportedRecord(kind))),
+        }
+    }
+    if !ended {
+        return Err(IhexError {
+            kind: IhexErrorKind::MissingEnd,
+            line: text.lines().count() + 1,
+        });
+    }
+    memory.truncate(length);
+    Ok(memory)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use michael_computer::error::{ErrorKind, McError};
+
+    use super::{from_ihex, to_ihex, IhexError, IhexErrorKind};
+
+    /// The Hello World program converted by `objcopy -I binary -O ihex`
+    const HELLO: &str = include_str!("../tests/fixtures/hello.hex");
+
+    /// The same program with `--change-addresses 0xE0`, which adds a start address record
+    const HELLO_E0: &str = "\
+        :1000E000030CF203C40B08FFE7C002FF48656C6C09\n\
+        :0A00F0006F2C20776F726C64210002\n\
+        :04000003000000E019\n\
+        :00000001FF\n";
+
+    fn error(text: &str) -> (IhexErrorKind, usize) {
+        let IhexError { kind, line } = from_ihex(text).unwrap_err();
+        (kind, line)
+    }
+
+    #[test]
+    fn known_good() {
+        let bytes = include_bytes!("../tests/fixtures/hello.bin");
+        assert_eq!(from_ihex(HELLO).unwrap(), bytes);
+        // objcopy ends its lines with `\r\n`
+        assert_eq!(to_ihex(bytes, 0), HELLO.replace('\r', ""));
+
+        let shifted = from_ihex(HELLO_E0).unwrap();
+        assert_eq!(shifted.len(), 0xE0 + bytes.len());
+        assert!(shifted[..0xE0].iter().all(|&byte| byte == 0));
+        assert_eq!(&shifted[0xE0..], bytes);
+        let records: Vec<_> = HELLO_E0.lines().collect();
+        let converted = to_ihex(bytes, 0xE0);
+        assert!(converted.lines().eq([records[0], records[1], records[3]]));
+    }
+
+    #[test]
+    fn round_trips() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(from_ihex(&to_ihex(&bytes, 0)).unwrap(), bytes);
+        assert_eq!(to_ihex(&bytes, 0).lines().count(), 17);
+        assert_eq!(from_ihex(&to_ihex(&[], 0)).unwrap(), []);
+        let text = to_ihex(&[1, 2, 3], 253);
+        assert_eq!(&from_ihex(&text).unwrap()[253..], [1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic = "doesn't fit in memory"]
+    fn origin_overflow() {
+        let _ = to_ihex(&[1, 2, 3], 254);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("0000000000\n"), (IhexErrorKind::MissingColon, 1));
+        assert_eq!(
+            error(":0100000001\n:00000001FF"),
+            (IhexErrorKind::InvalidRecord, 1)
+        );
+        assert_eq!(error(":0G000001FF"), (IhexErrorKind::InvalidRecord, 1));
+        assert_eq!(error(":00000001F"), (IhexErrorKind::InvalidRecord, 1));
+        assert_eq!(
+            error(":0100000042BE\n:00000001FF"),
+            (
+                IhexErrorKind::Checksum {
+                    expected: 0xBD,
+                    found: 0xBE
+                },
+                1
+            )
+        );
+        // The second byte of the record would be stored at address 256
+        assert_eq!(
+            error(":0200FF00010AF4\n:00000001FF"),
+            (IhexErrorKind::Overflow, 1)
+        );
+        assert_eq!(
+            error(":020000040001F9\n:00000001FF"),
+            (IhexErrorKind::Overflow, 1)
+        );
+        assert_eq!(
+            from_ihex(":020000040000FA\n:0100000042BD\n:00000001FF"),
+            Ok([0x42].to_vec())
+        );
+        assert_eq!(
+            error(":00000007F9\n:00000001FF"),
+            (IhexErrorKind::UnsupportedRecord(7), 1)
+        );
+        assert_eq!(error(":0100000042BD\n"), (IhexErrorKind::MissingEnd, 2));
+        assert_eq!(
+            error(":00000001FF\n:0100000042BD"),
+            (IhexErrorKind::AfterEnd, 2)
+        );
+        let error = from_ihex("").unwrap_err();
+        assert_eq!(error.kind, IhexErrorKind::MissingEnd);
+        assert_eq!(error.kind(), ErrorKind::Format);
+    }
+}
@@ -0,0 +1,394 @@
+//! The program image, a container for a program with the metadata a loader needs.
+//!
+//! An image starts with a header of 16 bytes, followed by the code and the data segment:
+//!
+//! offset | bytes | content
+//! -|-|-
+//! 0 | 4 | the magic bytes `MCPI`
+//! 4 | 1 | the [`FORMAT_VERSION`] of the container
+//! 5 | 1 | the [`ENCODING_VERSION`] of the instructions
+//! 6 | 4 | the [`Metadata`] of the toolchain
+//! 10 | 2 | the length of the code, little endian
+//! 12 | 1 | the entry offset, where execution starts
+//! 13 | 1 | the address the data segment is loaded at
+//! 14 | 2 | the length of the data segment, little endian
+//!
+//! ```
+//! use assembler::image::ProgramImage;
+//!
+//! let image = ProgramImage::new(vec![0x04, 0x80, 0x08, 0xFF, 0xFF]).with_data(0x80, vec![b'!']);
+//! let bytes = image.encode();
+//! assert_eq!(&bytes[..4], b"MCPI");
+//! assert_eq!(ProgramImage::decode(&bytes), Ok(image));
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+
+use michael_computer::{
+    bus::Bus,
+    error::{ErrorKind, McError},
+    state::InitialState,
+    Computer, ExecutionError,
+};
+
+use crate::metadata::{check_encoding, EncodingMismatch, Metadata, ENCODING_VERSION};
+
+/// The bytes every image starts with
+pub const MAGIC: [u8; 4] = *b"MCPI";
+
+/// The version of the container format, bumped whenever the header changes
+pub const FORMAT_VERSION: u8 = 1;
+
+/// The number of bytes of the header before the code
+pub const HEADER_LENGTH: usize = 16;
+
+/// The number of bytes of memory the code and data segment are loaded into
+const MEMORY: usize = 256;
+
+/// Why bytes aren't a valid program image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageError {
+    /// The bytes end before the header or the segments it describes
+    Truncated {
+        /// The number of bytes the image needs
+        expected: usize,
+
+        /// The number of bytes there are
+        found: usize,
+    },
+
+    /// The image doesn't start with [`MAGIC`], it isn't a program for this computer
+    Magic,
+
+    /// The image uses another container format, see [`FORMAT_VERSION`]
+    Version(u8),
+
+    /// The instructions use another encoding
+    Encoding(EncodingMismatch),
+
+    /// The code or the data segment doesn't fit in memory
+    TooLarge,
+
+    /// The entry offset is outside the code
+    Entry(u8),
+
+    /// There are bytes after the data segment
+    TrailingBytes,
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated { expected, found } => {
+                write!(f, "image is truncated, {found} of {expected} bytes")
+            }
+            Self::Magic => write!(f, "not a program image"),
+            Self::Version(version) => write!(
+                f,
+                "image uses format version {version}, expected {FORMAT_VERSION}"
+            ),
+            Self::Encoding(error) => error.fmt(f),
+            Self::TooLarge => write!(f, "image doesn't fit in memory"),
+            Self::Entry(entry) => write!(f, "entry offset {entry} is outside the code"),
+            Self::TrailingBytes => write!(f, "bytes after the end of the image"),
+        }
+    }
+}
+
+impl core::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Encoding(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl McError for ImageError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Format
+    }
+}
+
+impl From<EncodingMismatch> for ImageError {
+    fn from(value: EncodingMismatch) -> Self {
+        Self::Encoding(value)
+    }
+}
+
+/// A program with its entry offset, the data segment to initialize memory with and the metadata
+/// of the toolchain that built it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramImage {
+    /// The toolchain the program was built with
+    pub metadata: Metadata,
+
+    /// The offset in the code where execution starts
+    pub entry: u8,
+
+    /// The code, loaded at address 0
+    pub code: Vec<u8>,
+
+    /// The address the data segment is loaded at
+    pub data_address: u8,
+
+    /// The bytes memory is initialized with from the data address, they override the code
+    pub data: Vec<u8>,
+}
+
+impl ProgramImage {
+    /// Creates an image of the code built by this toolchain, starting at offset 0 and without a
+    /// data segment
+    #[must_use]
+    pub const fn new(code: Vec<u8>) -> Self {
+        Self {
+            metadata: Metadata::CURRENT,
+            entry: 0,
+            code,
+            data_address: 0,
+            data: Vec::new(),
+        }
+    }
+
+    /// Sets the entry offset
+    #[must_use]
+    pub const fn with_entry(mut self, entry: u8) -> Self {
+        self.entry = entry;
+        self
+    }
+
+    /// Sets the data segment and the address it is loaded at
+    #[must_use]
+    pub fn with_data(mut self, address: u8, data: Vec<u8>) -> Self {
+        self.data_address = address;
+        self.data = data;
+        self
+    }
+
+    /// Encodes the image into its header, code and data segment
+    ///
+    /// # Panics
+    /// Panics if the code or the data segment doesn't fit in memory
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        assert!(
+            self.code.len() <= MEMORY && usize::from(self.data_address) + self.data.len() <= MEMORY,
+            "the image doesn't fit in memory"
+        );
+        // Both lengths are at most 256 after the check
+        let length = |segment: &[u8]| u16::try_from(segment.len()).unwrap_or(u16::MAX);
+        let mut bytes = Vec::with_capacity(HEADER_LENGTH + self.code.len() + self.data.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&[FORMAT_VERSION, ENCODING_VERSION]);
+        bytes.extend_from_slice(&self.metadata.to_bytes());
+        bytes.extend_from_slice(&length(&self.code).to_le_bytes());
+        bytes.extend_from_slice(&[self.entry, self.data_address]);
+        bytes.extend_from_slice(&length(&self.data).to_le_bytes());
+        bytes.extend_from_slice(&self.code);
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Decodes an image, checking the header before the segments are read. The metadata isn't
+    /// checked, see [`Metadata::check`].
+    ///
+    /// # Errors
+    /// Returns an error if the bytes are truncated, don't start with the magic bytes, use another
+    /// format or encoding version, or describe segments that don't fit in memory
+    pub fn decode(bytes: &[u8]) -> Result<Self, ImageError> {
+        let truncated = |expected| ImageError::Truncated {
+            expected,
+            found: bytes.len(),
+        };
+        let magic = &bytes[..bytes.len().min(MAGIC.len())];
+        if magic != &MAGIC[..magic.len()] {
+            return Err(ImageError::Magic);
+        }
+        let header: &[u8; HEADER_LENGTH] = bytes
+            .get(..HEADER_LENGTH)
+            .and_then(|header| header.try_into().ok())
+            .ok_or_else(|| truncated(HEADER_LENGTH))?;
+        let [_, _, _, _, version, encoding, m0, m1, m2, m3, c0, c1, entry, data_address, d0, d1] =
+            *header;
+        if version != FORMAT_VERSION {
+            return Err(ImageError::Version(version));
+        }
+        check_encoding(encoding)?;
+        let code_length = usize::from(u16::from_le_bytes([c0, c1]));
+        let data_length = usize::from(u16::from_le_bytes([d0, d1]));
+        if code_length > MEMORY || usize::from(data_address) + data_length > MEMORY {
+            return Err(ImageError::TooLarge);
+        }
+        if usize::from(entry) >= code_length.max(1) {
+            return Err(ImageError::Entry(entry));
+        }
+        let end = HEADER_LENGTH + code_length + data_length;
+        let segments = bytes
+            .get(HEADER_LENGTH..end)
+            .ok_or_else(|| truncated(end))?;
+        if bytes.len() > end {
+            return Err(ImageError::TrailingBytes);
+        }
+        let (code, data) = segments.split_at(code_length);
+        Ok(Self {
+            metadata: Metadata::from_bytes([m0, m1, m2, m3]),
+            entry,
+            code: code.into(),
+            data_address,
+            data: data.into(),
+        })
+    }
+
+    /// Loads the image into a computer with the bus, see [`Computer::load_image`]
+    ///
+    /// # Errors
+    /// Returns an error if the code or the data segment doesn't fit in memory
+    pub fn load<'d>(&self, bus: Bus<'d>) -> Result<Computer<'d>, ExecutionError> {
+        Computer::load_image(&self.code, (self.data_address, &self.data), self.entry, bus)
+    }
+
+    /// Loads the image with the initial state, see [`Computer::load_image_with_state`]
+    ///
+    /// # Errors
+    /// Returns an error if the code or the data segment doesn't fit in memory, or the initial
+    /// state sets a memory cell of the image without allowing the overlap
+    pub fn load_with_state<'d>(
+        &self,
+        initial: &InitialState,
+        bus: Bus<'d>,
+    ) -> Result<Computer<'d>, ExecutionError> {
+        Computer::load_image_with_state(
+            &self.code,
+            (self.data_address, &self.data),
+            self.entry,
+            initial,
+            bus,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use michael_computer::{
+        bus::{Bus, ConsoleDevice},
+        error::{ErrorKind, McError},
+        StopReason,
+    };
+
+    use super::{ImageError, ProgramImage, HEADER_LENGTH};
+    use crate::{
+        metadata::{EncodingMismatch, Metadata},
+        parser::Parser,
+    };
+
+    /// Prints the text in the data segment, the halt at offset 0 is skipped by the entry
+    const PRINT: &str = "
+        hlt
+start:  ldi r3, 0x80
+loop:   ld r0, [r3]
+        jz r0, done
+        st r0, [255]
+        inc r3
+        jmp loop
+done:   hlt
+";
+
+    fn image() -> ProgramImage {
+        let code = Parser::new().assemble(PRINT).unwrap();
+        ProgramImage::new(code)
+            .with_entry(1)
+            .with_data(0x80, b"Hello, world!\0".to_vec())
+    }
+
+    #[test]
+    fn load_and_run() {
+        let bytes = image().encode();
+        assert_eq!(bytes.len(), HEADER_LENGTH + 13 + 14);
+        let decoded = ProgramImage::decode(&bytes).unwrap();
+        assert_eq!(decoded, image());
+        assert_eq!(decoded.metadata.check(true), Ok(None));
+        // The metadata of another toolchain is kept
+        let mut old = image();
+        old.metadata = Metadata::from_bytes([0, 0, 1, 0]);
+        assert_eq!(ProgramImage::decode(&old.encode()), Ok(old));
+
+        let mut console = ConsoleDevice::new(String::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let mut computer = decoded.load(bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(console.output(), "Hello, world!");
+    }
+
+    #[test]
+    fn header() {
+        let bytes = image().encode();
+        let mut expected = b"MCPI\x01\x01".to_vec();
+        expected.extend_from_slice(&Metadata::CURRENT.to_bytes());
+        expected.extend_from_slice(&[13, 0, 1, 0x80, 14, 0]);
+        assert_eq!(bytes[..HEADER_LENGTH], expected);
+        let empty = ProgramImage::new(Vec::new()).encode();
+        assert_eq!(
+            ProgramImage::decode(&empty),
+            Ok(ProgramImage::new(Vec::new()))
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = image().encode();
+        for length in [0, 3, 4, HEADER_LENGTH - 1] {
+            assert_eq!(
+                ProgramImage::decode(&bytes[..length]),
+                Err(ImageError::Truncated {
+                    expected: HEADER_LENGTH,
+                    found: length
+                })
+            );
+        }
+        let error = ProgramImage::decode(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(
+            error,
+            ImageError::Truncated {
+                expected: bytes.len(),
+                found: bytes.len() - 1
+            }
+        );
+        assert_eq!(error.kind(), ErrorKind::Format);
+        let mut longer = bytes;
+        longer.push(0);
+        assert_eq!(
+            ProgramImage::decode(&longer),
+            Err(ImageError::TrailingBytes)
+        );
+    }
+
+    #[test]
+    fn invalid_header() {
+        let bytes = image().encode();
+        let with = |offset: usize, value: u8| {
+            let mut bytes = bytes.clone();
+            bytes[offset] = value;
+            ProgramImage::decode(&bytes)
+        };
+        assert_eq!(with(0, b'X'), Err(ImageError::Magic));
+        // A raw program is rejected on its first bytes
+        assert_eq!(ProgramImage::decode(&[0x00, 0x41]), Err(ImageError::Magic));
+        assert_eq!(with(4, 2), Err(ImageError::Version(2)));
+        assert_eq!(
+            with(5, 0),
+            Err(ImageError::Encoding(EncodingMismatch { found: 0 }))
+        );
+        assert_eq!(with(11, 1), Err(ImageError::TooLarge));
+        assert_eq!(with(13, 0xF3), Err(ImageError::TooLarge));
+        assert_eq!(with(12, 13), Err(ImageError::Entry(13)));
+    }
+
+    #[test]
+    #[should_panic = "doesn't fit in memory"]
+    fn encode_too_large() {
+        let _ = ProgramImage::new(vec![0; 257]).encode();
+    }
+}
@@ -1,5 +1,21 @@
 //! This library contains the instructions and registers for the assembly language.
+//!
+//! The crate is `no_std`. The `alloc` feature (enabled by default) adds [`Instruction::to_bytes`],
+//! without it programs can be encoded into a buffer:
+//!
+//! ```
+//! use assembler::{Instruction, Register};
+//!
+//! let mut buffer = [0; 4];
+//! let program = [
+//!     Instruction::LoadConstant(Register::R0, b'!'),
+//!     Instruction::StoreMemory(Register::R0, 255),
+//! ];
+//! assert_eq!(Instruction::encode_into(program, &mut buffer), Some(4));
+//! assert_eq!(buffer, [0x00, b'!', 0x08, 0xFF]);
+//! ```
 
+#![no_std]
 #![warn(
     clippy::pedantic,
     clippy::nursery,
@@ -7,27 +23,45 @@
     missing_docs
 )]
 
-/// The type representing a register index
-#[expect(missing_docs)]
-pub enum Register {
-    R0,
-    R1,
-    R2,
-    R3,
-}
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
-impl From<Register> for u8 {
-    fn from(value: Register) -> Self {
-        match value {
-            Register::R0 => 0,
-            Register::R1 => 1,
-            Register::R2 => 2,
-            Register::R3 => 3,
-        }
-    }
-}
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+pub mod addresses;
+#[cfg(feature = "alloc")]
+pub mod analyze;
+#[cfg(feature = "alloc")]
+pub mod builder;
+#[cfg(feature = "alloc")]
+pub mod disassemble;
+pub mod error;
+#[cfg(feature = "alloc")]
+pub mod explain;
+#[cfg(feature = "alloc")]
+pub mod fixed;
+#[cfg(test)]
+mod format_stability;
+#[cfg(feature = "alloc")]
+pub mod ihex;
+#[cfg(feature = "alloc")]
+pub mod image;
+pub mod metadata;
+#[cfg(feature = "alloc")]
+pub mod optimize;
+#[cfg(feature = "alloc")]
+pub mod parser;
+
+use core::fmt::{self, Display, Formatter};
+
+pub use michael_computer::mux::byte::Register;
+use michael_computer::opcode::{
+    self, decode_opcode, decode_prefix, decode_prefixed, DecodedOp, Operand, RegisterPrefix,
+};
 
 /// The instructions for the computer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     /// Loads the next byte in the program as value for the register.
     LoadConstant(Register, u8),
@@ -76,6 +110,103 @@ pub enum Instruction {
     /// Subtracts the right register to the left and overflow, stores the result in the left
     /// register, stores whether it overflowed.
     SubOverflow(Register, Register),
+
+    /// Continues execution at the address.
+    Jump(u8),
+
+    /// Continues execution at the address if the last arithmetic instruction overflowed.
+    JumpIfOverflow(u8),
+
+    /// Pushes the offset of the next instruction onto the stack and continues execution at the
+    /// address.
+    Call(u8),
+
+    /// Pops the offset pushed by a call and continues execution there.
+    Return,
+
+    /// Continues execution at the address if the register is zero.
+    JumpIfZero(Register, u8),
+
+    /// Stores the value of the register at the stack pointer and increments the stack pointer.
+    Push(Register),
+
+    /// Decrements the stack pointer and loads the byte at the stack pointer into the register.
+    Pop(Register),
+
+    /// Shifts the register one bit to the left, stores the shifted out bit as overflow.
+    ShiftLeft(Register),
+
+    /// Shifts the register one bit to the right, stores the shifted out bit as overflow.
+    ShiftRight(Register),
+
+    /// Rotates the register one bit to the left, the top bit moves to the bottom and is stored as
+    /// overflow.
+    RotateLeft(Register),
+
+    /// Rotates the register one bit to the right, the bottom bit moves to the top and is stored as
+    /// overflow.
+    RotateRight(Register),
+
+    /// Shifts the byte at the address one bit to the left, stores the shifted out bit as overflow.
+    ShiftLeftMemory(u8),
+
+    /// Shifts the byte at the address one bit to the right, stores the shifted out bit as
+    /// overflow.
+    ShiftRightMemory(u8),
+
+    /// Shifts the byte at the address one bit to the left, shifts the overflow in and stores the
+    /// shifted out bit as overflow.
+    RotateLeftMemory(u8),
+
+    /// Shifts the byte at the address one bit to the right, shifts the overflow in and stores the
+    /// shifted out bit as overflow.
+    RotateRightMemory(u8),
+
+    /// Adds one to the register, stores whether it wrapped from 255 to 0 as overflow.
+    Inc(Register),
+
+    /// Subtracts one from the register, stores whether it wrapped from 0 to 255 as overflow.
+    Dec(Register),
+
+    /// Selects the memory bank in the lowest 4 bits of the register for the following loads and
+    /// stores. Instructions and the stack stay in bank 0 and the devices are mapped in every bank.
+    SetBank(Register),
+
+    /// Sets the overflow, like a carry into the next addition.
+    SetCarry,
+
+    /// Clears the overflow, so the next addition with overflow doesn't add a carry.
+    ClearCarry,
+
+    /// Loads the byte from the address held by the right register into the left register.
+    LoadIndirect(Register, Register),
+
+    /// Stores the value of the left register at the address held by the right register.
+    StoreIndirect(Register, Register),
+
+    /// Multiplies the 2 registers, stores the low byte of the product in the left register, stores
+    /// whether the high byte is nonzero as overflow.
+    Mul(Register, Register),
+
+    /// Divides the left register by the right register, stores the quotient in the left register.
+    /// Dividing by zero stores 0xFF and sets the overflow, otherwise the overflow is cleared.
+    Div(Register, Register),
+
+    /// Divides the left register by the right register, stores the remainder in the left
+    /// register. Dividing by zero keeps the left register and sets the overflow, otherwise the
+    /// overflow is cleared.
+    Rem(Register, Register),
+
+    /// Copies a block of memory from the address in the right register to the address in the left
+    /// register, the length of the block is in r0. Overlapping blocks are copied like `memmove`.
+    Copy(Register, Register),
+
+    /// Corrects the register holding the binary sum of two packed BCD bytes to their decimal sum,
+    /// with the overflow and the half carry of the addition. Stores the decimal carry as overflow.
+    DecimalAdjust(Register),
+
+    /// Stops the execution of the program.
+    Halt,
 }
 
 /*
@@ -94,48 +225,701 @@ pub enum Instruction {
 1001 RTRF | Add with overflow
 1010 RTRF | Sub
 1011 RTRF | Sub with overflow
+1100 0000 | Jump
+1100 0001 | Jump if overflow
+1100 0010 | Call
+1100 0011 | Return
+1100 01RR | Jump if zero
+1100 10RF | Push
+1100 11RT | Pop
+1101 00RR | Shift left
+1101 01RR | Shift right
+1101 10RR | Rotate left
+1101 11RR | Rotate right
+1110 0000 | Shift memory left
+1110 0001 | Shift memory right
+1110 0010 | Rotate memory left
+1110 0011 | Rotate memory right
+1110 01RR | Increment
+1110 10RR | Decrement
+1111 0000 | Set carry
+1111 0001 | Clear carry
+1111 0010 | Load indirect, next byte 0000 RTRA
+1111 0011 | Store indirect, next byte 0000 RFRA
+1111 0100 | Multiply, next byte 0000 RTRF
+1111 0101 | Divide, next byte 0000 RTRF
+1111 0110 | Remainder, next byte 0000 RTRF
+1111 0111 | Copy, next byte 0000 RARA
+1111 1100 | Decimal adjust, next byte 0000 00RR
+1111 10HL | Register prefix, the next byte is the opcode
+1111 1111 | Halt
+
+RA = register holding the address, the upper 4 bits of a register byte are reserved and 0
+H, L = the register in bits 2-3 or bits 0-1 of the opcode or register byte is r4 to r7
 */
 impl Instruction {
+    /// Returns the encoding of the instruction, only the first `length` bytes are used. An
+    /// instruction using `r4` to `r7` starts with a register prefix.
+    #[must_use]
+    pub fn encode(self) -> ([u8; 3], usize) {
+        let ([opcode, operand], length) = self.encode_fields();
+        let (high, low) = self.register_fields();
+        let prefix = RegisterPrefix {
+            high: high.is_some_and(Register::is_extended),
+            low: low.is_some_and(Register::is_extended),
+        };
+        if prefix.high || prefix.low {
+            ([prefix.byte(), opcode, operand], length + 1)
+        } else {
+            ([opcode, operand, 0], length)
+        }
+    }
+
+    /// Returns the registers in bits 2-3 and bits 0-1 of the opcode or register byte
+    const fn register_fields(self) -> (Option<Register>, Option<Register>) {
+        match self {
+            Self::Move(left, right)
+            | Self::Nand(left, right)
+            | Self::And(left, right)
+            | Self::Nor(left, right)
+            | Self::Or(left, right)
+            | Self::Xnor(left, right)
+            | Self::Xor(left, right)
+            | Self::Add(left, right)
+            | Self::AddOverflow(left, right)
+            | Self::Sub(left, right)
+            | Self::SubOverflow(left, right)
+            | Self::LoadIndirect(left, right)
+            | Self::StoreIndirect(left, right)
+            | Self::Mul(left, right)
+            | Self::Div(left, right)
+            | Self::Rem(left, right)
+            | Self::Copy(left, right) => (Some(left), Some(right)),
+            Self::LoadConstant(register, _)
+            | Self::LoadMemory(register, _)
+            | Self::StoreMemory(register, _)
+            | Self::Not(register)
+            | Self::JumpIfZero(register, _)
+            | Self::Push(register)
+            | Self::Pop(register)
+            | Self::ShiftLeft(register)
+            | Self::ShiftRight(register)
+            | Self::RotateLeft(register)
+            | Self::RotateRight(register)
+            | Self::Inc(register)
+            | Self::Dec(register)
+            | Self::SetBank(register)
+            | Self::DecimalAdjust(register) => (None, Some(register)),
+            _ => (None, None),
+        }
+    }
+
+    /// Returns the encoding of the instruction without the register prefix, with the lowest 2
+    /// bits of the register numbers in the fields
+    fn encode_fields(self) -> ([u8; 2], usize) {
+        let field = |register: Register| u8::from(register) & 0b11;
+        let pair = |opcode: u8, left: Register, right: Register| {
+            ([opcode | (field(left) << 2) | field(right), 0], 1)
+        };
+        let register_byte = |opcode: u8, left: Register, right: Register| {
+            ([opcode, (field(left) << 2) | field(right)], 2)
+        };
+        match self {
+            Self::LoadConstant(register, value) => ([field(register), value], 2),
+            Self::LoadMemory(register, address) => ([0b0000_0100 | field(register), address], 2),
+            Self::StoreMemory(register, address) => ([0b0000_1000 | field(register), address], 2),
+            Self::Not(register) => ([0b0000_1100 | field(register), 0], 1),
+            Self::Move(left, right) => pair(0b0001_0000, left, right),
+            Self::Nand(left, right) => pair(0b0010_0000, left, right),
+            Self::And(left, right) => pair(0b0011_0000, left, right),
+            Self::Nor(left, right) => pair(0b0100_0000, left, right),
+            Self::Or(left, right) => pair(0b0101_0000, left, right),
+            Self::Xnor(left, right) => pair(0b0110_0000, left, right),
+            Self::Xor(left, right) => pair(0b0111_0000, left, right),
+            Self::Add(left, right) => pair(0b1000_0000, left, right),
+            Self::AddOverflow(left, right) => pair(0b1001_0000, left, right),
+            Self::Sub(left, right) => pair(0b1010_0000, left, right),
+            Self::SubOverflow(left, right) => pair(0b1011_0000, left, right),
+            Self::Jump(address) => ([0b1100_0000, address], 2),
+            Self::JumpIfOverflow(address) => ([0b1100_0001, address], 2),
+            Self::Call(address) => ([0b1100_0010, address], 2),
+            Self::Return => ([0b1100_0011, 0], 1),
+            Self::JumpIfZero(register, address) => ([0b1100_0100 | field(register), address], 2),
+            Self::Push(register) => ([0b1100_1000 | field(register), 0], 1),
+            Self::Pop(register) => ([0b1100_1100 | field(register), 0], 1),
+            Self::ShiftLeft(register) => ([0b1101_0000 | field(register), 0], 1),
+            Self::ShiftRight(register) => ([0b1101_0100 | field(register), 0], 1),
+            Self::RotateLeft(register) => ([0b1101_1000 | field(register), 0], 1),
+            Self::RotateRight(register) => ([0b1101_1100 | field(register), 0], 1),
+            Self::ShiftLeftMemory(address) => ([0b1110_0000, address], 2),
+            Self::ShiftRightMemory(address) => ([0b1110_0001, address], 2),
+            Self::RotateLeftMemory(address) => ([0b1110_0010, address], 2),
+            Self::RotateRightMemory(address) => ([0b1110_0011, address], 2),
+            Self::Inc(register) => ([0b1110_0100 | field(register), 0], 1),
+            Self::Dec(register) => ([0b1110_1000 | field(register), 0], 1),
+            Self::SetBank(register) => ([0b1110_1100 | field(register), 0], 1),
+            Self::SetCarry => ([0b1111_0000, 0], 1),
+            Self::ClearCarry => ([0b1111_0001, 0], 1),
+            Self::LoadIndirect(target, address) => register_byte(0b1111_0010, target, address),
+            Self::StoreIndirect(source, address) => register_byte(0b1111_0011, source, address),
+            Self::Mul(left, right) => register_byte(0b1111_0100, left, right),
+            Self::Div(left, right) => register_byte(0b1111_0101, left, right),
+            Self::Rem(left, right) => register_byte(0b1111_0110, left, right),
+            Self::Copy(destination, source) => register_byte(0b1111_0111, destination, source),
+            Self::DecimalAdjust(register) => ([0b1111_1100, field(register)], 2),
+            Self::Halt => ([0b1111_1111, 0], 1),
+        }
+    }
+
+    /// Returns the number of cycles the instruction takes on the computer, to estimate the cost of
+    /// a program without running it
+    #[must_use]
+    pub fn cycles(self) -> u8 {
+        let (bytes, _) = self.encode();
+        match decode_prefix(bytes[0]) {
+            Some(_) => opcode::cycles(bytes[1]) + 1,
+            None => opcode::cycles(bytes[0]),
+        }
+    }
+
+    /// Returns the mnemonic of the instruction, without the operands
+    #[must_use]
+    pub const fn mnemonic(self) -> &'static str {
+        match self {
+            Self::LoadConstant(..) => "ldi",
+            Self::LoadMemory(..) | Self::LoadIndirect(..) => "ld",
+            Self::StoreMemory(..) | Self::StoreIndirect(..) => "st",
+            Self::Not(_) => "not",
+            Self::Move(..) => "mov",
+            Self::Nand(..) => "nand",
+            Self::And(..) => "and",
+            Self::Nor(..) => "nor",
+            Self::Or(..) => "or",
+            Self::Xnor(..) => "xnor",
+            Self::Xor(..) => "xor",
+            Self::Add(..) => "add",
+            Self::AddOverflow(..) => "adc",
+            Self::Sub(..) => "sub",
+            Self::SubOverflow(..) => "sbc",
+            Self::Jump(_) => "jmp",
+            Self::JumpIfOverflow(_) => "jo",
+            Self::Call(_) => "call",
+            Self::Return => "ret",
+            Self::JumpIfZero(..) => "jz",
+            Self::Push(_) => "push",
+            Self::Pop(_) => "pop",
+            Self::ShiftLeft(_) | Self::ShiftLeftMemory(_) => "shl",
+            Self::ShiftRight(_) | Self::ShiftRightMemory(_) => "shr",
+            Self::RotateLeft(_) | Self::RotateLeftMemory(_) => "rol",
+            Self::RotateRight(_) | Self::RotateRightMemory(_) => "ror",
+            Self::Inc(_) => "inc",
+            Self::Dec(_) => "dec",
+            Self::SetBank(_) => "bank",
+            Self::SetCarry => "stc",
+            Self::ClearCarry => "clc",
+            Self::Mul(..) => "mul",
+            Self::Div(..) => "div",
+            Self::Rem(..) => "rem",
+            Self::Copy(..) => "copy",
+            Self::DecimalAdjust(_) => "daa",
+            Self::Halt => "hlt",
+        }
+    }
+
+    /// Decodes the instruction at the start of the bytes, returns it with its length.
+    /// Returns `None` if the first byte isn't an instruction or its operand is missing.
+    #[must_use]
+    pub fn decode(bytes: &[u8]) -> Option<(Self, usize)> {
+        let (&first, rest) = bytes.split_first()?;
+        let (prefix, op, rest) = match decode_prefix(first) {
+            Some(prefix) => {
+                let (&opcode, rest) = rest.split_first()?;
+                (Some(prefix), decode_prefixed(prefix, opcode).ok()?, rest)
+            }
+            None => (None, decode_opcode(first).ok()?, rest),
+        };
+        let operand = match op.operand() {
+            Operand::None => 0,
+            Operand::Byte | Operand::Registers => *rest.first()?,
+        };
+        // The registers of a register byte, its upper 4 bits are reserved and so are bits 2-3
+        // without a register there
+        let (high, low) = prefix.map_or((false, false), |prefix| (prefix.high, prefix.low));
+        let (left, right) = (register(operand >> 2, high), register(operand, low));
+        let reserved = if op.register_fields().0 { 4 } else { 2 };
+        if op.operand() == Operand::Registers && operand >> reserved != 0 {
+            return None;
+        }
+        let instruction = match op {
+            DecodedOp::LoadConstant(register) => Self::LoadConstant(register, operand),
+            DecodedOp::LoadMemory(register) => Self::LoadMemory(register, operand),
+            DecodedOp::StoreMemory(register) => Self::StoreMemory(register, operand),
+            DecodedOp::Not(register) => Self::Not(register),
+            DecodedOp::Move(target, source) => Self::Move(target, source),
+            DecodedOp::Nand(target, source) => Self::Nand(target, source),
+            DecodedOp::And(target, source) => Self::And(target, source),
+            DecodedOp::Nor(target, source) => Self::Nor(target, source),
+            DecodedOp::Or(target, source) => Self::Or(target, source),
+            DecodedOp::Xnor(target, source) => Self::Xnor(target, source),
+            DecodedOp::Xor(target, source) => Self::Xor(target, source),
+            DecodedOp::Add(target, source) => Self::Add(target, source),
+            DecodedOp::AddOverflow(target, source) => Self::AddOverflow(target, source),
+            DecodedOp::Sub(target, source) => Self::Sub(target, source),
+            DecodedOp::SubOverflow(target, source) => Self::SubOverflow(target, source),
+            DecodedOp::Jump => Self::Jump(operand),
+            DecodedOp::JumpIfOverflow => Self::JumpIfOverflow(operand),
+            DecodedOp::Call => Self::Call(operand),
+            DecodedOp::Return => Self::Return,
+            DecodedOp::JumpIfZero(register) => Self::JumpIfZero(register, operand),
+            DecodedOp::Push(register) => Self::Push(register),
+            DecodedOp::Pop(register) => Self::Pop(register),
+            DecodedOp::ShiftLeft(register) => Self::ShiftLeft(register),
+            DecodedOp::ShiftRight(register) => Self::ShiftRight(register),
+            DecodedOp::RotateLeft(register) => Self::RotateLeft(register),
+            DecodedOp::RotateRight(register) => Self::RotateRight(register),
+            DecodedOp::ShiftLeftMemory => Self::ShiftLeftMemory(operand),
+            DecodedOp::ShiftRightMemory => Self::ShiftRightMemory(operand),
+            DecodedOp::RotateLeftMemory => Self::RotateLeftMemory(operand),
+            DecodedOp::RotateRightMemory => Self::RotateRightMemory(operand),
+            DecodedOp::Inc(register) => Self::Inc(register),
+            DecodedOp::Dec(register) => Self::Dec(register),
+            DecodedOp::SetBank(register) => Self::SetBank(register),
+            DecodedOp::SetCarry => Self::SetCarry,
+            DecodedOp::ClearCarry => Self::ClearCarry,
+            DecodedOp::LoadIndirect => Self::LoadIndirect(left, right),
+            DecodedOp::StoreIndirect => Self::StoreIndirect(left, right),
+            DecodedOp::Mul => Self::Mul(left, right),
+            DecodedOp::Div => Self::Div(left, right),
+            DecodedOp::Rem => Self::Rem(left, right),
+            DecodedOp::Copy => Self::Copy(left, right),
+            DecodedOp::DecimalAdjust => Self::DecimalAdjust(right),
+            DecodedOp::Halt => Self::Halt,
+        };
+        Some((instruction, op.length() + usize::from(prefix.is_some())))
+    }
+
+    /// Converts a stream of instructions to a stream of bytes
+    pub fn encode_iter(instructions: impl IntoIterator<Item = Self>) -> impl Iterator<Item = u8> {
+        instructions.into_iter().flat_map(|instruction| {
+            let (bytes, length) = instruction.encode();
+            bytes.into_iter().take(length)
+        })
+    }
+
+    /// Writes a stream of instructions into the buffer.
+    /// Returns the number of bytes written, or `None` if the buffer is too small.
+    pub fn encode_into(
+        instructions: impl IntoIterator<Item = Self>,
+        buffer: &mut [u8],
+    ) -> Option<usize> {
+        let mut length = 0;
+        for byte in Self::encode_iter(instructions) {
+            *buffer.get_mut(length)? = byte;
+            length += 1;
+        }
+        Some(length)
+    }
+
     /// Converts a stream of instructions to a vector of bytes
+    #[cfg(feature = "alloc")]
     pub fn to_bytes(instructions: impl IntoIterator<Item = Self>) -> Vec<u8> {
-        instructions
-            .into_iter()
-            .fold(Vec::new(), |mut result, instruction| {
-                match instruction {
-                    Self::LoadConstant(register, value) => {
-                        result.extend_from_slice(&[u8::from(register), value]);
-                    }
-                    Self::LoadMemory(register, address) => {
-                        result.extend_from_slice(&[4 | u8::from(register), address]);
-                    }
-                    Self::StoreMemory(register, address) => {
-                        result.extend_from_slice(&[8 | u8::from(register), address]);
-                    }
-                    Self::Not(register) => result.extend_from_slice(&[12 | u8::from(register)]),
-                    Self::Move(register, register1) => result
-                        .extend_from_slice(&[16 | (u8::from(register) << 2), u8::from(register1)]),
-                    Self::Nand(register, register1) => result
-                        .extend_from_slice(&[32 | (u8::from(register) << 2), u8::from(register1)]),
-                    Self::And(register, register1) => result
-                        .extend_from_slice(&[48 | (u8::from(register) << 2), u8::from(register1)]),
-                    Self::Nor(register, register1) => result
-                        .extend_from_slice(&[64 | (u8::from(register) << 2), u8::from(register1)]),
-                    Self::Or(register, register1) => result
-                        .extend_from_slice(&[80 | (u8::from(register) << 2), u8::from(register1)]),
-                    Self::Xnor(register, register1) => result
-                        .extend_from_slice(&[96 | (u8::from(register) << 2), u8::from(register1)]),
-                    Self::Xor(register, register1) => result
-                        .extend_from_slice(&[112 | (u8::from(register) << 2), u8::from(register1)]),
-                    Self::Add(register, register1) => result
-                        .extend_from_slice(&[128 | (u8::from(register) << 2), u8::from(register1)]),
-                    Self::AddOverflow(register, register1) => result
-                        .extend_from_slice(&[144 | (u8::from(register) << 2), u8::from(register1)]),
-                    Self::Sub(register, register1) => result
-                        .extend_from_slice(&[160 | (u8::from(register) << 2), u8::from(register1)]),
-                    Self::SubOverflow(register, register1) => result
-                        .extend_from_slice(&[172 | (u8::from(register) << 2), u8::from(register1)]),
-                }
-                result
-            })
+        Self::encode_iter(instructions).collect()
+    }
+}
+
+/// Returns the register selected by the lowest 2 bits, plus 4 if it's extended by a prefix
+const fn register(bits: u8, extended: bool) -> Register {
+    Register::from_number(bits & 0b11 | (extended as u8) << 2)
+}
+
+/// Writes the instruction as its mnemonic with the operands, like `add r0, r1` or `ld r2, [0x10]`
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let r = u8::from;
+        match *self {
+            Self::LoadConstant(register, value) => write!(f, "ldi r{}, {value:#04x}", r(register)),
+            Self::LoadMemory(register, address) => {
+                write!(f, "ld r{}, [{address:#04x}]", r(register))
+            }
+            Self::StoreMemory(register, address) => {
+                write!(f, "st r{}, [{address:#04x}]", r(register))
+            }
+            Self::Not(register)
+            | Self::Push(register)
+            | Self::Pop(register)
+            | Self::ShiftLeft(register)
+            | Self::ShiftRight(register)
+            | Self::RotateLeft(register)
+            | Self::RotateRight(register)
+            | Self::Inc(register)
+            | Self::Dec(register)
+            | Self::SetBank(register)
+            | Self::DecimalAdjust(register) => write!(f, "{} r{}", self.mnemonic(), r(register)),
+            Self::Move(left, right)
+            | Self::Nand(left, right)
+            | Self::And(left, right)
+            | Self::Nor(left, right)
+            | Self::Or(left, right)
+            | Self::Xnor(left, right)
+            | Self::Xor(left, right)
+            | Self::Add(left, right)
+            | Self::AddOverflow(left, right)
+            | Self::Sub(left, right)
+            | Self::SubOverflow(left, right)
+            | Self::Mul(left, right)
+            | Self::Div(left, right)
+            | Self::Rem(left, right)
+            | Self::Copy(left, right) => {
+                write!(f, "{} r{}, r{}", self.mnemonic(), r(left), r(right))
+            }
+            Self::Jump(address) | Self::JumpIfOverflow(address) | Self::Call(address) => {
+                write!(f, "{} {address}", self.mnemonic())
+            }
+            Self::JumpIfZero(register, address) => write!(f, "jz r{}, {address}", r(register)),
+            Self::ShiftLeftMemory(address)
+            | Self::ShiftRightMemory(address)
+            | Self::RotateLeftMemory(address)
+            | Self::RotateRightMemory(address) => {
+                write!(f, "{} [{address:#04x}]", self.mnemonic())
+            }
+            Self::LoadIndirect(register, address) | Self::StoreIndirect(register, address) => {
+                write!(f, "{} r{}, [r{}]", self.mnemonic(), r(register), r(address))
+            }
+            Self::Return | Self::SetCarry | Self::ClearCarry | Self::Halt => {
+                f.write_str(self.mnemonic())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use heapless::String;
+    use michael_computer::{bus::Bus, opcode::decode_opcode, Computer};
+
+    use crate::{Instruction, Register};
+
+    #[test]
+    fn register_instructions() {
+        let mut buffer = [0; 16];
+        let program = [
+            Instruction::Not(Register::R2),
+            Instruction::Move(Register::R1, Register::R3),
+            Instruction::Nand(Register::R0, Register::R1),
+            Instruction::And(Register::R3, Register::R2),
+            Instruction::Nor(Register::R1, Register::R1),
+            Instruction::Or(Register::R2, Register::R0),
+            Instruction::Xnor(Register::R0, Register::R3),
+            Instruction::Xor(Register::R3, Register::R3),
+            Instruction::Add(Register::R1, Register::R2),
+            Instruction::AddOverflow(Register::R2, Register::R1),
+            Instruction::Sub(Register::R3, Register::R0),
+            Instruction::SubOverflow(Register::R0, Register::R2),
+        ];
+        assert_eq!(Instruction::encode_into(program, &mut buffer), Some(12));
+        assert_eq!(
+            buffer[..12],
+            [0x0E, 0x17, 0x21, 0x3E, 0x45, 0x58, 0x63, 0x7F, 0x86, 0x99, 0xAC, 0xB2]
+        );
+    }
+
+    #[test]
+    fn register_instructions_run() {
+        use michael_computer::{bus::Bus, Computer};
+
+        let program = [
+            Instruction::LoadConstant(Register::R0, 200),
+            Instruction::LoadConstant(Register::R1, 100),
+            Instruction::Add(Register::R0, Register::R1),
+            Instruction::LoadConstant(Register::R2, 50),
+            // 50 - 44 - the overflow of the addition
+            Instruction::SubOverflow(Register::R2, Register::R0),
+            Instruction::Sub(Register::R1, Register::R2),
+        ];
+        let mut computer = Computer::new(Instruction::encode_iter(program), Bus::new()).unwrap();
+        computer.run().unwrap();
+        assert_eq!(computer.register(Register::R0), 44);
+        assert_eq!(computer.register(Register::R1), 95);
+        assert_eq!(computer.register(Register::R2), 5);
+    }
+
+    #[test]
+    fn buffer_too_small() {
+        let mut buffer = [0; 3];
+        let program = [
+            Instruction::LoadConstant(Register::R1, 5),
+            Instruction::LoadMemory(Register::R2, 6),
+        ];
+        assert_eq!(Instruction::encode_into(program, &mut buffer), None);
+        assert_eq!(buffer, [0x01, 5, 0x06]);
+    }
+
+    #[test]
+    fn jumps() {
+        let program = [
+            Instruction::Jump(0x10),
+            Instruction::JumpIfOverflow(0x20),
+            Instruction::JumpIfZero(Register::R2, 0x30),
+        ];
+        assert!(Instruction::encode_iter(program).eq([0xC0, 0x10, 0xC1, 0x20, 0xC6, 0x30]));
+    }
+
+    #[test]
+    fn memory_shifts() {
+        let program = [
+            Instruction::ShiftLeftMemory(0x10),
+            Instruction::ShiftRightMemory(0x11),
+            Instruction::RotateLeftMemory(0x12),
+            Instruction::RotateRightMemory(0x13),
+        ];
+        assert!(
+            Instruction::encode_iter(program).eq([0xE0, 0x10, 0xE1, 0x11, 0xE2, 0x12, 0xE3, 0x13])
+        );
+    }
+
+    #[test]
+    fn register_shifts() {
+        let program = [
+            Instruction::ShiftLeft(Register::R0),
+            Instruction::ShiftRight(Register::R1),
+            Instruction::RotateLeft(Register::R2),
+            Instruction::RotateRight(Register::R3),
+        ];
+        assert!(Instruction::encode_iter(program).eq([0xD0, 0xD5, 0xDA, 0xDF]));
+    }
+
+    #[test]
+    fn indirect() {
+        let program = [
+            Instruction::LoadIndirect(Register::R0, Register::R1),
+            Instruction::StoreIndirect(Register::R3, Register::R2),
+        ];
+        assert!(Instruction::encode_iter(program).eq([0xF2, 0x01, 0xF3, 0x0E]));
+        // The reserved bits of the register byte have to be 0
+        assert_eq!(Instruction::decode(&[0xF2, 0x11]), None);
+    }
+
+    #[test]
+    fn multiply_divide() {
+        let program = [
+            Instruction::Mul(Register::R1, Register::R2),
+            Instruction::Div(Register::R3, Register::R0),
+            Instruction::Rem(Register::R0, Register::R3),
+        ];
+        assert!(Instruction::encode_iter(program).eq([0xF4, 0x06, 0xF5, 0x0C, 0xF6, 0x03]));
+        assert_eq!(Instruction::decode(&[0xF4, 0x86]), None);
+    }
+
+    #[test]
+    fn copy() {
+        let program = [
+            Instruction::Copy(Register::R1, Register::R2),
+            Instruction::Copy(Register::R7, Register::R0),
+        ];
+        assert!(Instruction::encode_iter(program).eq([0xF7, 0x06, 0xFA, 0xF7, 0x0C]));
+        assert_eq!(Instruction::cycles(program[0]), 2);
+    }
+
+    #[test]
+    fn decimal_adjust() {
+        let program = [
+            Instruction::DecimalAdjust(Register::R2),
+            Instruction::DecimalAdjust(Register::R5),
+        ];
+        assert!(Instruction::encode_iter(program).eq([0xFC, 0x02, 0xF9, 0xFC, 0x01]));
+        // There is no register in bits 2-3 of the register byte
+        assert_eq!(Instruction::decode(&[0xFC, 0x04]), None);
+        assert_eq!(Instruction::decode(&[0xFA, 0xFC, 0x00]), None);
+    }
+
+    #[test]
+    fn increment_decrement() {
+        let program = [
+            Instruction::Inc(Register::R2),
+            Instruction::Dec(Register::R0),
+        ];
+        assert!(Instruction::encode_iter(program).eq([0xE6, 0xE8]));
+    }
+
+    #[test]
+    fn set_bank() {
+        let program = [
+            Instruction::SetBank(Register::R0),
+            Instruction::SetBank(Register::R3),
+            Instruction::SetBank(Register::R5),
+        ];
+        assert!(Instruction::encode_iter(program).eq([0xEC, 0xEF, 0xF9, 0xED]));
+        assert_eq!(Instruction::SetBank(Register::R2).cycles(), 1);
+    }
+
+    #[test]
+    fn carry() {
+        let program = [Instruction::SetCarry, Instruction::ClearCarry];
+        assert!(Instruction::encode_iter(program).eq([0xF0, 0xF1]));
+    }
+
+    #[test]
+    fn stack() {
+        let program = [
+            Instruction::Push(Register::R1),
+            Instruction::Pop(Register::R2),
+            Instruction::Call(0x20),
+            Instruction::Return,
+        ];
+        assert!(Instruction::encode_iter(program).eq([0xC9, 0xCE, 0xC2, 0x20, 0xC3]));
+    }
+
+    #[test]
+    fn halt() {
+        assert!(Instruction::encode_iter([Instruction::Halt]).eq([0xFF]));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn to_bytes() {
+        let program = [
+            Instruction::LoadConstant(Register::R3, b'A'),
+            Instruction::Add(Register::R3, Register::R0),
+            Instruction::StoreMemory(Register::R3, 255),
+        ];
+        assert_eq!(
+            Instruction::to_bytes(program),
+            [0x03, b'A', 0x8C, 0x0B, 0xFF]
+        );
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        let program = [
+            Instruction::LoadConstant(Register::R1, 0x48),
+            Instruction::LoadMemory(Register::R2, 0x10),
+            Instruction::StoreMemory(Register::R3, 255),
+            Instruction::Not(Register::R1),
+            Instruction::Move(Register::R0, Register::R3),
+            Instruction::Xnor(Register::R2, Register::R1),
+            Instruction::SubOverflow(Register::R3, Register::R0),
+            Instruction::JumpIfOverflow(7),
+            Instruction::Call(3),
+            Instruction::Return,
+            Instruction::JumpIfZero(Register::R2, 9),
+            Instruction::Push(Register::R1),
+            Instruction::Pop(Register::R0),
+            Instruction::ShiftRight(Register::R3),
+            Instruction::RotateLeft(Register::R0),
+            Instruction::RotateRightMemory(0x20),
+            Instruction::Inc(Register::R3),
+            Instruction::Dec(Register::R1),
+            Instruction::SetBank(Register::R2),
+            Instruction::SetCarry,
+            Instruction::ClearCarry,
+            Instruction::LoadIndirect(Register::R2, Register::R3),
+            Instruction::StoreIndirect(Register::R1, Register::R0),
+            Instruction::Mul(Register::R3, Register::R3),
+            Instruction::Div(Register::R1, Register::R2),
+            Instruction::Rem(Register::R2, Register::R1),
+            Instruction::Copy(Register::R3, Register::R1),
+            Instruction::DecimalAdjust(Register::R0),
+            Instruction::Halt,
+        ];
+        let mut buffer = [0; 48];
+        let length = Instruction::encode_into(program, &mut buffer).unwrap();
+        let mut bytes = &buffer[..length];
+        for instruction in program {
+            let (decoded, length) = Instruction::decode(bytes).unwrap();
+            assert_eq!(decoded, instruction);
+            bytes = &bytes[length..];
+        }
+        assert!(bytes.is_empty());
+
+        assert_eq!(Instruction::decode(&[0xFE, 0]), None);
+        assert_eq!(Instruction::decode(&[0x04]), None);
+        assert_eq!(Instruction::decode(&[]), None);
+    }
+
+    #[test]
+    fn decoder_agrees() {
+        for byte in 0..=u8::MAX {
+            let decoded = Instruction::decode(&[byte, 0]);
+            let op = decode_opcode(byte);
+            assert_eq!(decoded.is_some(), op.is_ok(), "{byte:#04X}");
+            if let (Some((instruction, length)), Ok(op)) = (decoded, op) {
+                assert_eq!(length, op.length(), "{byte:#04X}");
+                assert_eq!(instruction.encode(), ([byte, 0, 0], length), "{byte:#04X}");
+            }
+        }
+    }
+
+    #[test]
+    fn extended_registers() {
+        let program = [
+            Instruction::LoadConstant(Register::R5, 7),
+            Instruction::Add(Register::R4, Register::R5),
+            Instruction::Add(Register::R0, Register::R6),
+            Instruction::Mul(Register::R7, Register::R1),
+            Instruction::JumpIfZero(Register::R4, 0x20),
+            Instruction::Push(Register::R3),
+        ];
+        let bytes = [
+            0xF9, 0x01, 7, 0xFB, 0x81, 0xF9, 0x82, 0xFA, 0xF4, 0x0D, 0xF9, 0xC4, 0x20, 0xCB,
+        ];
+        assert!(Instruction::encode_iter(program).eq(bytes));
+        let mut rest = &bytes[..];
+        for instruction in program {
+            let (decoded, length) = Instruction::decode(rest).unwrap();
+            assert_eq!(decoded, instruction);
+            rest = &rest[length..];
+        }
+        // The prefix takes a cycle to fetch
+        assert_eq!(program.map(Instruction::cycles), [3, 2, 2, 11, 3, 1]);
+        // The prefix of a field the instruction doesn't have, and a prefix without an opcode
+        assert_eq!(Instruction::decode(&[0xFA, 0x01, 7]), None);
+        assert_eq!(Instruction::decode(&[0xF9]), None);
+        assert_eq!(Instruction::decode(&[0xF8, 0x01, 7]), None);
+    }
+
+    #[test]
+    fn static_cycles() {
+        let program = [
+            Instruction::LoadConstant(Register::R0, b'!'),
+            Instruction::StoreMemory(Register::R0, 255),
+            Instruction::Div(Register::R0, Register::R1),
+            Instruction::Halt,
+        ];
+        let estimate: u64 = program.iter().map(|&i| u64::from(i.cycles())).sum();
+        assert_eq!(estimate, 2 + 2 + 10 + 1);
+
+        let mut buffer = [0; 8];
+        let length = Instruction::encode_into(program, &mut buffer).unwrap();
+        let mut computer = Computer::new(buffer[..length].iter().copied(), Bus::new()).unwrap();
+        computer.run().unwrap();
+        assert_eq!(computer.cycles(), estimate);
+    }
+
+    #[test]
+    fn mnemonics() {
+        let mut text = String::<128>::new();
+        for instruction in [
+            Instruction::LoadConstant(Register::R0, b'H'),
+            Instruction::LoadMemory(Register::R1, 0x10),
+            Instruction::Add(Register::R0, Register::R1),
+            Instruction::JumpIfZero(Register::R3, 12),
+            Instruction::ShiftLeftMemory(0xF0),
+            Instruction::RotateRight(Register::R2),
+            Instruction::Inc(Register::R1),
+            Instruction::SetBank(Register::R4),
+            Instruction::StoreIndirect(Register::R0, Register::R1),
+            Instruction::Copy(Register::R2, Register::R3),
+            Instruction::DecimalAdjust(Register::R1),
+            Instruction::Halt,
+        ] {
+            write!(text, "{instruction}; ").unwrap();
+        }
+        assert_eq!(
+            text.as_str(),
+            "ldi r0, 0x48; ld r1, [0x10]; add r0, r1; jz r3, 12; shl [0xf0]; ror r2; \
+             inc r1; bank r4; st r0, [r1]; copy r2, r3; daa r1; hlt; "
+        );
     }
 }
@@ -0,0 +1,254 @@
+//! Toolchain metadata for program images.
+
+use core::fmt::{self, Display, Formatter};
+
+use michael_computer::error::{ErrorKind, McError};
+
+/// The level of the instruction set, bumped whenever instructions are added
+pub const ISA_LEVEL: u8 = 14;
+
+/// The version of the byte encoding of the instructions.
+///
+/// Programs are stored as bytes, so the encoding is a wire format: bytes that decode to an
+/// instruction keep meaning that instruction. Adding instructions on unused opcodes only bumps
+/// [`ISA_LEVEL`], like the register prefixes for `r4` to `r7`, which were invalid bytes before.
+/// This version is only bumped when the meaning of existing bytes changes, with a migration note
+/// below, so loaders can reject programs they would misinterpret.
+///
+/// Migration notes:
+/// - 1: the initial encoding
+pub const ENCODING_VERSION: u8 = 1;
+
+/// Checks that a program was encoded with the current [`ENCODING_VERSION`]
+///
+/// # Errors
+/// Returns the mismatch if the program uses another encoding version
+pub const fn check_encoding(version: u8) -> Result<(), EncodingMismatch> {
+    if version == ENCODING_VERSION {
+        Ok(())
+    } else {
+        Err(EncodingMismatch { found: version })
+    }
+}
+
+/// Parses a decimal version component at compile time
+const fn parse_component(text: &str) -> u8 {
+    let bytes = text.as_bytes();
+    let mut value = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        value = value * 10 + (bytes[i] - b'0');
+        i += 1;
+    }
+    value
+}
+
+/// Describes the toolchain a program was assembled with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    /// The major, minor and patch version of the assembler crate
+    pub assembler_version: [u8; 3],
+
+    /// The instruction set level the program was assembled for
+    pub isa_level: u8,
+}
+
+impl Metadata {
+    /// The metadata of this toolchain
+    pub const CURRENT: Self = Self {
+        assembler_version: [
+            parse_component(env!("CARGO_PKG_VERSION_MAJOR")),
+            parse_component(env!("CARGO_PKG_VERSION_MINOR")),
+            parse_component(env!("CARGO_PKG_VERSION_PATCH")),
+        ],
+        isa_level: ISA_LEVEL,
+    };
+
+    /// The number of bytes the metadata takes in a header
+    pub const LENGTH: usize = 4;
+
+    /// Converts the metadata to the bytes stored in a header
+    #[must_use]
+    pub const fn to_bytes(self) -> [u8; Self::LENGTH] {
+        let [major, minor, patch] = self.assembler_version;
+        [major, minor, patch, self.isa_level]
+    }
+
+    /// Reads the metadata from the bytes stored in a header
+    #[must_use]
+    pub const fn from_bytes(bytes: [u8; Self::LENGTH]) -> Self {
+        let [major, minor, patch, isa_level] = bytes;
+        Self {
+            assembler_version: [major, minor, patch],
+            isa_level,
+        }
+    }
+
+    /// Reads the metadata from the header of a program file.
+    ///
+    /// Returns `None` for raw program bytes, which don't start with the
+    /// [`MAGIC`](crate::image::MAGIC) bytes of an image and carry no metadata, and for a header
+    /// too short to hold it. The rest of the header isn't checked, see
+    /// [`ProgramImage::decode`](crate::image::ProgramImage::decode).
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn of_program(bytes: &[u8]) -> Option<Self> {
+        use crate::image::MAGIC;
+
+        if !bytes.starts_with(&MAGIC) {
+            return None;
+        }
+        // The metadata follows the magic bytes, the format version and the encoding version
+        let start = MAGIC.len() + 2;
+        let metadata = bytes.get(start..start + Self::LENGTH)?;
+        metadata.try_into().ok().map(Self::from_bytes)
+    }
+
+    /// Compares the metadata to the current toolchain.
+    ///
+    /// Returns `Ok(None)` if the metadata matches. A mismatch is returned as a warning in
+    /// `Ok(Some(_))`, unless `strict` is set.
+    ///
+    /// # Errors
+    /// Returns the mismatch if the metadata doesn't match and `strict` is set
+    pub fn check(self, strict: bool) -> Result<Option<VersionMismatch>, VersionMismatch> {
+        if self == Self::CURRENT {
+            return Ok(None);
+        }
+        let mismatch = VersionMismatch {
+            found: self,
+            expected: Self::CURRENT,
+        };
+        if strict {
+            Err(mismatch)
+        } else {
+            Ok(Some(mismatch))
+        }
+    }
+}
+
+impl Display for Metadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let [major, minor, patch] = self.assembler_version;
+        write!(
+            f,
+            "assembler {major}.{minor}.{patch}, ISA level {}",
+            self.isa_level
+        )
+    }
+}
+
+/// The metadata of a program doesn't match the current toolchain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionMismatch {
+    /// The metadata of the program
+    pub found: Metadata,
+
+    /// The metadata of the current toolchain
+    pub expected: Metadata,
+}
+
+impl Display for VersionMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "program was built with {}, expected {}",
+            self.found, self.expected
+        )
+    }
+}
+
+impl core::error::Error for VersionMismatch {}
+
+impl McError for VersionMismatch {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Load
+    }
+}
+
+/// A program uses another encoding than [`ENCODING_VERSION`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingMismatch {
+    /// The encoding version of the program
+    pub found: u8,
+}
+
+impl Display for EncodingMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "program uses encoding version {}, expected {ENCODING_VERSION}",
+            self.found
+        )
+    }
+}
+
+impl core::error::Error for EncodingMismatch {}
+
+impl McError for EncodingMismatch {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Format
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use michael_computer::error::{ErrorKind, McError};
+
+    use super::{check_encoding, EncodingMismatch, Metadata, ENCODING_VERSION, ISA_LEVEL};
+
+    /// Metadata of a program built by an older assembler
+    const OLD_FIXTURE: [u8; Metadata::LENGTH] = [0, 0, 1, 0];
+
+    #[test]
+    fn current() {
+        assert_eq!(
+            Metadata::CURRENT.assembler_version.map(u32::from),
+            [
+                env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap(),
+                env!("CARGO_PKG_VERSION_MINOR").parse().unwrap(),
+                env!("CARGO_PKG_VERSION_PATCH").parse().unwrap(),
+            ]
+        );
+        assert_eq!(Metadata::CURRENT.isa_level, ISA_LEVEL);
+        assert_eq!(Metadata::CURRENT.check(true), Ok(None));
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = Metadata::CURRENT.to_bytes();
+        assert_eq!(Metadata::from_bytes(bytes), Metadata::CURRENT);
+        assert_eq!(Metadata::from_bytes(OLD_FIXTURE).to_bytes(), OLD_FIXTURE);
+    }
+
+    #[test]
+    fn mismatch() {
+        let old = Metadata::from_bytes(OLD_FIXTURE);
+        let warning = old.check(false).unwrap().unwrap();
+        assert_eq!(warning.found, old);
+        assert_eq!(warning.expected, Metadata::CURRENT);
+        assert_eq!(old.check(true), Err(warning));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn of_program() {
+        use crate::image::ProgramImage;
+
+        let mut image = ProgramImage::new([0xFF].into());
+        image.metadata = Metadata::from_bytes(OLD_FIXTURE);
+        let bytes = image.encode();
+        assert_eq!(Metadata::of_program(&bytes), Some(image.metadata));
+        // Raw programs and truncated headers carry no metadata
+        assert_eq!(Metadata::of_program(&[0x00, 0x41, 0xFF]), None);
+        assert_eq!(Metadata::of_program(&bytes[..8]), None);
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(check_encoding(ENCODING_VERSION), Ok(()));
+        let mismatch = check_encoding(ENCODING_VERSION + 1).unwrap_err();
+        assert_eq!(mismatch, EncodingMismatch { found: 2 });
+        assert_eq!(mismatch.kind(), ErrorKind::Format);
+    }
+}
@@ -0,0 +1,492 @@
+//! A peephole optimizer, removing instructions that don't change what a program does.
+//!
+//! The rewrites are:
+//! - `mov r, r` is dropped
+//! - of consecutive `ldi` to the same register only the last is kept
+//! - `not r; not r` is dropped
+//! - `ldi r, 0; add x, r` is dropped if `r`, the overflow and the half carry are overwritten
+//!   before they are read again. Only the straight-line code up to the next jump, call, return or
+//!   halt is followed, any of them keeps the instructions.
+//!
+//! The zero and negative flags aren't read by any instruction, so they may differ afterwards.
+//! Jump and call targets move with the instructions, and an instruction that is jumped to is never
+//! folded into the one before it:
+//!
+//! ```
+//! use assembler::{optimize::optimize, Instruction, Register};
+//!
+//! let program = [
+//!     Instruction::LoadConstant(Register::R0, b'A'),
+//!     Instruction::Move(Register::R1, Register::R1),
+//!     Instruction::StoreMemory(Register::R0, 255),
+//!     Instruction::Jump(3),
+//! ];
+//! assert_eq!(
+//!     optimize(program.to_vec()),
+//!     [
+//!         Instruction::LoadConstant(Register::R0, b'A'),
+//!         Instruction::StoreMemory(Register::R0, 255),
+//!         Instruction::Jump(2),
+//!     ]
+//! );
+//! ```
+//!
+//! Memory operands aren't moved with the instructions, so a program is returned unchanged if it
+//! accesses memory at a literal address other than the input and the console, which could be its
+//! own code or data placed after it, or at any address [`address_taken`] reports.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    addresses::{address_taken, literal_address},
+    Instruction, Register,
+};
+
+/// A register or a flag, which instructions read and write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Place {
+    Register(Register),
+    Overflow,
+    HalfCarry,
+}
+
+/// Returns the target of a jump or call
+pub(crate) const fn target(instruction: Instruction) -> Option<u8> {
+    match instruction {
+        Instruction::Jump(address)
+        | Instruction::JumpIfOverflow(address)
+        | Instruction::Call(address)
+        | Instruction::JumpIfZero(_, address) => Some(address),
+        _ => None,
+    }
+}
+
+/// Returns the instruction with its jump or call target replaced
+const fn retarget(instruction: Instruction, address: u8) -> Instruction {
+    match instruction {
+        Instruction::Jump(_) => Instruction::Jump(address),
+        Instruction::JumpIfOverflow(_) => Instruction::JumpIfOverflow(address),
+        Instruction::Call(_) => Instruction::Call(address),
+        Instruction::JumpIfZero(register, _) => Instruction::JumpIfZero(register, address),
+        instruction => instruction,
+    }
+}
+
+/// Returns whether the instruction reads the place
+pub(crate) fn reads(instruction: Instruction, place: Place) -> bool {
+    let register = match place {
+        Place::Register(register) => register,
+        Place::Overflow => {
+            return matches!(
+                instruction,
+                Instruction::AddOverflow(..)
+                    | Instruction::SubOverflow(..)
+                    | Instruction::JumpIfOverflow(_)
+                    | Instruction::RotateLeftMemory(_)
+                    | Instruction::RotateRightMemory(_)
+                    | Instruction::DecimalAdjust(_)
+            )
+        }
+        Place::HalfCarry => return matches!(instruction, Instruction::DecimalAdjust(_)),
+    };
+    match instruction {
+        Instruction::StoreMemory(source, _)
+        | Instruction::Not(source)
+        | Instruction::Move(_, source)
+        | Instruction::JumpIfZero(source, _)
+        | Instruction::Push(source)
+        | Instruction::ShiftLeft(source)
+        | Instruction::ShiftRight(source)
+        | Instruction::RotateLeft(source)
+        | Instruction::RotateRight(source)
+        | Instruction::Inc(source)
+        | Instruction::Dec(source)
+        | Instruction::SetBank(source)
+        | Instruction::LoadIndirect(_, source)
+        | Instruction::DecimalAdjust(source) => source == register,
+        Instruction::Nand(left, right)
+        | Instruction::And(left, right)
+        | Instruction::Nor(left, right)
+        | Instruction::Or(left, right)
+        | Instruction::Xnor(left, right)
+        | Instruction::Xor(left, right)
+        | Instruction::Add(left, right)
+        | Instruction::AddOverflow(left, right)
+        | Instruction::Sub(left, right)
+        | Instruction::SubOverflow(left, right)
+        | Instruction::StoreIndirect(left, right)
+        | Instruction::Mul(left, right)
+        | Instruction::Div(left, right)
+        | Instruction::Rem(left, right) => left == register || right == register,
+        Instruction::Copy(destination, source) => {
+            destination == register || source == register || register == Register::R0
+        }
+        _ => false,
+    }
+}
+
+/// Returns whether the instruction writes the place
+pub(crate) fn writes(instruction: Instruction, place: Place) -> bool {
+    let register = match place {
+        Place::Register(register) => register,
+        Place::Overflow => {
+            return matches!(
+                instruction,
+                Instruction::Add(..)
+                    | Instruction::AddOverflow(..)
+                    | Instruction::Sub(..)
+                    | Instruction::SubOverflow(..)
+                    | Instruction::ShiftLeft(_)
+                    | Instruction::ShiftRight(_)
+                    | Instruction::RotateLeft(_)
+                    | Instruction::RotateRight(_)
+                    | Instruction::ShiftLeftMemory(_)
+                    | Instruction::ShiftRightMemory(_)
+                    | Instruction::RotateLeftMemory(_)
+                    | Instruction::RotateRightMemory(_)
+                    | Instruction::Inc(_)
+                    | Instruction::Dec(_)
+                    | Instruction::SetCarry
+                    | Instruction::ClearCarry
+                    | Instruction::Mul(..)
+                    | Instruction::Div(..)
+                    | Instruction::Rem(..)
+                    | Instruction::DecimalAdjust(_)
+            )
+        }
+        Place::HalfCarry => {
+            return matches!(
+                instruction,
+                Instruction::Add(..)
+                    | Instruction::AddOverflow(..)
+                    | Instruction::Sub(..)
+                    | Instruction::SubOverflow(..)
+                    | Instruction::Inc(_)
+                    | Instruction::Dec(_)
+            )
+        }
+    };
+    match instruction {
+        Instruction::LoadConstant(target, _)
+        | Instruction::LoadMemory(target, _)
+        | Instruction::Not(target)
+        | Instruction::Move(target, _)
+        | Instruction::Nand(target, _)
+        | Instruction::And(target, _)
+        | Instruction::Nor(target, _)
+        | Instruction::Or(target, _)
+        | Instruction::Xnor(target, _)
+        | Instruction::Xor(target, _)
+        | Instruction::Add(target, _)
+        | Instruction::AddOverflow(target, _)
+        | Instruction::Sub(target, _)
+        | Instruction::SubOverflow(target, _)
+        | Instruction::Pop(target)
+        | Instruction::ShiftLeft(target)
+        | Instruction::ShiftRight(target)
+        | Instruction::RotateLeft(target)
+        | Instruction::RotateRight(target)
+        | Instruction::Inc(target)
+        | Instruction::Dec(target)
+        | Instruction::LoadIndirect(target, _)
+        | Instruction::Mul(target, _)
+        | Instruction::Div(target, _)
+        | Instruction::Rem(target, _)
+        | Instruction::DecimalAdjust(target) => target == register,
+        _ => false,
+    }
+}
+
+/// Returns whether the value of the place may be read by the instructions before it's
+/// overwritten. Jumps, calls, returns and halts, and the end of the instructions count as reads,
+/// the registers and flags a program halts with are part of what it computed.
+fn is_live(instructions: &[Instruction], place: Place) -> bool {
+    for &instruction in instructions {
+        if matches!(instruction, Instruction::Return | Instruction::Halt)
+            || target(instruction).is_some()
+        {
+            return true;
+        }
+        if reads(instruction, place) {
+            return true;
+        }
+        if writes(instruction, place) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Returns the number of instructions a rewrite at the start of the instructions drops,
+/// `next_is_target` is whether the second instruction is jumped to
+fn rewrite(instructions: &[Instruction], next_is_target: bool) -> usize {
+    match *instructions {
+        [Instruction::Move(target, source), ..] if target == source => 1,
+        [Instruction::LoadConstant(first, _), Instruction::LoadConstant(second, _), ..]
+            if first == second =>
+        {
+            1
+        }
+        [Instruction::Not(first), Instruction::Not(second), ..]
+            if first == second && !next_is_target =>
+        {
+            2
+        }
+        [Instruction::LoadConstant(zero, 0), Instruction::Add(_, source), ref rest @ ..]
+            if zero == source
+                && !next_is_target
+                && !is_live(rest, Place::Register(zero))
+                && !is_live(rest, Place::Overflow)
+                && !is_live(rest, Place::HalfCarry) =>
+        {
+            2
+        }
+        _ => 0,
+    }
+}
+
+/// Applies the rewrites once, returns `None` if nothing changed, a jump target isn't the start of
+/// an instruction or the instructions access memory that moves with them
+fn pass(instructions: &[Instruction]) -> Option<Vec<Instruction>> {
+    if address_taken(instructions).is_some()
+        || instructions
+            .iter()
+            .any(|&instruction| literal_address(instruction).is_some())
+    {
+        return None;
+    }
+
+    let mut offsets = Vec::with_capacity(instructions.len() + 1);
+    let mut offset = 0;
+    for instruction in instructions {
+        offsets.push(offset);
+        offset += instruction.encode().1;
+    }
+    offsets.push(offset);
+
+    let mut is_target = vec![false; instructions.len() + 1];
+    for address in instructions
+        .iter()
+        .filter_map(|&instruction| target(instruction))
+    {
+        let index = offsets.binary_search(&usize::from(address)).ok()?;
+        is_target[index] = true;
+    }
+
+    let mut keep = vec![true; instructions.len()];
+    let mut index = 0;
+    while index < instructions.len() {
+        match rewrite(&instructions[index..], is_target[index + 1]) {
+            0 => index += 1,
+            dropped => {
+                keep[index..index + dropped].fill(false);
+                index += dropped;
+            }
+        }
+    }
+    if keep.iter().all(|&kept| kept) {
+        return None;
+    }
+
+    // A dropped instruction's address becomes the address of the next kept one
+    let mut moved = Vec::with_capacity(offsets.len());
+    let mut offset = 0;
+    for (instruction, &kept) in instructions.iter().zip(&keep) {
+        moved.push(offset);
+        if kept {
+            offset += instruction.encode().1;
+        }
+    }
+    moved.push(offset);
+
+    Some(
+        instructions
+            .iter()
+            .zip(&keep)
+            .filter(|&(_, &kept)| kept)
+            .map(|(&instruction, _)| {
+                target(instruction).map_or(instruction, |address| {
+                    let index = offsets.binary_search(&usize::from(address)).unwrap_or(0);
+                    // Dropping instructions only moves addresses down, so they still fit
+                    retarget(instruction, u8::try_from(moved[index]).unwrap_or(address))
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Applies the rewrites until none applies anymore, as one can make room for another.
+///
+/// The instructions are returned unchanged if a jump or call target isn't the start of one of
+/// them, or if they access memory other than the devices.
+#[must_use]
+pub fn optimize(instructions: Vec<Instruction>) -> Vec<Instruction> {
+    let mut instructions = instructions;
+    while let Some(optimized) = pass(&instructions) {
+        instructions = optimized;
+    }
+    instructions
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use michael_computer::equivalence::EquivalenceChecker;
+
+    use super::optimize;
+    use crate::{disassemble::disassemble, parser::Parser, Instruction, Register};
+
+    /// Assembles the source into its instructions
+    fn instructions(source: &str) -> Vec<Instruction> {
+        let bytes = Parser::new().assemble(source).unwrap();
+        disassemble(&bytes)
+            .unwrap()
+            .into_iter()
+            .map(|(_, instruction)| instruction)
+            .collect()
+    }
+
+    /// Optimizes the assembled source and checks that the result has the same observable effects
+    fn optimized(source: &str) -> Vec<Instruction> {
+        let program = instructions(source);
+        let optimized = optimize(program.clone());
+        let divergence = EquivalenceChecker::new().check(
+            &Instruction::to_bytes(program),
+            &Instruction::to_bytes(optimized.clone()),
+        );
+        assert_eq!(divergence, None, "{source}");
+        optimized
+    }
+
+    #[test]
+    fn moves() {
+        assert_eq!(
+            optimized("mov r1, r1\nmov r1, r2\nmov r0, r0\nhlt"),
+            instructions("mov r1, r2\nhlt")
+        );
+    }
+
+    #[test]
+    fn load_constants() {
+        assert_eq!(
+            optimized("ldi r0, 1\nldi r0, 2\nldi r0, 3\nldi r1, 4\nhlt"),
+            instructions("ldi r0, 3\nldi r1, 4\nhlt")
+        );
+        // A load in between keeps both
+        let kept = instructions("ldi r0, 1\nldi r1, 2\nldi r0, 3\nhlt");
+        assert_eq!(optimize(kept.clone()), kept);
+    }
+
+    #[test]
+    fn nots() {
+        assert_eq!(
+            optimized("not r2\nnot r2\nnot r2\nnot r1\nnot r2\nhlt"),
+            instructions("not r2\nnot r1\nnot r2\nhlt")
+        );
+        // Removing the moves makes the nots consecutive
+        assert_eq!(
+            optimized("not r0\nmov r3, r3\nnot r0\nhlt"),
+            instructions("hlt")
+        );
+        // Jumping to the second not only inverts once
+        let kept = instructions("not r0\nsecond: not r0\njz r0, second\nhlt");
+        assert_eq!(optimize(kept.clone()), kept);
+    }
+
+    #[test]
+    fn add_zero() {
+        assert_eq!(
+            optimized("ldi r1, 0\nadd r0, r1\nldi r1, 5\nadd r2, r1\nhlt"),
+            instructions("ldi r1, 5\nadd r2, r1\nhlt")
+        );
+        for kept in [
+            // r1 is still read
+            "ldi r1, 0\nadd r0, r1\nst r1, [255]\nhlt",
+            // The cleared overflow is read
+            "ldi r1, 0\nadd r0, r1\nadc r2, r3\nldi r1, 1\nhlt",
+            // The cleared half carry is read, even with the overflow overwritten
+            "ldi r1, 0\nadd r0, r1\nstc\ndaa r2\nhlt",
+            // The liveness isn't followed past control flow
+            "ldi r1, 0\nadd r0, r1\njmp end\nend: hlt",
+            // Nothing is known about what follows the instructions
+            "ldi r1, 0\nadd r0, r1",
+            // The program halts with r1 and the cleared overflow
+            "ldi r1, 0\nadd r0, r1\nhlt",
+            // The half carry is only cleared
+            "ldi r1, 0\nadd r0, r1\nldi r1, 5\nclc\nhlt",
+            // Only a constant 0 adds nothing
+            "ldi r1, 1\nadd r0, r1\nhlt",
+        ] {
+            let kept = instructions(kept);
+            assert_eq!(optimize(kept.clone()), kept);
+        }
+    }
+
+    #[test]
+    fn targets() {
+        assert_eq!(
+            optimized("start: mov r0, r0\ndec r0\nmov r1, r1\njz r0, end\njmp start\nend: hlt"),
+            instructions("start: dec r0\njz r0, end\njmp start\nend: hlt")
+        );
+        // A target in the middle of an instruction can't be moved
+        let kept = instructions("mov r0, r0\nldi r0, 0\njmp 2");
+        assert_eq!(optimize(kept.clone()), kept);
+    }
+
+    #[test]
+    fn memory_operands() {
+        for kept in [
+            // A data byte placed after the code would move down by one
+            &[
+                Instruction::LoadMemory(Register::R0, 7),
+                Instruction::Move(Register::R1, Register::R1),
+                Instruction::StoreMemory(Register::R0, 255),
+                Instruction::Halt,
+            ][..],
+            // The store patches the second `ldi`, which would move to 4
+            &[
+                Instruction::LoadConstant(Register::R0, 0xE4),
+                Instruction::Move(Register::R1, Register::R1),
+                Instruction::StoreMemory(Register::R0, 5),
+                Instruction::LoadConstant(Register::R1, 2),
+                Instruction::Halt,
+            ],
+        ] {
+            assert_eq!(optimize(kept.to_vec()), kept);
+        }
+        // Loading through a register may address the code as well
+        let kept = instructions("ldi r1, 7\nmov r0, r0\nld r0, [r1]\nst r0, [255]\nhlt");
+        assert_eq!(optimize(kept.clone()), kept);
+        // The copy prints the byte at 9, the operand of the `ldi` after the `hlt`
+        let kept = instructions(
+            "ldi r1, 9\nldi r2, 255\nldi r0, 1\nmov r3, r3\ncopy r2, r1\nhlt\nldi r3, 0x41",
+        );
+        assert_eq!(optimize(kept.clone()), kept);
+    }
+
+    #[test]
+    fn same_output() {
+        // Prints `Hi!` three times
+        let source = "
+                ldi r1, 3
+        loop:   ldi r0, 0
+                ldi r0, 'H'
+                st r0, [255]
+                mov r2, r2
+                ldi r0, 'i'
+                st r0, [255]
+                not r1
+                not r1
+                ldi r3, 0
+                add r0, r3
+                ldi r3, '!'
+                st r3, [255]
+                dec r1
+                jz r1, end
+                jmp loop
+        end:    hlt
+        ";
+        assert_eq!(optimized(source).len(), instructions(source).len() - 6);
+    }
+}
@@ -0,0 +1,1348 @@
+//! The text assembler, parsing instructions in the syntax they are displayed in.
+//!
+//! Every line holds an optional label, then an instruction or a directive, and an optional comment
+//! starting with `;`. Labels can be used wherever an address or constant is expected:
+//!
+//! ```
+//! use assembler::parser::Parser;
+//!
+//! let source = "
+//! echo:   ld r0, [254]    ; read a byte
+//!         jz r0, end
+//!         st r0, [255]
+//!         jmp echo
+//! end:    hlt
+//! ";
+//! let bytes = Parser::new().assemble(source).unwrap();
+//! assert_eq!(bytes, [0x04, 0xFE, 0xC4, 0x08, 0x08, 0xFF, 0xC0, 0x00, 0xFF]);
+//! ```
+//!
+//! Directives start with a `.`, like `.byte 1, 2, 0x10` emitting raw bytes. Tools can add their
+//! own with [`Parser::register_directive`], the built-in directives are registered the same way.
+//!
+//! After `.data` the directives emit into the data segment instead of the code, until `.code`
+//! switches back. The data segment is placed right after the code, so execution ends before it,
+//! and its labels become known once the program is finished:
+//!
+//! ```
+//! use assembler::parser::Parser;
+//!
+//! let source = "
+//!         ldi r3, text
+//! loop:   ld r0, [r3]
+//!         jz r0, end
+//!         st r0, [255]
+//!         inc r3
+//!         jmp loop
+//! end:    hlt
+//!
+//!         .data
+//! text:   .string \"Hi\\n\"
+//!         .zero 1
+//! ";
+//! let image = Parser::new().assemble_image(source).unwrap();
+//! assert_eq!((image.data_address, &image.data[..]), (12, &b"Hi\n\0"[..]));
+//! assert_eq!(image.code[1], 12);
+//! ```
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{
+    fmt::{self, Display, Formatter},
+    ops::Range,
+};
+
+use michael_computer::error::{ErrorKind, Location, McError};
+
+use crate::{image::ProgramImage, Instruction, Register};
+
+/// The prefix of the names of directives registered by other tools
+pub const CUSTOM_PREFIX: &str = ".x-";
+
+/// The number of bytes a program can hold
+const MAX_LENGTH: usize = 256;
+
+/// The mnemonics of the instructions, to tell unknown ones from ones with invalid operands
+const MNEMONICS: [&str; 36] = [
+    "ldi", "ld", "st", "not", "mov", "nand", "and", "nor", "or", "xnor", "xor", "add", "adc",
+    "sub", "sbc", "jmp", "jo", "call", "ret", "jz", "push", "pop", "shl", "shr", "rol", "ror",
+    "inc", "dec", "stc", "clc", "mul", "div", "rem", "copy", "daa", "hlt",
+];
+
+/// A word or operand of a line, with its position in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'s> {
+    /// The text of the token
+    pub text: &'s str,
+
+    /// The offset of the first byte of the token in the source
+    pub start: usize,
+}
+
+impl Token<'_> {
+    /// Returns the offset after the last byte of the token in the source
+    #[must_use]
+    pub const fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    /// Returns an error of the kind at the token
+    #[must_use]
+    pub const fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            start: self.start,
+            end: self.end(),
+        }
+    }
+
+    /// Parses the token as a byte: a decimal, `0x` hexadecimal or `0b` binary number, a negative
+    /// decimal from -128 as its two's complement, or a character like `'H'` or `'\n'`. Digits can
+    /// be grouped with `_`, like `0b0100_1000`.
+    ///
+    /// # Errors
+    /// Returns an error if the token isn't a number or character, or its value doesn't fit in a
+    /// byte
+    pub fn number(&self) -> Result<u8, ParseError> {
+        if let Some(literal) = self.text.strip_prefix('\'') {
+            return self.character(literal);
+        }
+        let (negative, text) = self
+            .text
+            .strip_prefix('-')
+            .map_or((false, self.text), |text| (true, text));
+        let (digits, radix) = text.strip_prefix("0x").map_or_else(
+            || {
+                text.strip_prefix("0b")
+                    .map_or((text, 10), |digits| (digits, 2))
+            },
+            |digits| (digits, 16),
+        );
+        if (negative && radix != 10)
+            || digits.is_empty()
+            || digits.starts_with('_')
+            || !digits
+                .chars()
+                .all(|char| char == '_' || char.is_digit(radix))
+        {
+            return Err(self.error(ParseErrorKind::InvalidNumber));
+        }
+        let value = digits
+            .chars()
+            .filter_map(|char| char.to_digit(radix))
+            .fold(0u32, |value, digit| {
+                value.saturating_mul(radix).saturating_add(digit)
+            });
+        let out_of_range = || self.error(ParseErrorKind::ValueOutOfRange(self.text.into()));
+        if negative {
+            if value > 128 {
+                return Err(out_of_range());
+            }
+            return u8::try_from(value)
+                .map(u8::wrapping_neg)
+                .map_err(|_| out_of_range());
+        }
+        u8::try_from(value).map_err(|_| out_of_range())
+    }
+
+    /// Parses the rest of a character literal after the opening `'`, it takes the escapes of
+    /// `.string`
+    fn character(&self, literal: &str) -> Result<u8, ParseError> {
+        let invalid = || self.error(ParseErrorKind::InvalidString);
+        let inner = literal.strip_suffix('\'').ok_or_else(invalid)?;
+        let bytes = unescape(inner, '\'').map_err(|()| invalid())?;
+        match bytes[..] {
+            [byte] => Ok(byte),
+            // A character outside ASCII is more than one byte in UTF-8
+            [_, _, ..] if inner.chars().count() == 1 => {
+                Err(self.error(ParseErrorKind::ValueOutOfRange(self.text.into())))
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Returns whether the token is a label name: a letter or `_` followed by letters, digits or
+    /// `_`
+    fn is_label(&self) -> bool {
+        let mut bytes = self.text.bytes();
+        bytes
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || first == b'_')
+            && bytes.all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+    }
+
+    /// Parses the token as a register, `r0` to `r7`
+    fn register(&self) -> Option<Register> {
+        match self.text {
+            "r0" => Some(Register::R0),
+            "r1" => Some(Register::R1),
+            "r2" => Some(Register::R2),
+            "r3" => Some(Register::R3),
+            "r4" => Some(Register::R4),
+            "r5" => Some(Register::R5),
+            "r6" => Some(Register::R6),
+            "r7" => Some(Register::R7),
+            _ => None,
+        }
+    }
+}
+
+/// What is wrong with the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The mnemonic isn't an instruction
+    UnknownMnemonic,
+
+    /// No directive with this name is registered
+    UnknownDirective,
+
+    /// The operands don't fit the instruction or directive
+    InvalidOperands,
+
+    /// The operand isn't a number that fits in a byte
+    InvalidNumber,
+
+    /// The text before the `:` isn't a label name
+    InvalidLabel,
+
+    /// The label is defined more than once
+    DuplicateLabel,
+
+    /// The label is used but never defined
+    UndefinedLabel,
+
+    /// The program doesn't fit in the 256 bytes of memory
+    ProgramTooLarge,
+
+    /// The string or character literal isn't closed or has an unknown escape sequence, or the
+    /// character literal doesn't hold exactly one character
+    InvalidString,
+
+    /// The literal is a valid number or character, but its value doesn't fit in a byte
+    ValueOutOfRange(String),
+
+    /// An instruction is in the data segment
+    InstructionInData,
+
+    /// A directive reported a problem with its operands
+    Directive(String),
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownMnemonic => write!(f, "unknown mnemonic"),
+            Self::UnknownDirective => write!(f, "unknown directive"),
+            Self::InvalidOperands => write!(f, "invalid operands"),
+            Self::InvalidNumber => write!(f, "invalid number"),
+            Self::InvalidLabel => write!(f, "invalid label name"),
+            Self::DuplicateLabel => write!(f, "label is already defined"),
+            Self::UndefinedLabel => write!(f, "undefined label"),
+            Self::ProgramTooLarge => write!(f, "program doesn't fit in memory"),
+            Self::InvalidString => write!(f, "invalid string literal"),
+            Self::ValueOutOfRange(literal) => write!(f, "`{literal}` doesn't fit in a byte"),
+            Self::InstructionInData => write!(f, "instruction in the data segment"),
+            Self::Directive(message) => f.write_str(message),
+        }
+    }
+}
+
+/// An error in the source text, with the range of bytes it refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// What is wrong
+    pub kind: ParseErrorKind,
+
+    /// The offset of the first byte of the offending text
+    pub start: usize,
+
+    /// The offset after the last byte of the offending text
+    pub end: usize,
+}
+
+impl ParseError {
+    /// Returns the line and column of the start of the error in the source, both counted from 1.
+    /// The column counts characters, so it matches what an editor shows.
+    #[must_use]
+    pub fn line_column(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start.min(source.len())];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+
+    /// Returns the source text the error refers to
+    #[must_use]
+    pub fn text<'s>(&self, source: &'s str) -> &'s str {
+        source.get(self.start..self.end).unwrap_or_default()
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at bytes {}..{}", self.kind, self.start, self.end)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+impl McError for ParseError {
+    fn kind(&self) -> ErrorKind {
+        match self.kind {
+            ParseErrorKind::UndefinedLabel | ParseErrorKind::ProgramTooLarge => ErrorKind::Assemble,
+            _ => ErrorKind::Parse,
+        }
+    }
+
+    fn location(&self) -> Option<Location> {
+        Some(Location::Span {
+            start: self.start,
+            end: self.end,
+        })
+    }
+}
+
+/// A label reference whose byte is filled in once every label is known
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Fixup {
+    offset: usize,
+    data: bool,
+    label: String,
+    start: usize,
+}
+
+/// The state of the assembler while a program is assembled, given to the directives
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Assembly {
+    bytes: Vec<u8>,
+    data: Vec<u8>,
+    in_data: bool,
+    symbols: Vec<(String, u8)>,
+    data_labels: Vec<(String, usize, usize)>,
+    fixups: Vec<Fixup>,
+    statement: (usize, usize),
+}
+
+impl Assembly {
+    /// Returns the offset the next byte is emitted at, in the code or, after `.data`, in the data
+    /// segment
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        self.section().len()
+    }
+
+    /// Returns the bytes emitted so far into the code or the data segment. Labels that are used
+    /// before they are defined read as 0 until the program is finished.
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        self.section()
+    }
+
+    /// Returns whether the bytes are emitted into the data segment
+    #[must_use]
+    pub const fn in_data(&self) -> bool {
+        self.in_data
+    }
+
+    /// Appends the byte to the code or the data segment
+    pub fn emit_byte(&mut self, byte: u8) {
+        if self.in_data {
+            self.data.push(byte);
+        } else {
+            self.bytes.push(byte);
+        }
+    }
+
+    /// Appends the encoding of the instruction to the program
+    pub fn emit(&mut self, instruction: Instruction) {
+        let (bytes, length) = instruction.encode();
+        for byte in &bytes[..length] {
+            self.emit_byte(*byte);
+        }
+    }
+
+    /// Appends the value of the operand, a number or a label, to the program
+    ///
+    /// # Errors
+    /// Returns an error if the operand is neither a number nor a label name
+    pub fn emit_operand(&mut self, operand: Token<'_>) -> Result<(), ParseError> {
+        let value = self.operand(operand)?;
+        self.emit_byte(value);
+        Ok(())
+    }
+
+    /// Defines the symbol with the value, it can be used like a label
+    ///
+    /// # Errors
+    /// Returns an error if the name isn't a label name or is already defined
+    pub fn define(&mut self, name: Token<'_>, value: u8) -> Result<(), ParseError> {
+        if !name.is_label() {
+            return Err(name.error(ParseErrorKind::InvalidLabel));
+        }
+        if self.defined(name.text) {
+            return Err(name.error(ParseErrorKind::DuplicateLabel));
+        }
+        self.symbols.push((name.text.into(), value));
+        Ok(())
+    }
+
+    /// Returns the value of the label or symbol, if it is defined yet. The addresses of labels in
+    /// the data segment are only known once the program is finished.
+    #[must_use]
+    pub fn symbol(&self, name: &str) -> Option<u8> {
+        self.symbols
+            .iter()
+            .find(|(symbol, _)| symbol == name)
+            .map(|&(_, value)| value)
+    }
+
+    /// Returns an error raised by a directive, at the directive being assembled
+    #[must_use]
+    pub fn diagnostic(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            kind: ParseErrorKind::Directive(message.into()),
+            start: self.statement.0,
+            end: self.statement.1,
+        }
+    }
+
+    /// Returns the value of a number or a label. A label that isn't defined yet is filled in when
+    /// the program is finished, the operand has to be emitted next.
+    fn operand(&mut self, operand: Token<'_>) -> Result<u8, ParseError> {
+        if !operand.is_label() {
+            return operand.number();
+        }
+        if let Some(value) = self.symbol(operand.text) {
+            return Ok(value);
+        }
+        self.fixups.push(Fixup {
+            offset: self.offset(),
+            data: self.in_data,
+            label: operand.text.into(),
+            start: operand.start,
+        });
+        Ok(0)
+    }
+
+    /// Returns an error of the kind at the statement being assembled
+    const fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            start: self.statement.0,
+            end: self.statement.1,
+        }
+    }
+
+    /// Returns the bytes of the section the bytes are emitted into
+    const fn section(&self) -> &Vec<u8> {
+        if self.in_data {
+            &self.data
+        } else {
+            &self.bytes
+        }
+    }
+
+    /// Returns the number of bytes of the code and the data segment together
+    const fn length(&self) -> usize {
+        self.bytes.len() + self.data.len()
+    }
+
+    /// Returns whether the name is a symbol or a label, also in the data segment
+    fn defined(&self, name: &str) -> bool {
+        self.symbol(name).is_some() || self.data_labels.iter().any(|(label, ..)| label == name)
+    }
+
+    /// Defines the label at the offset the next byte is emitted at
+    fn label(&mut self, name: Token<'_>) -> Result<(), ParseError> {
+        if !self.in_data {
+            let offset = u8::try_from(self.offset())
+                .map_err(|_| name.error(ParseErrorKind::ProgramTooLarge))?;
+            return self.define(name, offset);
+        }
+        if !name.is_label() {
+            return Err(name.error(ParseErrorKind::InvalidLabel));
+        }
+        if self.defined(name.text) {
+            return Err(name.error(ParseErrorKind::DuplicateLabel));
+        }
+        self.data_labels
+            .push((name.text.into(), self.offset(), name.start));
+        Ok(())
+    }
+
+    /// Fills in the labels, returns the code and the data segment placed after it
+    fn finish(mut self) -> Result<(Vec<u8>, Vec<u8>), ParseError> {
+        let code = self.bytes.len();
+        for (label, offset, start) in core::mem::take(&mut self.data_labels) {
+            let name = Token {
+                text: &label,
+                start,
+            };
+            let address = u8::try_from(code + offset)
+                .map_err(|_| name.error(ParseErrorKind::ProgramTooLarge))?;
+            self.symbols.push((label.clone(), address));
+        }
+        for fixup in &self.fixups {
+            let label = Token {
+                text: &fixup.label,
+                start: fixup.start,
+            };
+            let value = self
+                .symbol(&fixup.label)
+                .ok_or_else(|| label.error(ParseErrorKind::UndefinedLabel))?;
+            let section = if fixup.data {
+                &mut self.data
+            } else {
+                &mut self.bytes
+            };
+            section[fixup.offset] = value;
+        }
+        Ok((self.bytes, self.data))
+    }
+}
+
+/// The code, the data segment and the range of the program bytes of every line
+type Sections = (Vec<u8>, Vec<u8>, Vec<Range<usize>>);
+
+/// The function assembling a directive from its operands
+pub type DirectiveFn<'h> = dyn Fn(&[Token<'_>], &mut Assembly) -> Result<(), ParseError> + 'h;
+
+/// A directive couldn't be registered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectiveError {
+    /// The name doesn't start with [`CUSTOM_PREFIX`] followed by a label name
+    Namespace(&'static str),
+
+    /// A directive with the name is already registered
+    Duplicate(&'static str),
+}
+
+impl Display for DirectiveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Namespace(name) => {
+                write!(f, "directive `{name}` doesn't start with `{CUSTOM_PREFIX}`")
+            }
+            Self::Duplicate(name) => write!(f, "directive `{name}` is already registered"),
+        }
+    }
+}
+
+impl core::error::Error for DirectiveError {}
+
+impl McError for DirectiveError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Parse
+    }
+}
+
+/// `.byte 1, 2, 0x10` emits every operand, a number or a label, as a byte
+fn byte_directive(operands: &[Token<'_>], assembly: &mut Assembly) -> Result<(), ParseError> {
+    if operands.is_empty() {
+        return Err(assembly.error(ParseErrorKind::InvalidOperands));
+    }
+    operands
+        .iter()
+        .try_for_each(|&operand| assembly.emit_operand(operand))
+}
+
+/// `.data` emits the following directives into the data segment
+const fn data_directive(operands: &[Token<'_>], assembly: &mut Assembly) -> Result<(), ParseError> {
+    if let Some(operand) = operands.first() {
+        return Err(operand.error(ParseErrorKind::InvalidOperands));
+    }
+    assembly.in_data = true;
+    Ok(())
+}
+
+/// `.code` switches back from the data segment to the code
+const fn code_directive(operands: &[Token<'_>], assembly: &mut Assembly) -> Result<(), ParseError> {
+    if let Some(operand) = operands.first() {
+        return Err(operand.error(ParseErrorKind::InvalidOperands));
+    }
+    assembly.in_data = false;
+    Ok(())
+}
+
+/// `.string "Hello\n"` emits the bytes of the string, without a terminating 0
+fn string_directive(operands: &[Token<'_>], assembly: &mut Assembly) -> Result<(), ParseError> {
+    let [string] = operands else {
+        return Err(assembly.error(ParseErrorKind::InvalidOperands));
+    };
+    let text = string
+        .text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .ok_or_else(|| string.error(ParseErrorKind::InvalidString))?;
+    for byte in unescape(text, '"').map_err(|()| string.error(ParseErrorKind::InvalidString))? {
+        assembly.emit_byte(byte);
+    }
+    Ok(())
+}
+
+/// `.zero 4` emits the number of zero bytes
+fn zero_directive(operands: &[Token<'_>], assembly: &mut Assembly) -> Result<(), ParseError> {
+    let [count] = operands else {
+        return Err(assembly.error(ParseErrorKind::InvalidOperands));
+    };
+    for _ in 0..count.number()? {
+        assembly.emit_byte(0);
+    }
+    Ok(())
+}
+
+/// Returns the bytes of the text with its escape sequences replaced: `\n`, `\r`, `\t`, `\0`,
+/// `\\`, `\"`, `\'` and `\x` with 2 hex digits. Other characters are encoded as UTF-8, except the
+/// quote of the literal which has to be escaped.
+fn unescape(text: &str, quote: char) -> Result<Vec<u8>, ()> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        if char == quote {
+            return Err(());
+        }
+        if char != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(char.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next().ok_or(())? {
+            'n' => b'\n',
+            'r' => b'\r',
+            't' => b'\t',
+            '0' => 0,
+            '\\' => b'\\',
+            '"' => b'"',
+            '\'' => b'\'',
+            'x' => {
+                let digits = chars.as_str().get(..2).ok_or(())?;
+                if !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                    return Err(());
+                }
+                chars.nth(1);
+                u8::from_str_radix(digits, 16).map_err(|_| ())?
+            }
+            _ => return Err(()),
+        });
+    }
+    Ok(bytes)
+}
+
+/// Returns the offset of the first separator outside of string and character literals
+fn find_unquoted(text: &str, separator: char) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (offset, char) in text.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if char == '\\' => escaped = true,
+            Some(open) if char == open => quote = None,
+            None if char == separator => return Some(offset),
+            None if char == '"' || char == '\'' => quote = Some(char),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// An operand of an instruction
+enum Operand<'s> {
+    Register(Register),
+    Value(Token<'s>),
+    Memory(Token<'s>),
+    Indirect(Register),
+}
+
+impl<'s> Operand<'s> {
+    fn parse(token: Token<'s>) -> Self {
+        if let Some(register) = token.register() {
+            return Self::Register(register);
+        }
+        let inner = token
+            .text
+            .strip_prefix('[')
+            .and_then(|text| text.strip_suffix(']'));
+        let Some(inner) = inner else {
+            return Self::Value(token);
+        };
+        let leading = inner.len() - inner.trim_start().len();
+        let inner = Token {
+            text: inner.trim(),
+            start: token.start + 1 + leading,
+        };
+        inner.register().map_or(Self::Memory(inner), Self::Indirect)
+    }
+}
+
+/// Splits the text at the commas into trimmed tokens, the text starts at the offset
+fn operands(mut text: &str, mut start: usize) -> Vec<Token<'_>> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    let mut tokens = Vec::new();
+    loop {
+        let end = find_unquoted(text, ',').unwrap_or(text.len());
+        let piece = &text[..end];
+        let leading = piece.len() - piece.trim_start().len();
+        tokens.push(Token {
+            text: piece.trim(),
+            start: start + leading,
+        });
+        if end == text.len() {
+            return tokens;
+        }
+        text = &text[end + 1..];
+        start += end + 1;
+    }
+}
+
+/// Parses the instruction, returns it with the operand that becomes its second byte
+fn instruction<'s>(
+    mnemonic: &str,
+    operands: &[Operand<'s>],
+) -> Option<(Instruction, Option<Token<'s>>)> {
+    use Operand::{Indirect, Memory, Register as R, Value};
+    let register = |build: fn(Register) -> Instruction| match operands {
+        [R(register)] => Some((build(*register), None)),
+        _ => None,
+    };
+    let pair = |build: fn(Register, Register) -> Instruction| match operands {
+        [R(left), R(right)] => Some((build(*left, *right), None)),
+        _ => None,
+    };
+    let target = |build: fn(u8) -> Instruction| match operands {
+        [Value(value)] => Some((build(0), Some(*value))),
+        _ => None,
+    };
+    let shift = |build: fn(Register) -> Instruction, memory: fn(u8) -> Instruction| match operands {
+        [R(register)] => Some((build(*register), None)),
+        [Memory(address)] => Some((memory(0), Some(*address))),
+        _ => None,
+    };
+    let none = |instruction| operands.is_empty().then_some((instruction, None));
+    match mnemonic {
+        "ldi" => match operands {
+            [R(register), Value(value)] => {
+                Some((Instruction::LoadConstant(*register, 0), Some(*value)))
+            }
+            _ => None,
+        },
+        "ld" => match operands {
+            [R(register), Memory(address)] => {
+                Some((Instruction::LoadMemory(*register, 0), Some(*address)))
+            }
+            [R(register), Indirect(address)] => {
+                Some((Instruction::LoadIndirect(*register, *address), None))
+            }
+            _ => None,
+        },
+        "st" => match operands {
+            [R(register), Memory(address)] => {
+                Some((Instruction::StoreMemory(*register, 0), Some(*address)))
+            }
+            [R(register), Indirect(address)] => {
+                Some((Instruction::StoreIndirect(*register, *address), None))
+            }
+            _ => None,
+        },
+        "jz" => match operands {
+            [R(register), Value(address)] => {
+                Some((Instruction::JumpIfZero(*register, 0), Some(*address)))
+            }
+            _ => None,
+        },
+        "not" => register(Instruction::Not),
+        "push" => register(Instruction::Push),
+        "pop" => register(Instruction::Pop),
+        "inc" => register(Instruction::Inc),
+        "daa" => register(Instruction::DecimalAdjust),
+        "dec" => register(Instruction::Dec),
+        "bank" => register(Instruction::SetBank),
+        "mov" => pair(Instruction::Move),
+        "nand" => pair(Instruction::Nand),
+        "and" => pair(Instruction::And),
+        "nor" => pair(Instruction::Nor),
+        "or" => pair(Instruction::Or),
+        "xnor" => pair(Instruction::Xnor),
+        "xor" => pair(Instruction::Xor),
+        "add" => pair(Instruction::Add),
+        "adc" => pair(Instruction::AddOverflow),
+        "sub" => pair(Instruction::Sub),
+        "sbc" => pair(Instruction::SubOverflow),
+        "mul" => pair(Instruction::Mul),
+        "div" => pair(Instruction::Div),
+        "rem" => pair(Instruction::Rem),
+        "copy" => pair(Instruction::Copy),
+        "jmp" => target(Instruction::Jump),
+        "jo" => target(Instruction::JumpIfOverflow),
+        "call" => target(Instruction::Call),
+        "shl" => shift(Instruction::ShiftLeft, Instruction::ShiftLeftMemory),
+        "shr" => shift(Instruction::ShiftRight, Instruction::ShiftRightMemory),
+        "rol" => shift(Instruction::RotateLeft, Instruction::RotateLeftMemory),
+        "ror" => shift(Instruction::RotateRight, Instruction::RotateRightMemory),
+        "ret" => none(Instruction::Return),
+        "stc" => none(Instruction::SetCarry),
+        "clc" => none(Instruction::ClearCarry),
+        "hlt" => none(Instruction::Halt),
+        _ => None,
+    }
+}
+
+/// Assembles source text into program bytes, with the directives registered on it
+pub struct Parser<'h> {
+    directives: Vec<(&'static str, Box<DirectiveFn<'h>>)>,
+}
+
+impl Default for Parser<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'h> Parser<'h> {
+    /// Creates a parser with the built-in directives
+    #[must_use]
+    pub fn new() -> Self {
+        let mut parser = Self {
+            directives: Vec::new(),
+        };
+        parser.insert(".byte", byte_directive);
+        parser.insert(".string", string_directive);
+        parser.insert(".zero", zero_directive);
+        parser.insert(".data", data_directive);
+        parser.insert(".code", code_directive);
+        parser
+    }
+
+    /// Adds the directive without checking the name
+    fn insert(
+        &mut self,
+        name: &'static str,
+        handler: impl Fn(&[Token<'_>], &mut Assembly) -> Result<(), ParseError> + 'h,
+    ) {
+        self.directives.push((name, Box::new(handler)));
+    }
+
+    /// Registers a directive, the handler is called with the operands and the state of the
+    /// assembler whenever the directive is used. Custom directive names start with
+    /// [`CUSTOM_PREFIX`], so they never collide with directives added to the assembler later.
+    ///
+    /// # Errors
+    /// Returns an error if the name is outside the custom namespace or already registered
+    pub fn register_directive(
+        &mut self,
+        name: &'static str,
+        handler: impl Fn(&[Token<'_>], &mut Assembly) -> Result<(), ParseError> + 'h,
+    ) -> Result<(), DirectiveError> {
+        let suffix = name.strip_prefix(CUSTOM_PREFIX).unwrap_or_default();
+        let valid = !suffix.is_empty()
+            && suffix
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_');
+        if !valid {
+            return Err(DirectiveError::Namespace(name));
+        }
+        if self
+            .directives
+            .iter()
+            .any(|&(directive, _)| directive == name)
+        {
+            return Err(DirectiveError::Duplicate(name));
+        }
+        self.insert(name, handler);
+        Ok(())
+    }
+
+    /// Assembles the source into the bytes of the program
+    ///
+    /// # Errors
+    /// Returns the first error in the source
+    pub fn assemble(&self, source: &str) -> Result<Vec<u8>, ParseError> {
+        self.assemble_lines(source).map(|(bytes, _)| bytes)
+    }
+
+    /// Assembles the source into a program image, with the data segment after the code
+    ///
+    /// # Errors
+    /// Returns the first error in the source
+    pub fn assemble_image(&self, source: &str) -> Result<ProgramImage, ParseError> {
+        let (code, data, _) = self.assemble_sections(source)?;
+        // The data address fits after the code, otherwise finishing the program fails
+        let address = u8::try_from(code.len()).unwrap_or(u8::MAX);
+        Ok(ProgramImage::new(code).with_data(address, data))
+    }
+
+    /// Assembles the source like [`Parser::assemble`], also returning the range of program bytes
+    /// every line of the source emitted, for listings
+    ///
+    /// # Errors
+    /// Returns the first error in the source
+    pub fn assemble_lines(&self, source: &str) -> Result<(Vec<u8>, Vec<Range<usize>>), ParseError> {
+        let (mut code, data, lines) = self.assemble_sections(source)?;
+        code.extend_from_slice(&data);
+        Ok((code, lines))
+    }
+
+    /// Assembles the source into the code and the data segment, with the range of the program
+    /// bytes of every line
+    fn assemble_sections(&self, source: &str) -> Result<Sections, ParseError> {
+        let mut assembly = Assembly::default();
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for line in source.split('\n') {
+            let (in_data, offset) = (assembly.in_data, assembly.offset());
+            self.line(line, start, &mut assembly)?;
+            // A line switching the section emits nothing, it is at the end of the new section
+            let offset = if assembly.in_data == in_data {
+                offset
+            } else {
+                assembly.offset()
+            };
+            lines.push((assembly.in_data, offset..assembly.offset()));
+            start += line.len() + 1;
+        }
+        let (code, data) = assembly.finish()?;
+        let lines = lines
+            .into_iter()
+            .map(|(in_data, range)| {
+                if in_data {
+                    code.len() + range.start..code.len() + range.end
+                } else {
+                    range
+                }
+            })
+            .collect();
+        Ok((code, data, lines))
+    }
+
+    /// Assembles a line of the source, the line starts at the offset
+    fn line(&self, line: &str, start: usize, assembly: &mut Assembly) -> Result<(), ParseError> {
+        let code = &line[..find_unquoted(line, ';').unwrap_or(line.len())];
+        let (mut code, mut start) = (code, start);
+        if let Some(colon) = find_unquoted(code, ':') {
+            let (label, rest) = (&code[..colon], &code[colon + 1..]);
+            let leading = label.len() - label.trim_start().len();
+            let label = Token {
+                text: label.trim(),
+                start: start + leading,
+            };
+            assembly.label(label)?;
+            start += colon + 1;
+            code = rest;
+        }
+
+        let leading = code.len() - code.trim_start().len();
+        let code = code.trim();
+        if code.is_empty() {
+            return Ok(());
+        }
+        let start = start + leading;
+        let (name, rest) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+        let name = Token { text: name, start };
+        let operands = operands(rest, start + name.text.len() + 1);
+        assembly.statement = (start, start + code.len());
+
+        if name.text.starts_with('.') {
+            let (_, handler) = self
+                .directives
+                .iter()
+                .find(|&&(directive, _)| directive == name.text)
+                .ok_or_else(|| name.error(ParseErrorKind::UnknownDirective))?;
+            handler(&operands, assembly)?;
+        } else {
+            if assembly.in_data {
+                return Err(name.error(ParseErrorKind::InstructionInData));
+            }
+            if let Some(empty) = operands.iter().find(|operand| operand.text.is_empty()) {
+                return Err(empty.error(ParseErrorKind::InvalidOperands));
+            }
+            let parsed: Vec<_> = operands.iter().copied().map(Operand::parse).collect();
+            let Some((instruction, operand)) = instruction(name.text, &parsed) else {
+                return Err(if MNEMONICS.contains(&name.text) {
+                    assembly.error(ParseErrorKind::InvalidOperands)
+                } else {
+                    name.error(ParseErrorKind::UnknownMnemonic)
+                });
+            };
+            match operand {
+                Some(operand) => {
+                    // The register prefix and the opcode, the operand follows them
+                    let (bytes, length) = instruction.encode();
+                    for &byte in &bytes[..length - 1] {
+                        assembly.emit_byte(byte);
+                    }
+                    assembly.emit_operand(operand)?;
+                }
+                None => assembly.emit(instruction),
+            }
+        }
+        if assembly.length() > MAX_LENGTH {
+            return Err(assembly.error(ParseErrorKind::ProgramTooLarge));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::ToString, vec::Vec};
+    use core::cell::Cell;
+
+    use michael_computer::error::{ErrorKind, McError};
+
+    use super::{DirectiveError, ParseError, ParseErrorKind, Parser, Token};
+    use crate::Instruction;
+
+    /// `.x-checksum` emits the wrapping sum of every byte before it
+    fn checksum(parser: &mut Parser<'_>) {
+        parser
+            .register_directive(".x-checksum", |operands, assembly| {
+                if let Some(operand) = operands.first() {
+                    return Err(operand.error(ParseErrorKind::InvalidOperands));
+                }
+                let sum = assembly
+                    .bytes()
+                    .iter()
+                    .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+                assembly.emit_byte(sum);
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn checksum_directive() {
+        let mut parser = Parser::new();
+        checksum(&mut parser);
+        let source = "
+        start:  ldi r0, 0x48    ; 0x00 0x48
+                st r0, [255]    ; 0x08 0xFF
+                hlt
+        data:   .byte 1, 2, start, data
+                .x-checksum
+        ";
+        let bytes = parser.assemble(source).unwrap();
+        let sum = [0x00, 0x48, 0x08, 0xFF, 0xFF, 1, 2, 0, 5]
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        assert_eq!(bytes, [0x00, 0x48, 0x08, 0xFF, 0xFF, 1, 2, 0, 5, sum]);
+        assert_eq!(
+            parser.assemble(".byte 1, 2, 3\n.x-checksum").unwrap(),
+            [1, 2, 3, 6]
+        );
+
+        let error = parser.assemble(".x-checksum 4").unwrap_err();
+        assert_eq!(
+            (error.kind, error.start, error.end),
+            (ParseErrorKind::InvalidOperands, 12, 13)
+        );
+    }
+
+    #[test]
+    fn directive_state_and_diagnostics() {
+        let defined = Cell::new(0);
+        let mut parser = Parser::new();
+        parser
+            .register_directive(".x-align", |operands, assembly| {
+                let [alignment] = operands else {
+                    return Err(assembly.diagnostic("expected an alignment"));
+                };
+                let value = usize::from(alignment.number()?);
+                if !value.is_power_of_two() {
+                    let message = format!("{} isn't a power of 2", alignment.text);
+                    return Err(alignment.error(ParseErrorKind::Directive(message)));
+                }
+                while assembly.offset() % value != 0 {
+                    assembly.emit_byte(0);
+                }
+                Ok(())
+            })
+            .unwrap();
+        parser
+            .register_directive(".x-const", |operands, assembly| {
+                let [name, value] = operands else {
+                    return Err(assembly.diagnostic("expected a name and a value"));
+                };
+                defined.set(defined.get() + 1);
+                assembly.define(*name, value.number()?)
+            })
+            .unwrap();
+
+        let source = ".x-const port, 0xF8\nhlt\n.x-align 4\nld r0, [port]\n";
+        assert_eq!(
+            parser.assemble(source).unwrap(),
+            [0xFF, 0, 0, 0, 0x04, 0xF8]
+        );
+        assert_eq!(defined.get(), 1);
+
+        let error = parser.assemble("hlt\n.x-align").unwrap_err();
+        assert_eq!(
+            error,
+            ParseError {
+                kind: ParseErrorKind::Directive("expected an alignment".to_string()),
+                start: 4,
+                end: 12,
+            }
+        );
+        assert_eq!(format!("{error}"), "expected an alignment at bytes 4..12");
+        assert_eq!(error.kind(), ErrorKind::Parse);
+        let error = parser.assemble(".x-align 3").unwrap_err();
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::Directive("3 isn't a power of 2".to_string())
+        );
+        let error = parser
+            .assemble(".x-const port, 1\n.x-const port, 2")
+            .unwrap_err();
+        assert_eq!(
+            (error.kind, error.start),
+            (ParseErrorKind::DuplicateLabel, 26)
+        );
+    }
+
+    #[test]
+    fn directive_namespace() {
+        let mut parser = Parser::new();
+        let nothing = |_: &[Token<'_>], _: &mut super::Assembly| Ok(());
+        assert_eq!(
+            parser.register_directive(".byte", nothing),
+            Err(DirectiveError::Namespace(".byte"))
+        );
+        assert_eq!(
+            parser.register_directive(".checksum", nothing),
+            Err(DirectiveError::Namespace(".checksum"))
+        );
+        assert_eq!(
+            parser.register_directive(".x-", nothing),
+            Err(DirectiveError::Namespace(".x-"))
+        );
+        assert_eq!(parser.register_directive(".x-nothing", nothing), Ok(()));
+        assert_eq!(
+            parser.register_directive(".x-nothing", nothing),
+            Err(DirectiveError::Duplicate(".x-nothing"))
+        );
+        let error = parser.assemble("  .x-other").unwrap_err();
+        assert_eq!(
+            (error.kind, error.start, error.end),
+            (ParseErrorKind::UnknownDirective, 2, 10)
+        );
+    }
+
+    #[test]
+    fn every_instruction_round_trips() {
+        let prefixes = [None, Some(0xF9), Some(0xFA), Some(0xFB)];
+        for (prefix, byte) in prefixes
+            .into_iter()
+            .flat_map(|prefix| (0..=u8::MAX).map(move |byte| (prefix, byte)))
+        {
+            // The operand selects r1 and r2 for every register byte, or r5 and r6 with a prefix
+            let bytes = prefix.map_or([byte, 0x06, 0], |prefix| [prefix, byte, 0x06]);
+            let Some((instruction, _)) = Instruction::decode(&bytes) else {
+                continue;
+            };
+            let text = format!("{instruction}");
+            let (bytes, length) = instruction.encode();
+            assert_eq!(
+                Parser::new().assemble(&text).unwrap(),
+                bytes[..length],
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let parser = Parser::new();
+        let error = |source| {
+            let error: ParseError = parser.assemble(source).unwrap_err();
+            (error.kind, error.start, error.end)
+        };
+        assert_eq!(error("nop"), (ParseErrorKind::UnknownMnemonic, 0, 3));
+        assert_eq!(
+            error("hlt\nadd r0, 5"),
+            (ParseErrorKind::InvalidOperands, 4, 13)
+        );
+        assert_eq!(error("ldi r8, 1"), (ParseErrorKind::InvalidOperands, 0, 9));
+        assert_eq!(
+            error("ldi r0, 256"),
+            (ParseErrorKind::ValueOutOfRange("256".into()), 8, 11)
+        );
+        assert_eq!(error("ldi r0, 0x"), (ParseErrorKind::InvalidNumber, 8, 10));
+        assert_eq!(
+            error("st r0, [ 0x1g ]"),
+            (ParseErrorKind::InvalidNumber, 9, 13)
+        );
+        assert_eq!(error("ldi r0,, 1"), (ParseErrorKind::InvalidOperands, 7, 7));
+        assert_eq!(error("1a: hlt"), (ParseErrorKind::InvalidLabel, 0, 2));
+        assert_eq!(error("a: hlt\n a:"), (ParseErrorKind::DuplicateLabel, 8, 9));
+        assert_eq!(
+            error("jmp nowhere"),
+            (ParseErrorKind::UndefinedLabel, 4, 11)
+        );
+        assert_eq!(error(".byte"), (ParseErrorKind::InvalidOperands, 0, 5));
+
+        let too_large = "ldi r0, 0\n".repeat(128);
+        assert!(parser.assemble(&too_large).is_ok());
+        let error = parser.assemble(&(too_large + "hlt")).unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::ProgramTooLarge);
+        assert_eq!(error.kind(), ErrorKind::Assemble);
+
+        // Labels resolve forwards and backwards
+        let bytes: Vec<u8> = parser
+            .assemble("jmp end\nstart: ret\nend: call start")
+            .unwrap();
+        assert_eq!(bytes, [0xC0, 0x03, 0xC3, 0xC2, 0x02]);
+    }
+
+    #[test]
+    fn lines_and_positions() {
+        let parser = Parser::new();
+        let source = "start:\n  ldi r0, 1 ; one\n\n  .byte 2, 3, 4\n  hlt";
+        let (bytes, lines) = parser.assemble_lines(source).unwrap();
+        assert_eq!(bytes, [0x00, 0x01, 0x02, 0x03, 0x04, 0xFF]);
+        assert_eq!(lines, [0..0, 0..2, 2..2, 2..5, 5..6]);
+
+        let source = "hlt\n; é\n  a: hlt ; é\n  jmp nowhere";
+        let error = parser.assemble(source).unwrap_err();
+        assert_eq!(error.line_column(source), (4, 7));
+        assert_eq!(error.text(source), "nowhere");
+        let error = parser.assemble("nop").unwrap_err();
+        assert_eq!(error.line_column("nop"), (1, 1));
+        let source = "; é\n .byte 1, é";
+        let error = parser.assemble(source).unwrap_err();
+        assert_eq!(error.line_column(source), (2, 11));
+        assert_eq!(error.text(source), "é");
+    }
+
+    #[test]
+    fn data_directives() {
+        let parser = Parser::new();
+        let source = "
+                ld r0, [second]
+                hlt
+                .data
+        table:  .byte 1, table, 0x10
+        second: .string \"a, b; \\\"c\\\"\\x41\\t\\\\\"
+                .zero 3
+                .code
+                st r0, [table]
+        ";
+        let image = parser.assemble_image(source).unwrap();
+        assert_eq!(image.code, [0x04, 8, 0xFF, 0x08, 5]);
+        assert_eq!(image.data_address, 5);
+        assert_eq!(image.data, b"\x01\x05\x10a, b; \"c\"A\t\\\0\0\0");
+        // The flat program is the code followed by the data
+        let (bytes, lines) = parser.assemble_lines(source).unwrap();
+        assert_eq!(bytes[..5], image.code);
+        assert_eq!(bytes[5..], image.data);
+        assert_eq!(lines[1..=4], [0..2, 2..3, 5..5, 5..8]);
+        assert_eq!(lines[6..=8], [bytes.len() - 3..bytes.len(), 3..3, 3..5]);
+        // Without `.data` the directives emit into the code
+        assert_eq!(
+            parser.assemble(".string \"hi\"\n.zero 2").unwrap(),
+            [b'h', b'i', 0, 0]
+        );
+
+        let error = |source| {
+            let error: ParseError = parser.assemble(source).unwrap_err();
+            (error.kind, error.start, error.end)
+        };
+        assert_eq!(
+            error(".data\nhlt"),
+            (ParseErrorKind::InstructionInData, 6, 9)
+        );
+        assert_eq!(
+            error(".string \"abc"),
+            (ParseErrorKind::InvalidString, 8, 12)
+        );
+        assert_eq!(
+            error(".string \"\\q\""),
+            (ParseErrorKind::InvalidString, 8, 12)
+        );
+        assert_eq!(
+            error(".string \"\\x4\""),
+            (ParseErrorKind::InvalidString, 8, 13)
+        );
+        assert_eq!(
+            error(".string \"a\" \"b\""),
+            (ParseErrorKind::InvalidString, 8, 15)
+        );
+        assert_eq!(error(".string"), (ParseErrorKind::InvalidOperands, 0, 7));
+        assert_eq!(error(".zero x"), (ParseErrorKind::InvalidNumber, 6, 7));
+        assert_eq!(error(".data 1"), (ParseErrorKind::InvalidOperands, 6, 7));
+        assert_eq!(
+            error("a: hlt\n.data\na: .zero 1"),
+            (ParseErrorKind::DuplicateLabel, 13, 14)
+        );
+        assert_eq!(
+            error(".data\n.zero 255\n.code\nhlt\nhlt"),
+            (ParseErrorKind::ProgramTooLarge, 26, 29)
+        );
+        // The label after the last byte of the data would be at address 256
+        assert_eq!(
+            error("hlt\n.data\n.zero 255\nend:"),
+            (ParseErrorKind::ProgramTooLarge, 20, 23)
+        );
+    }
+
+    #[test]
+    fn literals() {
+        let parser = Parser::new();
+        let value = |literal: &str| {
+            let bytes = parser.assemble(&alloc::format!("ldi r0, {literal}"));
+            bytes.map(|bytes| bytes[1]).map_err(|error| error.kind)
+        };
+        assert_eq!(value("72"), Ok(72));
+        assert_eq!(value("0x48"), Ok(0x48));
+        assert_eq!(value("0b0100_1000"), Ok(0x48));
+        assert_eq!(value("0b1111_1111"), Ok(255));
+        assert_eq!(value("1_0"), Ok(10));
+        assert_eq!(value("-1"), Ok(255));
+        assert_eq!(value("-128"), Ok(0x80));
+        assert_eq!(value("-0"), Ok(0));
+        assert_eq!(value("'H'"), Ok(b'H'));
+        assert_eq!(value("' '"), Ok(b' '));
+        assert_eq!(value("','"), Ok(b','));
+        assert_eq!(value("';'"), Ok(b';'));
+        assert_eq!(value("'\"'"), Ok(b'"'));
+        assert_eq!(value(r"'\n'"), Ok(b'\n'));
+        assert_eq!(value(r"'\0'"), Ok(0));
+        assert_eq!(value(r"'\''"), Ok(b'\''));
+        assert_eq!(value(r"'\xFF'"), Ok(0xFF));
+        assert_eq!(
+            parser.assemble("st r0, ['A']\n.byte 'a', -2, 0b1"),
+            Ok([0x08, b'A', b'a', 254, 1].to_vec())
+        );
+
+        let out_of_range = |literal: &str| Err(ParseErrorKind::ValueOutOfRange(literal.into()));
+        assert_eq!(value("256"), out_of_range("256"));
+        assert_eq!(value("0x100"), out_of_range("0x100"));
+        assert_eq!(value("0b1_0000_0000"), out_of_range("0b1_0000_0000"));
+        assert_eq!(value("-129"), out_of_range("-129"));
+        assert_eq!(value("1_000"), out_of_range("1_000"));
+        assert_eq!(value("99999999999999"), out_of_range("99999999999999"));
+        assert_eq!(value("'é'"), out_of_range("'é'"));
+
+        for invalid in ["0b", "0b102", "0b_1", "-0x1", "--1", "-"] {
+            assert_eq!(
+                value(invalid),
+                Err(ParseErrorKind::InvalidNumber),
+                "{invalid}"
+            );
+        }
+        for invalid in ["''", "'ab'", "'a", r"'\q'", "'''", r"'\x4'"] {
+            assert_eq!(
+                value(invalid),
+                Err(ParseErrorKind::InvalidString),
+                "{invalid}"
+            );
+        }
+        let error = parser.assemble("ldi r0, -200").unwrap_err();
+        assert_eq!((error.start, error.end), (8, 12));
+        assert_eq!(
+            error.kind.to_string(),
+            "`-200` doesn't fit in a byte".to_string()
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Runs the `assemble` binary on the source files in `tests/fixtures` and compares the program
+//! and the listing it produces with the checked in ones.
+
+#![cfg(feature = "alloc")]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use michael_computer::{alu, StopReason};
+
+/// Returns the path of the file in the fixtures directory
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// Returns a path in the temporary directory for the test
+fn temporary(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("assemble-{}-{name}", std::process::id()))
+}
+
+fn assemble(args: &[&Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_assemble"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn hello_world() {
+    let output = temporary("hello.bin");
+    let result = assemble(&[
+        &fixture("hello.s"),
+        Path::new("-o"),
+        &output,
+        Path::new("--listing"),
+    ]);
+    assert!(result.status.success());
+    assert_eq!(
+        String::from_utf8(result.stdout).unwrap(),
+        fs::read_to_string(fixture("hello.lst")).unwrap()
+    );
+    let bytes = fs::read(&output).unwrap();
+    fs::remove_file(output).unwrap();
+    assert_eq!(bytes, fs::read(fixture("hello.bin")).unwrap());
+
+    let mut console = String::new();
+    assert_eq!(alu(bytes, [], &mut console), Ok(StopReason::Halted));
+    assert_eq!(console, "Hello, world!");
+}
+
+#[test]
+fn errors() {
+    let source = temporary("error.s");
+    fs::write(&source, "start:  hlt\n        jmp   nowhere ; missing\n").unwrap();
+    let result = assemble(&[&source]);
+    fs::remove_file(&source).unwrap();
+    assert_eq!(result.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8(result.stderr).unwrap(),
+        format!("{}:2:15: undefined label `nowhere`\n", source.display())
+    );
+    assert!(!source.with_extension("bin").exists());
+
+    let result = assemble(&[Path::new("--listing")]);
+    assert_eq!(result.status.code(), Some(1));
+    assert!(String::from_utf8(result.stderr)
+        .unwrap()
+        .contains("usage: assemble"));
+}
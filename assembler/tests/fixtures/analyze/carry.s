@@ -0,0 +1,12 @@
+; Adds and subtracts with overflow where the overflow can't be set
+        ldi r0, 1
+        ldi r1, 2
+        adc r0, r1              ; the overflow starts cleared
+        add r0, r1
+        adc r0, r1
+        sbc r0, r1
+        clc
+        sbc r0, r1              ; the overflow was just cleared
+        stc
+        adc r0, r1
+        hlt
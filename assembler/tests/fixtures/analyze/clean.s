@@ -0,0 +1,21 @@
+; Prints Hello, world! and a digit from a subroutine, without any diagnostics
+start:  ldi r3, text
+loop:   ld r0, [r3]
+        jz r0, done
+        st r0, [255]
+        inc r3
+        jmp loop
+done:   xor r1, r1              ; clearing doesn't read r1
+        call digit
+        ld r2, [254]
+        hlt
+
+digit:  ldi r0, '0'
+        ldi r2, 0xFF
+        add r2, r2              ; sets the overflow
+        adc r0, r1
+        st r0, [255]
+        ret
+
+        .data
+text:   .string "Hello, world!\0"
@@ -0,0 +1,8 @@
+; Uses the addresses of the devices for something else than input and output
+        ld r0, [254]
+        st r0, [255]
+        ld r1, [255]            ; the output can't be read back
+        st r1, [254]            ; the input can't be written
+        rol [255]               ; memory rotates bypass the devices
+        st r1, [0x40]
+        hlt
@@ -0,0 +1,7 @@
+; Writes a register to the output that was never loaded
+        ldi r0, 'A'
+        st r0, [255]
+        ld r2, [254]
+        st r2, [255]
+        st r1, [255]
+        hlt
@@ -0,0 +1,8 @@
+; Reads registers that aren't written on every path
+        ldi r0, 1
+        add r0, r1              ; r1 is never written
+        ld r3, [254]
+        jz r3, skip
+        ldi r2, 5
+skip:   st r2, [0x40]           ; r2 isn't written when jumping here
+        hlt
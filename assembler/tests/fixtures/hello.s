@@ -0,0 +1,10 @@
+; Prints Hello, world! on the console
+start:  ldi r3, text
+loop:   ld r0, [r3]             ; the next character
+        jz r0, done
+        st r0, [255]
+        inc r3
+        jmp loop
+done:   hlt
+
+text:   .byte 72, 101, 108, 108, 111, 44, 32, 119, 111, 114, 108, 100, 33, 0
@@ -0,0 +1,47 @@
+//! Drives the monitor in `examples/monitor.asm` through the whole pipeline: the text assembler,
+//! the loader, the input and console devices and the executor.
+
+#![cfg(feature = "alloc")]
+
+use assembler::parser::Parser;
+use michael_computer::{alu_with_initial_state, state::InitialState, StopReason};
+
+/// Assembles the monitor and runs it on the input, returns the output
+fn run(initial: &InitialState, input: &str) -> String {
+    let program = Parser::new()
+        .assemble(include_str!("../examples/monitor.asm"))
+        .unwrap();
+    let mut output = String::new();
+    let reason = alu_with_initial_state(program, initial, input.bytes(), &mut output);
+    assert_eq!(reason, Ok(StopReason::Halted));
+    output
+}
+
+#[test]
+fn registers() {
+    let initial = InitialState::new().with_registers([0x00, 0x1F, 0xA2, 0xFF]);
+    assert_eq!(run(&initial, "r\n"), "00 1F A2 FF\n");
+}
+
+#[test]
+fn memory() {
+    let initial = InitialState::new()
+        .with_memory(0xC0, 0x3A)
+        .with_memory(0xC1, 0x07);
+    assert_eq!(run(&initial, "mc0\nmC1\n"), "3A\n07\n");
+    // The program is in memory as well, it starts with a store of r0
+    assert_eq!(run(&InitialState::new(), "m00\n"), "08\n");
+}
+
+#[test]
+fn write_then_read() {
+    let input = "wC0ab\nmc0\nwc1\x30\x31\nmC1\nmc2\n";
+    assert_eq!(run(&InitialState::new(), input), "AB\n01\n00\n");
+}
+
+#[test]
+fn unknown_commands() {
+    let initial = InitialState::new().with_registers([1, 2, 3, 4]);
+    assert_eq!(run(&initial, "x\n\nr"), "?\n01 02 03 04\n");
+    assert_eq!(run(&initial, ""), "");
+}
@@ -0,0 +1,61 @@
+//! Lists a program binary, with the toolchain metadata of an image.
+//!
+//! ```text
+//! michael-dis <program>
+//! ```
+//!
+//! The program file holds a program image, or the raw program bytes if it doesn't start with the
+//! magic bytes of an image. The code is printed as the listing of `fmt_image_listing`, with the
+//! metadata of an image as a comment line before it.
+//!
+//! Bytes that don't decode are listed as `.byte`, the listing is printed and the first of them is
+//! reported with the exit code of its error kind. Files that can't be read exit with 1.
+
+#![warn(
+    clippy::pedantic,
+    clippy::nursery,
+    clippy::missing_const_for_fn,
+    missing_docs
+)]
+
+use std::{env, fmt::Display, fs, process::ExitCode};
+
+use assembler::{
+    disassemble::{fmt_image_listing, fmt_listing},
+    image::{ProgramImage, MAGIC},
+};
+use michael_computer::error::McError;
+
+const USAGE: &str = "usage: michael-dis <program>";
+
+/// Prints the message of a failure and returns the exit code, 1 for failures outside the
+/// toolchain and the exit code of the [`McError`] otherwise
+fn fail(message: impl Display, code: u8) -> ExitCode {
+    eprintln!("michael-dis: {message}");
+    ExitCode::from(code)
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let path = match (args.next(), args.next()) {
+        (Some(path), None) if !path.starts_with('-') => path,
+        _ => return fail(USAGE, 1),
+    };
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(error) => return fail(format_args!("{path}: {error}"), 1),
+    };
+    let (listing, error) = if bytes.starts_with(&MAGIC) {
+        match ProgramImage::decode(&bytes) {
+            Ok(image) => fmt_image_listing(&image),
+            Err(error) => return fail(format_args!("{path}: {error}"), error.exit_code()),
+        }
+    } else {
+        let (listing, error) = fmt_listing(&bytes);
+        (format!("; raw program without metadata\n{listing}"), error)
+    };
+    print!("{listing}");
+    error.map_or(ExitCode::SUCCESS, |error| {
+        fail(format_args!("{path}: {error}"), error.exit_code())
+    })
+}
@@ -0,0 +1,539 @@
+//! Runs a program binary on the computer, with the console on the standard output.
+//!
+//! ```text
+//! michael-run [--fuel N] [--trace] [--dump-state] [--stdin] [--info] [--strict-version]
+//!             [--set TARGET=VALUE]... [--allow-overlap] [--vector FILE] [--explain]
+//!             [--emit-const NAME] [--explore input|rN|mem[X]] [--charset FILE] <program>
+//! ```
+//!
+//! The program file holds a program image, or the raw program bytes if it doesn't start with the
+//! magic bytes of an image. Bytes stored at address 255 are written to the standard output as
+//! they are, or with `--charset FILE` as the characters of a 6 bit character set. The file holds
+//! the 64 characters of the bytes 0 to 63 in order, line breaks are skipped, and other bytes are
+//! written as escapes like `\x41`. Test vectors still check the bytes as they were stored. With
+//! `--stdin` the standard input is read at address 254. The trace and the state dump go to the
+//! standard error, so they don't mix with the output. `--emit-const NAME` writes the state the
+//! program stopped with to the standard error as the `MachineState` constant `NAME`, to pin it in
+//! a test.
+//!
+//! An image built by another toolchain is run with a warning, `--strict-version` refuses to load
+//! it. `--info` prints the metadata and the segments of the program instead of running it.
+//! `--explain` prints a walkthrough of every executed instruction instead, of up to `--fuel`
+//! instructions or 1000 without it, with no input and the console output described in the text.
+//! `--explore` runs the program once for every value of the input byte, a register or a memory
+//! cell, with fuel for `--fuel` or 1000 instructions each. It prints every distinct outcome with
+//! the values leading to it and fails with the execution exit code if any run failed or ran out of
+//! fuel. Images are only explored without a data segment and from entry 0.
+//!
+//! `--set r0=5 --set mem[0x10]=0xFF` sets registers, memory cells, `overflow` and `sp` before the
+//! first instruction. Setting a cell of the program is an error unless `--allow-overlap` lets the
+//! value replace the program byte. `--vector FILE` starts the program from the `set` statements of
+//! a test vector, with `--set` applied on top, and checks its `expect` and `output` statements once
+//! the program stopped, failing with exit code 1 on a mismatch.
+//!
+//! Errors of the toolchain exit with the code of their kind, see `ErrorKind::exit_code`. Usage
+//! errors and files that can't be read exit with 1.
+
+#![warn(
+    clippy::pedantic,
+    clippy::nursery,
+    clippy::missing_const_for_fn,
+    missing_docs
+)]
+
+use std::{
+    env,
+    fmt::{self, Display},
+    fs,
+    io::{self, Read, StdoutLock, Write},
+    process::ExitCode,
+};
+
+use assembler::{
+    explain::{explain, explain_image},
+    image::{ImageError, ProgramImage, MAGIC},
+    metadata::Metadata,
+    Instruction,
+};
+use michael_computer::{
+    bus::{Bus, InputStream, OutputDecoder, OutputDevice, OutputSink},
+    error::{ErrorKind, McError},
+    explore::{explore_with_fuel, Outcome, Output, Variable},
+    opcode::decode_prefix,
+    state::{Assignment, InitialState, MachineState},
+    vector::TestVector,
+    Computer, ExecutionError, StepOutcome, StopReason,
+};
+
+const USAGE: &str = "usage: michael-run [--fuel N] [--trace] [--dump-state] [--stdin] [--info] \
+                     [--strict-version] [--set TARGET=VALUE]... [--allow-overlap] \
+                     [--vector FILE] [--explain] [--emit-const NAME] \
+                     [--explore input|rN|mem[X]] [--charset FILE] <program>";
+
+/// The number of instructions `--explain` describes without `--fuel`
+const EXPLAIN_STEPS: usize = 1000;
+
+/// The fuel of every run of `--explore` without `--fuel`
+const EXPLORE_FUEL: u64 = 1000;
+
+/// The options given on the command line
+#[derive(Debug, Default)]
+#[expect(clippy::struct_excessive_bools)]
+struct Options {
+    path: String,
+    fuel: Option<u64>,
+    trace: bool,
+    dump_state: bool,
+    stdin: bool,
+    info: bool,
+    strict_version: bool,
+    assignments: Vec<Assignment>,
+    allow_overlap: bool,
+    vector: Option<String>,
+    explain: bool,
+    emit_const: Option<String>,
+    explore: Option<Variable>,
+    charset: Option<String>,
+}
+
+impl Options {
+    /// Parses the arguments after the name of the binary
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut path = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--fuel" => {
+                    let fuel = args.next().ok_or("--fuel needs a number")?;
+                    options.fuel =
+                        Some(fuel.parse().map_err(|_| format!("invalid fuel `{fuel}`"))?);
+                }
+                "--trace" => options.trace = true,
+                "--dump-state" => options.dump_state = true,
+                "--stdin" => options.stdin = true,
+                "--info" => options.info = true,
+                "--strict-version" => options.strict_version = true,
+                "--set" => {
+                    let assignment = args.next().ok_or("--set needs `target=value`")?;
+                    options.assignments.push(
+                        assignment
+                            .parse()
+                            .map_err(|error| format!("invalid `--set {assignment}`: {error}"))?,
+                    );
+                }
+                "--allow-overlap" => options.allow_overlap = true,
+                "--vector" => options.vector = Some(args.next().ok_or("--vector needs a file")?),
+                "--explain" => options.explain = true,
+                "--charset" => options.charset = Some(args.next().ok_or("--charset needs a file")?),
+                "--emit-const" => {
+                    let name = args.next().ok_or("--emit-const needs a name")?;
+                    if !is_identifier(&name) {
+                        return Err(format!("invalid constant name `{name}`"));
+                    }
+                    options.emit_const = Some(name);
+                }
+                "--explore" => {
+                    let variable = args.next().ok_or("--explore needs a variable")?;
+                    options.explore = Some(
+                        variable
+                            .parse()
+                            .map_err(|error| format!("invalid `--explore {variable}`: {error}"))?,
+                    );
+                }
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ if path.is_some() => return Err(format!("unexpected argument `{arg}`")),
+                _ => path = Some(arg),
+            }
+        }
+        options.path = path.ok_or("missing the program file")?;
+        Ok(options)
+    }
+}
+
+/// Returns whether the name can be used as the name of a constant
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The contents of a program file
+enum Program {
+    /// A program image with its metadata and segments
+    Image(ProgramImage),
+
+    /// The raw program bytes, loaded at address 0
+    Raw(Vec<u8>),
+}
+
+impl Program {
+    /// Decodes the file as an image if it starts with the magic bytes, as raw bytes otherwise
+    fn read(bytes: Vec<u8>) -> Result<Self, ImageError> {
+        if bytes.starts_with(&MAGIC) {
+            ProgramImage::decode(&bytes).map(Self::Image)
+        } else {
+            Ok(Self::Raw(bytes))
+        }
+    }
+
+    /// Returns the metadata of an image, raw programs have none
+    const fn metadata(&self) -> Option<Metadata> {
+        match self {
+            Self::Image(image) => Some(image.metadata),
+            Self::Raw(_) => None,
+        }
+    }
+
+    /// Returns the bytes to explore, which are loaded at address 0 and start there
+    fn explorable(&self) -> Option<&[u8]> {
+        match self {
+            Self::Image(image) if image.entry == 0 && image.data.is_empty() => Some(&image.code),
+            Self::Image(_) => None,
+            Self::Raw(bytes) => Some(bytes),
+        }
+    }
+
+    /// Describes the execution of up to `steps` instructions
+    fn explain(&self, steps: usize) -> String {
+        match self {
+            Self::Image(image) => explain_image(image, steps),
+            Self::Raw(bytes) => explain(bytes, steps),
+        }
+    }
+
+    /// Loads the program into a computer with the bus and applies the initial state
+    fn load<'d>(
+        &self,
+        initial: &InitialState,
+        bus: Bus<'d>,
+    ) -> Result<Computer<'d>, ExecutionError> {
+        match self {
+            Self::Image(image) => image.load_with_state(initial, bus),
+            Self::Raw(bytes) => Computer::with_initial_state(bytes.iter().copied(), initial, bus),
+        }
+    }
+}
+
+/// Formats the metadata and the segments of a program for `--info`
+impl Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Image(image) => {
+                writeln!(f, "image built with {}", image.metadata)?;
+                writeln!(
+                    f,
+                    "code {} bytes, entry {:02X}",
+                    image.code.len(),
+                    image.entry
+                )?;
+                writeln!(
+                    f,
+                    "data {} bytes at {:02X}",
+                    image.data.len(),
+                    image.data_address
+                )
+            }
+            Self::Raw(bytes) => writeln!(f, "raw program without metadata, {} bytes", bytes.len()),
+        }
+    }
+}
+
+/// Writes the bytes stored at the console address to the standard output, keeping a copy of
+/// them when a test vector checks the output. The raw decoder writes the bytes as they are, other
+/// decoders write the text they convert the bytes to.
+struct Stdout<'a> {
+    out: StdoutLock<'static>,
+    decoder: OutputDecoder<'a>,
+    written: Option<Vec<u8>>,
+}
+
+impl OutputSink for Stdout<'_> {
+    fn write_byte(&mut self, byte: u8) -> bool {
+        if let Some(written) = &mut self.written {
+            written.push(byte);
+        }
+        if self.decoder == OutputDecoder::Raw {
+            return self.out.write_all(&[byte]).is_ok();
+        }
+        let mut text = String::new();
+        self.decoder.write(byte, &mut text).is_ok() && self.out.write_all(text.as_bytes()).is_ok()
+    }
+}
+
+/// Parses a character set file, the characters of the bytes 0 to 63 in order with line breaks
+/// between them skipped
+fn parse_charset(text: &str) -> Result<[char; 64], String> {
+    let characters: Vec<char> = text.chars().filter(|&c| c != '\n' && c != '\r').collect();
+    characters.try_into().map_err(|characters: Vec<char>| {
+        format!("expected 64 characters, found {}", characters.len())
+    })
+}
+
+/// Prints the message of a failure and returns the exit code, 1 for failures outside the
+/// toolchain and the exit code of the [`McError`] otherwise
+fn fail(message: impl Display, code: u8) -> ExitCode {
+    eprintln!("michael-run: {message}");
+    ExitCode::from(code)
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => return fail(format_args!("{message}\n{USAGE}"), 1),
+    };
+    match execute(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(code) => code,
+    }
+}
+
+/// Loads and runs the program as the options say, failures are printed before their exit code
+/// is returned
+fn execute(options: &Options) -> Result<(), ExitCode> {
+    let path = &options.path;
+    let bytes = fs::read(path).map_err(|error| fail(format_args!("{path}: {error}"), 1))?;
+    let program = Program::read(bytes)
+        .map_err(|error| fail(format_args!("{path}: {error}"), error.exit_code()))?;
+    if options.info {
+        print!("{program}");
+        return Ok(());
+    }
+    if let Some(metadata) = program.metadata() {
+        match metadata.check(options.strict_version) {
+            Ok(None) => {}
+            Ok(Some(warning)) => eprintln!("michael-run: warning: {path}: {warning}"),
+            Err(error) => return Err(fail(format_args!("{path}: {error}"), error.exit_code())),
+        }
+    }
+    if options.explain {
+        let steps = options.fuel.map_or(EXPLAIN_STEPS, |fuel| {
+            usize::try_from(fuel).unwrap_or(usize::MAX)
+        });
+        print!("{}", program.explain(steps));
+        return Ok(());
+    }
+    if let Some(variable) = options.explore {
+        let Some(bytes) = program.explorable() else {
+            return Err(fail(
+                format_args!("{path}: --explore needs an image without data starting at 0"),
+                1,
+            ));
+        };
+        return explore_program(bytes, variable, options.fuel.unwrap_or(EXPLORE_FUEL));
+    }
+    run_program(options, &program)
+}
+
+/// Runs the program from the initial state of the options and checks the test vector
+fn run_program(options: &Options, program: &Program) -> Result<(), ExitCode> {
+    let vector_text = match &options.vector {
+        Some(vector) => Some(
+            fs::read_to_string(vector)
+                .map_err(|error| fail(format_args!("{vector}: {error}"), 1))?,
+        ),
+        None => None,
+    };
+    let vector = vector_text
+        .as_deref()
+        .map(TestVector::parse)
+        .transpose()
+        .map_err(|error| {
+            let name = options.vector.as_deref().unwrap_or_default();
+            fail(format_args!("{name}: {error}"), error.exit_code())
+        })?;
+    let initial = options
+        .assignments
+        .iter()
+        .fold(
+            vector.map_or_else(InitialState::new, |vector| vector.initial),
+            |initial, &assignment| initial.with_assignment(assignment),
+        )
+        .with_allow_overlap(options.allow_overlap);
+
+    let mut input = Vec::new();
+    if options.stdin {
+        io::stdin().read_to_end(&mut input).map_err(|error| {
+            fail(
+                format_args!("failed to read the standard input: {error}"),
+                1,
+            )
+        })?;
+    }
+    let charset = match &options.charset {
+        Some(charset) => Some(
+            fs::read_to_string(charset)
+                .map_err(|error| error.to_string())
+                .and_then(|text| parse_charset(&text))
+                .map_err(|error| fail(format_args!("{charset}: {error}"), 1))?,
+        ),
+        None => None,
+    };
+    let mut input = InputStream::new(input);
+    let mut output = OutputDevice::new(Stdout {
+        out: io::stdout().lock(),
+        decoder: charset
+            .as_ref()
+            .map_or(OutputDecoder::Raw, OutputDecoder::Table),
+        written: vector.and(Some(Vec::new())),
+    });
+    let bus = Bus::new()
+        .with_device(254..=254, &mut input)
+        .with_device(255..=255, &mut output);
+    let mut computer = program
+        .load(&initial, bus)
+        .map_err(|error| fail(format_args!("{}: {error}", options.path), error.exit_code()))?;
+    computer.set_fuel(options.fuel);
+
+    let result = run(&mut computer, options.trace);
+    let state = computer.state();
+    // The output is flushed before the messages about how the program stopped
+    let mut stdout = output.into_sink();
+    let _ = stdout.out.flush();
+    if options.dump_state {
+        eprint!("{}", Dump(&state));
+    }
+    if let Some(name) = &options.emit_const {
+        eprint!("{}", state.to_rust_const(name));
+    }
+    match result {
+        Ok(StopReason::OutOfFuel { executed }) => Err(fail(
+            format_args!("out of fuel after {executed} instructions"),
+            ErrorKind::Execution.exit_code(),
+        )),
+        Ok(_) => match vector {
+            Some(vector) => vector
+                .check(&state, &stdout.written.unwrap_or_default())
+                .map_err(|mismatch| {
+                    let name = options.vector.as_deref().unwrap_or_default();
+                    fail(format_args!("{name}: {mismatch}"), 1)
+                }),
+            None => Ok(()),
+        },
+        Err((error, bytes)) => Err(fail(
+            format_args!("{error} ({})", Fetched(bytes)),
+            error.exit_code(),
+        )),
+    }
+}
+
+/// Prints every distinct outcome of exploring the variable with the values leading to it.
+/// Returns the execution exit code if any run failed or ran out of fuel.
+fn explore_program(bytes: &[u8], variable: Variable, fuel: u64) -> Result<(), ExitCode> {
+    let report = explore_with_fuel(bytes, variable, fuel);
+    // The outcomes in the order of the first value leading to them, with the number of values
+    let mut groups: Vec<(&Outcome, u8, usize)> = Vec::new();
+    for value in 0..=u8::MAX {
+        let outcome = report.outcome(value);
+        match groups.iter_mut().find(|(group, ..)| *group == outcome) {
+            Some((_, _, count)) => *count += 1,
+            None => groups.push((outcome, value, 1)),
+        }
+    }
+    println!("{variable}:");
+    for (outcome, first, count) in groups {
+        match count {
+            1 => print!("  {first:#04x}: "),
+            _ => print!("  {first:#04x} and {} more: ", count - 1),
+        }
+        match outcome {
+            Outcome::Completed(output) => println!("output {}", Text(output)),
+            Outcome::OutOfFuel(output) => println!("out of fuel, output {}", Text(output)),
+            Outcome::Fault(error) => println!("error: {error}"),
+        }
+    }
+    let failed = report.faults().next().is_some() || report.out_of_fuel().next().is_some();
+    if failed {
+        return Err(ExitCode::from(ErrorKind::Execution.exit_code()));
+    }
+    Ok(())
+}
+
+/// Formats an explored output as a quoted string, with the length if not all of it was captured
+struct Text<'a>(&'a Output);
+
+impl Display for Text<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text: String = self.0.bytes().iter().copied().map(char::from).collect();
+        write!(f, "{text:?}")?;
+        if self.0.len() > self.0.bytes().len() {
+            write!(f, " of {} bytes", self.0.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats the bytes fetched for a failing instruction, as the instruction if they decode to one
+struct Fetched([u8; 3]);
+
+impl Display for Fetched {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (instruction, length) = match Instruction::decode(&self.0) {
+            Some((instruction, length)) => (Some(instruction), length),
+            None => (None, 1 + usize::from(decode_prefix(self.0[0]).is_some())),
+        };
+        match instruction {
+            Some(instruction) => write!(f, "{instruction}, bytes")?,
+            None => write!(f, "opcode")?,
+        }
+        for byte in &self.0[..length] {
+            write!(f, " {byte:#04X}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the program until it stops, printing every executed instruction with `trace`.
+/// A failure is returned with the bytes at the program counter of the failing instruction.
+fn run(computer: &mut Computer, trace: bool) -> Result<StopReason, (ExecutionError, [u8; 3])> {
+    loop {
+        let pc = computer.program_counter();
+        // The longest instruction has a register prefix, an opcode and an operand
+        let bytes = [0, 1, 2].map(|i| computer.memory(pc.wrapping_add(i)));
+        let outcome = if trace {
+            computer.step_traced(|event| {
+                let instruction = Instruction::decode(&bytes).map(|(instruction, _)| instruction);
+                eprintln!(
+                    "{:02X}  {:<16} registers {:02X?} overflow {}",
+                    event.offset,
+                    instruction.map_or_else(
+                        || format!(".byte {:#04X}", event.opcode),
+                        |instruction| instruction.to_string()
+                    ),
+                    event.registers,
+                    u8::from(event.overflow)
+                );
+            })
+        } else {
+            computer.step()
+        }
+        .map_err(|error| (error, bytes))?;
+        if let StepOutcome::Stopped(reason) = outcome {
+            return Ok(reason);
+        }
+    }
+}
+
+/// Formats the registers, flags and memory of a state for `--dump-state`
+struct Dump<'a>(&'a MachineState);
+
+impl Display for Dump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.0;
+        writeln!(
+            f,
+            "registers {:02X?} overflow {} zero {} negative {} sp {:02X}",
+            state.registers,
+            u8::from(state.overflow),
+            u8::from(state.zero),
+            u8::from(state.negative),
+            state.stack_pointer
+        )?;
+        for (row, bytes) in state.memory.chunks(16).enumerate() {
+            write!(f, "{:02X}:", row * 16)?;
+            for byte in bytes {
+                write!(f, " {byte:02X}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
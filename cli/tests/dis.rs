@@ -0,0 +1,83 @@
+//! Runs `michael-dis` on program files and checks the listing it prints.
+
+use std::{
+    fs,
+    process::{Command, Output},
+};
+
+use assembler::{
+    image::ProgramImage,
+    metadata::{Metadata, ENCODING_VERSION},
+};
+
+/// Runs `michael-dis` on a file of its own in the temporary directory holding the program
+fn dis(name: &str, program: &[u8]) -> Output {
+    let path = std::env::temp_dir().join(format!("michael-dis-{}-{name}.bin", std::process::id()));
+    fs::write(&path, program).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_michael-dis"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(path).unwrap();
+    output
+}
+
+fn text(bytes: &[u8]) -> &str {
+    std::str::from_utf8(bytes).unwrap()
+}
+
+/// Prints 'H': ldi r0, 0x48; st r0, [255]
+const CODE: [u8; 4] = [0x00, 0x48, 0x08, 0xFF];
+
+/// The listing of [`CODE`]
+const LISTING: &str = "0000: 00 48    ldi r0, 0x48\n0002: 08 FF    st r0, [0xff]\n";
+
+#[test]
+fn image_metadata() {
+    let mut image = ProgramImage::new(CODE.to_vec());
+    let output = dis("image", &image.encode());
+    assert!(output.status.success());
+    assert_eq!(
+        text(&output.stdout),
+        format!(
+            "; {}, encoding version {ENCODING_VERSION}\n{LISTING}",
+            Metadata::CURRENT
+        )
+    );
+
+    // The metadata of another toolchain is listed as it is
+    image.metadata = Metadata::from_bytes([0, 0, 1, 0]);
+    let output = dis("old", &image.encode());
+    assert!(output.status.success());
+    assert!(text(&output.stdout).starts_with("; assembler 0.0.1, ISA level 0, "));
+
+    let mut corrupt = image.encode();
+    corrupt[4] = 9;
+    let output = dis("corrupt", &corrupt);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(text(&output.stderr).ends_with(": image uses format version 9, expected 1\n"));
+}
+
+#[test]
+fn raw_program() {
+    let output = dis("raw", &CODE);
+    assert!(output.status.success());
+    assert_eq!(
+        text(&output.stdout),
+        format!("; raw program without metadata\n{LISTING}")
+    );
+}
+
+#[test]
+fn invalid_bytes() {
+    // The listing is printed before the first byte that doesn't decode is reported
+    let mut program = CODE.to_vec();
+    program.push(0xC5);
+    let output = dis("invalid", &program);
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(
+        text(&output.stdout),
+        format!("; raw program without metadata\n{LISTING}0004: C5       .byte 0xc5\n")
+    );
+    assert!(text(&output.stderr).ends_with(": 0xC5 at offset 4 doesn't decode as an instruction\n"));
+}
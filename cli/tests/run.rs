@@ -0,0 +1,522 @@
+//! Runs `michael-run` on assembled programs and checks what the process prints and returns.
+
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+use assembler::{image::ProgramImage, metadata::Metadata, parser::Parser, Instruction, Register};
+
+/// Prints the text one character at a time
+fn print(text: &str) -> Vec<u8> {
+    Instruction::to_bytes(text.bytes().flat_map(|byte| {
+        [
+            Instruction::LoadConstant(Register::R0, byte),
+            Instruction::StoreMemory(Register::R0, 255),
+        ]
+    }))
+}
+
+/// Writes the program to a file of its own in the temporary directory
+fn program_file(name: &str, program: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("michael-run-{}-{name}.bin", std::process::id()));
+    fs::write(&path, program).unwrap();
+    path
+}
+
+/// Runs `michael-run` with the arguments before the program file and the standard input
+fn run(name: &str, program: &[u8], args: &[&str], input: &[u8]) -> Output {
+    let path = program_file(name, program);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_michael-run"))
+        .args(args)
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // A program that doesn't read the input may exit before all of it is written
+    match child.stdin.take().unwrap().write_all(input) {
+        Err(error) if error.kind() != ErrorKind::BrokenPipe => panic!("{error}"),
+        _ => {}
+    }
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(path).unwrap();
+    output
+}
+
+/// Writes a text file of its own in the temporary directory
+fn text_file(name: &str, text: &str) -> String {
+    let path = std::env::temp_dir().join(format!("michael-run-{}-{name}.txt", std::process::id()));
+    fs::write(&path, text).unwrap();
+    path.to_str().unwrap().to_owned()
+}
+
+fn text(bytes: &[u8]) -> &str {
+    std::str::from_utf8(bytes).unwrap()
+}
+
+#[test]
+fn hello_world() {
+    let output = run("hello", &print("Hello, world!"), &[], b"");
+    assert!(output.status.success());
+    assert_eq!(text(&output.stdout), "Hello, world!");
+    assert_eq!(text(&output.stderr), "");
+}
+
+/// Prints the text in the data segment of the image
+const PRINT_DATA: &str = "
+        ldi r3, 0x80
+loop:   ld r0, [r3]
+        jz r0, done
+        st r0, [255]
+        inc r3
+        jmp loop
+done:   hlt
+";
+
+/// An image of [`PRINT_DATA`] printing the text
+fn print_image(text: &[u8]) -> ProgramImage {
+    let mut data = text.to_vec();
+    data.push(0);
+    ProgramImage::new(Parser::new().assemble(PRINT_DATA).unwrap()).with_data(0x80, data)
+}
+
+#[test]
+fn image() {
+    let image = print_image(b"image").encode();
+    let output = run("image", &image, &[], b"");
+    assert!(output.status.success());
+    assert_eq!(text(&output.stdout), "image");
+    assert_eq!(text(&output.stderr), "");
+
+    let output = run("info", &image, &["--info"], b"");
+    assert!(output.status.success());
+    assert_eq!(
+        text(&output.stdout),
+        format!(
+            "image built with {}\ncode 12 bytes, entry 00\ndata 6 bytes at 80\n",
+            Metadata::CURRENT
+        )
+    );
+    let output = run("raw-info", &print("raw"), &["--info"], b"");
+    assert_eq!(
+        text(&output.stdout),
+        "raw program without metadata, 12 bytes\n"
+    );
+
+    let mut corrupt = image;
+    corrupt[4] = 9;
+    let output = run("corrupt", &corrupt, &[], b"");
+    assert_eq!(output.status.code(), Some(4));
+    assert!(text(&output.stderr).ends_with(": image uses format version 9, expected 1\n"));
+}
+
+#[test]
+fn version_mismatch() {
+    // An image built by an older assembler
+    let mut image = ProgramImage::new(print("old"));
+    image.metadata = Metadata::from_bytes([0, 0, 1, 0]);
+    let image = image.encode();
+    let warning = format!(
+        "program was built with assembler 0.0.1, ISA level 0, expected {}",
+        Metadata::CURRENT
+    );
+
+    let output = run("old", &image, &[], b"");
+    assert!(output.status.success());
+    assert_eq!(text(&output.stdout), "old");
+    let stderr = text(&output.stderr);
+    assert!(stderr.starts_with("michael-run: warning: "));
+    assert!(stderr.ends_with(&format!("{warning}\n")));
+
+    let output = run("old-strict", &image, &["--strict-version"], b"");
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(text(&output.stdout), "");
+    assert!(text(&output.stderr).ends_with(&format!("{warning}\n")));
+}
+
+/// Adds r1 to r0, prints r0 and the memory cell 0x10
+fn add_and_print() -> Vec<u8> {
+    vec![
+        0x81, // add r0, r1
+        0x08, 0xFF, // st r0, [255]
+        0x06, 0x10, // ld r2, [0x10]
+        0x0A, 0xFF, // st r2, [255]
+    ]
+}
+
+#[test]
+fn initial_state() {
+    let output = run(
+        "set",
+        &add_and_print(),
+        &["--set", "R0=0x40", "--set", "r1=1", "--set", "mem[0x10]=66"],
+        b"",
+    );
+    assert!(output.status.success());
+    assert_eq!(text(&output.stdout), "AB");
+
+    let output = run("set-invalid", &add_and_print(), &["--set", "r8=1"], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(text(&output.stderr).contains("invalid `--set r8=1`"));
+
+    // Storing the sum at 0x10 instead of printing it needs `--allow-overlap`
+    let output = run(
+        "set-overlap",
+        &add_and_print(),
+        &["--set", "mem[2]=0x10"],
+        b"",
+    );
+    assert_eq!(output.status.code(), Some(5));
+    assert!(text(&output.stderr).ends_with("initial state sets address 0x02 of the program\n"));
+    let output = run(
+        "set-allow-overlap",
+        &add_and_print(),
+        &[
+            "--set",
+            "mem[2]=0x10",
+            "--set",
+            "r0=0x40",
+            "--set",
+            "r1=2",
+            "--allow-overlap",
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+    assert_eq!(text(&output.stdout), "B");
+}
+
+#[test]
+fn test_vector() {
+    let vector = text_file(
+        "add",
+        "# adds two numbers\nset r0=0x40\nset r1=1\nset mem[0x10]=66\nexpect r0=0x41\noutput AB\n",
+    );
+    let output = run("vector", &add_and_print(), &["--vector", &vector], b"");
+    assert!(output.status.success(), "{}", text(&output.stderr));
+    assert_eq!(text(&output.stdout), "AB");
+
+    // `--set` takes precedence over the `set` statements of the vector
+    let output = run(
+        "vector-set",
+        &add_and_print(),
+        &["--vector", &vector, "--set", "r1=2"],
+        b"",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(text(&output.stdout), "BB");
+    assert!(
+        text(&output.stderr).ends_with("expected r0=0x41, found 0x42\n"),
+        "{}",
+        text(&output.stderr)
+    );
+
+    let invalid = text_file("invalid", "run\n");
+    let output = run(
+        "vector-invalid",
+        &add_and_print(),
+        &["--vector", &invalid],
+        b"",
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(text(&output.stderr).contains("line 1"));
+    fs::remove_file(vector).unwrap();
+    fs::remove_file(invalid).unwrap();
+}
+
+#[test]
+fn echo_stdin() {
+    let program = Parser::new()
+        .assemble("loop: ld r0, [254]\njz r0, done\nst r0, [255]\njmp loop\ndone: hlt")
+        .unwrap();
+    let output = run("echo", &program, &["--stdin"], b"abc");
+    assert!(output.status.success());
+    assert_eq!(text(&output.stdout), "abc");
+    // Without the flag the input stays empty
+    let output = run("echo-empty", &program, &[], b"abc");
+    assert!(output.status.success());
+    assert_eq!(text(&output.stdout), "");
+}
+
+#[test]
+fn execution_error() {
+    let mut program = print("ok");
+    program.push(0xFE);
+    let output = run("invalid", &program, &[], b"");
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(text(&output.stdout), "ok");
+    assert_eq!(
+        text(&output.stderr),
+        "michael-run: invalid instruction 0xFE at offset 8 (opcode 0xFE)\n"
+    );
+
+    // The failing instruction is reported with its bytes
+    let program = Parser::new().assemble("loop: push r1\njmp loop").unwrap();
+    let output = run("overflow", &program, &[], b"");
+    assert_eq!(output.status.code(), Some(6));
+    let stderr = text(&output.stderr);
+    assert!(stderr.contains("(push r1, bytes 0xC9)"), "{stderr}");
+}
+
+#[test]
+fn prefixed_execution_error() {
+    // The prefix selects r4 to r7, the byte after it isn't an opcode
+    let program = Parser::new()
+        .assemble("ldi r0, 1\n.byte 0xF9, 0xFE")
+        .unwrap();
+    let output = run("invalid-prefixed", &program, &[], b"");
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(
+        text(&output.stderr),
+        "michael-run: invalid instruction 0xFE at offset 3 (opcode 0xF9 0xFE)\n"
+    );
+
+    // The stack overflows at the prefixed push, which is reported with its prefix
+    let program = Parser::new().assemble("loop: push r5\njmp loop").unwrap();
+    let output = run("overflow-prefixed", &program, &[], b"");
+    assert_eq!(output.status.code(), Some(6));
+    let stderr = text(&output.stderr);
+    assert!(stderr.contains("(push r5, bytes 0xF9 0xC9)"), "{stderr}");
+}
+
+#[test]
+fn fuel() {
+    let program = Parser::new().assemble("loop: jmp loop").unwrap();
+    let output = run("fuel", &program, &["--fuel", "5"], b"");
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(
+        text(&output.stderr),
+        "michael-run: out of fuel after 5 instructions\n"
+    );
+}
+
+#[test]
+fn trace_and_dump() {
+    let program = Parser::new()
+        .assemble("ldi r1, 0x2A\nmov r6, r1\nhlt")
+        .unwrap();
+    let output = run("trace", &program, &["--trace", "--dump-state"], b"");
+    assert!(output.status.success());
+    let stderr = text(&output.stderr);
+    let mut lines = stderr.lines();
+    assert!(
+        lines.next().unwrap().starts_with("00  ldi r1, 0x2a"),
+        "{stderr}"
+    );
+    assert!(
+        lines.next().unwrap().starts_with("02  mov r6, r1"),
+        "{stderr}"
+    );
+    assert!(lines.next().unwrap().starts_with("04  hlt"), "{stderr}");
+    assert!(
+        lines
+            .next()
+            .unwrap()
+            .starts_with("registers [00, 2A, 00, 00, 00, 00, 2A, 00]"),
+        "{stderr}"
+    );
+    assert_eq!(
+        lines.next(),
+        Some("00: 01 2A FA 19 FF 00 00 00 00 00 00 00 00 00 00 00")
+    );
+    assert_eq!(lines.count(), 15);
+}
+
+#[test]
+fn explain() {
+    let output = run("explain", &print("A"), &["--explain"], b"");
+    assert!(output.status.success());
+    assert_eq!(
+        text(&output.stdout),
+        "\
+step 1, offset 0: ldi r0, 0x41
+  reads the constant 0x41
+  routes the byte into the register through the demux
+  r0: 0x00 -> 0x41
+step 2, offset 2: st r0, [0xff]
+  reads r0 = 0x41
+  routes the byte into memory through the 256 way demux
+  [0xff]: 0x00 -> 0x41
+  outputs 'A'
+reached the end of the program
+"
+    );
+
+    // An image is described from its code
+    let image = ProgramImage::new(print("A")).encode();
+    let explained = run("explain-image", &image, &["--explain"], b"");
+    assert_eq!(explained.stdout, output.stdout);
+
+    // `--fuel` limits the described instructions
+    let output = run(
+        "explain-fuel",
+        &print("A"),
+        &["--explain", "--fuel", "1"],
+        b"",
+    );
+    assert!(text(&output.stdout).ends_with("stopped after 1 steps\n"));
+
+    let image = print_image(b"!").encode();
+    let output = run("explain-image", &image, &["--explain"], b"");
+    assert!(output.status.success());
+    assert!(text(&output.stdout).contains("  outputs '!'\n"));
+}
+
+#[test]
+fn emit_const() {
+    let program = [
+        0x01, 0x2A, // ldi r1, 0x2a
+        0x09, 0x10, // st r1, [0x10]
+    ];
+    let output = run(
+        "emit-const",
+        &program,
+        &["--emit-const", "DONE", "--set", "sp=0xE0"],
+        b"",
+    );
+    assert!(output.status.success());
+    let stderr = text(&output.stderr);
+    assert!(
+        stderr.starts_with(
+            "pub const DONE: MachineState = MachineState {\n    \
+             registers: [0x00, 0x2a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],\n    memory: [\n"
+        ),
+        "{stderr}"
+    );
+    assert!(stderr.contains("\n        0x2a, 0x00,"), "{stderr}");
+    assert!(stderr.ends_with(
+        "    ],\n    overflow: false,\n    zero: false,\n    negative: false,\n    \
+         half_carry: false,\n    stack_pointer: 0xe0,\n};\n"
+    ));
+
+    let output = run(
+        "emit-const-invalid",
+        &program,
+        &["--emit-const", "1st"],
+        b"",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(text(&output.stderr).contains("invalid constant name `1st`"));
+}
+
+#[test]
+fn explore() {
+    // Prints the low bit of r0 as a digit
+    let program = [
+        0x01, 0x01, // ldi r1, 1
+        0x31, // and r0, r1
+        0x01, b'0', // ldi r1, '0'
+        0x81, // add r0, r1
+        0x08, 0xFF, // st r0, [255]
+        0xFF, // hlt
+    ];
+    let output = run("explore", &program, &["--explore", "r0"], b"");
+    assert!(output.status.success(), "{}", text(&output.stderr));
+    assert_eq!(
+        text(&output.stdout),
+        "r0:\n  0x00 and 127 more: output \"0\"\n  0x01 and 127 more: output \"1\"\n"
+    );
+
+    // Fails for input 0, loops for 0xFF
+    let program = [
+        0x04, 0xFE, // ld r0, [254]
+        0xC4, 0x0A, // jz r0, bad
+        0x01, 0x01, // ldi r1, 1
+        0x81, // add r0, r1
+        0xC4, 0x07, // loop: jz r0, loop
+        0xFF, // hlt
+        0xFE, // bad: invalid
+    ];
+    let output = run("explore-faults", &program, &["--explore", "input"], b"");
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(
+        text(&output.stdout),
+        "input:\n  0x00: error: invalid instruction 0xFE at offset 10\n  \
+         0x01 and 253 more: output \"\"\n  0xff: out of fuel, output \"\"\n"
+    );
+
+    // `--fuel` replaces the fuel of every run
+    let output = run(
+        "explore-fuel",
+        &program,
+        &["--explore", "input", "--fuel", "3"],
+        b"",
+    );
+    assert_eq!(
+        text(&output.stdout),
+        "input:\n  0x00: error: invalid instruction 0xFE at offset 10\n  \
+         0x01 and 254 more: out of fuel, output \"\"\n"
+    );
+
+    let output = run("explore-invalid", &program, &["--explore", "overflow"], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(text(&output.stderr).contains("invalid `--explore overflow`"));
+
+    // The data segment of an image isn't explored
+    let image = print_image(b"data").encode();
+    let output = run("explore-image", &image, &["--explore", "input"], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(text(&output.stderr).contains("--explore needs an image without data starting at 0"));
+}
+
+#[test]
+fn charset() {
+    // Code 1 is `A` and code 33 `!` in the character set, `A` itself isn't in it
+    let charset: String = (0..64)
+        .map(|code| match code {
+            1 => 'A',
+            33 => '!',
+            _ => '.',
+        })
+        .collect();
+    // The file holds the set in two lines
+    let charset = text_file(
+        "charset",
+        &format!("{}\n{}\n", &charset[..32], &charset[32..]),
+    );
+    let program = [
+        0x00, 1, // ldi r0, 1
+        0x08, 0xFF, // st r0, [255]
+        0x00, 33, // ldi r0, 33
+        0x08, 0xFF, // st r0, [255]
+        0x00, b'A', // ldi r0, 'A'
+        0x08, 0xFF, // st r0, [255]
+    ];
+    let vector = text_file("charset-vector", "output \u{1}!A\n");
+    let output = run(
+        "charset",
+        &program,
+        &["--charset", &charset, "--vector", &vector],
+        b"",
+    );
+    assert!(output.status.success(), "{}", text(&output.stderr));
+    assert_eq!(text(&output.stdout), "A!\\x41");
+
+    let short = text_file("charset-short", "ABC\n");
+    let output = run("charset-short", &program, &["--charset", &short], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(text(&output.stderr).contains("expected 64 characters, found 3"));
+    for file in [charset, vector, short] {
+        fs::remove_file(file).unwrap();
+    }
+}
+
+#[test]
+fn usage() {
+    let output = Command::new(env!("CARGO_BIN_EXE_michael-run"))
+        .arg("--frobnicate")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(text(&output.stderr).starts_with("michael-run: unknown option `--frobnicate`\nusage:"));
+    let output = Command::new(env!("CARGO_BIN_EXE_michael-run"))
+        .arg("--fuel")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(text(&output.stderr).contains("--fuel needs a number"));
+}
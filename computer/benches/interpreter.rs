@@ -0,0 +1,38 @@
+//! Times a loop heavy program, compare `cargo bench -p michael_computer` with
+//! `cargo bench -p michael_computer --features fast`.
+
+use std::{hint::black_box, time::Instant};
+
+use michael_computer::{bus::Bus, Computer};
+
+/// Counts r0 down from 255 in an inner loop, 32 times, adding to r2 and going through memory
+const CODE: [u8; 22] = [
+    0x03, 32, // r3 = 32
+    0x00, 255, // r0 = 255
+    0x01, 3, 0x89, 0x09, 0x80, 0x05, 0x80, // r1 = 3, r2 += r1, [0x80] = r1, r1 = [0x80]
+    0xE8, 0xC4, 16, 0xC0, 4, // r0 -= 1 until it is 0
+    0xEB, 0xC7, 21, 0xC0, 2, // r3 -= 1 until it is 0
+    0xFF,
+];
+
+fn main() {
+    let runs = 5;
+    let start = Instant::now();
+    let mut executed = 0;
+    for _ in 0..runs {
+        let mut computer = Computer::new(black_box(CODE), Bus::new()).unwrap();
+        computer.run().unwrap();
+        executed += computer.executed();
+        black_box(computer.registers());
+    }
+    let elapsed = start.elapsed();
+    let backend = if cfg!(feature = "fast") {
+        "fast"
+    } else {
+        "circuit"
+    };
+    println!(
+        "{backend}: {executed} instructions in {elapsed:?}, {:.0} ns per instruction",
+        elapsed.as_secs_f64() * 1e9 / f64::from(u32::try_from(executed).unwrap())
+    );
+}
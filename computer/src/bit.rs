@@ -2,8 +2,170 @@
 
 use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
 
+#[cfg(feature = "gate-count")]
+use crate::gate_count;
+
+/// Defines a gate built from nand gates. It is a `const fn`, except with the `gate-count` feature,
+/// where the nand gates update a counter.
+macro_rules! gate {
+    ($(#[$attr:meta])* $vis:vis fn $name:ident($($args:tt)*) -> $ret:ty $body:block) => {
+        $(#[$attr])*
+        #[cfg(not(feature = "gate-count"))]
+        $vis const fn $name($($args)*) -> $ret $body
+
+        $(#[$attr])*
+        #[cfg(feature = "gate-count")]
+        // `Bit::not` only counts as a candidate for the `Not` trait once it isn't const
+        #[allow(clippy::should_implement_trait)]
+        $vis fn $name($($args)*) -> $ret $body
+    };
+}
+
+pub(crate) use gate;
+
+/// Defines the gates built from the `nand` method of a signal and implements [`Gates`] with them.
+/// [`Bit`] and [`Wire`](crate::depth::Wire) both get their gates here, so they share one
+/// construction of every gate.
+macro_rules! nand_gates {
+    ($signal:ty) => {
+        impl $signal {
+            $crate::bit::gate! {
+                /// Inverts the signal
+                pub fn not(self) -> Self {
+                    self.nand(self)
+                }
+            }
+
+            $crate::bit::gate! {
+                /// Returns high if both signals are high
+                pub fn and(self, other: Self) -> Self {
+                    self.nand(other).not()
+                }
+            }
+
+            $crate::bit::gate! {
+                /// Returns high if either or both signals are high
+                pub fn or(self, other: Self) -> Self {
+                    self.not().nand(other.not())
+                }
+            }
+
+            $crate::bit::gate! {
+                /// Returns high if neither signal is high
+                pub fn nor(self, other: Self) -> Self {
+                    self.or(other).not()
+                }
+            }
+
+            $crate::bit::gate! {
+                /// Returns high if none or both signals are high
+                pub fn xnor(self, other: Self) -> Self {
+                    self.nand(other).nand(self.or(other))
+                }
+            }
+
+            $crate::bit::gate! {
+                /// Returns high if either but not both signals are high
+                pub fn xor(self, other: Self) -> Self {
+                    self.xnor(other).not()
+                }
+            }
+
+            $crate::bit::gate! {
+                /// Adds 2 signals and returns a sum and a carry
+                pub fn half_adder(self, right: Self) -> (Self, Self) {
+                    (self.xor(right), self.and(right))
+                }
+            }
+
+            $crate::bit::gate! {
+                /// Adds 3 signals (one of which a carry), and returns a sum and a new carry
+                pub fn full_adder(self, right: Self, carry: Self) -> (Self, Self) {
+                    (
+                        self.xor(right).xor(carry),
+                        self.or(right).and(carry).or(self.and(right)),
+                    )
+                }
+            }
+        }
+
+        impl $crate::bit::Gates for $signal {
+            fn nand(self, other: Self) -> Self {
+                Self::nand(self, other)
+            }
+
+            fn not(self) -> Self {
+                Self::not(self)
+            }
+
+            fn and(self, other: Self) -> Self {
+                Self::and(self, other)
+            }
+
+            fn or(self, other: Self) -> Self {
+                Self::or(self, other)
+            }
+
+            fn nor(self, other: Self) -> Self {
+                Self::nor(self, other)
+            }
+
+            fn xnor(self, other: Self) -> Self {
+                Self::xnor(self, other)
+            }
+
+            fn xor(self, other: Self) -> Self {
+                Self::xor(self, other)
+            }
+
+            fn half_adder(self, right: Self) -> (Self, Self) {
+                Self::half_adder(self, right)
+            }
+
+            fn full_adder(self, right: Self, carry: Self) -> (Self, Self) {
+                Self::full_adder(self, right, carry)
+            }
+        }
+    };
+}
+
+pub(crate) use nand_gates;
+
+/// The gates of a signal, built from nand gates by the same constructions for every signal.
+/// Circuits written for any `Gates` compute [`Bit`]s and can be measured on
+/// [`Wire`](crate::depth::Wire)s.
+pub trait Gates: Copy {
+    /// The nand gate, all other gates are built from it
+    fn nand(self, other: Self) -> Self;
+
+    /// Inverts the signal
+    fn not(self) -> Self;
+
+    /// Returns high if both signals are high
+    fn and(self, other: Self) -> Self;
+
+    /// Returns high if either or both signals are high
+    fn or(self, other: Self) -> Self;
+
+    /// Returns high if neither signal is high
+    fn nor(self, other: Self) -> Self;
+
+    /// Returns high if none or both signals are high
+    fn xnor(self, other: Self) -> Self;
+
+    /// Returns high if either but not both signals are high
+    fn xor(self, other: Self) -> Self;
+
+    /// Adds 2 signals and returns a sum and a carry
+    fn half_adder(self, right: Self) -> (Self, Self);
+
+    /// Adds 3 signals (one of which a carry), and returns a sum and a new carry
+    fn full_adder(self, right: Self, carry: Self) -> (Self, Self);
+}
+
 /// The most primitive datatype, all other data types use this datatype.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Bit {
     /// Logical 0, boolean false
     Low,
@@ -13,59 +175,29 @@ pub enum Bit {
 }
 
 impl Bit {
-    /// The most primitive gate of this library, all other gates and circuits use this
-    pub const fn nand(self, other: Self) -> Self {
+    gate! {
+        /// The most primitive gate of this library, all other gates and circuits use this
+        pub fn nand(self, other: Self) -> Self {
+            #[cfg(feature = "gate-count")]
+            gate_count::count_nand();
+            self.nand_uncounted(other)
+        }
+    }
+
+    /// The nand gate without counting it, for models of circuits like [`Wire`]
+    ///
+    /// [`Wire`]: crate::depth::Wire
+    pub(crate) const fn nand_uncounted(self, other: Self) -> Self {
         if matches!((self, other), (Self::High, Self::High)) {
             Self::Low
         } else {
             Self::High
         }
     }
-
-    /// Inverts the bit
-    pub const fn not(self) -> Self {
-        self.nand(self)
-    }
-
-    /// Returns `Bit::High` if both bits are high
-    pub const fn and(self, other: Self) -> Self {
-        self.nand(other).not()
-    }
-
-    /// Returns `Bit::High` if either or both bits are high
-    pub const fn or(self, other: Self) -> Self {
-        self.not().nand(other.not())
-    }
-
-    /// Returns `Bit::High` if neither bit is heigh
-    pub const fn nor(self, other: Self) -> Self {
-        self.or(other).not()
-    }
-
-    /// Returns `Bit::High` if none or both bits are high
-    pub const fn xnor(self, other: Self) -> Self {
-        self.nand(other).nand(self.or(other))
-    }
-
-    /// Returns `Bit::High` if either but not both bits are high
-    pub const fn xor(self, other: Self) -> Self {
-        self.xnor(other).not()
-    }
-
-    /// Adds 2 bits and returns a sum and a carry bit
-    pub const fn half_adder(self, right: Self) -> (Self, Self) {
-        (self.xor(right), self.and(right))
-    }
-
-    /// Adds 3 bits (one of which a carry bit), and returns a sum and a new carry bit
-    pub const fn full_adder(self, right: Self, carry: Self) -> (Self, Self) {
-        (
-            self.xor(right).xor(carry),
-            self.or(right).and(carry).or(self.and(right)),
-        )
-    }
 }
 
+nand_gates!(Bit);
+
 impl From<bool> for Bit {
     fn from(value: bool) -> Self {
         if value {
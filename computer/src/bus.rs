@@ -0,0 +1,873 @@
+//! The memory bus connecting the memory and the memory mapped devices.
+
+use core::{
+    cell::{Cell, RefCell},
+    fmt::{self, Write},
+    iter::{self, Empty},
+    ops::RangeInclusive,
+};
+
+use crate::{
+    bit::Bit,
+    byte::Byte,
+    mux::byte::{is_zero, mux, Ram, Ram4k},
+};
+
+/// The number of devices that can be attached to a bus
+pub const MAX_DEVICES: usize = 8;
+
+/// A device mapped into the address space of the computer.
+///
+/// The bus calls the device while the instruction accessing it executes, so every read and write
+/// reaches its device in program order, and the accesses of different devices are ordered the
+/// same way. A device makes a write visible to the host before returning from
+/// [`Device::write`], buffering it for later would break this order.
+pub trait Device {
+    /// Reads the byte at the address, or `None` to read the memory cell instead
+    fn read(&mut self, address: Byte) -> Option<Byte>;
+
+    /// Handles a write of the byte at the address, returns `false` if the device failed.
+    /// The memory cell is written as well.
+    fn write(&mut self, address: Byte, value: Byte) -> bool;
+}
+
+/// A device with the addresses it is mapped at
+struct Mapping<'d> {
+    addresses: RangeInclusive<u8>,
+    device: &'d mut dyn Device,
+}
+
+/// The memory with the devices mapped over it.
+///
+/// The first attached device mapped at an address handles it.
+///
+/// The memory of the bus is bank 0. A [`Ram4k`] attached with [`Bus::with_banks`] adds its banks
+/// 1 to 15, which [`Bus::load_banked`] and [`Bus::store_banked`] select, without it every bank is
+/// bank 0. The devices are mapped in every bank: an access to the address of a device reaches it
+/// whatever the bank, so the devices stay reachable after switching banks.
+pub struct Bus<'d> {
+    memory: Ram,
+    banks: Option<&'d mut Ram4k>,
+    devices: [Option<Mapping<'d>>; MAX_DEVICES],
+}
+
+impl Default for Bus<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'d> Bus<'d> {
+    /// Creates a bus with cleared memory and no devices
+    pub fn new() -> Self {
+        Self {
+            memory: Ram::new(),
+            banks: None,
+            devices: [const { None }; MAX_DEVICES],
+        }
+    }
+
+    /// Maps the device at the addresses
+    ///
+    /// # Panics
+    /// Panics if [`MAX_DEVICES`] devices are already attached
+    pub fn with_device(
+        mut self,
+        addresses: RangeInclusive<u8>,
+        device: &'d mut dyn Device,
+    ) -> Self {
+        let slot = self
+            .devices
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .expect("no room for another device on the bus");
+        *slot = Some(Mapping { addresses, device });
+        self
+    }
+
+    /// Attaches the banks 1 to 15 of the memory, bank 0 of the [`Ram4k`] isn't used as the memory
+    /// of the bus is bank 0
+    pub const fn with_banks(mut self, banks: &'d mut Ram4k) -> Self {
+        self.banks = Some(banks);
+        self
+    }
+
+    /// Returns the device mapped at the address
+    fn device(&mut self, address: Byte) -> Option<&mut (dyn Device + 'd)> {
+        let address = u8::from(address);
+        self.devices
+            .iter_mut()
+            .flatten()
+            .find(|mapping| mapping.addresses.contains(&address))
+            .map(|mapping| &mut *mapping.device)
+    }
+
+    /// Loads the byte at the address from the device mapped there, or from memory
+    pub fn load(&mut self, address: Byte) -> Byte {
+        self.load_banked([Bit::Low; 4], address)
+    }
+
+    /// Stores the byte in memory and passes it to the device mapped at the address.
+    /// Returns `false` if the device failed.
+    pub fn store(&mut self, address: Byte, value: Byte) -> bool {
+        self.store_banked([Bit::Low; 4], address, value)
+    }
+
+    /// Loads the byte at the address from the device mapped there, or from the bank of memory
+    pub fn load_banked(&mut self, bank: [Bit; 4], address: Byte) -> Byte {
+        let value = self.device(address).and_then(|device| device.read(address));
+        value.unwrap_or_else(|| self.load_cell(bank, address))
+    }
+
+    /// Stores the byte in the bank of memory and passes it to the device mapped at the address.
+    /// Returns `false` if the device failed.
+    pub fn store_banked(&mut self, bank: [Bit; 4], address: Byte, value: Byte) -> bool {
+        self.store_cell(bank, address, value);
+        self.device(address)
+            .is_none_or(|device| device.write(address, value))
+    }
+
+    /// Loads the memory cell at the address in the bank, bypassing the devices
+    pub fn load_cell(&self, bank: [Bit; 4], address: Byte) -> Byte {
+        match &self.banks {
+            Some(banks) if bank != [Bit::Low; 4] => banks.load(banked_address(bank, address)),
+            _ => self.memory.load(address),
+        }
+    }
+
+    /// Stores the byte in the memory cell at the address in the bank, bypassing the devices
+    pub fn store_cell(&mut self, bank: [Bit; 4], address: Byte, value: Byte) {
+        match &mut self.banks {
+            Some(banks) if bank != [Bit::Low; 4] => {
+                banks.store(banked_address(bank, address), value);
+            }
+            _ => self.memory.store(address, value),
+        }
+    }
+
+    /// Returns whether banks are attached, without them every bank is bank 0
+    pub const fn has_banks(&self) -> bool {
+        self.banks.is_some()
+    }
+
+    /// Returns the memory behind the devices, bank 0
+    pub const fn memory(&self) -> &Ram {
+        &self.memory
+    }
+
+    /// Returns the memory behind the devices, for changes that bypass them
+    pub const fn memory_mut(&mut self) -> &mut Ram {
+        &mut self.memory
+    }
+}
+
+/// Returns the 12 bit address of the cell at the address in the bank
+fn banked_address(bank: [Bit; 4], address: Byte) -> [Bit; 12] {
+    let address: [Bit; 8] = address.into();
+    core::array::from_fn(|i| if i < 8 { address[i] } else { bank[i - 8] })
+}
+
+/// Converts the bytes written to the console to text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputDecoder<'a> {
+    /// Every byte is the character with the same code point
+    Raw,
+
+    /// Printable ASCII characters and newlines are written as they are, every other byte as an
+    /// escape sequence like `\x07`. Backslashes are escaped as `\\`.
+    Ascii,
+
+    /// Bytes below 64 are looked up in the table, like a custom 6 bit character set, every other
+    /// byte is written as an escape sequence like `\x40`
+    Table(&'a [char; 64]),
+}
+
+impl OutputDecoder<'_> {
+    /// Writes the text for the byte
+    ///
+    /// # Errors
+    /// Returns an error if writing to the output failed
+    pub fn write(&self, byte: u8, out: &mut impl Write) -> fmt::Result {
+        match self {
+            Self::Raw => out.write_char(char::from(byte)),
+            Self::Ascii => match byte {
+                b'\\' => out.write_str("\\\\"),
+                b'\n' | b' '..=b'~' => out.write_char(char::from(byte)),
+                _ => write!(out, "\\x{byte:02x}"),
+            },
+            Self::Table(table) => match table.get(usize::from(byte)) {
+                Some(&character) => out.write_char(character),
+                None => write!(out, "\\x{byte:02x}"),
+            },
+        }
+    }
+
+    /// Writes the text for every byte
+    ///
+    /// # Errors
+    /// Returns an error if writing to the output failed
+    pub fn write_all(
+        &self,
+        bytes: impl IntoIterator<Item = u8>,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        bytes.into_iter().try_for_each(|byte| self.write(byte, out))
+    }
+}
+
+/// Writes every stored byte to the output as text, as a character by default
+pub struct ConsoleDevice<'a, W> {
+    out: W,
+    decoder: OutputDecoder<'a>,
+}
+
+impl<'a, W: Write> ConsoleDevice<'a, W> {
+    /// Creates a console writing to the output
+    pub const fn new(out: W) -> Self {
+        Self {
+            out,
+            decoder: OutputDecoder::Raw,
+        }
+    }
+
+    /// Sets how the bytes are converted to text
+    pub const fn with_decoder(mut self, decoder: OutputDecoder<'a>) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Returns the output
+    pub const fn output(&self) -> &W {
+        &self.out
+    }
+
+    /// Returns the output, consuming the console
+    pub fn into_output(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> Device for ConsoleDevice<'_, W> {
+    fn read(&mut self, _: Byte) -> Option<Byte> {
+        None
+    }
+
+    fn write(&mut self, _: Byte, value: Byte) -> bool {
+        self.decoder.write(u8::from(value), &mut self.out).is_ok()
+    }
+}
+
+/// Receives the raw bytes stored to an output device, without converting them to text
+pub trait OutputSink {
+    /// Handles the byte, returns `false` if the output failed
+    fn write_byte(&mut self, byte: u8) -> bool;
+}
+
+impl<S: OutputSink + ?Sized> OutputSink for &mut S {
+    fn write_byte(&mut self, byte: u8) -> bool {
+        (**self).write_byte(byte)
+    }
+}
+
+/// Writes every byte to a [`Write`] as text, like the [`ConsoleDevice`].
+///
+/// Bytes from 0x80 are written as multi-byte characters by the default decoder, so binary output
+/// should go to a sink storing the bytes instead.
+pub struct FmtWriteSink<'a, W> {
+    out: W,
+    decoder: OutputDecoder<'a>,
+}
+
+impl<'a, W: Write> FmtWriteSink<'a, W> {
+    /// Creates a sink writing every byte as the character with the same code point
+    pub const fn new(out: W) -> Self {
+        Self {
+            out,
+            decoder: OutputDecoder::Raw,
+        }
+    }
+
+    /// Sets how the bytes are converted to text
+    pub const fn with_decoder(mut self, decoder: OutputDecoder<'a>) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Returns the output, consuming the sink
+    pub fn into_output(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> OutputSink for FmtWriteSink<'_, W> {
+    fn write_byte(&mut self, byte: u8) -> bool {
+        self.decoder.write(byte, &mut self.out).is_ok()
+    }
+}
+
+/// Passes every stored byte to the sink unchanged
+pub struct OutputDevice<S> {
+    sink: S,
+}
+
+impl<S: OutputSink> OutputDevice<S> {
+    /// Creates a device writing to the sink
+    pub const fn new(sink: S) -> Self {
+        Self { sink }
+    }
+
+    /// Returns the sink
+    pub const fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Returns the sink, consuming the device
+    pub fn into_sink(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: OutputSink> Device for OutputDevice<S> {
+    fn read(&mut self, _: Byte) -> Option<Byte> {
+        None
+    }
+
+    fn write(&mut self, _: Byte, value: Byte) -> bool {
+        self.sink.write_byte(value.into())
+    }
+}
+
+/// Reads the next byte of the input on every load, or the sentinel once it is exhausted
+pub struct InputStream<I> {
+    input: I,
+    sentinel: u8,
+}
+
+impl<I: Iterator<Item = u8>> InputStream<I> {
+    /// Creates a device reading the input, returning 0 once it is exhausted
+    pub fn new(input: impl IntoIterator<IntoIter = I>) -> Self {
+        Self {
+            input: input.into_iter(),
+            sentinel: 0,
+        }
+    }
+
+    /// Sets the byte read once the input is exhausted
+    pub const fn with_sentinel(mut self, sentinel: u8) -> Self {
+        self.sentinel = sentinel;
+        self
+    }
+}
+
+impl<I: Iterator<Item = u8>> Device for InputStream<I> {
+    fn read(&mut self, _: Byte) -> Option<Byte> {
+        Some(self.input.next().unwrap_or(self.sentinel).into())
+    }
+
+    fn write(&mut self, _: Byte, _: Byte) -> bool {
+        true
+    }
+}
+
+/// The number of bytes an [`InputDevice`] buffers
+pub const INPUT_BUFFER: usize = 16;
+
+/// A keyboard-like input mapped at 2 addresses, which programs poll instead of waiting for input.
+///
+/// Reading the status at the base address returns 1 when a byte is available and 0 otherwise.
+/// Reading the data at the base address + 1 consumes the next byte, or returns 0 without one.
+/// The host pushes the bytes into a buffer of [`INPUT_BUFFER`] bytes, or wraps an iterator that
+/// fills the buffer whenever it is empty. Writes are ignored.
+pub struct InputDevice<I = Empty<u8>> {
+    base: u8,
+    buffer: [u8; INPUT_BUFFER],
+    start: usize,
+    len: usize,
+    input: I,
+}
+
+impl InputDevice {
+    /// Creates an empty device with the status at `base` and the data at `base + 1`
+    ///
+    /// # Panics
+    /// Panics if `base` is 255, leaving no address for the data
+    pub fn new(base: u8) -> Self {
+        Self::with_input(base, iter::empty())
+    }
+}
+
+impl<I: Iterator<Item = u8>> InputDevice<I> {
+    /// Creates a device reading the input once the pushed bytes are consumed
+    ///
+    /// # Panics
+    /// Panics if `base` is 255, leaving no address for the data
+    pub fn with_input(base: u8, input: impl IntoIterator<IntoIter = I>) -> Self {
+        assert!(base < u8::MAX, "no address for the input data");
+        Self {
+            base,
+            buffer: [0; INPUT_BUFFER],
+            start: 0,
+            len: 0,
+            input: input.into_iter(),
+        }
+    }
+
+    /// Adds a byte to the buffer, returns `false` if the buffer is full and the byte was dropped
+    pub const fn push(&mut self, byte: u8) -> bool {
+        if self.len == INPUT_BUFFER {
+            return false;
+        }
+        self.buffer[(self.start + self.len) % INPUT_BUFFER] = byte;
+        self.len += 1;
+        true
+    }
+
+    /// Returns the number of buffered bytes
+    pub const fn buffered(&self) -> usize {
+        self.len
+    }
+
+    /// Moves the next byte of the input into the empty buffer
+    fn refill(&mut self) {
+        if self.len == 0 {
+            if let Some(byte) = self.input.next() {
+                self.push(byte);
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = u8>> Device for InputDevice<I> {
+    fn read(&mut self, address: Byte) -> Option<Byte> {
+        let offset = u8::from(address).wrapping_sub(self.base);
+        if offset > 1 {
+            return None;
+        }
+        self.refill();
+        if offset == 0 {
+            return Some(u8::from(self.len > 0).into());
+        }
+        if self.len == 0 {
+            return Some(0.into());
+        }
+        let byte = self.buffer[self.start];
+        self.start = (self.start + 1) % INPUT_BUFFER;
+        self.len -= 1;
+        Some(byte.into())
+    }
+
+    fn write(&mut self, _: Byte, _: Byte) -> bool {
+        true
+    }
+}
+
+/// Reads the next byte of an 8-bit linear feedback shift register on every load, writes set the
+/// seed.
+///
+/// The register shifts left and feeds the xor of bits 7, 5, 4 and 3 back into bit 0, the taps of
+/// x^8 + x^6 + x^5 + x^4 + 1, so it runs through all 255 nonzero states before repeating. The
+/// state 0 would lock up and never change, so a seed of 0 is replaced by 1.
+pub struct RngDevice {
+    state: Byte,
+}
+
+impl RngDevice {
+    /// Creates a generator starting from the seed
+    pub fn new(seed: u8) -> Self {
+        Self {
+            state: nonzero(seed.into()),
+        }
+    }
+
+    /// Moves the register one step and returns the new state
+    pub fn next_byte(&mut self) -> u8 {
+        let bits: [Bit; 8] = self.state.into();
+        let feedback = bits[7].xor(bits[5]).xor(bits[4].xor(bits[3]));
+        self.state = Byte::from([
+            feedback, bits[0], bits[1], bits[2], bits[3], bits[4], bits[5], bits[6],
+        ]);
+        self.state.into()
+    }
+}
+
+/// Replaces the seed 0, which the register can't leave, by 1
+fn nonzero(seed: Byte) -> Byte {
+    mux(seed, Byte::from(1), is_zero(seed))
+}
+
+impl Device for RngDevice {
+    fn read(&mut self, _: Byte) -> Option<Byte> {
+        Some(self.next_byte().into())
+    }
+
+    fn write(&mut self, _: Byte, value: Byte) -> bool {
+        self.state = nonzero(value);
+        true
+    }
+}
+
+/// A handle to a device shared by several buses, every bus is given its own handle.
+///
+/// The computers take turns, so only one of them accesses the device at a time.
+pub struct SharedDevice<'a, D> {
+    device: &'a RefCell<D>,
+}
+
+impl<'a, D: Device> SharedDevice<'a, D> {
+    /// Creates a handle to the device
+    pub const fn new(device: &'a RefCell<D>) -> Self {
+        Self { device }
+    }
+}
+
+impl<D: Device> Device for SharedDevice<'_, D> {
+    fn read(&mut self, address: Byte) -> Option<Byte> {
+        self.device.borrow_mut().read(address)
+    }
+
+    fn write(&mut self, address: Byte, value: Byte) -> bool {
+        self.device.borrow_mut().write(address, value)
+    }
+}
+
+/// A write seen by a [`SequencedSink`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequencedWrite {
+    /// The position of the write among the writes of every sink sharing the counter
+    pub sequence: usize,
+
+    /// The address that was written
+    pub address: u8,
+
+    /// The byte that was written
+    pub value: u8,
+}
+
+/// Records up to `N` writes, numbered by a counter shared with other sinks.
+///
+/// Sinks on several devices sharing a counter show the global order of the writes, to test that
+/// the writes to different devices arrive in program order. Reads go to the memory cell, a write
+/// fails once the sink is full.
+pub struct SequencedSink<'a, const N: usize> {
+    counter: &'a Cell<usize>,
+    writes: [SequencedWrite; N],
+    length: usize,
+}
+
+impl<'a, const N: usize> SequencedSink<'a, N> {
+    /// Creates a sink numbering its writes with the counter
+    pub const fn new(counter: &'a Cell<usize>) -> Self {
+        Self {
+            counter,
+            writes: [SequencedWrite {
+                sequence: 0,
+                address: 0,
+                value: 0,
+            }; N],
+            length: 0,
+        }
+    }
+
+    /// Returns the recorded writes in the order they arrived
+    pub fn writes(&self) -> &[SequencedWrite] {
+        &self.writes[..self.length]
+    }
+}
+
+impl<const N: usize> Device for SequencedSink<'_, N> {
+    fn read(&mut self, _: Byte) -> Option<Byte> {
+        None
+    }
+
+    fn write(&mut self, address: Byte, value: Byte) -> bool {
+        let Some(slot) = self.writes.get_mut(self.length) else {
+            return false;
+        };
+        *slot = SequencedWrite {
+            sequence: self.counter.get(),
+            address: address.into(),
+            value: value.into(),
+        };
+        self.counter.set(slot.sequence + 1);
+        self.length += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        array,
+        cell::{Cell, RefCell},
+    };
+
+    use heapless::String;
+
+    use super::{
+        Bus, ConsoleDevice, Device, InputDevice, InputStream, OutputDecoder, RngDevice,
+        SequencedSink, SequencedWrite, SharedDevice, INPUT_BUFFER,
+    };
+    use crate::{byte::Byte, state::InitialState, Computer, StopReason};
+
+    /// Counts the reads at its address, writes set the count
+    struct Counter {
+        count: u8,
+    }
+
+    impl Device for Counter {
+        fn read(&mut self, _: Byte) -> Option<Byte> {
+            self.count = self.count.wrapping_add(1);
+            Some(self.count.into())
+        }
+
+        fn write(&mut self, _: Byte, value: Byte) -> bool {
+            self.count = value.into();
+            true
+        }
+    }
+
+    #[test]
+    fn second_device() {
+        let mut console = ConsoleDevice::new(String::<4>::new());
+        let mut counter = Counter { count: 0 };
+        let bus = Bus::new()
+            .with_device(255..=255, &mut console)
+            .with_device(250..=250, &mut counter);
+        let code = [
+            0, b'0', 8, 250, // counter = '0'
+            4, 250, 8, 255, // print the next count
+            4, 250, 4, 250, 8, 255, // skip a count, print the next one
+        ];
+        let mut computer = Computer::new(code, bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        // The memory cell keeps the stored value
+        assert_eq!(computer.memory(250), b'0');
+        assert_eq!(counter.count, b'3');
+        assert_eq!(console.output(), "13");
+    }
+
+    #[test]
+    fn first_device_wins() {
+        let mut first = Counter { count: 10 };
+        let mut second = Counter { count: 20 };
+        let mut bus = Bus::new()
+            .with_device(0x10..=0x1F, &mut first)
+            .with_device(0x18..=0x18, &mut second);
+        assert_eq!(u8::from(bus.load(0x18.into())), 11);
+        assert_eq!(u8::from(bus.load(0x20.into())), 0);
+        assert!(bus.store(0x20.into(), 5.into()));
+        assert_eq!(u8::from(bus.load(0x20.into())), 5);
+    }
+
+    #[test]
+    fn interleaved_write_order() {
+        let counter = Cell::new(0);
+        let mut console = SequencedSink::<4>::new(&counter);
+        let mut port = SequencedSink::<4>::new(&counter);
+        let mut serial = SequencedSink::<4>::new(&counter);
+        let bus = Bus::new()
+            .with_device(255..=255, &mut console)
+            .with_device(0xF8..=0xF8, &mut port)
+            .with_device(0xF9..=0xF9, &mut serial);
+        // Writes r0 to the console, r1 to the port and r2 to the serial device in a mixed order
+        let code = [
+            0x08, 0xFF, 0x09, 0xF8, 0x0A, 0xF9, 0x0A, 0xF9, // console, port, serial, serial
+            0x08, 0xFF, 0x09, 0xF8, 0x09, 0xF8, 0x08, 0xFF, // console, port, port, console
+        ];
+        let initial = InitialState::new().with_registers([b'c', b'p', b's', 0]);
+        let mut computer = Computer::with_initial_state(code, &initial, bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(counter.get(), 8);
+
+        // Every write gets the position of its store in the program
+        let mut order = [0; 8];
+        for write in [console.writes(), port.writes(), serial.writes()]
+            .into_iter()
+            .flatten()
+        {
+            order[write.sequence] = write.value;
+        }
+        assert_eq!(order, *b"cpsscppc");
+        assert_eq!(
+            serial.writes(),
+            [2, 3].map(|sequence| SequencedWrite {
+                sequence,
+                address: 0xF9,
+                value: b's'
+            })
+        );
+    }
+
+    #[test]
+    fn sequenced_sink_full() {
+        let counter = Cell::new(5);
+        let mut sink = SequencedSink::<1>::new(&counter);
+        assert!(sink.write(1.into(), 2.into()));
+        assert!(!sink.write(1.into(), 3.into()));
+        assert_eq!(
+            sink.writes(),
+            [SequencedWrite {
+                sequence: 5,
+                address: 1,
+                value: 2
+            }]
+        );
+        assert_eq!(counter.get(), 6);
+    }
+
+    #[test]
+    fn input_sentinel() {
+        let mut input = InputStream::new(*b"A").with_sentinel(b'$');
+        let mut bus = Bus::new().with_device(254..=254, &mut input);
+        assert_eq!(u8::from(bus.load(254.into())), b'A');
+        assert_eq!(u8::from(bus.load(254.into())), b'$');
+        assert!(bus.store(254.into(), 1.into()));
+        assert_eq!(u8::from(bus.load(254.into())), b'$');
+        assert_eq!(u8::from(bus.memory().load(254.into())), 1);
+    }
+
+    #[test]
+    fn output_decoders() {
+        let bytes = *b"a\\b\n\x07\xE9";
+        let mut raw = String::<16>::new();
+        OutputDecoder::Raw.write_all(bytes, &mut raw).unwrap();
+        assert_eq!(raw, "a\\b\n\u{7}\u{e9}");
+        // The raw text holds the bytes unmodified
+        assert!(raw.chars().map(u32::from).eq(bytes.map(u32::from)));
+
+        let mut ascii = String::<32>::new();
+        OutputDecoder::Ascii.write_all(bytes, &mut ascii).unwrap();
+        assert_eq!(ascii, "a\\\\b\n\\x07\\xe9");
+
+        let mut table = ['?'; 64];
+        table[..26].copy_from_slice(&core::array::from_fn::<_, 26, _>(|i| {
+            char::from(b'A' + u8::try_from(i).unwrap())
+        }));
+        table[63] = ' ';
+        let mut text = String::<16>::new();
+        OutputDecoder::Table(&table)
+            .write_all([7, 4, 11, 11, 14, 63, 30, 64], &mut text)
+            .unwrap();
+        assert_eq!(text, "HELLO ?\\x40");
+    }
+
+    #[test]
+    fn console_decoder() {
+        let mut console =
+            ConsoleDevice::new(String::<16>::new()).with_decoder(OutputDecoder::Ascii);
+        let code = [0, b'A', 8, 255, 0, 0x1B, 8, 255];
+        let mut computer =
+            Computer::new(code, Bus::new().with_device(255..=255, &mut console)).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        // The memory cell gets the raw byte
+        assert_eq!(computer.memory(255), 0x1B);
+        assert_eq!(console.output(), "A\\x1b");
+    }
+
+    #[test]
+    fn rng_period() {
+        let mut rng = RngDevice::new(1);
+        let mut seen = [false; 256];
+        for _ in 0..255 {
+            let byte = rng.next_byte();
+            assert!(!seen[usize::from(byte)], "{byte} repeated early");
+            seen[usize::from(byte)] = true;
+        }
+        // Every nonzero state was visited once, the next step starts over
+        assert!(!seen[0]);
+        assert_eq!(rng.next_byte(), RngDevice::new(1).next_byte());
+        // The seed 0 is the same as 1
+        assert_eq!(RngDevice::new(0).next_byte(), RngDevice::new(1).next_byte());
+    }
+
+    #[test]
+    fn rng_program() {
+        // Seed the generator at 0xF8 with 0x5A, then store 16 bytes from it at 0x80
+        let code: [u8; 69] = array::from_fn(|i| match i {
+            0 => 0x00,
+            1 => 0x5A,
+            2 => 0x08,
+            3 => 0xF8,
+            68 => 0xFF,
+            _ => match (i - 4) % 4 {
+                0 => 0x04,
+                1 => 0xF8,
+                2 => 0x08,
+                _ => 0x80 + u8::try_from((i - 4) / 4).unwrap(),
+            },
+        });
+        let run = || {
+            let mut rng = RngDevice::new(1);
+            let bus = Bus::new().with_device(0xF8..=0xF8, &mut rng);
+            let mut computer = Computer::new(code, bus).unwrap();
+            assert_eq!(computer.run(), Ok(StopReason::Halted));
+            let memory = computer.state().memory;
+            let mut bytes = [0; 16];
+            bytes.copy_from_slice(&memory[0x80..0x90]);
+            bytes
+        };
+        let bytes = run();
+        assert_eq!(run(), bytes);
+        let mut rng = RngDevice::new(0x5A);
+        assert_eq!(bytes, array::from_fn(|_| rng.next_byte()));
+        assert_ne!(bytes[0], bytes[1]);
+    }
+
+    /// Polls the input at 0xF0 and echoes every byte to the console until a `.`
+    const ECHO: [u8; 16] = [
+        0x04, 0xF0, 0xC4, 0, // wait for a byte
+        0x04, 0xF1, 0x08, 0xFF, // echo it
+        0x01, b'.', 0xA1, 0xC4, 15, 0xC0, 0, // stop after a .
+        0xFF,
+    ];
+
+    #[test]
+    fn input_device_polling() {
+        let input = RefCell::new(InputDevice::new(0xF0));
+        let mut device = SharedDevice::new(&input);
+        let mut console = ConsoleDevice::new(String::<8>::new());
+        let bus = Bus::new()
+            .with_device(0xF0..=0xF1, &mut device)
+            .with_device(255..=255, &mut console);
+        let mut computer = Computer::new(ECHO, bus).unwrap();
+        assert!(input.borrow_mut().push(b'h'));
+        assert!(input.borrow_mut().push(b'i'));
+        // Without input the program keeps polling the status
+        for _ in 0..40 {
+            computer.step().unwrap();
+        }
+        assert_eq!(input.borrow().buffered(), 0);
+        assert!(computer.program_counter() < 4);
+        for byte in *b"!.x" {
+            assert!(input.borrow_mut().push(byte));
+        }
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(input.borrow().buffered(), 1);
+        assert_eq!(console.output(), "hi!.");
+    }
+
+    #[test]
+    fn input_device_iterator() {
+        let mut input = InputDevice::with_input(0xF0, *b"ok.more");
+        let mut console = ConsoleDevice::new(String::<8>::new());
+        let bus = Bus::new()
+            .with_device(0xF0..=0xF1, &mut input)
+            .with_device(255..=255, &mut console);
+        let mut computer = Computer::new(ECHO, bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(console.output(), "ok.");
+
+        // Reading without a byte returns 0, a full buffer drops the byte
+        let mut input = InputDevice::new(0x10);
+        assert_eq!(input.read(0x11.into()).map(u8::from), Some(0));
+        assert_eq!(input.read(0x12.into()).map(u8::from), None);
+        for byte in 0..16 {
+            assert!(input.push(byte));
+        }
+        assert!(!input.push(16));
+        assert_eq!(input.buffered(), INPUT_BUFFER);
+        assert_eq!(input.read(0x10.into()).map(u8::from), Some(1));
+        assert_eq!(input.read(0x11.into()).map(u8::from), Some(0));
+        assert!(input.push(16));
+    }
+}
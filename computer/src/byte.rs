@@ -2,72 +2,149 @@
 
 use core::{
     array,
-    ops::{Add, BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Sub},
+    fmt::{self, Debug, Formatter},
+    ops::{
+        Add, BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Neg, Not, Shl,
+        ShlAssign, Shr, ShrAssign, Sub,
+    },
 };
 
-use crate::bit::Bit;
+use crate::{
+    bit::{gate, Bit, Gates},
+    fast,
+    mux::{
+        self,
+        byte::{mux, shift_left, shift_right_logical},
+    },
+};
+
+/// Applies the gate on every pair of bits of both bytes, with a loop a `const fn` can run
+macro_rules! bitwise {
+    ($left:expr, $right:expr, $gate:ident) => {{
+        let mut bits = [Bit::Low; 8];
+        let mut index = 0;
+        while index < 8 {
+            bits[index] = $left.bits[index].$gate($right.bits[index]);
+            index += 1;
+        }
+        Byte { bits }
+    }};
+}
 
-/// The byte datatype is the smallest datatype a pointer can point to
-#[derive(Debug, Clone, Copy)]
+/// The byte datatype is the smallest datatype a pointer can point to.
+/// Bytes are equal when their values are, and are debug formatted as their value in hexadecimal.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "u8", into = "u8")
+)]
 pub struct Byte {
     bits: [Bit; 8],
 }
 
 impl Byte {
-    /// Applies the `Bit::nand` function on the bits of both bytes
-    pub fn nand(&self, other: &Self) -> Self {
-        Self {
-            bits: array::from_fn(|index| self.bits[index].nand(other.bits[index])),
+    /// The byte with every bit low
+    pub const ZERO: Self = Self::new(0);
+
+    /// The byte with only bit 0 high
+    pub const ONE: Self = Self::new(1);
+
+    /// The byte with every bit high
+    pub const MAX: Self = Self::new(u8::MAX);
+
+    /// Creates the byte with the value, like `Byte::from` but usable in constants
+    pub const fn new(value: u8) -> Self {
+        let mut bits = [Bit::Low; 8];
+        let mut index = 0;
+        while index < 8 {
+            if (value >> index) & 1 == 1 {
+                bits[index] = Bit::High;
+            }
+            index += 1;
         }
+        Self { bits }
     }
 
-    /// Applies the `Bit::and` function on the bits of both bytes
-    pub fn and(&self, other: &Self) -> Self {
-        Self {
-            bits: array::from_fn(|index| self.bits[index].and(other.bits[index])),
+    /// Returns the value of the byte, like `u8::from` but usable in constants
+    pub const fn to_u8(self) -> u8 {
+        let mut value = 0;
+        let mut index = 0;
+        while index < 8 {
+            if matches!(self.bits[index], Bit::High) {
+                value |= 1 << index;
+            }
+            index += 1;
         }
+        value
     }
 
-    /// Applies the `Bit::or` function on the bits of both bytes
-    pub fn or(&self, other: &Self) -> Self {
-        Self {
-            bits: array::from_fn(|index| self.bits[index].or(other.bits[index])),
+    /// Creates the byte with the bits of the two's complement number. This is no `From<i8>` impl,
+    /// as that would make the integer literal in `Byte::from(0)` ambiguous.
+    pub const fn from_i8(value: i8) -> Self {
+        Self::new(value.cast_unsigned())
+    }
+
+    /// Returns the value of the byte as a two's complement number
+    pub const fn to_i8(self) -> i8 {
+        self.to_u8().cast_signed()
+    }
+
+    gate! {
+        /// Applies the `Bit::nand` function on the bits of both bytes
+        pub fn nand(&self, other: &Self) -> Self {
+            bitwise!(self, other, nand)
         }
     }
 
-    /// Applies the `Bit::nor` function on the bits of both bytes
-    pub fn nor(&self, other: &Self) -> Self {
-        Self {
-            bits: array::from_fn(|index| self.bits[index].nor(other.bits[index])),
+    gate! {
+        /// Applies the `Bit::and` function on the bits of both bytes
+        pub fn and(&self, other: &Self) -> Self {
+            bitwise!(self, other, and)
         }
     }
 
-    /// Applies the `Bit::xnor` function on the bits of both bytes
-    pub fn xnor(&self, other: &Self) -> Self {
-        Self {
-            bits: array::from_fn(|index| self.bits[index].xnor(other.bits[index])),
+    gate! {
+        /// Applies the `Bit::or` function on the bits of both bytes
+        pub fn or(&self, other: &Self) -> Self {
+            bitwise!(self, other, or)
         }
     }
 
-    /// Applies the `Bit::xor` function on the bits of both bytes
-    pub fn xor(&self, other: &Self) -> Self {
-        Self {
-            bits: array::from_fn(|index| self.bits[index].xor(other.bits[index])),
+    gate! {
+        /// Applies the `Bit::nor` function on the bits of both bytes
+        pub fn nor(&self, other: &Self) -> Self {
+            bitwise!(self, other, nor)
+        }
+    }
+
+    gate! {
+        /// Applies the `Bit::xnor` function on the bits of both bytes
+        pub fn xnor(&self, other: &Self) -> Self {
+            bitwise!(self, other, xnor)
+        }
+    }
+
+    gate! {
+        /// Applies the `Bit::xor` function on the bits of both bytes
+        pub fn xor(&self, other: &Self) -> Self {
+            bitwise!(self, other, xor)
         }
     }
 
     /// Adds 2 bytes and the carry bit
-    pub fn add_with_carry(self, right: Self, mut carry: Bit) -> (Self, Bit) {
-        let left: [Bit; 8] = self.into();
-        let right: [Bit; 8] = right.into();
-        (
-            Self::from(array::from_fn(|i| {
-                let result;
-                (result, carry) = left[i].full_adder(right[i], carry);
-                result
-            })),
-            carry,
-        )
+    pub fn add_with_carry(self, right: Self, carry: Bit) -> (Self, Bit) {
+        if fast::enabled() {
+            fast::add_with_carry(self, right, carry)
+        } else {
+            self.add_with_carry_circuit(right, carry)
+        }
+    }
+
+    /// The circuit of [`Self::add_with_carry`], the chain of full adders of [`ripple_add`]
+    pub(crate) fn add_with_carry_circuit(self, right: Self, carry: Bit) -> (Self, Bit) {
+        let (result, carry) = ripple_add(self.into(), right.into(), carry);
+        (Self::from(result), carry)
     }
 
     /// Subtracts one byte from an other and subtracts the carry
@@ -78,25 +155,196 @@ impl Byte {
         let (result, carry2) = self - right;
         (result, carry.or(carry2))
     }
+
+    /// Adds one with a chain of half adders, returns the result and whether it wrapped from 255 to 0
+    pub fn increment(self) -> (Self, Bit) {
+        if fast::enabled() {
+            fast::increment(self)
+        } else {
+            self.increment_circuit()
+        }
+    }
+
+    /// The circuit of [`Self::increment`], which the `fast` feature skips
+    pub(crate) fn increment_circuit(self) -> (Self, Bit) {
+        let mut carry = Bit::High;
+        (
+            Self::from(array::from_fn(|i| {
+                let result;
+                (result, carry) = self.bits[i].half_adder(carry);
+                result
+            })),
+            carry,
+        )
+    }
+
+    /// Subtracts one with a chain of half subtractors, returns the result and whether it wrapped
+    /// from 0 to 255
+    pub fn decrement(self) -> (Self, Bit) {
+        if fast::enabled() {
+            fast::decrement(self)
+        } else {
+            self.decrement_circuit()
+        }
+    }
+
+    /// The circuit of [`Self::decrement`], which the `fast` feature skips
+    pub(crate) fn decrement_circuit(self) -> (Self, Bit) {
+        let mut borrow = Bit::High;
+        (
+            Self::from(array::from_fn(|i| {
+                let result = self.bits[i].xor(borrow);
+                borrow = self.bits[i].not().and(borrow);
+                result
+            })),
+            borrow,
+        )
+    }
+
+    /// Negates the two's complement number by subtracting it from 0. Returns the result and
+    /// `Bit::High` if the negation overflowed, which only -128 does as it negates to itself.
+    pub fn negate(self) -> (Self, Bit) {
+        let (result, _) = Self::ZERO - self;
+        (result, self.is_negative().and(result.is_negative()))
+    }
+
+    /// Returns the absolute value of the two's complement number, selecting the negated byte for
+    /// negative numbers. -128 wraps around to itself.
+    pub fn abs(self) -> Self {
+        mux(self, self.negate().0, self.is_negative())
+    }
+
+    /// Returns the sign bit, `Bit::High` if the byte is negative as a two's complement number
+    pub const fn is_negative(self) -> Bit {
+        self.bits[7]
+    }
+
+    /// Multiplies 2 bytes by shifting and adding, returns the low and the high byte of the product.
+    ///
+    /// Every bit of the right byte selects whether the left byte, shifted to the position of the
+    /// bit, is added to the product.
+    pub fn multiply(self, right: Self) -> (Self, Self) {
+        if fast::enabled() {
+            fast::multiply(self, right)
+        } else {
+            self.multiply_circuit(right)
+        }
+    }
+
+    /// The circuit of [`Self::multiply`], which the `fast` feature skips
+    pub(crate) fn multiply_circuit(self, right: Self) -> (Self, Self) {
+        let zero = Self::from(0);
+        let (mut low, mut high) = (zero, zero);
+        let (mut shifted_low, mut shifted_high) = (self, zero);
+        for select in right.bits {
+            let (carry, out);
+            (low, carry) = low.add_with_carry_circuit(mux(zero, shifted_low, select), Bit::Low);
+            (high, _) = high.add_with_carry_circuit(mux(zero, shifted_high, select), carry);
+            (shifted_low, out) = shifted_low.rotate_left_through_carry(Bit::Low);
+            (shifted_high, _) = shifted_high.rotate_left_through_carry(out);
+        }
+        (low, high)
+    }
+
+    /// Divides by the divisor with restoring division, returns the quotient and the remainder.
+    ///
+    /// The bits of the byte are shifted into the remainder from the top, every time the remainder
+    /// reaches the divisor it is subtracted and the quotient bit is set. Dividing by zero sets
+    /// every quotient bit and leaves the byte as the remainder.
+    pub fn divide(self, divisor: Self) -> (Self, Self) {
+        if fast::enabled() {
+            fast::divide(self, divisor)
+        } else {
+            self.divide_circuit(divisor)
+        }
+    }
+
+    /// The circuit of [`Self::divide`], which the `fast` feature skips
+    pub(crate) fn divide_circuit(self, divisor: Self) -> (Self, Self) {
+        let mut remainder = Self::from(0);
+        let mut quotient = [Bit::Low; 8];
+        for i in (0..8).rev() {
+            // The bit shifted out is the ninth bit of the remainder
+            let out;
+            (remainder, out) = remainder.rotate_left_through_carry(self.bits[i]);
+            let (difference, no_borrow) =
+                remainder.add_with_carry_circuit(divisor.not(), Bit::High);
+            quotient[i] = out.or(no_borrow);
+            remainder = mux(remainder, difference, quotient[i]);
+        }
+        (Self::from(quotient), remainder)
+    }
+
+    /// Moves every bit one position up, `carry` becomes the lowest bit.
+    /// Returns the new byte and the bit shifted out at the top.
+    pub fn rotate_left_through_carry(self, carry: Bit) -> (Self, Bit) {
+        let mut bits = [carry; 8];
+        bits[1..].copy_from_slice(&self.bits[..7]);
+        (Self { bits }, self.bits[7])
+    }
+
+    /// Moves every bit one position down, `carry` becomes the highest bit.
+    /// Returns the new byte and the bit shifted out at the bottom.
+    pub fn rotate_right_through_carry(self, carry: Bit) -> (Self, Bit) {
+        let mut bits = [carry; 8];
+        bits[..7].copy_from_slice(&self.bits[1..]);
+        (Self { bits }, self.bits[0])
+    }
+
+    /// Rotates the bits up by `n` places with the barrel shifter, `n` wraps around at 8
+    pub fn rotate_left(self, n: u8) -> Self {
+        mux::byte::rotate_left(self, Self::from(n).amount())
+    }
+
+    /// Rotates the bits down by `n` places with the barrel shifter, `n` wraps around at 8
+    pub fn rotate_right(self, n: u8) -> Self {
+        mux::byte::rotate_right(self, Self::from(n).amount())
+    }
+
+    /// Returns the low 3 bits, the amount the barrel shifter moves by
+    const fn amount(self) -> [Bit; 3] {
+        [self.bits[0], self.bits[1], self.bits[2]]
+    }
+
+    gate! {
+        /// Returns `Bit::High` if a shift by the byte moves every bit out, the or of bits 3 to 7
+        fn shifts_out(self) -> Bit {
+            let [.., b3, b4, b5, b6, b7] = self.bits;
+            b3.or(b4).or(b5.or(b6)).or(b7)
+        }
+    }
+}
+
+/// Adds 2 bytes of signals, lowest bit first, returns the sum and the carry out
+///
+/// This chain of full adders is the circuit of [`Byte::add_with_carry`], on
+/// [`Wire`](crate::depth::Wire)s it measures the depth of it.
+pub fn ripple_add<G: Gates>(left: [G; 8], right: [G; 8], mut carry: G) -> ([G; 8], G) {
+    (
+        array::from_fn(|i| {
+            let result;
+            (result, carry) = left[i].full_adder(right[i], carry);
+            result
+        }),
+        carry,
+    )
+}
+
+impl Debug for Byte {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#04X}", u8::from(*self))
+    }
 }
 
 impl From<u8> for Byte {
     fn from(value: u8) -> Self {
-        Self {
-            bits: array::from_fn(|i| Bit::from((value >> i) & 1 == 1)),
-        }
+        Self::new(value)
     }
 }
 
 impl From<Byte> for u8 {
     fn from(value: Byte) -> Self {
-        value
-            .bits
-            .into_iter()
-            .enumerate()
-            .fold(0, |result, (i, bit)| {
-                result | (Self::from(bool::from(bit)) << i)
-            })
+        value.to_u8()
     }
 }
 
@@ -130,6 +378,57 @@ impl Sub for Byte {
     }
 }
 
+/// Negates the two's complement number, wrapping -128 around to itself
+impl Neg for Byte {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        self.negate().0
+    }
+}
+
+/// Shifts the bits up with the barrel shifter, filling in `Bit::Low`. Shifting by 8 or more moves
+/// every bit out and returns 0, instead of panicking like the shift of a `u8`.
+impl Shl<u8> for Byte {
+    type Output = Self;
+
+    fn shl(self, rhs: u8) -> Self::Output {
+        let amount = Self::from(rhs);
+        mux(
+            shift_left(self, amount.amount()),
+            Self::ZERO,
+            amount.shifts_out(),
+        )
+    }
+}
+
+impl ShlAssign<u8> for Byte {
+    fn shl_assign(&mut self, rhs: u8) {
+        *self = *self << rhs;
+    }
+}
+
+/// Shifts the bits down with the barrel shifter, filling in `Bit::Low`. Shifting by 8 or more
+/// moves every bit out and returns 0, instead of panicking like the shift of a `u8`.
+impl Shr<u8> for Byte {
+    type Output = Self;
+
+    fn shr(self, rhs: u8) -> Self::Output {
+        let amount = Self::from(rhs);
+        mux(
+            shift_right_logical(self, amount.amount()),
+            Self::ZERO,
+            amount.shifts_out(),
+        )
+    }
+}
+
+impl ShrAssign<u8> for Byte {
+    fn shr_assign(&mut self, rhs: u8) {
+        *self = *self >> rhs;
+    }
+}
+
 impl BitAnd for Byte {
     type Output = Self;
 
@@ -184,6 +483,10 @@ impl Not for Byte {
 
 #[cfg(test)]
 mod tests {
+    use core::fmt::Write;
+
+    use heapless::String;
+
     use crate::{bit::Bit, byte::Byte};
 
     #[test]
@@ -193,6 +496,47 @@ mod tests {
         }
     }
 
+    #[test]
+    fn constants() {
+        const BYTE: Byte = Byte::new(0x48);
+        const TABLE: [Byte; 3] = [Byte::ZERO, Byte::ONE, Byte::MAX];
+        const VALUE: u8 = BYTE.to_u8();
+        assert_eq!(BYTE, Byte::from(0x48));
+        assert_eq!(VALUE, 0x48);
+        assert_eq!(TABLE.map(u8::from), [0, 1, 0xFF]);
+        for value in 0..=u8::MAX {
+            assert_eq!(Byte::new(value).to_u8(), value);
+            assert_eq!(Byte::new(value), Byte::from(value));
+        }
+    }
+
+    /// The gates are only `const fn` without the counter of the `gate-count` feature
+    #[cfg(not(feature = "gate-count"))]
+    #[test]
+    fn const_gates() {
+        const LEFT: Byte = Byte::new(0xF0);
+        const RIGHT: Byte = Byte::new(0x3C);
+        const RESULTS: [Byte; 6] = [
+            LEFT.nand(&RIGHT),
+            LEFT.and(&RIGHT),
+            LEFT.or(&RIGHT),
+            LEFT.nor(&RIGHT),
+            LEFT.xnor(&RIGHT),
+            LEFT.xor(&RIGHT),
+        ];
+        assert_eq!(RESULTS.map(u8::from), [0xCF, 0x30, 0xFC, 0x03, 0x33, 0xCC]);
+    }
+
+    #[test]
+    fn debug_and_equality() {
+        let mut text = String::<16>::new();
+        write!(text, "{:?} {:?}", Byte::from(0x48), Byte::from(0)).unwrap();
+        assert_eq!(text, "0x48 0x00");
+
+        assert_eq!(Byte::from(0x48), Byte::from(0x48));
+        assert_ne!(Byte::from(0x48), Byte::from(0x49));
+    }
+
     #[test]
     fn add_without_carry() {
         for left in 0..=u8::MAX {
@@ -248,4 +592,89 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn increment_decrement() {
+        for value in 0..=u8::MAX {
+            let (result, carry) = Byte::from(value).increment();
+            assert_eq!(u8::from(result), value.wrapping_add(1));
+            assert_eq!(carry, Bit::from(value == u8::MAX));
+            let (result, borrow) = Byte::from(value).decrement();
+            assert_eq!(u8::from(result), value.wrapping_sub(1));
+            assert_eq!(borrow, Bit::from(value == 0));
+        }
+    }
+
+    #[test]
+    fn multiply() {
+        for left in 0..=u8::MAX {
+            for right in 0..=u8::MAX {
+                let (low, high) = Byte::from(left).multiply(Byte::from(right));
+                let product = u16::from(left) * u16::from(right);
+                assert_eq!(u16::from_le_bytes([low.into(), high.into()]), product);
+            }
+        }
+    }
+
+    #[test]
+    fn divide() {
+        for left in 0..=u8::MAX {
+            for right in 1..=u8::MAX {
+                let (quotient, remainder) = Byte::from(left).divide(Byte::from(right));
+                assert_eq!(u8::from(quotient), left / right);
+                assert_eq!(u8::from(remainder), left % right);
+            }
+            let (quotient, remainder) = Byte::from(left).divide(Byte::from(0));
+            assert_eq!((u8::from(quotient), u8::from(remainder)), (0xFF, left));
+        }
+    }
+
+    #[test]
+    fn shifts() {
+        for value in 0..=u8::MAX {
+            let byte = Byte::from(value);
+            for n in (0..=8).chain([9, 15, 16, 200, u8::MAX]) {
+                let expected = |shifted: Option<u8>| shifted.unwrap_or(0);
+                assert_eq!(u8::from(byte << n), expected(value.checked_shl(n.into())));
+                assert_eq!(u8::from(byte >> n), expected(value.checked_shr(n.into())));
+                let (mut left, mut right) = (byte, byte);
+                left <<= n;
+                right >>= n;
+                assert_eq!((left, right), (byte << n, byte >> n));
+                assert_eq!(u8::from(byte.rotate_left(n)), value.rotate_left(n.into()));
+                assert_eq!(u8::from(byte.rotate_right(n)), value.rotate_right(n.into()));
+            }
+        }
+    }
+
+    #[test]
+    fn signed() {
+        for value in i8::MIN..=i8::MAX {
+            let byte = Byte::from_i8(value);
+            assert_eq!(byte.to_i8(), value);
+            assert_eq!(u8::from(byte), value.cast_unsigned());
+            assert_eq!(byte.is_negative(), Bit::from(value.is_negative()));
+
+            let (negated, overflow) = byte.negate();
+            assert_eq!(negated.to_i8(), value.wrapping_neg());
+            assert_eq!(overflow, Bit::from(value == i8::MIN));
+            assert_eq!(-byte, negated);
+            assert_eq!(byte.abs().to_i8(), value.wrapping_abs());
+        }
+    }
+
+    #[test]
+    fn rotate_through_carry() {
+        for byte in 0..=u8::MAX {
+            for carry in [Bit::Low, Bit::High] {
+                let carry_value = u8::from(bool::from(carry));
+                let (left, left_carry) = Byte::from(byte).rotate_left_through_carry(carry);
+                assert_eq!(u8::from(left), (byte << 1) | carry_value);
+                assert_eq!(left_carry, Bit::from(byte & 0x80 != 0));
+                let (right, right_carry) = Byte::from(byte).rotate_right_through_carry(carry);
+                assert_eq!(u8::from(right), (byte >> 1) | (carry_value << 7));
+                assert_eq!(right_carry, Bit::from(byte & 1 != 0));
+            }
+        }
+    }
 }
@@ -0,0 +1,420 @@
+//! A small combinational netlist runner for using the circuits without the CPU.
+//!
+//! Inputs and nodes are registered by name. A node is a closure reading other inputs and nodes by
+//! name through [`Signals`], so circuits can be composed from the gates in this crate:
+//!
+//! ```
+//! use michael_computer::{
+//!     bit::Bit,
+//!     circuit::{Signals, Simulator},
+//! };
+//!
+//! let nand = |signals: &Signals| Ok(signals.bit("a")?.nand(signals.bit("b")?).into());
+//! let mut simulator = Simulator::<3>::new();
+//! simulator.input("a", Bit::High).unwrap();
+//! simulator.input("b", Bit::High).unwrap();
+//! simulator.node("out", &nand).unwrap();
+//! assert_eq!(simulator.eval_bit("out"), Ok(Bit::Low));
+//! simulator.set("b", Bit::Low).unwrap();
+//! assert_eq!(simulator.eval_bit("out"), Ok(Bit::High));
+//! ```
+
+use core::{
+    cell::Cell,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    bit::Bit,
+    byte::Byte,
+    error::{ErrorKind, McError},
+};
+
+/// The value of an input or node
+#[derive(Debug, Clone, Copy)]
+pub enum Value {
+    /// A single bit
+    Bit(Bit),
+
+    /// A byte
+    Byte(Byte),
+}
+
+impl From<Bit> for Value {
+    fn from(value: Bit) -> Self {
+        Self::Bit(value)
+    }
+}
+
+impl From<Byte> for Value {
+    fn from(value: Byte) -> Self {
+        Self::Byte(value)
+    }
+}
+
+/// An error while building or evaluating a circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitError {
+    /// No input or node has this name
+    UnknownName(&'static str),
+
+    /// An input or node with this name already exists
+    DuplicateName(&'static str),
+
+    /// The name belongs to a node, only inputs can be set
+    NotAnInput(&'static str),
+
+    /// The value of the input or node isn't a bit or byte as requested
+    TypeMismatch(&'static str),
+
+    /// The node depends on its own value
+    Cycle(&'static str),
+
+    /// The simulator has no room for another input or node
+    Full,
+
+    /// A truth table can't have this number of inputs
+    InputCount(u8),
+}
+
+impl Display for CircuitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownName(name) => write!(f, "no input or node named `{name}`"),
+            Self::DuplicateName(name) => write!(f, "`{name}` is already defined"),
+            Self::NotAnInput(name) => write!(f, "`{name}` is a node, not an input"),
+            Self::TypeMismatch(name) => write!(f, "`{name}` has a different type"),
+            Self::Cycle(name) => write!(f, "`{name}` depends on its own value"),
+            Self::Full => write!(f, "no room for another input or node"),
+            Self::InputCount(inputs) => write!(f, "a truth table can't have {inputs} inputs"),
+        }
+    }
+}
+
+impl core::error::Error for CircuitError {}
+
+impl McError for CircuitError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Circuit
+    }
+}
+
+/// The closure computing the value of a node
+pub type NodeFn<'a> = dyn Fn(&Signals<'_>) -> Result<Value, CircuitError> + 'a;
+
+/// How the value of an entry is determined
+#[derive(Clone, Copy)]
+enum Source<'a> {
+    Input(Value),
+    Node(&'a NodeFn<'a>),
+}
+
+/// The progress of evaluating an entry
+#[derive(Clone, Copy)]
+enum Memo {
+    Pending,
+    Evaluating,
+    Done(Value),
+}
+
+/// Evaluates entries by name, hides the size of the simulator from the nodes
+trait Evaluate {
+    fn evaluate(&self, name: &'static str) -> Result<Value, CircuitError>;
+}
+
+/// Gives the closure of a node access to the values of the other inputs and nodes
+pub struct Signals<'s> {
+    simulator: &'s dyn Evaluate,
+}
+
+impl Signals<'_> {
+    /// Evaluates the input or node
+    ///
+    /// # Errors
+    /// Returns an error if the name is unknown or evaluating it fails
+    pub fn value(&self, name: &'static str) -> Result<Value, CircuitError> {
+        self.simulator.evaluate(name)
+    }
+
+    /// Evaluates the input or node holding a bit
+    ///
+    /// # Errors
+    /// Returns an error if the name is unknown, evaluating it fails, or it holds a byte
+    pub fn bit(&self, name: &'static str) -> Result<Bit, CircuitError> {
+        match self.value(name)? {
+            Value::Bit(bit) => Ok(bit),
+            Value::Byte(_) => Err(CircuitError::TypeMismatch(name)),
+        }
+    }
+
+    /// Evaluates the input or node holding a byte
+    ///
+    /// # Errors
+    /// Returns an error if the name is unknown, evaluating it fails, or it holds a bit
+    pub fn byte(&self, name: &'static str) -> Result<Byte, CircuitError> {
+        match self.value(name)? {
+            Value::Byte(byte) => Ok(byte),
+            Value::Bit(_) => Err(CircuitError::TypeMismatch(name)),
+        }
+    }
+}
+
+/// Evaluates a circuit of up to `N` named inputs and nodes.
+///
+/// Names are string literals. Every node is evaluated at most once per evaluation, later reads of
+/// it use the memoized value.
+pub struct Simulator<'a, const N: usize> {
+    entries: [Option<(&'static str, Source<'a>)>; N],
+    memo: [Cell<Memo>; N],
+}
+
+impl<const N: usize> Default for Simulator<'_, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, const N: usize> Simulator<'a, N> {
+    /// Creates a simulator without inputs or nodes
+    pub const fn new() -> Self {
+        Self {
+            entries: [None; N],
+            memo: [const { Cell::new(Memo::Pending) }; N],
+        }
+    }
+
+    /// Returns the index of the entry with the name
+    fn find(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.is_some_and(|(entry, _)| entry == name))
+    }
+
+    /// Adds an entry in the first free slot
+    fn add(&mut self, name: &'static str, source: Source<'a>) -> Result<(), CircuitError> {
+        if self.find(name).is_some() {
+            return Err(CircuitError::DuplicateName(name));
+        }
+        let slot = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.is_none())
+            .ok_or(CircuitError::Full)?;
+        *slot = Some((name, source));
+        Ok(())
+    }
+
+    /// Registers an input with its initial value
+    ///
+    /// # Errors
+    /// Returns an error if the name is already used or the simulator is full
+    pub fn input(
+        &mut self,
+        name: &'static str,
+        value: impl Into<Value>,
+    ) -> Result<(), CircuitError> {
+        self.add(name, Source::Input(value.into()))
+    }
+
+    /// Registers a node computed by the closure
+    ///
+    /// # Errors
+    /// Returns an error if the name is already used or the simulator is full
+    pub fn node(&mut self, name: &'static str, node: &'a NodeFn<'a>) -> Result<(), CircuitError> {
+        self.add(name, Source::Node(node))
+    }
+
+    /// Changes the value of an input
+    ///
+    /// # Errors
+    /// Returns an error if the name is unknown or belongs to a node
+    pub fn set(&mut self, name: &'static str, value: impl Into<Value>) -> Result<(), CircuitError> {
+        let index = self.find(name).ok_or(CircuitError::UnknownName(name))?;
+        match &mut self.entries[index] {
+            Some((_, Source::Input(input))) => {
+                *input = value.into();
+                Ok(())
+            }
+            _ => Err(CircuitError::NotAnInput(name)),
+        }
+    }
+
+    /// Evaluates the input or node from the current inputs
+    ///
+    /// # Errors
+    /// Returns an error if a name is unknown, a node has the wrong type or depends on itself
+    pub fn eval(&self, name: &'static str) -> Result<Value, CircuitError> {
+        for memo in &self.memo {
+            memo.set(Memo::Pending);
+        }
+        self.evaluate(name)
+    }
+
+    /// Evaluates the input or node holding a bit
+    ///
+    /// # Errors
+    /// Returns an error like [`Simulator::eval`], or if the value is a byte
+    pub fn eval_bit(&self, name: &'static str) -> Result<Bit, CircuitError> {
+        match self.eval(name)? {
+            Value::Bit(bit) => Ok(bit),
+            Value::Byte(_) => Err(CircuitError::TypeMismatch(name)),
+        }
+    }
+
+    /// Evaluates the input or node holding a byte
+    ///
+    /// # Errors
+    /// Returns an error like [`Simulator::eval`], or if the value is a bit
+    pub fn eval_byte(&self, name: &'static str) -> Result<Byte, CircuitError> {
+        match self.eval(name)? {
+            Value::Byte(byte) => Ok(byte),
+            Value::Bit(_) => Err(CircuitError::TypeMismatch(name)),
+        }
+    }
+}
+
+impl<const N: usize> Evaluate for Simulator<'_, N> {
+    /// Evaluates the entry, reusing the values computed earlier in this evaluation
+    fn evaluate(&self, name: &'static str) -> Result<Value, CircuitError> {
+        let index = self.find(name).ok_or(CircuitError::UnknownName(name))?;
+        let Some((_, source)) = self.entries[index] else {
+            unreachable!("find only returns occupied entries");
+        };
+        match (source, self.memo[index].get()) {
+            (Source::Input(value), _) | (_, Memo::Done(value)) => Ok(value),
+            (_, Memo::Evaluating) => Err(CircuitError::Cycle(name)),
+            (Source::Node(node), Memo::Pending) => {
+                self.memo[index].set(Memo::Evaluating);
+                let value = node(&Signals { simulator: self });
+                // Leave the entry evaluating on errors, the evaluation is aborted anyway
+                let value = value?;
+                self.memo[index].set(Memo::Done(value));
+                Ok(value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::{CircuitError, Signals, Simulator};
+    use crate::{bit::Bit, byte::Byte};
+
+    #[test]
+    fn four_bit_adder() {
+        // Bit `i` of the adder, `output` 0 is the sum and 1 the carry out
+        let full_adder = |i: usize, output: usize| {
+            move |signals: &Signals| {
+                let carry = match i {
+                    0 => Bit::Low,
+                    _ => signals.bit(["c1", "c2", "c3"][i - 1])?,
+                };
+                let a: [Bit; 8] = signals.byte("a")?.into();
+                let b: [Bit; 8] = signals.byte("b")?.into();
+                let outputs: [Bit; 2] = a[i].full_adder(b[i], carry).into();
+                Ok(outputs[output].into())
+            }
+        };
+        let sums = [0, 1, 2, 3].map(|i| full_adder(i, 0));
+        let carries = [0, 1, 2, 3].map(|i| full_adder(i, 1));
+        let result = |signals: &Signals| {
+            let bits = [
+                signals.bit("s0")?,
+                signals.bit("s1")?,
+                signals.bit("s2")?,
+                signals.bit("s3")?,
+                signals.bit("c4")?,
+                Bit::Low,
+                Bit::Low,
+                Bit::Low,
+            ];
+            Ok(Byte::from(bits).into())
+        };
+
+        let mut simulator = Simulator::<16>::new();
+        simulator.input("a", Byte::from(0)).unwrap();
+        simulator.input("b", Byte::from(0)).unwrap();
+        for (name, node) in ["s0", "s1", "s2", "s3"].into_iter().zip(&sums) {
+            simulator.node(name, node).unwrap();
+        }
+        for (name, node) in ["c1", "c2", "c3", "c4"].into_iter().zip(&carries) {
+            simulator.node(name, node).unwrap();
+        }
+        simulator.node("result", &result).unwrap();
+        for a in 0..16 {
+            for b in 0..16 {
+                simulator.set("a", Byte::from(a)).unwrap();
+                simulator.set("b", Byte::from(b)).unwrap();
+                let result = simulator.eval_byte("result").unwrap();
+                assert_eq!(u8::from(result), a + b);
+            }
+        }
+    }
+
+    #[test]
+    fn majority() {
+        let evaluations = Cell::new(0);
+        let ab = |signals: &Signals| {
+            evaluations.set(evaluations.get() + 1);
+            Ok(signals.bit("a")?.and(signals.bit("b")?).into())
+        };
+        let bc = |signals: &Signals| Ok(signals.bit("b")?.and(signals.bit("c")?).into());
+        let ac = |signals: &Signals| Ok(signals.bit("a")?.and(signals.bit("c")?).into());
+        let majority = |signals: &Signals| {
+            // Reads `ab` twice, the second read is memoized
+            let ab = signals.bit("ab")?.or(signals.bit("ab")?);
+            Ok(ab.or(signals.bit("bc")?).or(signals.bit("ac")?).into())
+        };
+        let mut simulator = Simulator::<8>::new();
+        for name in ["a", "b", "c"] {
+            simulator.input(name, Bit::Low).unwrap();
+        }
+        simulator.node("ab", &ab).unwrap();
+        simulator.node("bc", &bc).unwrap();
+        simulator.node("ac", &ac).unwrap();
+        simulator.node("majority", &majority).unwrap();
+        for inputs in 0..8_u8 {
+            let bits = [0, 1, 2].map(|i| (inputs >> i) & 1 == 1);
+            for (name, bit) in ["a", "b", "c"].into_iter().zip(bits) {
+                simulator.set(name, Bit::from(bit)).unwrap();
+            }
+            let expected = bits.iter().filter(|&&bit| bit).count() >= 2;
+            assert_eq!(simulator.eval_bit("majority"), Ok(Bit::from(expected)));
+        }
+        assert_eq!(evaluations.get(), 8);
+    }
+
+    #[test]
+    fn errors() {
+        let ring = |signals: &Signals| Ok(signals.bit("back")?.not().into());
+        let back = |signals: &Signals| signals.value("ring");
+        let mut simulator = Simulator::<4>::new();
+        simulator.input("in", Bit::High).unwrap();
+        simulator.node("ring", &ring).unwrap();
+        simulator.node("back", &back).unwrap();
+        assert_eq!(
+            simulator.eval("ring").err(),
+            Some(CircuitError::Cycle("ring"))
+        );
+        assert_eq!(
+            simulator.input("in", Bit::Low),
+            Err(CircuitError::DuplicateName("in"))
+        );
+        assert_eq!(
+            simulator.set("ring", Bit::Low),
+            Err(CircuitError::NotAnInput("ring"))
+        );
+        assert_eq!(
+            simulator.eval_byte("in").err(),
+            Some(CircuitError::TypeMismatch("in"))
+        );
+        assert_eq!(
+            simulator.eval("out").err(),
+            Some(CircuitError::UnknownName("out"))
+        );
+        simulator.input("last", Bit::Low).unwrap();
+        assert_eq!(simulator.input("full", Bit::Low), Err(CircuitError::Full));
+    }
+}
@@ -0,0 +1,2401 @@
+//! The computer, combining the memory, the registers and the ALU.
+
+use core::{
+    array,
+    fmt::{self, Debug, Formatter},
+    ops::Range,
+};
+
+use crate::{
+    bit::Bit,
+    bus::Bus,
+    byte::Byte,
+    mux::{
+        self,
+        byte::{
+            decimal_adjust, decrement, half_carry, increment, is_zero, less_than, Ram, Register,
+            Rom, REGISTER_COUNT,
+        },
+    },
+    opcode::{
+        cycles, decode_opcode, decode_prefix, decode_prefixed, DecodeError, DecodedOp,
+        RegisterPrefix,
+    },
+    profile::Profile,
+    state::{InitialState, MachineState},
+    time::{CycleClock, InterruptSource},
+    word::{RegisterFile, WordType},
+    ExecutionError, StopReason,
+};
+
+#[cfg(feature = "gate-count")]
+use crate::gate_count;
+
+/// Where the program is stored and fetched from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionModel {
+    /// The program is copied into the data memory at address 0 and fetched from there, so loads
+    /// and stores see the program bytes and a program can modify its own code
+    #[default]
+    VonNeumann,
+
+    /// The program is stored in a separate ROM and fetched from there. Jumps and calls address
+    /// the ROM, loads and stores address the data memory, which starts without the program, so
+    /// stores can't change the code and all 256 data bytes are free for the program.
+    Harvard,
+
+    /// The program is stored in a ROM at address 0 and fetched from there, the data memory after
+    /// it is RAM. The program is copied into the data memory as well, so loads see the program
+    /// bytes, but a store in bank 0 to one of their addresses fails with
+    /// [`ExecutionError::WriteToRom`] so stray stores can't corrupt the code.
+    ProtectedRom,
+}
+
+/// The extent of the program in memory with the program counter pointing at the next byte to
+/// execute
+struct Program {
+    length: usize,
+    counter: Byte,
+    wrapped: Bit,
+    rom: Option<Rom>,
+    model: ExecutionModel,
+}
+
+impl Program {
+    /// Copies the program into the memory cells starting at address 0, into a ROM under the
+    /// Harvard model, or into both with a protected ROM
+    fn load(
+        program: impl IntoIterator<Item = u8>,
+        cells: &mut [Byte; 256],
+        model: ExecutionModel,
+    ) -> Result<Self, ExecutionError> {
+        let mut rom = [Byte::from(0); 256];
+        let target = match model {
+            ExecutionModel::VonNeumann => &mut *cells,
+            ExecutionModel::Harvard | ExecutionModel::ProtectedRom => &mut rom,
+        };
+        let mut program = program.into_iter();
+        let mut length = 0;
+        for (cell, byte) in target.iter_mut().zip(program.by_ref()) {
+            *cell = byte.into();
+            length += 1;
+        }
+        if program.next().is_some() {
+            return Err(ExecutionError::ProgramTooLarge);
+        }
+        if model == ExecutionModel::ProtectedRom {
+            cells[..length].copy_from_slice(&rom[..length]);
+        }
+        Ok(Self {
+            length,
+            counter: Byte::from(0),
+            wrapped: Bit::Low,
+            rom: (model != ExecutionModel::VonNeumann).then(|| Rom::from_cells(&rom, length)),
+            model,
+        })
+    }
+
+    /// Returns whether the address is in a protected ROM, so storing to it fails
+    fn protects(&self, address: Byte) -> bool {
+        self.model == ExecutionModel::ProtectedRom
+            && self.rom.as_ref().is_some_and(|rom| rom.contains(address))
+    }
+
+    /// The offset of the next byte in the program
+    fn offset(&self) -> usize {
+        usize::from(u8::from(self.counter))
+    }
+
+    /// Returns whether the program counter moved past the end of the program
+    fn at_end(&self) -> bool {
+        bool::from(self.wrapped) || self.offset() >= self.length
+    }
+
+    /// Fetches the byte at the program counter from the ROM, or from memory without one, and
+    /// moves the program counter to the next byte
+    fn fetch(&mut self, memory: &Ram) -> Option<u8> {
+        if self.at_end() {
+            return None;
+        }
+        let byte = self
+            .rom
+            .as_ref()
+            .map_or_else(|| memory.load(self.counter), |rom| rom.load(self.counter));
+        let carry;
+        (self.counter, carry) = increment(self.counter);
+        self.wrapped = self.wrapped.or(carry);
+        Some(byte.into())
+    }
+
+    /// Fetches the operand of the instruction at `offset`
+    fn operand(&mut self, memory: &Ram, offset: usize) -> Result<u8, ExecutionError> {
+        self.fetch(memory)
+            .ok_or(ExecutionError::TruncatedOperand { offset })
+    }
+
+    /// Sets the program counter to the target if `condition` is `Bit::High`
+    fn jump(&mut self, target: Byte, condition: Bit) {
+        self.counter = mux::byte::mux(self.counter, target, condition);
+        self.wrapped = self.wrapped.and(condition.not());
+    }
+}
+
+/// The initial stack pointer unless the initial state sets another one.
+///
+/// The stack takes the cells from here up to [`STACK_LIMIT`]. The 8 cells below it, 0xE8 to 0xEF,
+/// are reserved for the scratch memory of the fixed-point routines of the assembler.
+pub const STACK_BASE: u8 = 0xF0;
+
+/// The first address the stack can't grow into, where the input and console are mapped
+pub const STACK_LIMIT: u8 = 0xFE;
+
+/// The bits of a register `SetBank` uses as the bank
+const BANK_MASK: u8 = 0x0F;
+
+/// The number of breakpoints a computer can hold
+pub const MAX_BREAKPOINTS: usize = 8;
+
+/// The number of watchpoints a computer can hold
+pub const MAX_WATCHPOINTS: usize = 8;
+
+/// Returns the registers selected by bits 2-3 and bits 0-1 of a register byte, extended by the
+/// prefix of the instruction
+fn register_pair(operand: u8, prefix: Option<RegisterPrefix>) -> ([Bit; 3], [Bit; 3]) {
+    let (high, low) = prefix.map_or((false, false), |prefix| (prefix.high, prefix.low));
+    let select = |bits: u8, extended: bool| {
+        array::from_fn(|i| {
+            Bit::from(if i == 2 {
+                extended
+            } else {
+                (bits >> i) & 1 == 1
+            })
+        })
+    };
+    (select(operand >> 2, high), select(operand, low))
+}
+
+/// The result of executing a single step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// An instruction was executed
+    Executed,
+
+    /// The computer stopped. No instruction was executed, except at a watchpoint, which stops
+    /// after the instruction accessing the address.
+    Stopped(StopReason),
+}
+
+/// The accesses of an address a watchpoint stops at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WatchKind {
+    /// A load of the address
+    Read,
+
+    /// A store at the address
+    Write,
+
+    /// A load or a store
+    ReadWrite,
+}
+
+impl WatchKind {
+    /// Returns whether a watchpoint of this kind stops at the access, which is a read or a write
+    const fn matches(self, access: Self) -> bool {
+        matches!(
+            (self, access),
+            (Self::ReadWrite, _) | (Self::Read, Self::Read) | (Self::Write, Self::Write)
+        )
+    }
+}
+
+/// Checks that the initial state sets none of the memory cells in the range, unless it allows the
+/// overlap
+fn check_overlap(initial: &InitialState, cells: Range<usize>) -> Result<(), ExecutionError> {
+    let overlap = (0..=u8::MAX)
+        .zip(initial.memory)
+        .skip(cells.start)
+        .take(cells.len())
+        .find_map(|(addr, cell)| cell.map(|_| addr));
+    match overlap {
+        Some(addr) if !initial.allow_overlap => Err(ExecutionError::InitialStateOverlap { addr }),
+        _ => Ok(()),
+    }
+}
+
+/// The state of the computer after an instruction, passed to the tracer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// The offset of the instruction in the program
+    pub offset: usize,
+
+    /// The first byte of the instruction, its opcode or its register prefix
+    pub opcode: u8,
+
+    /// The register file after the instruction, indexed by register number
+    pub registers: [u8; REGISTER_COUNT],
+
+    /// The overflow flag after the instruction
+    pub overflow: bool,
+}
+
+/// The complete state of a computer at one point of a run, to continue from there later.
+///
+/// The devices on the bus aren't part of the snapshot, they keep their own state, and neither are
+/// the memory banks above bank 0, they keep their contents. A snapshot is restored into the
+/// computer that took it, a program stored in a ROM is never changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    /// The registers, memory, flags and stack pointer
+    pub state: MachineState,
+
+    /// The offset of the next instruction in the program
+    pub program_counter: u8,
+
+    /// The remaining fuel, or `None` without a limit
+    pub fuel: Option<u64>,
+
+    /// The number of executed instructions
+    pub executed: u64,
+
+    /// The number of cycles the executed instructions took
+    pub cycles: u64,
+
+    /// The memory bank loads and stores access, see [`Computer::bank`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bank: u8,
+
+    wrapped: bool,
+    stack_base: u8,
+    stopped: Option<StopReason>,
+    at_breakpoint: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    handler_stack: Option<u8>,
+}
+
+/// A computer executing a program one instruction at a time.
+///
+/// By default the program is copied into the memory of the bus at address 0 and fetched from
+/// there, so loads and stores see the program bytes and a program can modify its own code, see
+/// [`ExecutionModel`] for the alternative. All loads and stores go through the bus, so they reach
+/// the devices mapped on it, while instructions are fetched from the memory behind them.
+///
+/// The registers and the ALU are bytes by default. `Computer<Word>` is the 16 bit machine, which
+/// [`Computer::load_program`] creates, see [`WordType`] for the instructions that change with
+/// it. The state, snapshots and traces hold bytes, so they are only available on the 8 bit
+/// machine.
+pub struct Computer<'d, W: WordType = Byte> {
+    program: Program,
+    registers: W::Registers,
+    bus: Bus<'d>,
+    overflow: Bit,
+    zero: Bit,
+    negative: Bit,
+    half_carry: Bit,
+    stack_pointer: Byte,
+    stack_base: Byte,
+    bank: Byte,
+    stopped: Option<StopReason>,
+    fuel: Option<u64>,
+    executed: u64,
+    cycles: u64,
+    #[cfg(feature = "gate-count")]
+    gates: u64,
+    profile: Option<Profile>,
+    clock: Option<&'d CycleClock>,
+    interrupt: Option<(&'d dyn InterruptSource, u8)>,
+    handler_stack: Option<u8>,
+    breakpoints: [Option<u8>; MAX_BREAKPOINTS],
+    at_breakpoint: bool,
+    watchpoints: [Option<(u8, WatchKind)>; MAX_WATCHPOINTS],
+    watch_hit: Option<StopReason>,
+}
+
+impl<'d> Computer<'d> {
+    /// Loads the program into memory, with cleared registers and the rest of memory cleared.
+    ///
+    /// # Errors
+    /// Returns an error if the program is larger than 256 bytes
+    pub fn new(
+        program: impl IntoIterator<Item = u8>,
+        bus: Bus<'d>,
+    ) -> Result<Self, ExecutionError> {
+        Self::with_initial_state(program, &InitialState::new(), bus)
+    }
+
+    /// Loads the program and applies the initial state, replacing the memory of the bus.
+    /// Memory cells set by the initial state override the program bytes if the state allows the
+    /// overlap, see [`InitialState::allow_overlap`].
+    ///
+    /// # Errors
+    /// Returns an error if the program is larger than 256 bytes, or the initial state sets a
+    /// memory cell of the program without allowing the overlap
+    pub fn with_initial_state(
+        program: impl IntoIterator<Item = u8>,
+        initial: &InitialState,
+        bus: Bus<'d>,
+    ) -> Result<Self, ExecutionError> {
+        Self::with_execution_model(program, initial, bus, ExecutionModel::VonNeumann)
+    }
+
+    /// Loads the program like [`Computer::with_initial_state`], storing it as the model
+    /// describes. Under the Harvard model the program isn't in the data memory, so the initial
+    /// state can't overlap it.
+    ///
+    /// # Errors
+    /// Returns an error if the program is larger than 256 bytes, or the initial state sets a
+    /// memory cell of the program without allowing the overlap
+    pub fn with_execution_model(
+        program: impl IntoIterator<Item = u8>,
+        initial: &InitialState,
+        bus: Bus<'d>,
+        model: ExecutionModel,
+    ) -> Result<Self, ExecutionError> {
+        Self::load_program(program, initial, bus, model)
+    }
+
+    /// Loads a program image: the code at address 0 and the data segment at its address, where
+    /// it overrides the code bytes it overlaps. Execution starts at the entry offset, an entry
+    /// past the end of the code ends the program right away.
+    ///
+    /// # Errors
+    /// Returns an error if the code is larger than 256 bytes or the data segment doesn't fit in
+    /// memory after its address
+    pub fn load_image(
+        code: &[u8],
+        data: (u8, &[u8]),
+        entry: u8,
+        bus: Bus<'d>,
+    ) -> Result<Self, ExecutionError> {
+        Self::load_image_with_state(code, data, entry, &InitialState::new(), bus)
+    }
+
+    /// Loads a program image like [`Computer::load_image`] and applies the initial state. The
+    /// code and the data segment are both part of the image, so the memory cells of the state
+    /// may only set them if the state allows the overlap, the state wins then.
+    ///
+    /// # Errors
+    /// Returns an error if the code is larger than 256 bytes, the data segment doesn't fit in
+    /// memory after its address, or the initial state sets a memory cell of the image without
+    /// allowing the overlap
+    pub fn load_image_with_state(
+        code: &[u8],
+        data: (u8, &[u8]),
+        entry: u8,
+        initial: &InitialState,
+        bus: Bus<'d>,
+    ) -> Result<Self, ExecutionError> {
+        let (address, data) = data;
+        let start = usize::from(address);
+        let data_cells = start..start + data.len();
+        check_overlap(initial, 0..code.len())?;
+        check_overlap(initial, data_cells.clone())?;
+        // The data segment overrides the code it overlaps, and the state overrides both
+        let mut image = initial.with_allow_overlap(true);
+        let cells = image
+            .memory
+            .get_mut(data_cells)
+            .ok_or(ExecutionError::ProgramTooLarge)?;
+        for (cell, &byte) in cells.iter_mut().zip(data) {
+            *cell = cell.or(Some(byte));
+        }
+        let mut computer = Self::with_initial_state(code.iter().copied(), &image, bus)?;
+        computer.program.counter = entry.into();
+        Ok(computer)
+    }
+
+    /// Returns the registers, memory, flags and stack pointer
+    pub fn state(&self) -> MachineState {
+        MachineState {
+            registers: self.registers(),
+            memory: self.ram().dump(),
+            overflow: self.overflow(),
+            zero: self.zero(),
+            negative: self.negative(),
+            half_carry: self.half_carry(),
+            stack_pointer: self.stack_pointer(),
+        }
+    }
+
+    /// Captures the state of the computer, see [`Computer::restore`]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            state: self.state(),
+            program_counter: self.program_counter(),
+            fuel: self.fuel,
+            executed: self.executed,
+            cycles: self.cycles,
+            bank: self.bank(),
+            wrapped: self.program.wrapped.into(),
+            stack_base: self.stack_base.into(),
+            stopped: self.stopped,
+            at_breakpoint: self.at_breakpoint,
+            handler_stack: self.handler_stack,
+        }
+    }
+
+    /// Returns the computer to the state of the snapshot, running again from there executes the
+    /// same instructions as before as long as the devices answer the same way. Breakpoints and
+    /// watchpoints are kept as they are.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        let state = &snapshot.state;
+        self.registers.set_all(state.registers);
+        *self.bus.memory_mut() = Ram::from_cells(&state.memory.map(Byte::from));
+        self.overflow = Bit::from(state.overflow);
+        self.zero = Bit::from(state.zero);
+        self.negative = Bit::from(state.negative);
+        self.half_carry = Bit::from(state.half_carry);
+        self.stack_pointer = state.stack_pointer.into();
+        self.stack_base = snapshot.stack_base.into();
+        self.bank = Byte::from(snapshot.bank) & Byte::from(BANK_MASK);
+        self.program.counter = snapshot.program_counter.into();
+        self.program.wrapped = snapshot.wrapped.into();
+        self.fuel = snapshot.fuel;
+        self.executed = snapshot.executed;
+        self.cycles = snapshot.cycles;
+        if let Some(clock) = self.clock {
+            clock.set(self.executed, self.cycles);
+        }
+        self.stopped = snapshot.stopped;
+        self.at_breakpoint = snapshot.at_breakpoint;
+        self.handler_stack = snapshot.handler_stack;
+        self.watch_hit = None;
+    }
+
+    /// Executes instructions until the program stops, passing the state after every executed
+    /// instruction to the tracer.
+    ///
+    /// # Errors
+    /// Returns an error if an invalid instruction was found, the program ended unexpectedly or
+    /// writing the output failed
+    pub fn run_traced(
+        &mut self,
+        mut tracer: impl FnMut(TraceEvent),
+    ) -> Result<StopReason, ExecutionError> {
+        loop {
+            if let StepOutcome::Stopped(reason) = self.step_traced(&mut tracer)? {
+                return Ok(reason);
+            }
+        }
+    }
+
+    /// Executes a single instruction like [`Computer::step`], passing the state after the
+    /// instruction to the tracer if one was executed.
+    ///
+    /// # Errors
+    /// Returns an error if an invalid instruction was found, the program ended unexpectedly or
+    /// writing the output failed
+    pub fn step_traced(
+        &mut self,
+        tracer: impl FnOnce(TraceEvent),
+    ) -> Result<StepOutcome, ExecutionError> {
+        self.advance(Some(|computer: &Self, offset, opcode| {
+            tracer(TraceEvent {
+                offset,
+                opcode,
+                registers: computer.registers(),
+                overflow: computer.overflow(),
+            });
+        }))
+    }
+}
+
+impl<'d, W: WordType> Computer<'d, W> {
+    /// Loads the program like [`Computer::with_execution_model`], for a computer of any word
+    /// type. The register values of the initial state are zero extended.
+    ///
+    /// # Errors
+    /// Returns an error if the program is larger than 256 bytes, or the initial state sets a
+    /// memory cell of the program without allowing the overlap
+    pub fn load_program(
+        program: impl IntoIterator<Item = u8>,
+        initial: &InitialState,
+        mut bus: Bus<'d>,
+        model: ExecutionModel,
+    ) -> Result<Self, ExecutionError> {
+        let mut cells = [Byte::from(0); 256];
+        let program = Program::load(program, &mut cells, model)?;
+        if model != ExecutionModel::Harvard {
+            check_overlap(initial, 0..program.length)?;
+        }
+        for (cell, value) in cells.iter_mut().zip(initial.memory) {
+            if let Some(value) = value {
+                *cell = value.into();
+            }
+        }
+        let registers = W::Registers::from_bytes(&initial.registers);
+        *bus.memory_mut() = Ram::from_cells(&cells);
+        Ok(Self {
+            program,
+            registers,
+            bus,
+            overflow: Bit::from(initial.overflow),
+            zero: Bit::Low,
+            negative: Bit::Low,
+            half_carry: Bit::Low,
+            stack_pointer: initial.stack_pointer.into(),
+            stack_base: initial.stack_pointer.into(),
+            bank: Byte::from(0),
+            stopped: None,
+            fuel: None,
+            executed: 0,
+            cycles: 0,
+            #[cfg(feature = "gate-count")]
+            gates: 0,
+            profile: None,
+            clock: None,
+            interrupt: None,
+            handler_stack: None,
+            breakpoints: [None; MAX_BREAKPOINTS],
+            at_breakpoint: false,
+            watchpoints: [None; MAX_WATCHPOINTS],
+            watch_hit: None,
+        })
+    }
+
+    /// Counts the executions and cycles of every group of instructions, see [`Computer::profile`].
+    /// Without the profiler the computer doesn't keep the counters.
+    pub const fn with_profiler(mut self) -> Self {
+        self.profile = Some(Profile::new());
+        self
+    }
+
+    /// Returns the profile of the executed instructions, or `None` without the profiler
+    pub const fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Drives the clock with the executed instructions, for devices reading the time from it
+    pub fn with_clock(mut self, clock: &'d CycleClock) -> Self {
+        clock.set(self.executed, self.cycles);
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Connects the interrupt source, asked before every instruction is fetched. When it requests
+    /// an interrupt, the program counter is pushed like the return address of a call and
+    /// execution continues at the vector, in the same step. The handler returns with `ret`, no
+    /// other interrupt is taken until then. Entering the handler takes no cycles and fuel.
+    pub fn with_interrupt(mut self, source: &'d dyn InterruptSource, vector: u8) -> Self {
+        self.interrupt = Some((source, vector));
+        self
+    }
+
+    /// Returns whether the computer is running an interrupt handler
+    pub const fn in_interrupt(&self) -> bool {
+        self.handler_stack.is_some()
+    }
+
+    /// Limits the number of instructions that may be executed, without a limit by default
+    pub const fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    /// Replaces the remaining fuel, `None` removes the limit
+    pub const fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    /// Returns the remaining fuel, or `None` without a limit
+    pub const fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Stops the execution with [`StopReason::Breakpoint`] whenever the program counter reaches
+    /// the address, before the instruction there is executed. Adding a breakpoint twice has no
+    /// effect.
+    ///
+    /// # Panics
+    /// Panics if [`MAX_BREAKPOINTS`] other breakpoints are already set
+    pub fn add_breakpoint(&mut self, address: u8) {
+        if self.breakpoints.contains(&Some(address)) {
+            return;
+        }
+        let slot = self
+            .breakpoints
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .expect("no room for another breakpoint");
+        *slot = Some(address);
+    }
+
+    /// Removes the breakpoint at the address, if there is one
+    pub fn remove_breakpoint(&mut self, address: u8) {
+        for slot in &mut self.breakpoints {
+            if *slot == Some(address) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Stops the execution with [`StopReason::Watchpoint`] after a load or store of the kind
+    /// accesses the address, directly or through a register. Watching an address again replaces
+    /// the kind.
+    ///
+    /// # Panics
+    /// Panics if [`MAX_WATCHPOINTS`] other addresses are already watched
+    pub fn watch(&mut self, address: u8, kind: WatchKind) {
+        let slot = self
+            .watchpoints
+            .iter()
+            .position(|slot| matches!(slot, Some((watched, _)) if *watched == address))
+            .or_else(|| self.watchpoints.iter().position(Option::is_none))
+            .expect("no room for another watchpoint");
+        self.watchpoints[slot] = Some((address, kind));
+    }
+
+    /// Removes the watchpoint at the address, if there is one
+    pub fn unwatch(&mut self, address: u8) {
+        for slot in &mut self.watchpoints {
+            if matches!(slot, Some((watched, _)) if *watched == address) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Returns the number of executed instructions, including a final halt
+    pub const fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Returns the number of cycles the executed instructions took, see [`cycles`]
+    pub const fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the number of nand gates the fetched and executed instructions evaluated, including
+    /// those of the devices they accessed
+    #[cfg(feature = "gate-count")]
+    pub const fn gate_count(&self) -> u64 {
+        self.gates
+    }
+
+    /// Returns the value of the register
+    pub fn register(&self, register: Register) -> W::Value {
+        self.registers.load_reg(register).value()
+    }
+
+    /// Returns the values of all registers, indexed by register number
+    pub fn registers(&self) -> [W::Value; REGISTER_COUNT] {
+        self.registers.values()
+    }
+
+    /// Returns where the program is fetched from
+    pub const fn execution_model(&self) -> ExecutionModel {
+        self.program.model
+    }
+
+    /// Returns the value of the memory cell at the address, without reading the devices
+    pub fn memory(&self, address: u8) -> u8 {
+        self.bus.memory().load(address.into()).into()
+    }
+
+    /// Returns whether the last arithmetic instruction overflowed
+    pub fn overflow(&self) -> bool {
+        self.overflow.into()
+    }
+
+    /// Returns whether the result of the last logic or arithmetic instruction was 0
+    pub fn zero(&self) -> bool {
+        self.zero.into()
+    }
+
+    /// Returns whether the top bit of the result of the last logic or arithmetic instruction was
+    /// set, bit 7 on the 8 bit machine
+    pub fn negative(&self) -> bool {
+        self.negative.into()
+    }
+
+    /// Returns whether the last addition, subtraction, increment or decrement carried out of bit 3,
+    /// in the sense its overflow has for bit 7. `DecimalAdjust` corrects the low digit with it.
+    pub fn half_carry(&self) -> bool {
+        self.half_carry.into()
+    }
+
+    /// Returns the memory bank the loads and stores of the instructions access, selected by
+    /// `SetBank`. Instructions are fetched from bank 0 and the stack is in bank 0, whatever the
+    /// bank, and the devices are mapped in every bank.
+    pub fn bank(&self) -> u8 {
+        self.bank.into()
+    }
+
+    /// Returns the address the next push stores at
+    pub fn stack_pointer(&self) -> u8 {
+        self.stack_pointer.into()
+    }
+
+    /// Returns the offset of the next instruction in the program
+    pub fn program_counter(&self) -> u8 {
+        self.program.counter.into()
+    }
+
+    /// Returns the memory, for host side inspection without the address decoder
+    pub(crate) const fn ram(&self) -> &Ram {
+        self.bus.memory()
+    }
+
+    /// Executes instructions until the program stops.
+    ///
+    /// # Errors
+    /// Returns an error if an invalid instruction was found, the program ended unexpectedly or
+    /// writing the output failed
+    pub fn run(&mut self) -> Result<StopReason, ExecutionError> {
+        loop {
+            if let StepOutcome::Stopped(reason) = self.step()? {
+                return Ok(reason);
+            }
+        }
+    }
+
+    /// Executes a single instruction.
+    ///
+    /// Once the program stopped, every following step returns the same reason again. Running out
+    /// of fuel is the exception, execution continues once fuel is added with
+    /// [`Computer::set_fuel`].
+    ///
+    /// # Errors
+    /// Returns an error if an invalid instruction was found, the program ended unexpectedly or
+    /// writing the output failed
+    pub fn step(&mut self) -> Result<StepOutcome, ExecutionError> {
+        self.advance(None::<fn(&Self, usize, u8)>)
+    }
+
+    /// Executes a single instruction, the tracer is only called when there is one so untraced
+    /// steps don't read the registers
+    fn advance(
+        &mut self,
+        tracer: Option<impl FnOnce(&Self, usize, u8)>,
+    ) -> Result<StepOutcome, ExecutionError> {
+        if let Some(reason) = self.stopped {
+            return Ok(StepOutcome::Stopped(reason));
+        }
+        if self.fuel == Some(0) && !self.program.at_end() {
+            return Ok(StepOutcome::Stopped(StopReason::OutOfFuel {
+                executed: self.executed,
+            }));
+        }
+        // The instruction at a breakpoint runs on the step after the one stopping there
+        let pc = self.program_counter();
+        if !self.at_breakpoint && self.breakpoints.contains(&Some(pc)) {
+            self.at_breakpoint = true;
+            return Ok(StepOutcome::Stopped(StopReason::Breakpoint { pc }));
+        }
+        self.at_breakpoint = false;
+        #[cfg(feature = "gate-count")]
+        let start = gate_count::count();
+        if let Some((source, vector)) = self.interrupt {
+            if self.handler_stack.is_none() && source.take() {
+                self.push(self.program.offset(), self.program.counter)?;
+                self.handler_stack = Some(self.stack_pointer.into());
+                self.program.jump(Byte::from(vector), Bit::High);
+            }
+        }
+        let offset = self.program.offset();
+        let Some(opcode) = self.program.fetch(self.bus.memory()) else {
+            self.stopped = Some(StopReason::EndOfProgram);
+            return Ok(StepOutcome::Stopped(StopReason::EndOfProgram));
+        };
+        self.watch_hit = None;
+        let result = self.execute(offset, opcode);
+        #[cfg(feature = "gate-count")]
+        {
+            self.gates += gate_count::count() - start;
+        }
+        let reason = result?;
+        if let Some(tracer) = tracer {
+            tracer(self, offset, opcode);
+        }
+        self.stopped = reason;
+        // A watchpoint doesn't stop for good, running again continues after the access
+        match (reason, self.watch_hit) {
+            (Some(reason), _) => Ok(StepOutcome::Stopped(reason)),
+            (None, Some(hit)) => Ok(StepOutcome::Stopped(hit)),
+            (None, None) => Ok(StepOutcome::Executed),
+        }
+    }
+
+    /// Decodes the fetched byte and the opcode after it if the byte is a register prefix, and
+    /// counts the instruction
+    fn decode(
+        &mut self,
+        offset: usize,
+        byte: u8,
+    ) -> Result<(DecodedOp, Option<RegisterPrefix>), ExecutionError> {
+        self.executed += 1;
+        self.fuel = self.fuel.map(|fuel| fuel - 1);
+        let prefix = decode_prefix(byte);
+        let opcode = match prefix {
+            Some(_) => self.program.operand(self.bus.memory(), offset)?,
+            None => byte,
+        };
+        let cycles = cycles(opcode) + u8::from(prefix.is_some());
+        self.cycles += u64::from(cycles);
+        if let Some(clock) = self.clock {
+            clock.set(self.executed, self.cycles);
+        }
+        let op = prefix
+            .map_or_else(
+                || decode_opcode(byte),
+                |prefix| decode_prefixed(prefix, opcode),
+            )
+            .and_then(|op| match op {
+                DecodedOp::DecimalAdjust if !W::DECIMAL_ADJUST => Err(DecodeError { byte: opcode }),
+                op => Ok(op),
+            });
+        // An invalid opcode is reported at its own offset after the prefix
+        let op = op.map_err(|DecodeError { byte }| ExecutionError::InvalidInstruction {
+            offset: if prefix.is_some() && byte == opcode {
+                offset + 1
+            } else {
+                offset
+            },
+            byte,
+        })?;
+        if let Some(profile) = &mut self.profile {
+            profile.record(op.group(), cycles);
+        }
+        Ok((op, prefix))
+    }
+
+    /// Executes the fetched instruction, returns the reason if the program stopped
+    fn execute(&mut self, offset: usize, byte: u8) -> Result<Option<StopReason>, ExecutionError> {
+        let (op, prefix) = self.decode(offset, byte)?;
+        let registers = &mut self.registers;
+        match op {
+            // Bytes are zero extended into the registers, stores write the low byte
+            DecodedOp::LoadConstant(register) => registers.store_reg(
+                register,
+                W::zero_extend(self.program.operand(self.bus.memory(), offset)?.into()),
+            ),
+            DecodedOp::LoadMemory(register) => {
+                let address = self.program.operand(self.bus.memory(), offset)?;
+                let value = self.load(address.into());
+                self.registers.store_reg(register, W::zero_extend(value));
+            }
+            DecodedOp::StoreMemory(register) => {
+                let address = self.program.operand(self.bus.memory(), offset)?;
+                let value = registers.load_reg(register).low();
+                self.store(offset, address.into(), value)?;
+            }
+            DecodedOp::Not(_)
+            | DecodedOp::Move(..)
+            | DecodedOp::Nand(..)
+            | DecodedOp::And(..)
+            | DecodedOp::Nor(..)
+            | DecodedOp::Or(..)
+            | DecodedOp::Xnor(..)
+            | DecodedOp::Xor(..) => self.logic(op),
+            DecodedOp::Add(..)
+            | DecodedOp::AddOverflow(..)
+            | DecodedOp::Sub(..)
+            | DecodedOp::SubOverflow(..) => self.arithmetic(op),
+            DecodedOp::Jump => self.jump(offset, Bit::High)?,
+            DecodedOp::JumpIfOverflow => self.jump(offset, self.overflow)?,
+            DecodedOp::JumpIfZero(register) => {
+                self.jump(offset, self.registers.load_reg(register).is_zero())?;
+            }
+            DecodedOp::Call => {
+                let target = Byte::from(self.program.operand(self.bus.memory(), offset)?);
+                self.push(offset, self.program.counter)?;
+                self.program.jump(target, Bit::High);
+            }
+            DecodedOp::Return => {
+                // Popping the return address pushed for the interrupt leaves the handler
+                let leaves_handler = self.handler_stack == Some(self.stack_pointer.into());
+                let target = self.pop(offset)?;
+                self.program.jump(target, Bit::High);
+                if leaves_handler {
+                    self.handler_stack = None;
+                }
+            }
+            // A word is pushed low byte first and popped high byte first
+            DecodedOp::Push(register) => {
+                let value = self.registers.load_reg(register);
+                for index in 0..W::BYTES {
+                    self.push(offset, value.byte(index))?;
+                }
+            }
+            DecodedOp::Pop(register) => {
+                let mut value = W::zero_extend(Byte::from(0));
+                for index in (0..W::BYTES).rev() {
+                    value = value.with_byte(index, self.pop(offset)?);
+                }
+                self.registers.store_reg(register, value);
+            }
+            // Shifts feed in 0, rotates feed the shifted out bit back in
+            DecodedOp::ShiftLeft(register) => self.shift(register, Bit::Low, Bit::Low),
+            DecodedOp::ShiftRight(register) => self.shift(register, Bit::High, Bit::Low),
+            DecodedOp::RotateLeft(register) => self.shift(register, Bit::Low, Bit::High),
+            DecodedOp::RotateRight(register) => self.shift(register, Bit::High, Bit::High),
+            // Memory rotates feed the overflow in
+            DecodedOp::ShiftLeftMemory => self.shift_memory(offset, Bit::Low, Bit::Low)?,
+            DecodedOp::ShiftRightMemory => self.shift_memory(offset, Bit::High, Bit::Low)?,
+            DecodedOp::RotateLeftMemory => self.shift_memory(offset, Bit::Low, Bit::High)?,
+            DecodedOp::RotateRightMemory => self.shift_memory(offset, Bit::High, Bit::High)?,
+            // The half carry is the carry or borrow out of bit 3, like the overflow out of bit 7
+            DecodedOp::Inc(register) => {
+                let input = self.registers.load_reg(register);
+                let (value, carry) = input.increment();
+                self.registers.store_reg(register, value);
+                self.overflow = carry;
+                self.half_carry = half_carry(input.low(), Byte::from(0), Bit::High, Bit::Low);
+            }
+            DecodedOp::Dec(register) => {
+                let input = self.registers.load_reg(register);
+                let (value, borrow) = input.decrement();
+                self.registers.store_reg(register, value);
+                self.overflow = borrow;
+                self.half_carry =
+                    half_carry(input.low(), Byte::from(0), Bit::High, Bit::High).not();
+            }
+            DecodedOp::SetBank(register) => {
+                self.bank = self.registers.load_reg(register).low() & Byte::from(BANK_MASK);
+            }
+            DecodedOp::SetCarry => self.overflow = Bit::High,
+            DecodedOp::ClearCarry => self.overflow = Bit::Low,
+            DecodedOp::LoadIndirect
+            | DecodedOp::StoreIndirect
+            | DecodedOp::Mul
+            | DecodedOp::Div
+            | DecodedOp::Rem
+            | DecodedOp::Copy
+            | DecodedOp::DecimalAdjust => self.register_byte(offset, op, prefix)?,
+            DecodedOp::Halt => return Ok(Some(StopReason::Halted)),
+        }
+        Ok(None)
+    }
+
+    /// Executes a logic instruction, the result is stored in the left register
+    fn logic(&mut self, op: DecodedOp) {
+        let registers = &mut self.registers;
+        let (target, source) = match op {
+            DecodedOp::Not(register) => (register, register),
+            DecodedOp::Move(target, source)
+            | DecodedOp::Nand(target, source)
+            | DecodedOp::And(target, source)
+            | DecodedOp::Nor(target, source)
+            | DecodedOp::Or(target, source)
+            | DecodedOp::Xnor(target, source)
+            | DecodedOp::Xor(target, source) => (target, source),
+            _ => unreachable!("not a logic instruction"),
+        };
+        let (left, right) = (registers.load_reg(target), registers.load_reg(source));
+        // A move bypasses the ALU, a not is the nand of the register with itself
+        let result = if matches!(op, DecodedOp::Move(..)) {
+            right
+        } else {
+            W::alu_op(left, right, Bit::Low, Self::alu_select(op)).0
+        };
+        registers.store_reg(target, result);
+        if !matches!(op, DecodedOp::Move(..)) {
+            self.set_flags(result);
+        }
+    }
+
+    /// Executes an addition or subtraction, the result is stored in the left register
+    fn arithmetic(&mut self, op: DecodedOp) {
+        let registers = &mut self.registers;
+        let (DecodedOp::Add(target, source)
+        | DecodedOp::AddOverflow(target, source)
+        | DecodedOp::Sub(target, source)
+        | DecodedOp::SubOverflow(target, source)) = op
+        else {
+            unreachable!("not an arithmetic instruction")
+        };
+        let (left, right) = (registers.load_reg(target), registers.load_reg(source));
+        let subtract = Bit::from(matches!(
+            op,
+            DecodedOp::Sub(..) | DecodedOp::SubOverflow(..)
+        ));
+        let with_carry = Bit::from(matches!(
+            op,
+            DecodedOp::AddOverflow(..) | DecodedOp::SubOverflow(..)
+        ));
+        let carry_in = self.overflow.and(with_carry);
+        let (result, carry) = W::alu_op(left, right, carry_in, Self::alu_select(op));
+        registers.store_reg(target, result);
+        self.half_carry = half_carry(left.low(), right.low(), carry_in, subtract);
+        // Subtracting with the overflow has always set the overflow, whether it borrows or not
+        self.overflow = carry.or(subtract.and(with_carry));
+        self.set_flags(result);
+    }
+
+    /// Returns the select bits of the ALU function of a logic or arithmetic instruction, see
+    /// [`alu_op`]
+    fn alu_select(op: DecodedOp) -> [Bit; 3] {
+        let function: u8 = match op {
+            DecodedOp::And(..) => 0,
+            DecodedOp::Or(..) => 1,
+            DecodedOp::Xor(..) => 2,
+            DecodedOp::Not(_) | DecodedOp::Nand(..) => 3,
+            DecodedOp::Nor(..) => 4,
+            DecodedOp::Xnor(..) => 5,
+            DecodedOp::Add(..) | DecodedOp::AddOverflow(..) => 6,
+            DecodedOp::Sub(..) | DecodedOp::SubOverflow(..) => 7,
+            _ => unreachable!("not an ALU instruction"),
+        };
+        array::from_fn(|bit| Bit::from((function >> bit) & 1 == 1))
+    }
+
+    /// Sets the zero flag with the nor tree of the result bits and the negative flag to the sign
+    /// bit
+    fn set_flags(&mut self, result: W) {
+        self.zero = result.is_zero();
+        self.negative = result.sign();
+    }
+
+    /// Loads the byte at the address through the bus, for the load instructions
+    fn load(&mut self, address: Byte) -> Byte {
+        let value = self.bus.load_banked(self.bank_select(), address);
+        self.watched(address, value, WatchKind::Read);
+        value
+    }
+
+    /// Stores the byte at the address through the bus, for the store instructions
+    fn store(&mut self, offset: usize, address: Byte, value: Byte) -> Result<(), ExecutionError> {
+        self.check_rom(offset, address)?;
+        if !self.bus.store_banked(self.bank_select(), address, value) {
+            return Err(ExecutionError::OutputError { offset });
+        }
+        self.watched(address, value, WatchKind::Write);
+        Ok(())
+    }
+
+    /// Fails if a store at the address would change the protected ROM of the program, which is
+    /// in bank 0
+    fn check_rom(&self, offset: usize, address: Byte) -> Result<(), ExecutionError> {
+        let in_bank_0 = u8::from(self.bank) == 0 || !self.bus.has_banks();
+        if in_bank_0 && self.program.protects(address) {
+            return Err(ExecutionError::WriteToRom {
+                offset,
+                addr: address.into(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the select bits of the bank the loads and stores access
+    fn bank_select(&self) -> [Bit; 4] {
+        let bits: [Bit; 8] = self.bank.into();
+        array::from_fn(|i| bits[i])
+    }
+
+    /// Records the access if a watchpoint stops at it
+    fn watched(&mut self, address: Byte, value: Byte, access: WatchKind) {
+        let addr = u8::from(address);
+        let hit = self
+            .watchpoints
+            .iter()
+            .flatten()
+            .any(|&(watched, kind)| watched == addr && kind.matches(access));
+        if hit && self.watch_hit.is_none() {
+            self.watch_hit = Some(StopReason::Watchpoint {
+                addr,
+                kind: access,
+                value: value.into(),
+            });
+        }
+    }
+
+    /// Jumps to the address in the operand if the condition is `Bit::High`
+    fn jump(&mut self, offset: usize, condition: Bit) -> Result<(), ExecutionError> {
+        let target = Byte::from(self.program.operand(self.bus.memory(), offset)?);
+        self.program.jump(target, condition);
+        Ok(())
+    }
+
+    /// Stores the value at the stack pointer and increments it, the stack grows up from the
+    /// initial stack pointer to [`STACK_LIMIT`]
+    fn push(&mut self, offset: usize, value: Byte) -> Result<(), ExecutionError> {
+        if u8::from(self.stack_pointer) >= STACK_LIMIT {
+            return Err(ExecutionError::StackOverflow { offset });
+        }
+        if self.program.protects(self.stack_pointer) {
+            return Err(ExecutionError::WriteToRom {
+                offset,
+                addr: self.stack_pointer.into(),
+            });
+        }
+        if !self.bus.store(self.stack_pointer, value) {
+            return Err(ExecutionError::OutputError { offset });
+        }
+        (self.stack_pointer, _) = increment(self.stack_pointer);
+        Ok(())
+    }
+
+    /// Decrements the stack pointer and loads the byte at it, the stack can't shrink below the
+    /// initial stack pointer
+    fn pop(&mut self, offset: usize) -> Result<Byte, ExecutionError> {
+        if u8::from(self.stack_pointer) <= u8::from(self.stack_base) {
+            return Err(ExecutionError::StackUnderflow { offset });
+        }
+        (self.stack_pointer, _) = decrement(self.stack_pointer);
+        Ok(self.bus.load(self.stack_pointer))
+    }
+
+    /// Shifts or rotates the register one bit in the direction through the barrel shifter,
+    /// `right` is `Bit::High` for right
+    fn shift(&mut self, register: Register, right: Bit, rotate: Bit) {
+        let (value, out) = self.registers.load_reg(register).shift(right, rotate);
+        self.registers.store_reg(register, value);
+        self.overflow = out;
+    }
+
+    /// Shifts or rotates the memory cell at the operand one bit in the direction, rotating
+    /// through the overflow
+    fn shift_memory(
+        &mut self,
+        offset: usize,
+        right: Bit,
+        rotate: Bit,
+    ) -> Result<(), ExecutionError> {
+        // Read-modify-write directly on the memory cell, bypassing the devices.
+        let address = Byte::from(self.program.operand(self.bus.memory(), offset)?);
+        self.check_rom(offset, address)?;
+        let value = self.bus.load_cell(self.bank_select(), address);
+        let carry = self.overflow.and(rotate);
+        let (left_value, left_carry) = value.rotate_left_through_carry(carry);
+        let (right_value, right_carry) = value.rotate_right_through_carry(carry);
+        self.bus.store_cell(
+            self.bank_select(),
+            address,
+            mux::byte::mux(left_value, right_value, right),
+        );
+        self.overflow = mux::bit::mux(left_carry, right_carry, right);
+        Ok(())
+    }
+
+    /// Executes an instruction followed by a register byte, which selects a register in bits 2-3
+    /// and one in bits 0-1, the upper bits are reserved
+    fn register_byte(
+        &mut self,
+        offset: usize,
+        op: DecodedOp,
+        prefix: Option<RegisterPrefix>,
+    ) -> Result<(), ExecutionError> {
+        let operand = self.program.operand(self.bus.memory(), offset)?;
+        let (left, right) = register_pair(operand, prefix);
+        let registers = &mut self.registers;
+        match op {
+            // The low byte of the right register holds the address
+            DecodedOp::LoadIndirect => {
+                let address = registers.load(right).low();
+                let value = self.load(address);
+                self.registers.store(left, W::zero_extend(value));
+            }
+            DecodedOp::StoreIndirect => {
+                let (address, value) = (registers.load(right).low(), registers.load(left).low());
+                self.store(offset, address, value)?;
+            }
+            DecodedOp::Mul => {
+                let (product, lost) = registers.load(left).multiply_low(registers.load(right));
+                registers.store(left, product);
+                self.overflow = lost;
+            }
+            DecodedOp::Div | DecodedOp::Rem => {
+                // Dividing by zero sets the overflow, the quotient has every bit set and the
+                // remainder is the left register
+                let divisor = registers.load(right);
+                let (quotient, remainder) = registers.load(left).divide(divisor);
+                let keep_remainder = Bit::from(op == DecodedOp::Rem);
+                registers.store(left, W::mux(quotient, remainder, keep_remainder));
+                self.overflow = divisor.is_zero();
+            }
+            // The left register holds the destination, the right one the source, r0 the count
+            DecodedOp::Copy => {
+                let (destination, source) =
+                    (registers.load(left).low(), registers.load(right).low());
+                let count = registers.load_reg(Register::R0).low();
+                self.copy(offset, destination, source, count)?;
+            }
+            // Corrects the sum of the last addition with its carry and half carry, it's only
+            // decoded on the 8 bit machine
+            DecodedOp::DecimalAdjust => {
+                let (value, carry) =
+                    decimal_adjust(registers.load(right).low(), self.overflow, self.half_carry);
+                let value = W::zero_extend(value);
+                registers.store(right, value);
+                self.overflow = carry;
+                self.set_flags(value);
+            }
+            _ => unreachable!("not an instruction with a register byte"),
+        }
+        Ok(())
+    }
+
+    /// Copies `count` bytes from the source to the destination one load and store at a time,
+    /// with the addresses wrapping around at the end of memory.
+    ///
+    /// The block is copied like `memmove`: backwards from its last byte if the destination starts
+    /// inside the source block, so the source bytes are read before they are overwritten, and
+    /// forwards from its first byte otherwise.
+    fn copy(
+        &mut self,
+        offset: usize,
+        destination: Byte,
+        source: Byte,
+        count: Byte,
+    ) -> Result<(), ExecutionError> {
+        let (distance, _) = destination - source;
+        let backwards = less_than(distance, count);
+        let (last, _) = decrement(count);
+        let (mut destination, mut source) = (
+            mux::byte::mux(destination, (destination + last).0, backwards),
+            mux::byte::mux(source, (source + last).0, backwards),
+        );
+        let step = |address| mux::byte::mux(increment(address).0, decrement(address).0, backwards);
+        let mut remaining = count;
+        while !bool::from(is_zero(remaining)) {
+            let value = self.load(source);
+            self.store(offset, destination, value)?;
+            (destination, source) = (step(destination), step(source));
+            (remaining, _) = decrement(remaining);
+        }
+        Ok(())
+    }
+}
+
+impl<W: WordType> Debug for Computer<'_, W> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Computer")
+            .field("program_counter", &self.program.counter)
+            .field("registers", &self.registers)
+            .field("overflow", &self.overflow)
+            .field("zero", &self.zero)
+            .field("negative", &self.negative)
+            .field("half_carry", &self.half_carry)
+            .field("stack_pointer", &self.stack_pointer)
+            .field("bank", &self.bank)
+            .field("stopped", &self.stopped)
+            .field("executed", &self.executed)
+            .field("cycles", &self.cycles)
+            .field("memory", self.bus.memory())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{array, cell::RefCell, fmt::Write};
+
+    use heapless::String;
+
+    #[cfg(feature = "serde")]
+    use super::Snapshot;
+    use super::{Computer, ExecutionModel, StepOutcome, WatchKind};
+    use crate::{
+        bus::{Bus, ConsoleDevice, InputStream, SharedDevice},
+        mux::byte::{Ram4k, Register, Registers},
+        state::InitialState,
+        word::Word,
+        ExecutionError, StopReason,
+    };
+
+    #[test]
+    fn load_image() {
+        // The code starts with a halt that the entry skips, the text is in the data segment
+        let code = [
+            0xFF, // hlt
+            0x03, 0x80, // ldi r3, 0x80
+            0xF2, 0x03, // ld r0, [r3]
+            0xC4, 0x0C, // jz r0, 0x0c
+            0x08, 0xFF, // st r0, [255]
+            0xE7, // inc r3
+            0xC0, 0x03, // jmp 0x03
+            0xFF, // hlt
+        ];
+        let mut console = ConsoleDevice::new(String::<8>::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let mut computer = Computer::load_image(&code, (0x80, b"Hi!\0"), 1, bus).unwrap();
+        assert_eq!(computer.program_counter(), 1);
+        assert_eq!(computer.memory(0x82), b'!');
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.program_counter(), 13);
+        assert_eq!(console.output().as_str(), "Hi!");
+
+        // The data segment overrides the code it overlaps
+        let computer = Computer::load_image(&[0xFF, 0xFF], (1, &[0x42]), 0, Bus::new()).unwrap();
+        assert_eq!([computer.memory(0), computer.memory(1)], [0xFF, 0x42]);
+        let error = Computer::load_image(&[0xFF], (0xFE, &[1, 2, 3]), 0, Bus::new());
+        assert_eq!(error.err(), Some(ExecutionError::ProgramTooLarge));
+    }
+
+    #[test]
+    fn image_with_initial_state() {
+        let code = [0xFF, 0xFF];
+        let load = |initial: &InitialState| {
+            Computer::load_image_with_state(&code, (1, &[0x42, 0x43]), 0, initial, Bus::new())
+        };
+        // The state may not set the code or the data segment
+        for address in [1, 2] {
+            let initial = InitialState::new().with_memory(address, 1);
+            assert_eq!(
+                load(&initial).err(),
+                Some(ExecutionError::InitialStateOverlap { addr: address })
+            );
+        }
+        // With the overlap allowed the state wins over the data segment, which wins over the code
+        let initial = InitialState::new()
+            .with_memory(2, 0x44)
+            .with_memory(3, 0x45)
+            .with_registers([7, 0, 0, 0])
+            .with_allow_overlap(true);
+        let computer = load(&initial).unwrap();
+        assert_eq!(
+            [0, 1, 2, 3].map(|address| computer.memory(address)),
+            [0xFF, 0x42, 0x44, 0x45]
+        );
+        assert_eq!(computer.register(Register::R0), 7);
+    }
+
+    #[test]
+    fn step_hello_world() {
+        let expected = b"Hello, world!";
+        let code: [u8; 52] = array::from_fn(|i| match i % 4 {
+            0 => 0,
+            1 => expected[i / 4],
+            2 => 8,
+            3 => 255,
+            _ => unreachable!(),
+        });
+        let mut console = ConsoleDevice::new(String::<20>::new());
+        let mut computer =
+            Computer::new(code, Bus::new().with_device(255..=255, &mut console)).unwrap();
+        for &character in expected {
+            let previous = computer.memory(255);
+            assert_eq!(computer.step(), Ok(StepOutcome::Executed));
+            assert_eq!(computer.register(Register::R0), character);
+            assert_eq!(computer.memory(255), previous);
+            assert_eq!(computer.step(), Ok(StepOutcome::Executed));
+            assert_eq!(computer.memory(255), character);
+        }
+        assert_eq!(
+            computer.step(),
+            Ok(StepOutcome::Stopped(StopReason::EndOfProgram))
+        );
+        assert_eq!(computer.register(Register::R1), 0);
+        assert!(!computer.overflow());
+        assert_eq!(console.output().as_bytes(), expected);
+    }
+
+    #[test]
+    fn classic_program_unchanged() {
+        // A program for r0 to r3 assembled before the register prefixes, printing from a table
+        let mut code = [0; 26];
+        code[..12].copy_from_slice(&[
+            0x03, 0x0C, 0xF2, 0x03, 0xC4, 0x0B, 0x08, 0xFF, 0xE7, 0xC0, 0x02, 0xFF,
+        ]);
+        code[12..].copy_from_slice(b"Hello, world!\0");
+        let mut console = ConsoleDevice::new(String::<20>::new());
+        let mut computer =
+            Computer::new(code, Bus::new().with_device(255..=255, &mut console)).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.registers(), [0, 0, 0, 0x19, 0, 0, 0, 0]);
+        assert_eq!((computer.executed(), computer.cycles()), (69, 124));
+        assert_eq!(console.output(), "Hello, world!");
+    }
+
+    #[test]
+    fn debug_format() {
+        // ldi r1, 0x2A; st r1, 0x80; hlt
+        let mut computer = Computer::new([0x01, 0x2A, 0x09, 0x80, 0xFF], Bus::new()).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        let mut text = String::<512>::new();
+        write!(text, "{computer:?}").unwrap();
+        assert!(
+            text.starts_with("Computer { program_counter: 0x05, registers: {r0: 0x00, r1: 0x2A,")
+        );
+        assert!(text.contains("stopped: Some(Halted), executed: 3, cycles: 5"));
+        assert!(text.ends_with(
+            "memory: {0x00: 01 2A 09 80 FF 00 00 00 00 00 00 00 00 00 00 00, \
+             0x10..0x80: 00 x 112, \
+             0x80: 2A 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00, \
+             0x90..0x100: 00 x 112}, .. }"
+        ));
+    }
+
+    #[test]
+    fn extended_registers() {
+        let code = [
+            0xF9, 0x01, 7, // ldi r5, 7
+            0xF9, 0x02, 5, // ldi r6, 5
+            0xFB, 0x86, // add r5, r6
+            0xF9, 0x11, // mov r0, r5
+            0xFB, 0xF4, 0x09, // mul r6, r5
+            0xFF,
+        ];
+        let mut computer = Computer::new(code, Bus::new()).unwrap();
+        let mut first = heapless::Vec::<(usize, u8), 8>::new();
+        let reason = computer.run_traced(|event| first.push((event.offset, event.opcode)).unwrap());
+        assert_eq!(reason, Ok(StopReason::Halted));
+        assert_eq!(computer.registers(), [12, 0, 0, 0, 0, 12, 60, 0]);
+        assert_eq!(
+            first,
+            [
+                (0, 0xF9),
+                (3, 0xF9),
+                (6, 0xFB),
+                (8, 0xF9),
+                (10, 0xFB),
+                (13, 0xFF)
+            ]
+        );
+        // Every prefix takes a cycle to fetch
+        assert_eq!(computer.cycles(), 3 + 3 + 2 + 2 + 11 + 1);
+    }
+
+    #[test]
+    fn invalid_prefixes() {
+        let error = |code: &[u8]| {
+            let mut computer = Computer::new(code.iter().copied(), Bus::new()).unwrap();
+            computer.run().unwrap_err()
+        };
+        assert_eq!(
+            error(&[0xF9]),
+            ExecutionError::TruncatedOperand { offset: 0 }
+        );
+        // `ldi` has no register in bits 2-3
+        assert_eq!(
+            error(&[0xFA, 0x00, 1]),
+            ExecutionError::InvalidInstruction {
+                offset: 0,
+                byte: 0xFA
+            }
+        );
+        assert_eq!(
+            error(&[0xF9, 0xF8]),
+            ExecutionError::InvalidInstruction {
+                offset: 1,
+                byte: 0xF8
+            }
+        );
+        assert_eq!(
+            error(&[0xF8]),
+            ExecutionError::InvalidInstruction {
+                offset: 0,
+                byte: 0xF8
+            }
+        );
+    }
+
+    #[test]
+    fn cycle_count() {
+        let expected = b"Hello, world!";
+        let code: [u8; 52] = array::from_fn(|i| match i % 4 {
+            0 => 0,
+            1 => expected[i / 4],
+            2 => 8,
+            3 => 255,
+            _ => unreachable!(),
+        });
+        let mut console = ConsoleDevice::new(String::<20>::new());
+        let mut computer =
+            Computer::new(code, Bus::new().with_device(255..=255, &mut console)).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        // 13 loads of a constant and 13 stores, 2 cycles each
+        assert_eq!(computer.cycles(), 52);
+        assert_eq!(computer.executed(), 26);
+
+        // r0 = 3, r1 = 4, r0 *= r1, halt
+        let code = [0x00, 3, 0x01, 4, 0xF4, 0x01, 0xFF];
+        let mut computer = Computer::new(code, Bus::new()).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.cycles(), 2 + 2 + 10 + 1);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let expected = b"Hello, world!";
+        let code: [u8; 52] = array::from_fn(|i| match i % 4 {
+            0 => 0,
+            1 => expected[i / 4],
+            2 => 8,
+            3 => 255,
+            _ => unreachable!(),
+        });
+        let mut console = ConsoleDevice::new(String::<20>::new());
+        let mut computer =
+            Computer::new(code, Bus::new().with_device(255..=255, &mut console)).unwrap();
+        // Before the store of the comma, and before the load of the w
+        computer.add_breakpoint(22);
+        computer.add_breakpoint(28);
+        computer.add_breakpoint(22);
+        assert_eq!(computer.run(), Ok(StopReason::Breakpoint { pc: 22 }));
+        assert_eq!(computer.register(Register::R0), b',');
+        assert_eq!(computer.memory(255), b'o');
+        // Resuming runs the instruction at the breakpoint instead of stopping again
+        assert_eq!(computer.run(), Ok(StopReason::Breakpoint { pc: 28 }));
+        assert_eq!(computer.register(Register::R0), b' ');
+        computer.remove_breakpoint(28);
+        computer.add_breakpoint(0);
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(computer.executed(), 26);
+        assert_eq!(console.output().as_bytes(), expected);
+    }
+
+    #[test]
+    fn breakpoint_in_loop() {
+        // r0 += r1, jump back to the addition
+        let initial = InitialState::new().with_registers([0, 1, 0, 0]);
+        let code = [0x81, 0xC0, 0x00];
+        let mut computer = Computer::with_initial_state(code, &initial, Bus::new()).unwrap();
+        for address in 0..8 {
+            computer.add_breakpoint(address);
+        }
+        computer.remove_breakpoint(1);
+        for round in 1..=3 {
+            assert_eq!(computer.run(), Ok(StopReason::Breakpoint { pc: 0 }));
+            assert_eq!(computer.register(Register::R0), round - 1);
+        }
+        // The slot of the removed breakpoint is free again
+        computer.add_breakpoint(8);
+    }
+
+    #[test]
+    #[should_panic(expected = "no room for another breakpoint")]
+    fn too_many_breakpoints() {
+        let mut computer = Computer::new([0xFF], Bus::new()).unwrap();
+        for address in 0..=8 {
+            computer.add_breakpoint(address);
+        }
+    }
+
+    #[test]
+    fn watchpoints() {
+        // r0 = 1, r1 = 2, r0 += r1, not r2, [0x40] = r0, r3 = [0x40], halt
+        let code = [0x00, 1, 0x01, 2, 0x81, 0x0E, 0x08, 0x40, 0x07, 0x40, 0xFF];
+        let mut computer = Computer::new(code, Bus::new()).unwrap();
+        computer.watch(0x40, WatchKind::Write);
+        let write = StopReason::Watchpoint {
+            addr: 0x40,
+            kind: WatchKind::Write,
+            value: 3,
+        };
+        assert_eq!(computer.run(), Ok(write));
+        assert_eq!(computer.executed(), 5);
+        assert_eq!(computer.memory(0x40), 3);
+        // Only writes are watched, the load runs through
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.register(Register::R3), 3);
+
+        let mut computer = Computer::new(code, Bus::new()).unwrap();
+        computer.watch(0x40, WatchKind::Read);
+        computer.watch(0x40, WatchKind::ReadWrite);
+        assert_eq!(computer.run(), Ok(write));
+        assert_eq!(
+            computer.step(),
+            Ok(StepOutcome::Stopped(StopReason::Watchpoint {
+                addr: 0x40,
+                kind: WatchKind::Read,
+                value: 3,
+            }))
+        );
+        computer.unwatch(0x40);
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+    }
+
+    #[test]
+    fn watch_devices() {
+        // r1 = 0xFE, r0 = [r1], [255] = r0, [255] = r0 through r2
+        let code = [0x01, 0xFE, 0xF2, 0x01, 0x08, 0xFF, 0x02, 0xFF, 0xF3, 0x02];
+        let mut input = InputStream::new([b'x']);
+        let mut console = ConsoleDevice::new(String::<4>::new());
+        let bus = Bus::new()
+            .with_device(254..=254, &mut input)
+            .with_device(255..=255, &mut console);
+        let mut computer = Computer::new(code, bus).unwrap();
+        computer.watch(0xFE, WatchKind::ReadWrite);
+        computer.watch(0xFF, WatchKind::Write);
+        let watchpoint = |addr, kind| StopReason::Watchpoint {
+            addr,
+            kind,
+            value: b'x',
+        };
+        assert_eq!(computer.run(), Ok(watchpoint(0xFE, WatchKind::Read)));
+        assert_eq!(computer.run(), Ok(watchpoint(0xFF, WatchKind::Write)));
+        assert_eq!(computer.executed(), 3);
+        assert_eq!(computer.run(), Ok(watchpoint(0xFF, WatchKind::Write)));
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(console.output(), "xx");
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        // r0 = 5, print r0 with a call and decrement it until it is 0, halt
+        let code = [
+            0x00, 5, 0xC2, 9, 0xE8, 0xC4, 12, 0xC0, 2, // main loop
+            0x08, 0xFF, 0xC3, // print r0
+            0xFF,
+        ];
+        let console = RefCell::new(ConsoleDevice::new(String::<16>::new()));
+        let mut device = SharedDevice::new(&console);
+        let bus = Bus::new().with_device(255..=255, &mut device);
+        let mut computer = Computer::new(code, bus).unwrap().with_fuel(100);
+        // Inside the second call
+        for _ in 0..8 {
+            computer.step().unwrap();
+        }
+        let snapshot = computer.snapshot();
+        assert_eq!(snapshot.state.stack_pointer, 0xF1);
+        assert_eq!(snapshot.clone(), computer.snapshot());
+        let printed = console.borrow().output().len();
+
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        let first = (computer.snapshot(), computer.state());
+        let output = console.borrow().output().clone();
+        assert_eq!(output.as_bytes(), [5, 4, 3, 2, 1]);
+
+        computer.restore(&snapshot);
+        assert_eq!(computer.snapshot(), snapshot);
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!((computer.snapshot(), computer.state()), first);
+        // The console saw the output after the snapshot twice
+        let again = console.borrow().output().clone();
+        assert_eq!(again[..output.len()], output);
+        assert_eq!(again[output.len()..], output[printed..]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_snapshot() {
+        // The program of the snapshot test, resumed from JSON by a second computer
+        let code = [
+            0x00, 5, 0xC2, 9, 0xE8, 0xC4, 12, 0xC0, 2, 0x08, 0xFF, 0xC3, 0xFF,
+        ];
+        let mut console = ConsoleDevice::new(String::<16>::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let mut computer = Computer::new(code, bus).unwrap().with_fuel(100);
+        for _ in 0..8 {
+            computer.step().unwrap();
+        }
+        let json = serde_json::to_string(&computer.snapshot()).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        let state = computer.state();
+        let executed = computer.executed();
+
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(snapshot.state.stack_pointer, 0xF1);
+        let mut resumed_console = ConsoleDevice::new(String::<16>::new());
+        let bus = Bus::new().with_device(255..=255, &mut resumed_console);
+        let mut resumed = Computer::new(code, bus).unwrap();
+        resumed.restore(&snapshot);
+        assert_eq!(resumed.run(), Ok(StopReason::Halted));
+        assert_eq!(resumed.state(), state);
+        assert_eq!(resumed.executed(), executed);
+        assert_eq!(resumed_console.output().as_bytes(), [4, 3, 2, 1]);
+        assert_eq!(console.output().as_bytes(), [5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        let code = [
+            1, b'B', // r1 = 'B'
+            9, 5, // overwrite the operand of the next instruction with r1
+            0, b'A', // r0 = 'A'
+            8, 255, // print r0
+            6, 0, // r2 = the first byte of the program
+        ];
+        let mut console = ConsoleDevice::new(String::<4>::new());
+        let mut computer =
+            Computer::new(code, Bus::new().with_device(255..=255, &mut console)).unwrap();
+        assert_eq!(computer.memory(5), b'A');
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(computer.memory(5), b'B');
+        assert_eq!(computer.register(Register::R2), 1);
+        assert_eq!(console.output(), "B");
+    }
+
+    #[test]
+    fn initial_state_overlap() {
+        // ld r0, [4]; st r0, [255]; .byte 'P'
+        let code = [0x04, 0x04, 0x08, 0xFF, b'P'];
+        let overlapping = InitialState::new().with_memory(4, b'S');
+        let error = Computer::with_initial_state(code, &overlapping, Bus::new());
+        assert_eq!(
+            error.err(),
+            Some(ExecutionError::InitialStateOverlap { addr: 4 })
+        );
+        // Cells after the program are free
+        let after = InitialState::new().with_memory(5, b'S');
+        assert!(Computer::with_initial_state(code, &after, Bus::new()).is_ok());
+
+        // When the overlap is allowed the state overrides the program byte
+        let mut console = ConsoleDevice::new(String::<1>::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let initial = overlapping.with_allow_overlap(true);
+        let mut computer = Computer::with_initial_state(code, &initial, bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(console.output(), "S");
+
+        // The code of the Harvard model has a memory of its own, so nothing overlaps
+        let mut console = ConsoleDevice::new(String::<1>::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let mut computer =
+            Computer::with_execution_model(code, &overlapping, bus, ExecutionModel::Harvard)
+                .unwrap();
+        assert_eq!(computer.memory(4), b'S');
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(console.output(), "S");
+
+        // The protected ROM copies the program into memory, an allowed overlap overrides the copy
+        // while the ROM keeps the code
+        let protected = Computer::with_execution_model(
+            code,
+            &overlapping,
+            Bus::new(),
+            ExecutionModel::ProtectedRom,
+        );
+        assert_eq!(
+            protected.err(),
+            Some(ExecutionError::InitialStateOverlap { addr: 4 })
+        );
+        let mut console = ConsoleDevice::new(String::<1>::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let mut computer =
+            Computer::with_execution_model(code, &initial, bus, ExecutionModel::ProtectedRom)
+                .unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(console.output(), "S");
+    }
+
+    #[test]
+    fn harvard_self_modification() {
+        // The same program as above, the store doesn't reach the code
+        let code = [1, b'B', 9, 5, 0, b'A', 8, 255, 6, 0];
+        let mut console = ConsoleDevice::new(String::<4>::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let initial = InitialState::new();
+        let mut computer =
+            Computer::with_execution_model(code, &initial, bus, ExecutionModel::Harvard).unwrap();
+        assert_eq!(computer.execution_model(), ExecutionModel::Harvard);
+        assert_eq!(computer.memory(5), 0);
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(computer.memory(5), b'B');
+        // Loads read the data memory, not the program
+        assert_eq!(computer.register(Register::R2), 0);
+        assert_eq!(console.output(), "A");
+    }
+
+    #[test]
+    fn protected_rom() {
+        let code = [
+            1, b'B', // r1 = 'B'
+            9, 0x80, // store r1 in the data memory
+            6, 0x80, // r2 = r1
+            7, 0x00, // r3 = the first byte of the program
+            0x0A, 255, // print r2
+        ];
+        let mut console = ConsoleDevice::new(String::<4>::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let initial = InitialState::new();
+        let mut computer =
+            Computer::with_execution_model(code, &initial, bus, ExecutionModel::ProtectedRom)
+                .unwrap();
+        assert_eq!(computer.execution_model(), ExecutionModel::ProtectedRom);
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(computer.memory(0x80), b'B');
+        // Loads see the program bytes
+        assert_eq!(computer.register(Register::R3), 1);
+        assert_eq!(console.output(), "B");
+
+        // The self modifying program can't overwrite its operand, or push into the program
+        let code = [1, b'B', 9, 5, 0, b'A', 8, 255];
+        let mut computer = Computer::with_execution_model(
+            code,
+            &initial,
+            Bus::new(),
+            ExecutionModel::ProtectedRom,
+        )
+        .unwrap();
+        assert_eq!(
+            computer.run(),
+            Err(ExecutionError::WriteToRom { offset: 2, addr: 5 })
+        );
+        assert_eq!(computer.memory(5), b'A');
+        let code = [0xC8, 0xFF]; // push r0
+        let initial = InitialState::new().with_stack_pointer(1);
+        let mut computer = Computer::with_execution_model(
+            code,
+            &initial,
+            Bus::new(),
+            ExecutionModel::ProtectedRom,
+        )
+        .unwrap();
+        assert_eq!(
+            computer.run(),
+            Err(ExecutionError::WriteToRom { offset: 0, addr: 1 })
+        );
+    }
+
+    #[test]
+    fn memory_banks() {
+        let code = [
+            0x00, 0xF1, 0xEC, // bank 1, the high bits of r0 are ignored
+            0x01, b'A', 0x09, 0x20, // store 'A' at 0x20
+            0x00, 0x02, 0xEC, // bank 2
+            0x01, b'B', 0x09, 0x20, // store 'B' at 0x20
+            0x06, 0x20, 0x0A, 0xFF, // print the byte at 0x20, the console is in every bank
+            0x00, 0x01, 0xEC, // bank 1
+            0x07, 0x20, 0x0B, 0xFF, // print the byte at 0x20
+            0x00, 0x00, 0xEC, // bank 0
+            0x05, 0x20, // r1 = the byte at 0x20
+            0xFF,
+        ];
+        let mut banks = Ram4k::new();
+        let mut console = ConsoleDevice::new(String::<4>::new());
+        let bus = Bus::new()
+            .with_banks(&mut banks)
+            .with_device(255..=255, &mut console);
+        let mut computer = Computer::new(code, bus).unwrap();
+        for _ in 0..3 {
+            computer.step().unwrap();
+        }
+        assert_eq!(computer.bank(), 1);
+        assert_eq!(computer.snapshot().bank, 1);
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.bank(), 0);
+        assert_eq!(computer.register(Register::R1), 0);
+        assert_eq!(computer.memory(0x20), 0);
+        assert_eq!(console.output(), "BA");
+        assert_eq!(
+            banks.banks().each_ref().map(|bank| bank.dump()[0x20])[..3],
+            [0, b'A', b'B']
+        );
+
+        // Without banks every bank is the memory of the bus
+        let mut computer = Computer::new(code, Bus::new()).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.register(Register::R1), b'B');
+    }
+
+    #[test]
+    fn preloaded_registers() {
+        let mut registers = Registers::new();
+        registers.set_all([200, 100, 0, 0, 0, 0, 0, 7]);
+        let initial = InitialState::new().with_registers(registers.as_bytes());
+        // add r1, r0
+        let mut computer = Computer::with_initial_state([0x84], &initial, Bus::new()).unwrap();
+        computer.step().unwrap();
+        registers.set_all(computer.registers());
+        assert_eq!(registers.as_bytes(), [200, 44, 0, 0, 0, 0, 0, 7]);
+        assert!(computer.overflow());
+        registers.reset();
+        assert_eq!(registers, Registers::new());
+    }
+
+    #[test]
+    fn copy() {
+        // copy r1, r2 with the source block "ABCDE" at 0x80
+        let run = |destination, count| {
+            let initial = b"ABCDE"
+                .iter()
+                .zip(0x80..)
+                .fold(InitialState::new(), |initial, (&value, address)| {
+                    initial.with_memory(address, value)
+                })
+                .with_registers([count, destination, 0x80, 0]);
+            let mut computer =
+                Computer::with_initial_state([0xF7, 0x06, 0xFF], &initial, Bus::new()).unwrap();
+            assert_eq!(computer.run(), Ok(StopReason::Halted));
+            assert_eq!(computer.registers()[..3], [count, destination, 0x80]);
+            computer.state().memory
+        };
+        // Forward overlap, the destination is before the source
+        assert_eq!(&run(0x7E, 5)[0x7E..0x85], b"ABCDEDE");
+        // Backward overlap, the destination is inside the source
+        assert_eq!(&run(0x82, 5)[0x80..0x87], b"ABABCDE");
+        // Nothing is copied for a count of 0
+        assert_eq!(&run(0x7E, 0)[0x7E..0x85], b"\0\0ABCDE");
+
+        // The destination wraps around from the console at 0xFF to the start of memory
+        let initial = InitialState::new()
+            .with_memory(0x80, b'A')
+            .with_memory(0x81, b'B')
+            .with_memory(0x82, b'C')
+            .with_registers([3, 0xFF, 0x80, 0]);
+        let mut console = ConsoleDevice::new(String::<4>::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let mut computer = Computer::with_initial_state([0xF7, 0x06, 0xFF], &initial, bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!([computer.memory(0), computer.memory(1)], [b'B', b'C']);
+        assert_eq!(console.output(), "A");
+    }
+
+    #[test]
+    fn decimal_adjust() {
+        let code = [
+            0x00, 0x45, 0x01, 0x38, // r0 = 0x45, r1 = 0x38
+            0x81, 0xFC, 0x00, // r0 += r1, adjust r0 to 0x83
+            0x14, 0xD5, 0xD5, 0xD5, 0xD5, // r1 = r0 >> 4
+            0x02, b'0', 0x86, 0x09, 0xFF, // print r1 + '0'
+            0x03, 0x0F, 0x33, 0x82, 0x08, 0xFF, // print (r0 & 0x0F) + '0'
+            0xFF,
+        ];
+        let mut console = ConsoleDevice::new(String::<4>::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let mut computer = Computer::new(code, bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(console.output(), "83");
+
+        // add r0, r1; daa r0, the half carry corrects 9 + 9 and the decimal carry is the overflow
+        let run = |left, right| {
+            let initial = InitialState::new().with_registers([left, right, 0, 0]);
+            let mut computer =
+                Computer::with_initial_state([0x81, 0xFC, 0x00], &initial, Bus::new()).unwrap();
+            computer.run().unwrap();
+            (
+                computer.registers()[0],
+                computer.overflow(),
+                computer.zero(),
+            )
+        };
+        assert_eq!(run(0x09, 0x09), (0x18, false, false));
+        assert_eq!(run(0x99, 0x01), (0x00, true, true));
+        assert_eq!(run(0x58, 0x67), (0x25, true, false));
+    }
+
+    #[test]
+    fn both_models() {
+        let expected = b"Hi!";
+        let mut hello = [0; 12];
+        for (chunk, &character) in hello.chunks_mut(4).zip(expected) {
+            chunk.copy_from_slice(&[0, character, 8, 255]);
+        }
+        // r1 = 200 + 100, stored at 0x00, which holds the program under Von Neumann
+        let arithmetic = [1, 200, 2, 100, 0x86, 9, 0x00];
+        for model in [ExecutionModel::VonNeumann, ExecutionModel::Harvard] {
+            let program = hello.iter().chain(&arithmetic).copied();
+            let mut console = ConsoleDevice::new(String::<4>::new());
+            let bus = Bus::new().with_device(255..=255, &mut console);
+            let mut computer =
+                Computer::with_execution_model(program, &InitialState::new(), bus, model).unwrap();
+            assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+            assert_eq!(computer.register(Register::R1), 44);
+            assert!(computer.overflow());
+            assert_eq!(computer.memory(0), 44);
+            assert_eq!(console.output().as_bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn stays_halted() {
+        let code = [0, 7, 0xFF, 1, 8];
+        let mut computer = Computer::new(code, Bus::new()).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.program_counter(), 3);
+        assert_eq!(
+            computer.step(),
+            Ok(StepOutcome::Stopped(StopReason::Halted))
+        );
+        assert_eq!(computer.register(Register::R0), 7);
+        assert_eq!(computer.register(Register::R1), 0);
+    }
+
+    #[test]
+    fn overflow_accessor() {
+        let code = [0, 200, 1, 100, 0x81];
+        let mut computer = Computer::new(code, Bus::new()).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(computer.register(Register::R0), 44);
+        assert!(computer.overflow());
+    }
+
+    #[test]
+    fn register_shifts() {
+        type Operation = fn(u8) -> u8;
+        let operations: [(u8, Operation, u8); 4] = [
+            (0xD1, |value| value.wrapping_shl(1), 0x80),
+            (0xD5, |value| value.wrapping_shr(1), 0x01),
+            (0xD9, |value| value.rotate_left(1), 0x80),
+            (0xDD, |value| value.rotate_right(1), 0x01),
+        ];
+        for value in 0..=u8::MAX {
+            for (opcode, expected, shifted_out) in operations {
+                // The previous overflow never reaches the result
+                let initial = InitialState::new()
+                    .with_registers([0, value, 0, 0])
+                    .with_overflow(true);
+                let mut computer =
+                    Computer::with_initial_state([opcode], &initial, Bus::new()).unwrap();
+                assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+                assert_eq!(computer.registers(), [0, expected(value), 0, 0, 0, 0, 0, 0]);
+                assert_eq!(computer.overflow(), value & shifted_out != 0);
+            }
+        }
+    }
+
+    #[test]
+    fn increment_decrement() {
+        // inc r0, dec r1, dec r2
+        let program = [0xE4, 0xE9, 0xEA];
+        let initial = InitialState::new().with_registers([0xFF, 0, 1, 0]);
+        let mut computer = Computer::with_initial_state(program, &initial, Bus::new()).unwrap();
+        computer.step().unwrap();
+        assert_eq!(
+            (computer.registers(), computer.overflow()),
+            ([0, 0, 1, 0, 0, 0, 0, 0], true)
+        );
+        computer.step().unwrap();
+        assert_eq!(
+            (computer.registers(), computer.overflow()),
+            ([0, 0xFF, 1, 0, 0, 0, 0, 0], true)
+        );
+        computer.step().unwrap();
+        assert_eq!(
+            (computer.registers(), computer.overflow()),
+            ([0, 0xFF, 0, 0, 0, 0, 0, 0], false)
+        );
+
+        // A word carries into the high byte and wraps at 0xFFFF
+        let mut computer = Computer::<Word>::load_program(
+            program,
+            &initial,
+            Bus::new(),
+            ExecutionModel::VonNeumann,
+        )
+        .unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(
+            (computer.registers(), computer.overflow()),
+            ([0x100, 0xFFFF, 0, 0, 0, 0, 0, 0], false)
+        );
+    }
+
+    #[test]
+    fn zero_and_negative_flags() {
+        // The opcodes of nand, and, xor, add and sub of r0 and r1, and the expected results
+        type Operation = fn(u8, u8) -> u8;
+        let operations: [(u8, Operation); 5] = [
+            (0x21, |a, b| !(a & b)),
+            (0x31, |a, b| a & b),
+            (0x71, |a, b| a ^ b),
+            (0x81, u8::wrapping_add),
+            (0xA1, u8::wrapping_sub),
+        ];
+        for (opcode, operation) in operations {
+            for left in 0..=u8::MAX {
+                for right in [0, 1, 0x7F, 0x80, 0xFF, left, left.wrapping_neg()] {
+                    let initial = InitialState::new().with_registers([left, right, 0, 0]);
+                    let mut computer =
+                        Computer::with_initial_state([opcode], &initial, Bus::new()).unwrap();
+                    computer.step().unwrap();
+                    let result = operation(left, right);
+                    assert_eq!(computer.register(Register::R0), result);
+                    assert_eq!(
+                        (computer.zero(), computer.negative()),
+                        (result == 0, result >= 0x80),
+                        "{opcode:#04X} {left:#04X} {right:#04X}"
+                    );
+                }
+            }
+        }
+
+        // A word is negative from bit 15 and zero if both bytes are
+        for (left, right, zero, negative) in [(0x7F, 1, false, false), (0xFF, 0xFF, false, false)] {
+            let initial = InitialState::new().with_registers([left, right, 0, 0]);
+            let mut computer = Computer::<Word>::load_program(
+                [0x81],
+                &initial,
+                Bus::new(),
+                ExecutionModel::VonNeumann,
+            )
+            .unwrap();
+            computer.step().unwrap();
+            assert_eq!((computer.zero(), computer.negative()), (zero, negative));
+        }
+        let initial = InitialState::new().with_registers([0, 1, 0, 0]);
+        let mut computer = Computer::<Word>::load_program(
+            [0xA1],
+            &initial,
+            Bus::new(),
+            ExecutionModel::VonNeumann,
+        )
+        .unwrap();
+        computer.step().unwrap();
+        assert_eq!(computer.registers()[0], 0xFFFF);
+        assert!(!computer.zero() && computer.negative());
+    }
+
+    #[test]
+    fn flags_kept_by_moves() {
+        // not r0, ldi r1 0, mov r2 r1, st r1 [0x40], ld r3 [0x40], push r1, xor r1 r1
+        let program = [0x0C, 0x01, 0x00, 0x19, 0x09, 0x40, 0x07, 0x40, 0xC9, 0x75];
+        let initial = InitialState::new().with_registers([0x7F, 0, 0, 0]);
+        let mut computer = Computer::with_initial_state(program, &initial, Bus::new()).unwrap();
+        for _ in 0..6 {
+            computer.step().unwrap();
+            assert!(!computer.zero() && computer.negative());
+        }
+        assert_eq!(computer.registers(), [0x80, 0, 0, 0, 0, 0, 0, 0]);
+        computer.step().unwrap();
+        let state = computer.state();
+        assert!(state.zero && !state.negative);
+    }
+
+    #[test]
+    fn add_16_bits() {
+        // clc, r0 += r2, r1 += r3 + overflow
+        let program = [0xF1, 0x82, 0x97];
+        for (left, right) in [(0x00FF_u16, 0x0001_u16), (0x1234, 0xEDCC), (0xABCD, 0x1111)] {
+            let [left_low, left_high] = left.to_le_bytes();
+            let [right_low, right_high] = right.to_le_bytes();
+            let initial = InitialState::new()
+                .with_registers([left_low, left_high, right_low, right_high])
+                .with_overflow(true);
+            let mut computer = Computer::with_initial_state(program, &initial, Bus::new()).unwrap();
+            computer.step().unwrap();
+            assert!(!computer.overflow());
+            assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+            let [low, high, ..] = computer.registers();
+            assert_eq!(u16::from_le_bytes([low, high]), left.wrapping_add(right));
+            assert_eq!(computer.overflow(), left.checked_add(right).is_none());
+        }
+
+        // stc, r0 += r1 + overflow
+        let initial = InitialState::new().with_registers([1, 2, 0, 0]);
+        let mut computer =
+            Computer::with_initial_state([0xF0, 0x91], &initial, Bus::new()).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert_eq!(computer.registers(), [4, 2, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn multiply() {
+        // The circuit is tested for all pairs with the byte, this checks the wiring
+        for left in 0..=u8::MAX {
+            for right in [0, 1, 2, 3, 0x10, 0x55, 0x80, 0xFF] {
+                let initial = InitialState::new().with_registers([0, left, right, 0]);
+                // r1 *= r2
+                let mut computer =
+                    Computer::with_initial_state([0xF4, 0x06], &initial, Bus::new()).unwrap();
+                assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+                assert_eq!(
+                    computer.registers(),
+                    [0, left.wrapping_mul(right), right, 0, 0, 0, 0, 0]
+                );
+                assert_eq!(computer.overflow(), left.checked_mul(right).is_none());
+
+                // The product of 2 bytes fits in a word
+                let mut computer = Computer::<Word>::load_program(
+                    [0xF4, 0x06],
+                    &initial,
+                    Bus::new(),
+                    ExecutionModel::VonNeumann,
+                )
+                .unwrap();
+                assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+                let product = u16::from(left) * u16::from(right);
+                assert_eq!(
+                    computer.registers(),
+                    [0, product, right.into(), 0, 0, 0, 0, 0]
+                );
+                assert!(!computer.overflow());
+            }
+        }
+    }
+
+    #[test]
+    fn divide() {
+        for left in 0..=u8::MAX {
+            for right in [0, 1, 2, 3, 7, 0x10, 0x55, 0xFF] {
+                for (opcode, expected) in [
+                    (0xF5, left.checked_div(right)),
+                    (0xF6, left.checked_rem(right)),
+                ] {
+                    let initial = InitialState::new()
+                        .with_registers([0, 0, left, right])
+                        .with_overflow(right != 0);
+                    // r2 /= r3 or r2 %= r3
+                    let mut computer =
+                        Computer::with_initial_state([opcode, 0x0B], &initial, Bus::new()).unwrap();
+                    assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+                    let by_zero = if opcode == 0xF5 { 0xFF } else { left };
+                    assert_eq!(
+                        computer.registers(),
+                        [0, 0, expected.unwrap_or(by_zero), right, 0, 0, 0, 0]
+                    );
+                    assert_eq!(computer.overflow(), right == 0);
+
+                    let mut computer = Computer::<Word>::load_program(
+                        [opcode, 0x0B],
+                        &initial,
+                        Bus::new(),
+                        ExecutionModel::VonNeumann,
+                    )
+                    .unwrap();
+                    assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+                    let by_zero = if opcode == 0xF5 { 0xFFFF } else { left.into() };
+                    let expected = expected.map_or(by_zero, u16::from);
+                    assert_eq!(
+                        computer.registers(),
+                        [0, 0, expected, right.into(), 0, 0, 0, 0]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn copy_buffer_indirect() {
+        let buffer = *b"0123456789";
+        let code = [
+            1, 0xA0, // r1 = source
+            2, 0xC0, // r2 = destination
+            3, 10, // r3 = count
+            0xF2, 0x01, // loop: r0 = [r1]
+            0xF3, 0x02, // [r2] = r0
+            0, 1,    // r0 = 1
+            0x84, // r1 += r0
+            0x88, // r2 += r0
+            0xAC, // r3 -= r0
+            0xC7, 19, // jump to the end if r3 is zero
+            0xC0, 6, // jump to loop
+        ];
+        let initial = (0xA0..)
+            .zip(buffer)
+            .fold(InitialState::new(), |initial, (address, value)| {
+                initial.with_memory(address, value)
+            });
+        let mut computer = Computer::with_initial_state(code, &initial, Bus::new()).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        assert!((0xC0..0xCA)
+            .map(|address| computer.memory(address))
+            .eq(buffer));
+        assert_eq!(computer.memory(0xCA), 0);
+        assert_eq!(computer.registers(), [1, 0xAA, 0xCA, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn push_pop_round_trip() {
+        let code = [
+            0, 1, 1, 2, 2, 3, // r0 = 1, r1 = 2, r2 = 3
+            0xC8, 0xC9, 0xCA, // push r0, r1, r2
+            0xCF, 0xCF, 0xCF, // pop r3 three times
+            0xCC, // pop r0
+        ];
+        let mut computer = Computer::new(code, Bus::new()).unwrap();
+        for _ in 0..6 {
+            assert_eq!(computer.step(), Ok(StepOutcome::Executed));
+        }
+        assert_eq!(computer.stack_pointer(), 0xF3);
+        assert!((0xF0..0xF3)
+            .map(|address| computer.memory(address))
+            .eq([1, 2, 3]));
+        for expected in [3, 2, 1] {
+            assert_eq!(computer.step(), Ok(StepOutcome::Executed));
+            assert_eq!(computer.register(Register::R3), expected);
+        }
+        assert_eq!(computer.stack_pointer(), 0xF0);
+        assert_eq!(
+            computer.run(),
+            Err(ExecutionError::StackUnderflow { offset: 12 })
+        );
+    }
+
+    #[test]
+    fn stack_overflow() {
+        // The stack fills up to the input at 0xFE
+        let initial = InitialState::new().with_stack_pointer(0xFB);
+        let mut computer = Computer::with_initial_state([0xC8; 4], &initial, Bus::new()).unwrap();
+        assert_eq!(
+            computer.run(),
+            Err(ExecutionError::StackOverflow { offset: 3 })
+        );
+        assert_eq!(computer.stack_pointer(), 0xFE);
+
+        // Pops can't go below the configured start
+        let initial = InitialState::new().with_stack_pointer(0x80);
+        let mut computer =
+            Computer::with_initial_state([0xC8, 0xCD, 0xCD], &initial, Bus::new()).unwrap();
+        assert_eq!(
+            computer.run(),
+            Err(ExecutionError::StackUnderflow { offset: 2 })
+        );
+        assert_eq!(computer.stack_pointer(), 0x80);
+    }
+
+    #[test]
+    fn call_subroutine() {
+        let code = [
+            0, b'a', // r0 = 'a'
+            0xC2, 13, // call print_char
+            0, b'b', // r0 = 'b'
+            0xC2, 13, // call print_char
+            0, b'c', // r0 = 'c'
+            0xC2, 13,   // call print_char
+            0xFF, // halt
+            8, 255,  // print_char: print r0
+            0xC3, // return
+        ];
+        let mut console = ConsoleDevice::new(String::<4>::new());
+        let mut computer =
+            Computer::new(code, Bus::new().with_device(255..=255, &mut console)).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.stack_pointer(), 0xF0);
+        assert_eq!(console.output(), "abc");
+    }
+
+    #[test]
+    fn nested_calls() {
+        let code = [
+            0, b'x', // r0 = 'x'
+            0xC2, 7, // call print_twice
+            8, 255,  // print r0
+            0xFF, // halt
+            0xC2, 15, // print_twice: call print
+            1, 1,    // r1 = 1
+            0x81, // r0 += r1
+            0xC2, 15,   // call print
+            0xC3, // return
+            8, 255,  // print: print r0
+            0xC3, // return
+        ];
+        let mut console = ConsoleDevice::new(String::<4>::new());
+        let mut computer =
+            Computer::new(code, Bus::new().with_device(255..=255, &mut console)).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        // Both return addresses were on the stack at the deepest point
+        assert_eq!([computer.memory(0xF0), computer.memory(0xF1)], [4, 14]);
+        assert_eq!(computer.stack_pointer(), 0xF0);
+        assert_eq!(console.output(), "xyy");
+
+        let mut computer = Computer::new([0xC3], Bus::new()).unwrap();
+        assert_eq!(
+            computer.run(),
+            Err(ExecutionError::StackUnderflow { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn exhaust_fuel() {
+        // Operands aren't charged, the three instructions take exactly the fuel
+        let code = [0, 1, 0x10, 8, 0x10, 0xFF];
+        let mut computer = Computer::new(code, Bus::new()).unwrap().with_fuel(3);
+        assert_eq!(computer.run(), Ok(StopReason::OutOfFuel { executed: 3 }));
+        assert_eq!(computer.fuel(), Some(0));
+        assert_eq!(computer.memory(0x10), 1);
+        computer.set_fuel(Some(1));
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.executed(), 4);
+    }
+
+    #[test]
+    fn fuel_left_over() {
+        let code = [0, 1, 0xFF];
+        let mut computer = Computer::new(code, Bus::new()).unwrap().with_fuel(10);
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.executed(), 2);
+        assert_eq!(computer.fuel(), Some(8));
+    }
+
+    #[test]
+    fn fuel_stops_infinite_loop() {
+        let mut computer = Computer::new([0xC0, 0], Bus::new()).unwrap().with_fuel(100);
+        assert_eq!(computer.run(), Ok(StopReason::OutOfFuel { executed: 100 }));
+        // The end of the program takes no fuel
+        let mut computer = Computer::new([0x10], Bus::new()).unwrap().with_fuel(1);
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+    }
+}
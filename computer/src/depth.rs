@@ -0,0 +1,265 @@
+//! Critical path depth of circuits, counted in nand gates.
+//!
+//! A [`Wire`] carries a bit together with the length of the longest chain of nand gates from any
+//! input to it. Wires get their gates from the same constructions as [`Bit`], and circuits written
+//! for any [`Gates`](crate::bit::Gates) run on wires, so the depth of a wire is the number of gate delays before the
+//! bit settles in the circuit of the library:
+//!
+//! ```
+//! use michael_computer::{bit::Bit, depth::Wire};
+//!
+//! let (sum, carry) = Wire::input(Bit::High).half_adder(Wire::input(Bit::Low));
+//! assert_eq!((sum.bit(), sum.depth()), (Bit::High, 4));
+//! assert_eq!((carry.bit(), carry.depth()), (Bit::Low, 2));
+//! ```
+//!
+//! [`analyze_depth`] captures the outputs of a circuit in a [`Netlist`], to compare circuits
+//! computing the same function. The carry lookahead adder computes every carry from the inputs
+//! instead of the carry before it, so it settles much sooner than the ripple adder:
+//!
+//! ```
+//! use michael_computer::{
+//!     bit::Bit,
+//!     byte::ripple_add,
+//!     depth::{analyze_depth, ByteAdder, Wire},
+//! };
+//!
+//! let add = |add: ByteAdder| {
+//!     analyze_depth(|| {
+//!         let (sum, carry) = add(Wire::inputs(0x5A), Wire::inputs(0xC3), Wire::input(Bit::Low));
+//!         [sum[0], sum[1], sum[2], sum[3], sum[4], sum[5], sum[6], sum[7], carry]
+//!     })
+//! };
+//! assert!(add(Wire::lookahead_add).depth() < add(ripple_add).depth());
+//! ```
+
+use core::array;
+
+use crate::bit::{gate, nand_gates, Bit};
+
+/// An adder of 2 bytes of wires and a carry, like [`ripple_add`](crate::byte::ripple_add), returning the
+/// sum and the carry out
+pub type ByteAdder = fn([Wire; 8], [Wire; 8], Wire) -> ([Wire; 8], Wire);
+
+/// A bit and the number of nand gates on the longest path leading to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wire {
+    bit: Bit,
+    depth: u32,
+}
+
+impl Wire {
+    /// Creates an input of a circuit, it doesn't go through any gate
+    pub const fn input(bit: Bit) -> Self {
+        Self { bit, depth: 0 }
+    }
+
+    /// Returns the bit on the wire
+    pub const fn bit(self) -> Bit {
+        self.bit
+    }
+
+    /// Returns the number of nand gates on the longest path leading to the wire
+    pub const fn depth(self) -> u32 {
+        self.depth
+    }
+
+    gate! {
+        /// The nand gate, one deeper than the deepest input
+        pub fn nand(self, other: Self) -> Self {
+            Self {
+                bit: self.bit.nand_uncounted(other.bit),
+                depth: if self.depth > other.depth {
+                    self.depth
+                } else {
+                    other.depth
+                } + 1,
+            }
+        }
+    }
+
+    /// Adds 2 bytes of wires, lowest bit first, with a carry lookahead adder. Every carry is the
+    /// or of the bits generating a carry below it, anded with the bits propagating it, with the
+    /// gates combined in balanced trees. Returns the sum and the carry out.
+    pub fn lookahead_add(left: [Self; 8], right: [Self; 8], carry: Self) -> ([Self; 8], Self) {
+        let generate: [Self; 8] = array::from_fn(|i| left[i].and(right[i]));
+        let propagate: [Self; 8] = array::from_fn(|i| left[i].xor(right[i]));
+        // The carry into bit i, with a term for every lower bit generating it and the carry in
+        let carries: [Self; 9] = array::from_fn(|i| {
+            let terms: [Self; 9] = array::from_fn(|j| match j {
+                0 => Self::balanced(&propagate[..i], Self::and).map_or(carry, |all| all.and(carry)),
+                _ if j <= i => Self::balanced(&propagate[j..i], Self::and)
+                    .map_or(generate[j - 1], |all| all.and(generate[j - 1])),
+                _ => carry,
+            });
+            Self::balanced(&terms[..=i], Self::or).unwrap_or(carry)
+        });
+        (array::from_fn(|i| propagate[i].xor(carries[i])), carries[8])
+    }
+
+    /// Combines the wires with the gate in a balanced tree, returns `None` without wires
+    fn balanced(wires: &[Self], gate: fn(Self, Self) -> Self) -> Option<Self> {
+        match wires {
+            [] => None,
+            [wire] => Some(*wire),
+            _ => {
+                let (low, high) = wires.split_at(wires.len() / 2);
+                Some(gate(
+                    Self::balanced(low, gate)?,
+                    Self::balanced(high, gate)?,
+                ))
+            }
+        }
+    }
+
+    /// Creates the inputs for the bits of the byte, lowest bit first
+    pub fn inputs(byte: u8) -> [Self; 8] {
+        array::from_fn(|i| Self::input(Bit::from((byte >> i) & 1 == 1)))
+    }
+}
+
+nand_gates!(Wire);
+
+/// The outputs of a circuit with the depth of the paths leading to them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Netlist<const N: usize> {
+    outputs: [Wire; N],
+}
+
+impl<const N: usize> Netlist<N> {
+    /// Returns the outputs of the circuit
+    pub const fn outputs(&self) -> &[Wire; N] {
+        &self.outputs
+    }
+
+    /// Returns the number of nand gates on the longest path from any input to any output
+    pub fn depth(&self) -> u32 {
+        critical_path(&self.outputs)
+    }
+
+    /// Returns the depth of every output
+    pub fn output_depths(&self) -> [u32; N] {
+        self.outputs.map(Wire::depth)
+    }
+}
+
+/// Builds the circuit the closure returns the outputs of and captures them in a netlist
+pub fn analyze_depth<const N: usize>(circuit: impl FnOnce() -> [Wire; N]) -> Netlist<N> {
+    Netlist { outputs: circuit() }
+}
+
+/// Returns the depth of the circuit with the outputs, the longest path to any of them
+pub fn critical_path(outputs: &[Wire]) -> u32 {
+    outputs.iter().map(|wire| wire.depth).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze_depth, critical_path, ByteAdder, Wire};
+    use crate::{
+        bit::Bit,
+        byte::{ripple_add, Byte},
+    };
+
+    #[test]
+    fn gates_match_bits() {
+        for (a, b, c) in [Bit::Low, Bit::High]
+            .into_iter()
+            .flat_map(|a| [(a, Bit::Low), (a, Bit::High)])
+            .flat_map(|(a, b)| [(a, b, Bit::Low), (a, b, Bit::High)])
+        {
+            let (x, y, z) = (Wire::input(a), Wire::input(b), Wire::input(c));
+            assert_eq!(x.nor(y).bit(), a.nor(b));
+            assert_eq!(x.xnor(y).bit(), a.xnor(b));
+            let (sum, carry) = x.half_adder(y);
+            assert_eq!((sum.bit(), carry.bit()), a.half_adder(b));
+            let (sum, carry) = x.full_adder(y, z);
+            assert_eq!((sum.bit(), carry.bit()), a.full_adder(b, c));
+        }
+    }
+
+    #[test]
+    fn ripple_add_matches_bytes() {
+        for (left, right) in (0..=u8::MAX).flat_map(|left| [(left, 0), (left, 0x5A), (left, 0xFF)])
+        {
+            let (sum, carry) = ripple_add(
+                Wire::inputs(left),
+                Wire::inputs(right),
+                Wire::input(Bit::Low),
+            );
+            let (expected, expected_carry) =
+                Byte::from(left).add_with_carry(Byte::from(right), Bit::Low);
+            assert_eq!(u8::from(Byte::from(sum.map(Wire::bit))), u8::from(expected));
+            assert_eq!(carry.bit(), expected_carry);
+        }
+    }
+
+    #[test]
+    fn depths() {
+        let input = Wire::input(Bit::Low);
+        assert_eq!(critical_path(&[]), 0);
+        assert_eq!(critical_path(&[input.not()]), 1);
+        assert_eq!(critical_path(&[input.and(input)]), 2);
+        assert_eq!(critical_path(&[input.xor(input)]), 4);
+
+        let (sum, carry) = input.half_adder(input);
+        assert_eq!((sum.depth(), carry.depth()), (4, 2));
+        assert_eq!(critical_path(&[sum, carry]), 4);
+
+        let (sum, carry) = input.full_adder(input, input);
+        assert_eq!((sum.depth(), carry.depth()), (8, 6));
+
+        // Every full adder after the first adds 4 gates to the carry chain
+        let (sum, carry) = ripple_add(Wire::inputs(0), Wire::inputs(0), input);
+        assert!(sum.map(Wire::depth).eq(&[8, 10, 14, 18, 22, 26, 30, 34]));
+        assert_eq!(carry.depth(), 34);
+        let mut outputs = [input; 9];
+        outputs[..8].copy_from_slice(&sum);
+        outputs[8] = carry;
+        assert_eq!(critical_path(&outputs), 34);
+    }
+
+    #[test]
+    fn lookahead_add_matches_bytes() {
+        for (left, right, carry) in (0..=u8::MAX).flat_map(|left| {
+            (0..=u8::MAX).flat_map(move |right| [(left, right, Bit::Low), (left, right, Bit::High)])
+        }) {
+            let (sum, carry_out) =
+                Wire::lookahead_add(Wire::inputs(left), Wire::inputs(right), Wire::input(carry));
+            let (expected, expected_carry) =
+                Byte::from(left).add_with_carry(Byte::from(right), carry);
+            assert_eq!(u8::from(Byte::from(sum.map(Wire::bit))), u8::from(expected));
+            assert_eq!(carry_out.bit(), expected_carry);
+        }
+    }
+
+    #[test]
+    fn netlist_depths() {
+        let input = Wire::input(Bit::Low);
+        let half_adder = analyze_depth(|| input.half_adder(input).into());
+        assert_eq!(half_adder.depth(), 4);
+        assert_eq!(half_adder.output_depths(), [4, 2]);
+        let full_adder = analyze_depth(|| input.full_adder(input, input).into());
+        assert_eq!(full_adder.depth(), 8);
+
+        let add = |add: ByteAdder| {
+            analyze_depth(|| {
+                let (sum, carry) = add(Wire::inputs(0), Wire::inputs(0), input);
+                let mut outputs = [input; 9];
+                outputs[..8].copy_from_slice(&sum);
+                outputs[8] = carry;
+                outputs
+            })
+        };
+        let ripple = add(ripple_add);
+        assert_eq!(ripple.depth(), 34);
+        let lookahead = add(Wire::lookahead_add);
+        // The propagate bits take 4 gates, the balanced trees of the carries up to 14 more and
+        // the sum another 4, against 4 per bit in the carry chain of the ripple adder
+        assert_eq!(
+            lookahead.output_depths(),
+            [8, 12, 14, 18, 18, 20, 22, 22, 18]
+        );
+        assert_eq!(lookahead.depth(), 22);
+    }
+}
@@ -0,0 +1,149 @@
+//! A 16×16 one-bit framebuffer mapped into the memory bus, with a host side view of the pixels.
+//!
+//! A program stores the index of a framebuffer byte at the control address, then the pixels at the
+//! data address. Every row is 2 bytes, the left one first, and bit 7 of a byte is its leftmost
+//! pixel. The index moves to the next byte after every data store, so consecutive stores fill a
+//! row and continue with the next one.
+//!
+//! ```
+//! use michael_computer::{bus::Bus, display::DisplayDevice, Computer};
+//!
+//! let mut display = DisplayDevice::new(0xF0);
+//! // Select byte 2, the left half of row 1, and light its leftmost pixel
+//! let code = [0x00, 2, 0x08, 0xF0, 0x00, 0x80, 0x08, 0xF1];
+//! let bus = Bus::new().with_device(0xF0..=0xF1, &mut display);
+//! Computer::new(code, bus).unwrap().run().unwrap();
+//! assert!(bool::from(display.frame()[1][0]));
+//! ```
+
+use core::{
+    array,
+    fmt::{self, Write},
+};
+
+use crate::{bit::Bit, bus::Device, byte::Byte};
+
+/// The number of pixels in a row and of rows
+pub const SIZE: usize = 16;
+
+/// The number of bytes of the framebuffer
+const BYTES: u8 = 32;
+
+/// A 16×16 one-bit framebuffer, with the control at the base address and the data after it
+pub struct DisplayDevice {
+    base: u8,
+    index: u8,
+    bytes: [Byte; BYTES as usize],
+}
+
+impl DisplayDevice {
+    /// Creates a cleared display with the control at `base` and the data at `base + 1`
+    ///
+    /// # Panics
+    /// Panics if `base` is 255, leaving no address for the data
+    pub fn new(base: u8) -> Self {
+        assert!(base < u8::MAX, "no address for the display data");
+        Self {
+            base,
+            index: 0,
+            bytes: [Byte::from(0); BYTES as usize],
+        }
+    }
+
+    /// Returns the pixels by row, `Bit::High` for a lit pixel
+    pub fn frame(&self) -> [[Bit; SIZE]; SIZE] {
+        array::from_fn(|row| {
+            let left: [Bit; 8] = self.bytes[row * 2].into();
+            let right: [Bit; 8] = self.bytes[row * 2 + 1].into();
+            array::from_fn(|x| if x < 8 { left[7 - x] } else { right[15 - x] })
+        })
+    }
+
+    /// Draws the framebuffer as 16 lines, with `#` for a lit pixel and `.` otherwise
+    ///
+    /// # Errors
+    /// Returns an error if writing to `out` failed
+    pub fn render(&self, out: &mut impl Write) -> fmt::Result {
+        for row in self.frame() {
+            for pixel in row {
+                out.write_char(if bool::from(pixel) { '#' } else { '.' })?;
+            }
+            out.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+impl Device for DisplayDevice {
+    fn read(&mut self, address: Byte) -> Option<Byte> {
+        match u8::from(address).wrapping_sub(self.base) {
+            0 => Some(self.index.into()),
+            1 => Some(self.bytes[usize::from(self.index)]),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: Byte, value: Byte) -> bool {
+        match u8::from(address).wrapping_sub(self.base) {
+            // Indexes past the framebuffer wrap around
+            0 => self.index = u8::from(value) % BYTES,
+            1 => {
+                self.bytes[usize::from(self.index)] = value;
+                self.index = (self.index + 1) % BYTES;
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::String;
+
+    use super::DisplayDevice;
+    use crate::{bus::Bus, Computer, StopReason};
+
+    #[test]
+    fn diagonal_line() {
+        let code = [
+            0x01, 0, 0x09, 0xF0, // start at byte 0, r1 = 0
+            0x00, 0x80, 0x08, 0xF1, 0x09, 0xF1, 0xD4, 0xC4, 15, 0xC0, 6, // left halves
+            0x00, 0x80, 0x09, 0xF1, 0x08, 0xF1, 0xD4, 0xC4, 26, 0xC0, 17, // right halves
+            0xFF,
+        ];
+        let mut display = DisplayDevice::new(0xF0);
+        let bus = Bus::new().with_device(0xF0..=0xF1, &mut display);
+        let mut computer = Computer::new(code, bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+
+        let mut text = String::<{ 17 * 16 }>::new();
+        display.render(&mut text).unwrap();
+        let mut lines = text.lines();
+        for row in 0..16 {
+            let line = lines.next().unwrap();
+            assert_eq!(line.find('#'), Some(row), "{line}");
+            assert_eq!(line.matches('#').count(), 1, "{line}");
+        }
+        assert_eq!(lines.next(), None);
+        assert!(text.starts_with("#...............\n.#..............\n"));
+        assert!(text.ends_with("..............#.\n...............#\n"));
+
+        let frame = display.frame();
+        assert!((0..16).all(|i| (0..16).all(|j| bool::from(frame[i][j]) == (i == j))));
+    }
+
+    #[test]
+    fn control_and_reads() {
+        let mut display = DisplayDevice::new(0x40);
+        let bus = Bus::new().with_device(0x40..=0x41, &mut display);
+        // Select the last byte, store 0x01 and wrap around to 0, read back the index and byte 0
+        let code = [
+            0x00, 31, 0x08, 0x40, 0x00, 0x01, 0x08, 0x41, 0x05, 0x40, 0x06, 0x41, 0xFF,
+        ];
+        let mut computer = Computer::new(code, bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.registers()[1..3], [0, 0]);
+        assert!(bool::from(display.frame()[15][15]));
+    }
+}
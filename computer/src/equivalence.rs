@@ -0,0 +1,496 @@
+//! Checks that two programs have the same observable effects.
+//!
+//! This is meant for testing program transformations like optimizer passes: the original and the
+//! transformed program are both executed from a set of initial states, and the observable effects
+//! are compared.
+//!
+//! A program failing to execute is an observable effect as well, the effects it had until it
+//! failed are compared like those of a program that stopped.
+//!
+//! The programs are loaded into memory, so the memory cells holding the code of either program are
+//! neither initialized by the states nor compared.
+
+use core::{array, fmt, mem};
+
+use crate::{
+    bus::{Bus, ConsoleDevice},
+    byte::Byte,
+    mux::byte::REGISTER_COUNT,
+    state::InitialState,
+    Computer, ExecutionError,
+};
+
+/// The number of output bytes that are kept to locate a difference
+const CAPTURED_OUTPUT: usize = 256;
+
+/// Selects which effects of a program are compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Observables {
+    /// The bytes written to the output port
+    pub output: bool,
+
+    /// The final values of the registers and the overflow flag
+    pub registers: bool,
+
+    /// The final contents of memory after the code of both programs
+    pub memory: bool,
+}
+
+impl Observables {
+    /// Compares all observable effects
+    pub const ALL: Self = Self {
+        output: true,
+        registers: true,
+        memory: true,
+    };
+}
+
+/// An observable effect that differs between the programs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Observable {
+    /// The output differs at this position or, if one output is a prefix of the other, ends at it.
+    /// Outputs only differing after the first 256 bytes are reported at position 256.
+    Output {
+        /// The position of the first differing output byte
+        position: usize,
+    },
+
+    /// A register has a different final value
+    Register {
+        /// The index of the register
+        index: u8,
+
+        /// The value left by the original program
+        original: u8,
+
+        /// The value left by the transformed program
+        transformed: u8,
+    },
+
+    /// The overflow flag has a different final value
+    Overflow {
+        /// The value left by the original program
+        original: bool,
+
+        /// The value left by the transformed program
+        transformed: bool,
+    },
+
+    /// A memory cell has a different final value
+    Memory {
+        /// The address of the memory cell
+        address: u8,
+
+        /// The value left by the original program
+        original: u8,
+
+        /// The value left by the transformed program
+        transformed: u8,
+    },
+
+    /// Only one of the programs failed to execute, or they failed with different errors.
+    /// Errors of the same variant are the same failure even if their offsets differ, because a
+    /// transformation moves the instructions.
+    Failure {
+        /// The error the original program failed with
+        original: Option<ExecutionError>,
+
+        /// The error the transformed program failed with
+        transformed: Option<ExecutionError>,
+    },
+}
+
+/// The first difference found between the programs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// The initial state that exposed the difference
+    pub initial: InitialState,
+
+    /// The effect that differs
+    pub observable: Observable,
+}
+
+/// Records the output of a program, keeping the first bytes and a checksum of all of them
+pub(crate) struct Recorder {
+    captured: [u8; CAPTURED_OUTPUT],
+    length: usize,
+    checksum: u32,
+}
+
+impl Recorder {
+    pub(crate) const fn new() -> Self {
+        Self {
+            captured: [0; CAPTURED_OUTPUT],
+            length: 0,
+            checksum: 0x811C_9DC5,
+        }
+    }
+
+    /// Returns the position of the first difference between the outputs, if they differ
+    pub(crate) fn difference(&self, other: &Self) -> Option<usize> {
+        let captured = self.length.min(other.length).min(CAPTURED_OUTPUT);
+        (0..captured)
+            .find(|&i| self.captured[i] != other.captured[i])
+            .or_else(|| {
+                (self.length != other.length || self.checksum != other.checksum)
+                    .then(|| self.length.min(other.length).min(CAPTURED_OUTPUT))
+            })
+    }
+}
+
+impl fmt::Write for Recorder {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        for character in text.chars() {
+            // The output port writes every byte as the character with the same code point
+            let byte = u8::try_from(character).map_err(|_| fmt::Error)?;
+            if let Some(slot) = self.captured.get_mut(self.length) {
+                *slot = byte;
+            }
+            self.length += 1;
+            self.checksum = (self.checksum ^ u32::from(byte)).wrapping_mul(0x0100_0193);
+        }
+        Ok(())
+    }
+}
+
+/// The observable effects of running a program
+struct Run {
+    output: Recorder,
+    registers: [u8; REGISTER_COUNT],
+    overflow: bool,
+    memory: [Byte; 256],
+    failure: Option<ExecutionError>,
+}
+
+/// Generates pseudo random initial states with a xorshift generator
+pub(crate) struct RandomStates {
+    state: u32,
+}
+
+impl RandomStates {
+    /// Starts the generator from a seed other than 0, on which xorshift gets stuck
+    pub(crate) const fn new(seed: u32) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) const fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state.to_le_bytes()[0]
+    }
+
+    pub(crate) fn next_state(&mut self) -> InitialState {
+        let registers: [u8; REGISTER_COUNT] = array::from_fn(|_| self.next_byte());
+        let overflow = self.next_byte() & 1 == 1;
+        let mut state = InitialState::new()
+            .with_registers(registers)
+            .with_overflow(overflow);
+        for address in 0..=u8::MAX {
+            state = state.with_memory(address, self.next_byte());
+        }
+        state
+    }
+}
+
+/// Runs two programs from a battery of initial states and compares their observable effects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EquivalenceChecker {
+    observables: Observables,
+    random_states: usize,
+    seed: u32,
+}
+
+impl Default for EquivalenceChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EquivalenceChecker {
+    /// Creates a checker comparing all observable effects over the corner case states and 16
+    /// random states
+    pub const fn new() -> Self {
+        Self {
+            observables: Observables::ALL,
+            random_states: 16,
+            seed: 0x4D69_6368,
+        }
+    }
+
+    /// Sets which effects are compared
+    pub const fn observing(mut self, observables: Observables) -> Self {
+        self.observables = observables;
+        self
+    }
+
+    /// Sets the number of random initial states and the seed they are generated from
+    pub const fn with_random_states(mut self, count: usize, seed: u32) -> Self {
+        self.random_states = count;
+        // Xorshift gets stuck on 0
+        self.seed = if seed == 0 { 1 } else { seed };
+        self
+    }
+
+    /// The corner case initial states every pair of programs is checked with
+    fn corner_cases() -> impl Iterator<Item = InitialState> {
+        [0x00, 0xFF, 0x80, 0x7F, 0x01]
+            .into_iter()
+            .flat_map(|value| {
+                [false, true].into_iter().map(move |overflow| {
+                    (0..=u8::MAX).fold(
+                        InitialState::new()
+                            .with_registers([value; REGISTER_COUNT])
+                            .with_overflow(overflow),
+                        |state, address| state.with_memory(address, value),
+                    )
+                })
+            })
+    }
+
+    /// Executes both programs from every initial state.
+    /// Returns the first difference in the observed effects and the initial state exposing it, or
+    /// `None` if the programs behaved the same from every state.
+    pub fn check(&self, original: &[u8], transformed: &[u8]) -> Option<Divergence> {
+        let mut random = RandomStates::new(self.seed);
+        let random = (0..self.random_states).map(|_| random.next_state());
+        let code = original.len().max(transformed.len());
+        Self::corner_cases().chain(random).find_map(|mut initial| {
+            initial
+                .memory
+                .iter_mut()
+                .take(code)
+                .for_each(|cell| *cell = None);
+            self.compare(original, transformed, &initial)
+                .map(|observable| Divergence {
+                    initial,
+                    observable,
+                })
+        })
+    }
+
+    /// Runs the program from the initial state until it stops or fails.
+    /// Returns the error if the program can't be loaded.
+    fn execute(program: &[u8], initial: &InitialState) -> Result<Run, ExecutionError> {
+        let mut console = ConsoleDevice::new(Recorder::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let mut computer = Computer::with_initial_state(program.iter().copied(), initial, bus)?;
+        let failure = computer.run().err();
+        let registers = computer.registers();
+        let overflow = computer.overflow();
+        let memory = computer.ram().cells();
+        Ok(Run {
+            output: console.into_output(),
+            registers,
+            overflow,
+            memory,
+            failure,
+        })
+    }
+
+    /// Executes both programs from the initial state, returns the first observable difference
+    fn compare(
+        &self,
+        original: &[u8],
+        transformed: &[u8],
+        initial: &InitialState,
+    ) -> Option<Observable> {
+        let (original_code, transformed_code) = (original.len(), transformed.len());
+        let original = Self::execute(original, initial);
+        let transformed = Self::execute(transformed, initial);
+        let failure = |run: &Result<Run, ExecutionError>| match run {
+            Ok(run) => run.failure,
+            Err(error) => Some(*error),
+        };
+        let (original_failure, transformed_failure) = (failure(&original), failure(&transformed));
+        if original_failure.as_ref().map(mem::discriminant)
+            != transformed_failure.as_ref().map(mem::discriminant)
+        {
+            return Some(Observable::Failure {
+                original: original_failure,
+                transformed: transformed_failure,
+            });
+        }
+        // Both programs failed to load the same way
+        let (Ok(original), Ok(transformed)) = (original, transformed) else {
+            return None;
+        };
+
+        if self.observables.output {
+            if let Some(position) = original.output.difference(&transformed.output) {
+                return Some(Observable::Output { position });
+            }
+        }
+        if self.observables.registers {
+            for ((index, original), transformed) in
+                (0..).zip(original.registers).zip(transformed.registers)
+            {
+                if original != transformed {
+                    return Some(Observable::Register {
+                        index,
+                        original,
+                        transformed,
+                    });
+                }
+            }
+            if original.overflow != transformed.overflow {
+                return Some(Observable::Overflow {
+                    original: original.overflow,
+                    transformed: transformed.overflow,
+                });
+            }
+        }
+        if self.observables.memory {
+            let code = original_code.max(transformed_code);
+            let cells = original.memory.into_iter();
+            for ((address, original), transformed) in
+                (0..=u8::MAX).zip(cells).zip(transformed.memory).skip(code)
+            {
+                let (original, transformed) = (u8::from(original), u8::from(transformed));
+                if original != transformed {
+                    return Some(Observable::Memory {
+                        address,
+                        original,
+                        transformed,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EquivalenceChecker, Observable, Observables};
+    use crate::ExecutionError;
+
+    const REGISTERS_ONLY: Observables = Observables {
+        output: false,
+        registers: true,
+        memory: false,
+    };
+
+    #[test]
+    fn equivalent_programs() {
+        // Moving a register onto itself has no effect
+        let original = [0x10, 8, 255];
+        let transformed = [8, 255];
+        assert_eq!(
+            EquivalenceChecker::new().check(&original, &transformed),
+            None
+        );
+    }
+
+    #[test]
+    fn register_difference() {
+        // r0 ^= r1 is only a no-op if r1 is 0
+        let original = [0x71];
+        let divergence = EquivalenceChecker::new().check(&original, &[]).unwrap();
+        assert_eq!(
+            divergence.observable,
+            Observable::Register {
+                index: 0,
+                original: 0,
+                transformed: 0xFF
+            }
+        );
+        assert_eq!(divergence.initial.registers, [0xFF; 8]);
+        assert!(!divergence.initial.overflow);
+    }
+
+    #[test]
+    fn overflow_difference() {
+        // An addition always updates the overflow flag
+        let original = [0x80];
+        let transformed = [0x80, 0x10];
+        assert_eq!(
+            EquivalenceChecker::new().check(&original, &transformed),
+            None
+        );
+        let divergence = EquivalenceChecker::new().check(&original, &[]).unwrap();
+        assert_eq!(
+            divergence.observable,
+            Observable::Overflow {
+                original: false,
+                transformed: true
+            }
+        );
+    }
+
+    #[test]
+    fn output_difference() {
+        let original = [8, 255, 9, 255];
+        let transformed = [8, 255];
+        let divergence = EquivalenceChecker::new()
+            .check(&original, &transformed)
+            .unwrap();
+        assert_eq!(divergence.observable, Observable::Output { position: 1 });
+    }
+
+    #[test]
+    fn memory_difference() {
+        // A dead store to memory is only visible when memory is observed
+        let original = [0, 1, 8, 0x10, 0, 2];
+        let transformed = [0, 2];
+        let divergence = EquivalenceChecker::new()
+            .check(&original, &transformed)
+            .unwrap();
+        assert_eq!(
+            divergence.observable,
+            Observable::Memory {
+                address: 0x10,
+                original: 1,
+                transformed: 0
+            }
+        );
+        let checker = EquivalenceChecker::new().observing(REGISTERS_ONLY);
+        assert_eq!(checker.check(&original, &transformed), None);
+    }
+
+    #[test]
+    fn random_states_expose_difference() {
+        // r0 |= r1 is a no-op in the corner cases, where all registers are equal
+        let original = [0x51];
+        let corner_cases_only = EquivalenceChecker::new()
+            .observing(REGISTERS_ONLY)
+            .with_random_states(0, 1);
+        assert_eq!(corner_cases_only.check(&original, &[]), None);
+        let divergence = EquivalenceChecker::new()
+            .observing(REGISTERS_ONLY)
+            .check(&original, &[])
+            .unwrap();
+        let [r0, r1, ..] = divergence.initial.registers;
+        assert_ne!(r0 | r1, r0);
+    }
+
+    #[test]
+    fn failures() {
+        // A truncated `ldi` fails, dropping it doesn't
+        let divergence = EquivalenceChecker::new().check(&[0x00], &[]).unwrap();
+        assert_eq!(
+            divergence.observable,
+            Observable::Failure {
+                original: Some(ExecutionError::TruncatedOperand { offset: 0 }),
+                transformed: None
+            }
+        );
+        // The same failure at another offset, after the same effects
+        assert_eq!(
+            EquivalenceChecker::new().check(&[0x10, 0xFE], &[0xFE]),
+            None
+        );
+        // Both fail, but differently
+        let divergence = EquivalenceChecker::new().check(&[0xFE], &[0x00]).unwrap();
+        assert_eq!(
+            divergence.observable,
+            Observable::Failure {
+                original: Some(ExecutionError::InvalidInstruction {
+                    offset: 0,
+                    byte: 0xFE
+                }),
+                transformed: Some(ExecutionError::TruncatedOperand { offset: 0 })
+            }
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! The error interface shared by the crates of the toolchain.
+//!
+//! Every crate keeps its own error enums and implements [`McError`] for them, so tools can report
+//! any error with its kind and location and map it to an exit code.
+
+use core::fmt::{self, Debug, Display, Formatter};
+
+/// The stage of the toolchain an error comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The source text couldn't be parsed
+    Parse,
+
+    /// The parsed program couldn't be assembled
+    Assemble,
+
+    /// A program image or other file has an invalid format
+    Format,
+
+    /// The program couldn't be loaded into the computer
+    Load,
+
+    /// The program failed while it was executed
+    Execution,
+
+    /// A device attached to the computer failed
+    Device,
+
+    /// A circuit couldn't be built or evaluated
+    Circuit,
+}
+
+impl ErrorKind {
+    /// The exit code a command line tool returns for errors of this kind.
+    ///
+    /// 0 is success and 1 is left for failures outside the toolchain, like usage errors.
+    pub const fn exit_code(self) -> u8 {
+        match self {
+            Self::Parse => 2,
+            Self::Assemble => 3,
+            Self::Format => 4,
+            Self::Load => 5,
+            Self::Execution => 6,
+            Self::Device => 7,
+            Self::Circuit => 8,
+        }
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Parse => "parse error",
+            Self::Assemble => "assembly error",
+            Self::Format => "format error",
+            Self::Load => "load error",
+            Self::Execution => "execution error",
+            Self::Device => "device error",
+            Self::Circuit => "circuit error",
+        })
+    }
+}
+
+/// Where in the program or source an error occurred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// An offset in the program, like the program counter of the failing instruction
+    Offset(usize),
+
+    /// A range of bytes in the source text
+    Span {
+        /// The offset of the first byte
+        start: usize,
+
+        /// The offset after the last byte
+        end: usize,
+    },
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Offset(offset) => write!(f, "offset {offset}"),
+            Self::Span { start, end } => write!(f, "bytes {start}..{end}"),
+        }
+    }
+}
+
+/// Implemented by every error of the toolchain
+pub trait McError: Debug + Display {
+    /// The stage of the toolchain the error comes from
+    fn kind(&self) -> ErrorKind;
+
+    /// Where the error occurred, if it is tied to a location
+    fn location(&self) -> Option<Location> {
+        None
+    }
+
+    /// The exit code a command line tool returns for the error
+    fn exit_code(&self) -> u8 {
+        self.kind().exit_code()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorKind, Location, McError};
+    use crate::{circuit::CircuitError, ExecutionError};
+
+    #[test]
+    fn exit_codes() {
+        let kinds = [
+            ErrorKind::Parse,
+            ErrorKind::Assemble,
+            ErrorKind::Format,
+            ErrorKind::Load,
+            ErrorKind::Execution,
+            ErrorKind::Device,
+            ErrorKind::Circuit,
+        ];
+        assert_eq!(kinds.map(ErrorKind::exit_code), [2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn crate_errors() {
+        let invalid = ExecutionError::InvalidInstruction {
+            offset: 3,
+            byte: 0xFE,
+        };
+        assert_eq!(invalid.kind(), ErrorKind::Execution);
+        assert_eq!(invalid.location(), Some(Location::Offset(3)));
+        assert_eq!(invalid.exit_code(), 6);
+
+        let output = ExecutionError::OutputError { offset: 4 };
+        assert_eq!(output.kind(), ErrorKind::Device);
+        assert_eq!(output.location(), Some(Location::Offset(4)));
+
+        let overflow = ExecutionError::StackOverflow { offset: 5 };
+        assert_eq!(overflow.kind(), ErrorKind::Execution);
+        assert_eq!(overflow.location(), Some(Location::Offset(5)));
+
+        assert_eq!(ExecutionError::ProgramTooLarge.kind(), ErrorKind::Load);
+        assert_eq!(ExecutionError::ProgramTooLarge.location(), None);
+
+        let cycle = CircuitError::Cycle("out");
+        assert_eq!(cycle.kind(), ErrorKind::Circuit);
+        assert_eq!(cycle.exit_code(), 8);
+    }
+}
@@ -0,0 +1,286 @@
+//! Exhaustive exploration of tiny programs.
+//!
+//! A program is run once for every value of a single variable, which is enough to verify small
+//! exercises for all their inputs.
+
+use core::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{
+    bus::{Bus, ConsoleDevice, InputStream},
+    error::{ErrorKind, McError},
+    mux::byte::Register,
+    state::{InitialState, Target},
+    Computer, ExecutionError, StopReason,
+};
+
+/// The number of output bytes that are kept for every run
+pub const OUTPUT_CAPACITY: usize = 32;
+
+/// The value that is varied between the runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variable {
+    /// The single input byte, loads after it read 0
+    Input,
+
+    /// The initial value of a register
+    Register(Register),
+
+    /// The initial value of a memory cell, which may be a byte of the program
+    Memory(u8),
+}
+
+impl Display for Variable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Input => f.write_str("input"),
+            Self::Register(register) => Target::Register(register).fmt(f),
+            Self::Memory(address) => Target::Memory(address).fmt(f),
+        }
+    }
+}
+
+impl FromStr for Variable {
+    type Err = VariableError;
+
+    /// Parses `input`, or a register or memory cell written like the target of an assignment
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text.trim().eq_ignore_ascii_case("input") {
+            return Ok(Self::Input);
+        }
+        match text.parse() {
+            Ok(Target::Register(register)) => Ok(Self::Register(register)),
+            Ok(Target::Memory(address)) => Ok(Self::Memory(address)),
+            _ => Err(VariableError),
+        }
+    }
+}
+
+/// The text isn't a [`Variable`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VariableError;
+
+impl Display for VariableError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("expected `input`, a register or `mem[address]`")
+    }
+}
+
+impl core::error::Error for VariableError {}
+
+impl McError for VariableError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Parse
+    }
+}
+
+/// The output of a run, the first [`OUTPUT_CAPACITY`] bytes and the total length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Output {
+    captured: [u8; OUTPUT_CAPACITY],
+    length: usize,
+}
+
+impl Output {
+    const fn new() -> Self {
+        Self {
+            captured: [0; OUTPUT_CAPACITY],
+            length: 0,
+        }
+    }
+
+    /// Returns the captured bytes
+    pub fn bytes(&self) -> &[u8] {
+        &self.captured[..self.length.min(OUTPUT_CAPACITY)]
+    }
+
+    /// Returns the number of bytes written, including the ones that weren't captured
+    pub const fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns whether nothing was written
+    pub const fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+}
+
+impl fmt::Write for Output {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        for character in text.chars() {
+            // The output port writes every byte as the character with the same code point
+            let byte = u8::try_from(character).map_err(|_| fmt::Error)?;
+            if let Some(slot) = self.captured.get_mut(self.length) {
+                *slot = byte;
+            }
+            self.length += 1;
+        }
+        Ok(())
+    }
+}
+
+/// How a run for one value ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The program halted or reached its end
+    Completed(Output),
+
+    /// The program didn't stop before the fuel ran out
+    OutOfFuel(Output),
+
+    /// The program failed
+    Fault(ExecutionError),
+}
+
+/// The outcome of every value of the variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplorationReport {
+    outcomes: [Outcome; 256],
+}
+
+impl ExplorationReport {
+    /// Returns the outcome of the run with the value
+    pub const fn outcome(&self, value: u8) -> &Outcome {
+        &self.outcomes[value as usize]
+    }
+
+    /// Returns every distinct output of the completed runs, in the order of the values first
+    /// producing them
+    pub fn distinct_outputs(&self) -> impl Iterator<Item = &Output> {
+        (0..)
+            .zip(&self.outcomes)
+            .filter_map(|(i, outcome)| match outcome {
+                Outcome::Completed(output) if !self.outcomes[..i].contains(outcome) => Some(output),
+                _ => None,
+            })
+    }
+
+    /// Returns the values the program failed for, with the error
+    pub fn faults(&self) -> impl Iterator<Item = (u8, ExecutionError)> + '_ {
+        (0..=u8::MAX)
+            .zip(&self.outcomes)
+            .filter_map(|(value, outcome)| match *outcome {
+                Outcome::Fault(error) => Some((value, error)),
+                _ => None,
+            })
+    }
+
+    /// Returns the values the program ran out of fuel for
+    pub fn out_of_fuel(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=u8::MAX)
+            .zip(&self.outcomes)
+            .filter_map(|(value, outcome)| {
+                matches!(outcome, Outcome::OutOfFuel(_)).then_some(value)
+            })
+    }
+}
+
+/// Runs the program for all 256 values of the variable, with fuel for 1000 instructions each
+pub fn explore(program: &[u8], variable: Variable) -> ExplorationReport {
+    explore_with_fuel(program, variable, 1000)
+}
+
+/// Runs the program for all 256 values of the variable, with `fuel` instructions each
+pub fn explore_with_fuel(program: &[u8], variable: Variable, fuel: u64) -> ExplorationReport {
+    let mut outcomes = [Outcome::Completed(Output::new()); 256];
+    for (value, outcome) in (0..=u8::MAX).zip(&mut outcomes) {
+        *outcome = run(program, variable, value, fuel);
+    }
+    ExplorationReport { outcomes }
+}
+
+/// Runs the program with the variable set to the value
+fn run(program: &[u8], variable: Variable, value: u8, fuel: u64) -> Outcome {
+    let mut initial = InitialState::new();
+    let mut input = InputStream::new(None);
+    match variable {
+        Variable::Input => input = InputStream::new(Some(value)),
+        Variable::Register(register) => {
+            initial.registers[usize::from(u8::from(register))] = value;
+        }
+        Variable::Memory(address) => {
+            initial = initial.with_allow_overlap(true).with_memory(address, value);
+        }
+    }
+    let mut console = ConsoleDevice::new(Output::new());
+    let bus = Bus::new()
+        .with_device(254..=254, &mut input)
+        .with_device(255..=255, &mut console);
+    let result = Computer::with_initial_state(program.iter().copied(), &initial, bus)
+        .and_then(|computer| computer.with_fuel(fuel).run());
+    let output = console.into_output();
+    match result {
+        Ok(StopReason::OutOfFuel { .. }) => Outcome::OutOfFuel(output),
+        Ok(_) => Outcome::Completed(output),
+        Err(error) => Outcome::Fault(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explore, explore_with_fuel, Outcome, Output, Variable, VariableError};
+    use crate::{mux::byte::Register, ExecutionError};
+
+    #[test]
+    fn single_faulting_input() {
+        let program = [
+            4, 254, // r0 = input
+            0xC4, 7, // jump to the invalid instruction if r0 is zero
+            8, 255,  // print r0
+            0xFF, // halt
+            0xFE, // invalid
+        ];
+        let report = explore(&program, Variable::Input);
+        let mut faults = report.faults();
+        assert_eq!(
+            faults.next(),
+            Some((
+                0,
+                ExecutionError::InvalidInstruction {
+                    offset: 7,
+                    byte: 0xFE
+                }
+            ))
+        );
+        assert_eq!(faults.next(), None);
+        assert_eq!(report.distinct_outputs().count(), 255);
+        let Outcome::Completed(output) = report.outcome(b'A') else {
+            panic!("the program failed for 'A'");
+        };
+        assert_eq!(output.bytes(), b"A");
+        assert_eq!(report.out_of_fuel().count(), 0);
+    }
+
+    #[test]
+    fn register_and_memory() {
+        // Prints the low nibble of r0
+        let program = [1, 0x0F, 0x31, 8, 255];
+        let report = explore(&program, Variable::Register(Register::R0));
+        assert_eq!(report.distinct_outputs().count(), 16);
+        assert_eq!(report.faults().count(), 0);
+
+        // Loops forever unless the memory cell is 0
+        let program = [4, 0x10, 0xC4, 6, 0xC0, 0];
+        let report = explore_with_fuel(&program, Variable::Memory(0x10), 50);
+        assert!(report.out_of_fuel().eq(1..=u8::MAX));
+        let outputs = report.distinct_outputs();
+        assert!(outputs.map(Output::is_empty).eq([true]));
+
+        // A cell of the program is explored as well, here the operand of the load constant
+        let program = [0, 0, 8, 255]; // ldi r0, 0; st r0, [255]
+        let report = explore(&program, Variable::Memory(1));
+        assert_eq!(report.distinct_outputs().count(), 256);
+        assert_eq!(report.faults().count(), 0);
+    }
+
+    #[test]
+    fn parse_variables() {
+        assert_eq!("input".parse(), Ok(Variable::Input));
+        assert_eq!("R3".parse(), Ok(Variable::Register(Register::R3)));
+        assert_eq!("mem[0x10]".parse(), Ok(Variable::Memory(0x10)));
+        assert_eq!("overflow".parse::<Variable>(), Err(VariableError));
+        assert_eq!("r9".parse::<Variable>(), Err(VariableError));
+    }
+}
@@ -0,0 +1,251 @@
+//! Plain `u8` versions of the circuits the interpreter runs the most, used with the `fast` feature.
+//!
+//! Every function returns exactly what its circuit returns, including the carry and the borrow,
+//! so the feature only changes the speed. Both versions are always compiled, so the tests compare
+//! them in every build.
+
+#[cfg(test)]
+use core::cell::Cell;
+
+use crate::{bit::Bit, byte::Byte};
+
+#[cfg(test)]
+std::thread_local! {
+    /// The versions the tests of the thread run, `None` for the ones of the build
+    static FORCED: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+/// Returns whether the plain versions run instead of the circuits, with the `fast` feature
+#[cfg(not(test))]
+pub const fn enabled() -> bool {
+    cfg!(feature = "fast")
+}
+
+/// Returns whether the plain versions run instead of the circuits, the ones a test picked for its
+/// thread or the ones of the build
+#[cfg(test)]
+pub fn enabled() -> bool {
+    FORCED.with(Cell::get).unwrap_or(cfg!(feature = "fast"))
+}
+
+/// Returns the index the select bits pick, `select[0]` adds 1
+pub fn index<const N: usize>(select: [Bit; N]) -> usize {
+    select
+        .into_iter()
+        .rev()
+        .fold(0, |index, bit| index << 1 | usize::from(bool::from(bit)))
+}
+
+/// Adds 2 bytes and the carry bit, like [`Byte::add_with_carry`]
+pub fn add_with_carry(left: Byte, right: Byte, carry: Bit) -> (Byte, Bit) {
+    let sum = u16::from(u8::from(left)) + u16::from(u8::from(right)) + u16::from(bool::from(carry));
+    let [low, high] = sum.to_le_bytes();
+    (low.into(), Bit::from(high == 1))
+}
+
+/// Adds one, like [`Byte::increment`]
+pub fn increment(byte: Byte) -> (Byte, Bit) {
+    let (result, wrapped) = u8::from(byte).overflowing_add(1);
+    (result.into(), wrapped.into())
+}
+
+/// Subtracts one, like [`Byte::decrement`]
+pub fn decrement(byte: Byte) -> (Byte, Bit) {
+    let (result, wrapped) = u8::from(byte).overflowing_sub(1);
+    (result.into(), wrapped.into())
+}
+
+/// Returns the low and the high byte of the product, like [`Byte::multiply`]
+pub fn multiply(left: Byte, right: Byte) -> (Byte, Byte) {
+    let [low, high] = (u16::from(u8::from(left)) * u16::from(u8::from(right))).to_le_bytes();
+    (low.into(), high.into())
+}
+
+/// Returns the quotient and the remainder, like [`Byte::divide`] also for a divisor of 0
+pub fn divide(byte: Byte, divisor: Byte) -> (Byte, Byte) {
+    let (byte, divisor) = (u8::from(byte), u8::from(divisor));
+    match (byte.checked_div(divisor), byte.checked_rem(divisor)) {
+        (Some(quotient), Some(remainder)) => (quotient.into(), remainder.into()),
+        _ => (0xFF.into(), byte.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::array;
+
+    use super::{index, FORCED};
+    use crate::{
+        bit::Bit,
+        bus::{Bus, ConsoleDevice, InputStream},
+        byte::Byte,
+        equivalence::{RandomStates, Recorder},
+        mux::byte::{Ram, Registers},
+        opcode::{decode_opcode, DecodedOp, Operand},
+        Computer, StepOutcome,
+    };
+
+    /// Runs the function with the plain versions if `fast` is set, with the circuits otherwise
+    fn with_backend<R>(fast: bool, f: impl FnOnce() -> R) -> R {
+        let previous = FORCED.with(|forced| forced.replace(Some(fast)));
+        let result = f();
+        FORCED.with(|forced| forced.set(previous));
+        result
+    }
+
+    /// Runs the test for every pair of bytes
+    fn all_pairs(mut test: impl FnMut(u8, u8)) {
+        for left in 0..=u8::MAX {
+            for right in 0..=u8::MAX {
+                test(left, right);
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        all_pairs(|left, right| {
+            let (l, r) = (Byte::from(left), Byte::from(right));
+            for carry in [Bit::Low, Bit::High] {
+                let (fast, fast_carry) = super::add_with_carry(l, r, carry);
+                let (circuit, circuit_carry) = l.add_with_carry_circuit(r, carry);
+                assert_eq!(
+                    (u8::from(fast), fast_carry),
+                    (circuit.into(), circuit_carry)
+                );
+            }
+            let [fast, circuit] = [super::multiply(l, r), l.multiply_circuit(r)]
+                .map(|(low, high)| (u8::from(low), u8::from(high)));
+            assert_eq!(fast, circuit, "{left} * {right}");
+            let [fast, circuit] = [super::divide(l, r), l.divide_circuit(r)]
+                .map(|(quotient, remainder)| (u8::from(quotient), u8::from(remainder)));
+            assert_eq!(fast, circuit, "{left} / {right}");
+        });
+        for value in 0..=u8::MAX {
+            let byte = Byte::from(value);
+            let [fast, circuit] = [super::increment(byte), byte.increment_circuit()]
+                .map(|(result, wrapped)| (u8::from(result), wrapped));
+            assert_eq!(fast, circuit);
+            let [fast, circuit] = [super::decrement(byte), byte.decrement_circuit()]
+                .map(|(result, wrapped)| (u8::from(result), wrapped));
+            assert_eq!(fast, circuit);
+        }
+    }
+
+    #[test]
+    fn memory() {
+        let mut ram = Ram::new();
+        let mut registers = Registers::new();
+        for value in 0..=u8::MAX {
+            let address = Byte::from(value.wrapping_mul(97));
+            assert_eq!(index::<8>(address.into()), usize::from(u8::from(address)));
+            ram.store_circuit(address, Byte::from(value));
+            let select = [
+                Bit::from(value & 1 == 1),
+                Bit::from(value & 2 == 2),
+                Bit::from(value & 4 == 4),
+            ];
+            assert_eq!(index(select), usize::from(value & 7));
+            registers.store_circuit(select, Byte::from(value));
+        }
+        // The fast loads see the stores through the circuits and the other way around
+        for value in 0..=u8::MAX {
+            let address = Byte::from(value);
+            assert_eq!(u8::from(ram.load(address)), value.wrapping_mul(161));
+            assert_eq!(
+                u8::from(ram.load_circuit(address)),
+                u8::from(ram.load(address))
+            );
+        }
+        let fast: [u8; 8] = core::array::from_fn(|i| {
+            let select = [
+                Bit::from(i & 1 == 1),
+                Bit::from(i & 2 == 2),
+                Bit::from(i & 4 == 4),
+            ];
+            u8::from(registers.load(select))
+        });
+        assert_eq!(fast, [248, 249, 250, 251, 252, 253, 254, 255]);
+        assert_eq!(u8::from(registers.load_circuit([Bit::High; 3])), 255);
+    }
+
+    /// Returns a program of valid opcodes with random operands, jumps stay inside the program
+    fn random_program(random: &mut RandomStates) -> [u8; 48] {
+        let mut code = [0xFF; 48];
+        let mut offset = 0;
+        while offset < code.len() - 1 {
+            let opcode = random.next_byte();
+            let Ok(op) = decode_opcode(opcode) else {
+                continue;
+            };
+            code[offset] = opcode;
+            offset += 1;
+            code[offset] = match (op, op.operand()) {
+                (_, Operand::None) => continue,
+                (
+                    DecodedOp::Jump
+                    | DecodedOp::JumpIfOverflow
+                    | DecodedOp::Call
+                    | DecodedOp::JumpIfZero(_),
+                    _,
+                ) => random.next_byte() % 48,
+                // The reserved bits of the register byte stay clear
+                (_, Operand::Registers) => random.next_byte() & 0x0F,
+                (_, Operand::Byte) => random.next_byte(),
+            };
+            offset += 1;
+        }
+        code
+    }
+
+    /// Runs random programs from random initial states with the circuits and with the plain
+    /// versions side by side, and compares the machines after every step
+    #[test]
+    fn random_programs() {
+        let mut random = RandomStates::new(0x1234_5678);
+        for _ in 0..64 {
+            let code = random_program(&mut random);
+            let mut initial = random.next_state();
+            initial
+                .memory
+                .iter_mut()
+                .take(code.len())
+                .for_each(|cell| *cell = None);
+            let input: [u8; 8] = array::from_fn(|_| random.next_byte());
+
+            let mut inputs = [InputStream::new(input), InputStream::new(input)];
+            let mut consoles = [
+                ConsoleDevice::new(Recorder::new()),
+                ConsoleDevice::new(Recorder::new()),
+            ];
+            let [circuit_input, fast_input] = &mut inputs;
+            let [circuit_console, fast_console] = &mut consoles;
+            let load = |fast, input, console| {
+                let bus = Bus::new()
+                    .with_device(254..=254, input)
+                    .with_device(255..=255, console);
+                with_backend(fast, || {
+                    Computer::with_initial_state(code, &initial, bus)
+                        .unwrap()
+                        .with_fuel(500)
+                })
+            };
+            let mut circuit = load(false, circuit_input, circuit_console);
+            let mut fast = load(true, fast_input, fast_console);
+            loop {
+                let step = with_backend(false, || circuit.step());
+                assert_eq!(with_backend(true, || fast.step()), step, "{code:02X?}");
+                assert_eq!(fast.state(), circuit.state(), "{code:02X?} {step:?}");
+                assert_eq!(
+                    (fast.executed(), fast.cycles()),
+                    (circuit.executed(), circuit.cycles())
+                );
+                if step != Ok(StepOutcome::Executed) {
+                    break;
+                }
+            }
+            let [circuit, fast] = consoles.map(ConsoleDevice::into_output);
+            assert_eq!(circuit.difference(&fast), None, "{code:02X?}");
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Counting the nand gates circuits and programs evaluate, with the `gate-count` feature.
+//!
+//! Every gate is built from [`Bit::nand`], which increments a counter of the current thread with
+//! this feature. The feature needs `std` for the thread local counter, so tests running in
+//! parallel don't count each other's gates.
+//!
+//! ```
+//! use michael_computer::{bit::Bit, gate_count};
+//!
+//! let (_, gates) = gate_count::measure(|| Bit::High.xor(Bit::Low));
+//! assert_eq!(gates, 6);
+//! ```
+//!
+//! [`Bit::nand`]: crate::bit::Bit::nand
+
+use core::cell::Cell;
+
+std::thread_local! {
+    static COUNT: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Returns the number of nand gates evaluated by the current thread
+pub fn count() -> u64 {
+    COUNT.with(Cell::get)
+}
+
+/// Runs the function and returns its result with the number of nand gates it evaluated
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    let start = count();
+    let result = f();
+    (result, count() - start)
+}
+
+/// Counts a nand gate
+pub(crate) fn count_nand() {
+    COUNT.with(|count| count.set(count.get() + 1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::measure;
+    use crate::{bit::Bit, bus::Bus, byte::Byte, Computer, StepOutcome, StopReason};
+
+    #[test]
+    fn adders() {
+        let bits = [Bit::Low, Bit::High];
+        for (left, right, carry) in bits.into_iter().flat_map(|left| {
+            bits.into_iter()
+                .flat_map(move |right| bits.map(|carry| (left, right, carry)))
+        }) {
+            // 2 xors of 6 gates for the sum, 2 ors of 3 and 2 ands of 2 for the carry
+            assert_eq!(measure(|| left.full_adder(right, carry)).1, 22);
+            assert_eq!(measure(|| left.half_adder(right)).1, 8);
+        }
+    }
+
+    #[test]
+    fn byte_arithmetic() {
+        let (left, right) = (Byte::from(0x5A), Byte::from(0xC3));
+        let ((sum, _), gates) = measure(|| left.add_with_carry_circuit(right, Bit::Low));
+        assert_eq!(u8::from(sum), 0x1D);
+        assert_eq!(gates, 8 * 22);
+        // Subtracting adds the inverted byte, the 8 inverters are the overhead
+        let ((difference, _), gates) = measure(|| left.add_with_carry_circuit(!right, Bit::High));
+        assert_eq!(u8::from(difference), 0x97);
+        assert_eq!(gates, 8 * 22 + 8);
+    }
+
+    #[test]
+    fn computer() {
+        // r0 = 5, r1 = 7, r1 = r0, r1 += r0, halt
+        let code = [0x00, 5, 0x01, 7, 0x14, 0x84, 0xFF];
+        let mut computer = Computer::new(code, Bus::new()).unwrap();
+        let mut steps = [0; 5];
+        for step in &mut steps {
+            let before = computer.gate_count();
+            computer.step().unwrap();
+            *step = computer.gate_count() - before;
+        }
+        assert_eq!(
+            computer.step(),
+            Ok(StepOutcome::Stopped(StopReason::Halted))
+        );
+        assert_eq!(computer.gate_count(), steps.iter().sum::<u64>());
+        // Every gate is evaluated whatever the values, the constants fetch a second byte and the
+        // addition costs more than the move
+        assert_eq!(steps[0], steps[1]);
+        assert!(steps[0] > steps[2]);
+        assert!(steps[3] > steps[2]);
+
+        let mut again = Computer::new(code, Bus::new()).unwrap();
+        let (result, gates) = measure(|| again.run());
+        assert_eq!(result, Ok(StopReason::Halted));
+        assert_eq!(again.gate_count(), computer.gate_count());
+        assert!(gates >= again.gate_count());
+    }
+}
@@ -9,105 +9,409 @@
 
 //! This library contains the implementation of gates, circuits, and datatypes used by the computer
 
-use core::{array, fmt::Write};
+#[cfg(any(test, feature = "gate-count"))]
+extern crate std;
 
-use bit::Bit;
-use mux::byte::{Ram, Registers};
+use core::fmt::{self, Display, Formatter, Write};
+
+use bus::{Bus, ConsoleDevice, Device, InputStream, OutputDevice, OutputSink};
+use error::{ErrorKind, Location, McError};
+use state::{InitialState, MachineState};
 
 pub mod bit;
+pub mod bus;
 pub mod byte;
+pub mod circuit;
+pub mod computer;
+pub mod depth;
+pub mod display;
+pub mod equivalence;
+pub mod error;
+pub mod explore;
+mod fast;
+#[cfg(feature = "gate-count")]
+pub mod gate_count;
 pub mod mux;
+pub mod opcode;
+pub mod profile;
+pub mod scheduler;
+pub mod script;
+pub mod seq;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod state;
+pub mod synthesis;
+pub mod time;
+pub mod vector;
+pub mod word;
 
-/// The ALU executes all CPU instructions.
-///
-/// # Panics
-/// The program panics if an invalid instruction was found or the program ended unexpectedly
-pub fn alu(mut iter: impl Iterator<Item = u8>, out: &mut impl Write) {
-    let mut registers = Registers::new();
-    let mut memory = Ram::new();
-
-    let mut overflow = Bit::Low;
-    while let Some(byte) = iter.next() {
-        let reg_low = array::from_fn(|i| Bit::from((byte >> i) & 1 == 1));
-        let reg_high = array::from_fn(|i| Bit::from((byte >> (i + 2)) & 1 == 1));
-        match byte {
-            0..4 => registers.store(
-                reg_low,
-                iter.next().expect("Unexpected end of program").into(),
-            ),
-            4..8 => {
-                registers.store(
-                    reg_low,
-                    memory.load(iter.next().expect("Unexpected end of program").into()),
-                );
-            }
-            8..12 => {
-                let address = iter.next().expect("Unexpected end of program");
-                let value = registers.load(reg_low);
-                memory.store(address.into(), value);
-                if address == 255 {
-                    out.write_char(char::from(u8::from(value)))
-                        .expect("Failed to write byte to output");
-                }
-            }
-            12..16 => registers.store(reg_low, !registers.load(reg_low)),
-            16..32 => registers.store(reg_high, registers.load(reg_low)),
-            32..48 => registers.store(
-                reg_high,
-                registers.load(reg_high).nand(&registers.load(reg_low)),
-            ),
-            48..64 => registers.store(reg_high, registers.load(reg_high) & registers.load(reg_low)),
-            64..80 => registers.store(
-                reg_high,
-                registers.load(reg_high).nor(&registers.load(reg_low)),
-            ),
-            80..96 => registers.store(reg_high, registers.load(reg_high) | registers.load(reg_low)),
-            96..112 => registers.store(
-                reg_high,
-                registers.load(reg_high).xnor(&registers.load(reg_low)),
-            ),
-            112..128 => {
-                registers.store(reg_high, registers.load(reg_high) ^ registers.load(reg_low));
+pub use computer::{Computer, ExecutionModel, Snapshot, StepOutcome, TraceEvent, WatchKind};
+
+/// Why the execution of a program stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StopReason {
+    /// A halt instruction was executed
+    Halted,
+
+    /// The program counter reached the end of the program
+    EndOfProgram,
+
+    /// The fuel ran out before the next instruction
+    OutOfFuel {
+        /// The number of instructions executed so far
+        executed: u64,
+    },
+
+    /// The program counter reached a breakpoint, the instruction there runs when execution
+    /// continues
+    Breakpoint {
+        /// The address of the breakpoint
+        pc: u8,
+    },
+
+    /// The last instruction accessed a watched address, execution continues with the next one
+    Watchpoint {
+        /// The address that was accessed
+        addr: u8,
+
+        /// Whether the access was a read or a write
+        kind: WatchKind,
+
+        /// The byte that was loaded or stored
+        value: u8,
+    },
+}
+
+/// An error that stopped the execution of a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionError {
+    /// The byte at the offset isn't a valid instruction
+    InvalidInstruction {
+        /// The offset of the byte in the program
+        offset: usize,
+
+        /// The invalid byte
+        byte: u8,
+    },
+
+    /// The program ended before the operand of the instruction at the offset
+    TruncatedOperand {
+        /// The offset of the instruction in the program
+        offset: usize,
+    },
+
+    /// A device failed to handle the store of the instruction at the offset
+    OutputError {
+        /// The offset of the instruction in the program
+        offset: usize,
+    },
+
+    /// The push at the offset would grow the stack into the devices
+    StackOverflow {
+        /// The offset of the instruction in the program
+        offset: usize,
+    },
+
+    /// The pop at the offset would read below the start of the stack
+    StackUnderflow {
+        /// The offset of the instruction in the program
+        offset: usize,
+    },
+
+    /// The store of the instruction at the offset targets the protected ROM of the program
+    WriteToRom {
+        /// The offset of the instruction in the program
+        offset: usize,
+
+        /// The address of the store
+        addr: u8,
+    },
+
+    /// The program doesn't fit in the 256 byte memory
+    ProgramTooLarge,
+
+    /// The initial state sets a memory cell holding a byte of the program, without
+    /// [`InitialState::allow_overlap`]
+    InitialStateOverlap {
+        /// The address of the memory cell
+        addr: u8,
+    },
+}
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidInstruction { offset, byte } => {
+                write!(f, "invalid instruction {byte:#04X} at offset {offset}")
             }
-            128..144 => {
-                let (result, carry) = registers.load(reg_high) + registers.load(reg_low);
-                registers.store(reg_high, result);
-                overflow = carry;
+            Self::TruncatedOperand { offset } => {
+                write!(f, "missing operand for the instruction at offset {offset}")
             }
-            144..160 => {
-                let (result, carry) = registers
-                    .load(reg_high)
-                    .add_with_carry(registers.load(reg_low), overflow);
-                registers.store(reg_high, result);
-                overflow = carry;
+            Self::OutputError { offset } => {
+                write!(f, "failed to write output at offset {offset}")
             }
-            160..176 => {
-                let (result, carry) = registers.load(reg_high) - registers.load(reg_low);
-                registers.store(reg_high, result);
-                overflow = carry;
+            Self::StackOverflow { offset } => write!(f, "stack overflow at offset {offset}"),
+            Self::StackUnderflow { offset } => write!(f, "stack underflow at offset {offset}"),
+            Self::WriteToRom { offset, addr } => {
+                write!(f, "store to ROM address {addr:#04X} at offset {offset}")
             }
-            176..192 => {
-                let (result, carry) = registers
-                    .load(reg_high)
-                    .sub_with_carry(registers.load(reg_low), overflow);
-                registers.store(reg_high, result);
-                overflow = carry;
+            Self::ProgramTooLarge => write!(f, "program doesn't fit in memory"),
+            Self::InitialStateOverlap { addr } => {
+                write!(f, "initial state sets address {addr:#04X} of the program")
             }
-            192.. => panic!("Invalid instruction: {byte}"),
         }
     }
 }
 
+impl core::error::Error for ExecutionError {}
+
+impl McError for ExecutionError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::InvalidInstruction { .. }
+            | Self::TruncatedOperand { .. }
+            | Self::StackOverflow { .. }
+            | Self::StackUnderflow { .. }
+            | Self::WriteToRom { .. } => ErrorKind::Execution,
+            Self::OutputError { .. } => ErrorKind::Device,
+            Self::ProgramTooLarge | Self::InitialStateOverlap { .. } => ErrorKind::Load,
+        }
+    }
+
+    fn location(&self) -> Option<Location> {
+        match *self {
+            Self::InvalidInstruction { offset, .. }
+            | Self::TruncatedOperand { offset }
+            | Self::OutputError { offset }
+            | Self::StackOverflow { offset }
+            | Self::StackUnderflow { offset }
+            | Self::WriteToRom { offset, .. } => Some(Location::Offset(offset)),
+            Self::ProgramTooLarge | Self::InitialStateOverlap { .. } => None,
+        }
+    }
+}
+
+/// The ALU executes all CPU instructions.
+///
+/// The program is loaded into memory at address 0, so jump targets are offsets into the program
+/// and loads and stores see the program bytes. Loading from address 254 reads the next byte of
+/// the input, or 0 once it is exhausted.
+///
+/// # Errors
+/// Returns an error if an invalid instruction was found, the program ended unexpectedly, writing
+/// the output failed, or the program is larger than 256 bytes
+pub fn alu(
+    program: impl IntoIterator<Item = u8>,
+    input: impl IntoIterator<Item = u8>,
+    out: &mut impl Write,
+) -> Result<StopReason, ExecutionError> {
+    alu_with_initial_state(program, &InitialState::new(), input, out)
+}
+
+/// Executes the program like [`alu`], panicking instead of returning an error.
+///
+/// # Panics
+/// The program panics if [`alu`] returns an error
+pub fn alu_or_panic(
+    program: impl IntoIterator<Item = u8>,
+    input: impl IntoIterator<Item = u8>,
+    out: &mut impl Write,
+) -> StopReason {
+    alu(program, input, out).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Executes the program like [`alu`], starting from the initial state instead of a cleared
+/// machine.
+///
+/// # Errors
+/// Returns an error if an invalid instruction was found, the program ended unexpectedly, writing
+/// the output failed, or the program is larger than 256 bytes
+pub fn alu_with_initial_state(
+    program: impl IntoIterator<Item = u8>,
+    initial: &InitialState,
+    input: impl IntoIterator<Item = u8>,
+    out: &mut impl Write,
+) -> Result<StopReason, ExecutionError> {
+    with_console(input, out, |bus| {
+        Computer::with_initial_state(program, initial, bus)?.run()
+    })
+}
+
+/// Executes the program like [`alu`], returning the state of the machine once it stopped.
+///
+/// # Errors
+/// Returns an error if an invalid instruction was found, the program ended unexpectedly, writing
+/// the output failed, or the program is larger than 256 bytes
+pub fn alu_with_state(
+    program: impl IntoIterator<Item = u8>,
+    input: impl IntoIterator<Item = u8>,
+    out: &mut impl Write,
+) -> Result<MachineState, ExecutionError> {
+    with_console(input, out, |bus| {
+        let mut computer = Computer::new(program, bus)?;
+        computer.run()?;
+        Ok(computer.state())
+    })
+}
+
+/// Executes the program like [`alu`], passing the state after every executed instruction to the
+/// tracer.
+///
+/// # Errors
+/// Returns an error if an invalid instruction was found, the program ended unexpectedly, writing
+/// the output failed, or the program is larger than 256 bytes
+pub fn alu_traced(
+    program: impl IntoIterator<Item = u8>,
+    input: impl IntoIterator<Item = u8>,
+    out: &mut impl Write,
+    tracer: impl FnMut(TraceEvent),
+) -> Result<StopReason, ExecutionError> {
+    with_console(input, out, |bus| {
+        Computer::new(program, bus)?.run_traced(tracer)
+    })
+}
+
+/// Executes the program like [`alu`], passing every byte stored at address 255 to the sink.
+///
+/// Unlike the console, the bytes aren't converted to characters, so programs can write binary
+/// data.
+///
+/// # Errors
+/// Returns an error if an invalid instruction was found, the program ended unexpectedly, the sink
+/// failed, or the program is larger than 256 bytes
+pub fn alu_with_sink(
+    program: impl IntoIterator<Item = u8>,
+    input: impl IntoIterator<Item = u8>,
+    sink: &mut impl OutputSink,
+) -> Result<StopReason, ExecutionError> {
+    let mut output = OutputDevice::new(sink);
+    with_output(input, &mut output, |bus| Computer::new(program, bus)?.run())
+}
+
+/// Runs `run` with a bus reading the input at address 254 and writing the output at 255
+fn with_console<R>(
+    input: impl IntoIterator<Item = u8>,
+    out: &mut impl Write,
+    run: impl FnOnce(Bus<'_>) -> R,
+) -> R {
+    with_output(input, &mut ConsoleDevice::new(out), run)
+}
+
+/// Runs `run` with a bus reading the input at address 254 and the output device at 255
+fn with_output<R>(
+    input: impl IntoIterator<Item = u8>,
+    output: &mut dyn Device,
+    run: impl FnOnce(Bus<'_>) -> R,
+) -> R {
+    let mut input = InputStream::new(input);
+    run(Bus::new()
+        .with_device(254..=254, &mut input)
+        .with_device(255..=255, output))
+}
+
 #[cfg(test)]
 mod tests {
-    use core::array;
+    use core::{array, fmt::Write};
+
+    use heapless::{String, Vec};
+
+    use crate::{
+        alu, alu_or_panic, alu_traced, alu_with_initial_state, alu_with_sink, alu_with_state,
+        bus::{FmtWriteSink, OutputSink},
+        byte::Byte,
+        mux::byte::REGISTER_COUNT,
+        state::{InitialState, MachineState},
+        with_console,
+        word::{Word, WordType},
+        Computer, ExecutionError, ExecutionModel, StopReason, TraceEvent,
+    };
+
+    /// Runs the code on the machine of the word type with the console, passing the computer to
+    /// `inspect` once it stopped
+    fn run_on<W: WordType>(
+        code: &[u8],
+        out: &mut impl Write,
+        inspect: impl FnOnce(&Computer<'_, W>),
+    ) -> Result<StopReason, ExecutionError> {
+        with_console([], out, |bus| {
+            let mut computer = Computer::<W>::load_program(
+                code.iter().copied(),
+                &InitialState::new(),
+                bus,
+                ExecutionModel::VonNeumann,
+            )?;
+            let reason = computer.run();
+            inspect(&computer);
+            reason
+        })
+    }
+
+    /// Prints hello world on the machine of the word type
+    fn hello_world_on<W: WordType>() {
+        let mut output = String::<20>::new();
+        let expected = b"Hello, world!";
+        let code: [u8; 52] = array::from_fn(|i| match i % 4 {
+            0 => 0,
+            1 => expected[i / 4],
+            2 => 8,
+            3 => 255,
+            _ => unreachable!(),
+        });
+        let reason = run_on::<W>(&code, &mut output, |computer| {
+            assert_eq!(computer.registers()[0], W::Value::from(b'!'));
+        });
+        assert_eq!(reason, Ok(StopReason::EndOfProgram));
+        assert_eq!(output.as_bytes(), expected);
+    }
+
+    /// Stores every byte, fails once full
+    struct Bytes(Vec<u8, 4>);
+
+    impl OutputSink for Bytes {
+        fn write_byte(&mut self, byte: u8) -> bool {
+            self.0.push(byte).is_ok()
+        }
+    }
 
-    use heapless::String;
+    #[test]
+    fn binary_output() {
+        // r0 = 0xFF, [255] = r0
+        let code = [0, 0xFF, 8, 255];
+        let mut sink = Bytes(Vec::new());
+        assert_eq!(
+            alu_with_sink(code, [], &mut sink),
+            Ok(StopReason::EndOfProgram)
+        );
+        assert_eq!(sink.0, [0xFF]);
 
-    use crate::alu;
+        // The console writes the character U+00FF, which takes 2 bytes of UTF-8
+        let mut text = String::<4>::new();
+        alu(code, [], &mut text).unwrap();
+        assert_eq!(text.as_bytes(), [0xC3, 0xBF]);
+        let mut adapter = FmtWriteSink::new(String::<4>::new());
+        assert_eq!(
+            alu_with_sink(code, [], &mut adapter),
+            Ok(StopReason::EndOfProgram)
+        );
+        assert_eq!(adapter.into_output(), text);
+
+        let full: [u8; 20] = array::from_fn(|i| code[i % 4]);
+        let mut sink = Bytes(Vec::new());
+        assert_eq!(
+            alu_with_sink(full, [], &mut sink),
+            Err(ExecutionError::OutputError { offset: 18 })
+        );
+    }
 
     #[test]
     fn hello_world() {
+        hello_world_on::<Byte>();
+        hello_world_on::<Word>();
+    }
+
+    #[test]
+    fn trace_hello_world() {
         let mut output = String::<20>::new();
         let expected = b"Hello, world!";
         let code: [u8; 52] = array::from_fn(|i| match i % 4 {
@@ -117,7 +421,293 @@ mod tests {
             3 => 255,
             _ => unreachable!(),
         });
-        alu(code.into_iter(), &mut output);
-        assert_eq!(output.as_bytes(), expected);
+        let mut trace = Vec::<TraceEvent, 32>::new();
+        let reason = alu_traced(code, [], &mut output, |event| trace.push(event).unwrap());
+        assert_eq!(reason, Ok(StopReason::EndOfProgram));
+        assert_eq!(trace.len(), 26);
+        for (i, (event, &character)) in trace.chunks(2).zip(expected).enumerate() {
+            let load = TraceEvent {
+                offset: 4 * i,
+                opcode: 0,
+                registers: [character, 0, 0, 0, 0, 0, 0, 0],
+                overflow: false,
+            };
+            let store = TraceEvent {
+                offset: 4 * i + 2,
+                opcode: 8,
+                ..load
+            };
+            assert_eq!(event, [load, store]);
+        }
+    }
+
+    /// The code of the arithmetic tests
+    const ADD_SUB: [u8; 14] = [
+        0, 200, 1, 100,  // r0 = 200, r1 = 100
+        0x81, // r0 += r1, overflows a byte
+        8, 0x10, // store r0 at 0x10
+        2, 5, 3, 7,    // r2 = 5, r3 = 7
+        0xAB, // r2 -= r3, borrows
+        0xC8, 0xCF, // push r0, pop r3
+    ];
+
+    /// Runs [`ADD_SUB`] on the machine of the word type, expecting the registers and the stack
+    /// bytes after it
+    fn add_sub_on<W: WordType>(registers: [W::Value; REGISTER_COUNT], stack: [u8; 2]) {
+        let mut output = String::<4>::new();
+        let reason = run_on::<W>(&ADD_SUB, &mut output, |computer| {
+            assert_eq!(computer.registers(), registers);
+            assert_eq!([computer.memory(0xF0), computer.memory(0xF1)], stack);
+            assert_eq!(computer.memory(0x10), 44);
+            assert_eq!(computer.stack_pointer(), 0xF0);
+            assert!(!computer.overflow());
+        });
+        assert_eq!(reason, Ok(StopReason::EndOfProgram));
+    }
+
+    #[test]
+    fn add_sub_state() {
+        add_sub_on::<Byte>([44, 100, 254, 44, 0, 0, 0, 0], [44, 0]);
+        // 300 fits in 16 bits and takes 2 bytes of the stack, the store writes its low byte
+        add_sub_on::<Word>([300, 100, 0xFFFE, 300, 0, 0, 0, 0], [44, 1]);
+
+        let mut output = String::<4>::new();
+        // Memory holds the program as well
+        let mut memory = [0; 256];
+        memory[..ADD_SUB.len()].copy_from_slice(&ADD_SUB);
+        memory[0x10] = 44;
+        memory[0xF0] = 44;
+        let expected = MachineState {
+            registers: [44, 100, 254, 44, 0, 0, 0, 0],
+            memory,
+            overflow: false,
+            zero: false,
+            negative: true,
+            half_carry: false,
+            stack_pointer: 0xF0,
+        };
+        assert_eq!(alu_with_state(ADD_SUB, [], &mut output), Ok(expected));
+
+        let code = [0, 5, 1, 5, 0xA1, 8, 255]; // r0 -= r1 doesn't borrow, print r0
+        let state = alu_with_state(code, [], &mut output).unwrap();
+        assert_eq!(state.registers, [0, 5, 0, 0, 0, 0, 0, 0]);
+        assert!(state.overflow && state.zero && !state.negative);
+        assert_eq!(state.memory[..code.len()], code);
+        assert_eq!(output, "\0");
+    }
+
+    #[test]
+    fn decimal_adjust_on_the_byte_machine_only() {
+        // daa r0; hlt
+        let code = [0xFC, 0x00, 0xFF];
+        let mut output = String::<4>::new();
+        assert_eq!(
+            run_on::<Byte>(&code, &mut output, |_| ()),
+            Ok(StopReason::Halted)
+        );
+        assert_eq!(
+            run_on::<Word>(&code, &mut output, |_| ()),
+            Err(ExecutionError::InvalidInstruction {
+                offset: 0,
+                byte: 0xFC
+            })
+        );
+    }
+
+    #[test]
+    fn indirect_input_and_output() {
+        let mut output = String::<4>::new();
+        let code = [
+            1, 254, 2, 255, // r1 = the input address, r2 = the output address
+            0xF2, 0x01, 0xF3, 0x02, // r0 = [r1], [r2] = r0
+            0xF2, 0x01, 0xF3, 0x02, // r0 = [r1], [r2] = r0
+        ];
+        assert_eq!(alu(code, *b"Hi", &mut output), Ok(StopReason::EndOfProgram));
+        assert_eq!(output, "Hi");
+    }
+
+    #[test]
+    fn halt() {
+        let mut output = String::<4>::new();
+        // The invalid instruction after the halt is never executed
+        let code = [0, b'A', 8, 255, 0xFF, 0xFE, 0, b'B', 8, 255];
+        assert_eq!(alu(code, [], &mut output), Ok(StopReason::Halted));
+        assert_eq!(output, "A");
+    }
+
+    #[test]
+    fn invalid_instruction() {
+        let mut output = String::<4>::new();
+        let code = [0, b'A', 0x10, 0xFE, 8, 255];
+        assert_eq!(
+            alu(code, [], &mut output),
+            Err(ExecutionError::InvalidInstruction {
+                offset: 3,
+                byte: 0xFE
+            })
+        );
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn truncated_operand() {
+        let mut output = String::<4>::new();
+        let code = [0, b'A', 8, 255, 1];
+        assert_eq!(
+            alu(code, [], &mut output),
+            Err(ExecutionError::TruncatedOperand { offset: 4 })
+        );
+        assert_eq!(output, "A");
+    }
+
+    #[test]
+    fn output_error() {
+        let mut output = String::<1>::new();
+        let code = [0, b'A', 8, 255, 8, 255];
+        assert_eq!(
+            alu(code, [], &mut output),
+            Err(ExecutionError::OutputError { offset: 4 })
+        );
+    }
+
+    #[test]
+    fn program_too_large() {
+        let mut output = String::<1>::new();
+        assert_eq!(
+            alu([0x10; 257], [], &mut output),
+            Err(ExecutionError::ProgramTooLarge)
+        );
+        assert_eq!(
+            alu([0x10; 256], [], &mut output),
+            Ok(StopReason::EndOfProgram)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid instruction 0xFE at offset 0")]
+    fn panicking_wrapper() {
+        let mut output = String::<1>::new();
+        alu_or_panic([0xFE], [], &mut output);
+    }
+
+    #[test]
+    fn initial_state() {
+        let mut output = String::<4>::new();
+        let initial = InitialState::new()
+            .with_registers([b'A', 1, 0, 0])
+            .with_overflow(true)
+            .with_memory(0x10, b'X')
+            .with_memory(0x10, b'C');
+        let code = [
+            8, 255,  // print r0
+            0x91, // r0 += r1 + overflow
+            8, 255, // print r0
+            4, 0x10, 8, 255, // print memory 0x10
+        ];
+        alu_with_initial_state(code, &initial, [], &mut output).unwrap();
+        assert_eq!(output, "ACC");
+    }
+
+    #[test]
+    fn program_overrides_initial_state() {
+        let mut output = String::<4>::new();
+        let initial = InitialState::new()
+            .with_registers([b'A', 0, 0, 0])
+            .with_memory(0x10, b'B');
+        let code = [0, b'C', 8, 0x10, 4, 0x10, 8, 255];
+        alu_with_initial_state(code, &initial, [], &mut output).unwrap();
+        assert_eq!(output, "C");
+    }
+
+    #[test]
+    fn counting_loop() {
+        let mut output = String::<10>::new();
+        let code = [
+            0, b'0', // r0 = '0'
+            1, 10, // r1 = 10
+            2, 1, // r2 = 1
+            8, 255,  // loop: print r0
+            0x82, // r0 += r2
+            0xA6, // r1 -= r2
+            0xC5, 14, // jump to end if r1 is zero
+            0xC0, 6, // jump to loop
+        ];
+        alu(code, [], &mut output).unwrap();
+        assert_eq!(output, "0123456789");
+    }
+
+    #[test]
+    fn jump_if_overflow() {
+        let mut output = String::<4>::new();
+        let code = [
+            0, 200,  // r0 = 200
+            0x80, // r0 += r0, overflows
+            0xC1, 9, // jump over the first print
+            0, b'A', 8, 255, // print 'A'
+            0, b'B', 8, 255,  // print 'B'
+            0x85, // r1 += r1, doesn't overflow
+            0xC1, 20, // doesn't jump to the end
+            0, b'C', 8, 255, // print 'C'
+        ];
+        alu(code, [], &mut output).unwrap();
+        assert_eq!(output, "BC");
+    }
+
+    #[test]
+    fn shift_buffer_left() {
+        let buffer = [0x81_u8, 0x7F, 0x00, 0xFF, 0x55, 0xAA, 0x80, 0x01];
+        // The buffer is placed after the code
+        let mut code = Vec::<u8, 80>::new();
+        for (i, byte) in (0xA0..).zip(buffer) {
+            code.extend_from_slice(&[0, byte, 8, i]).unwrap();
+        }
+        // Shift the lowest byte, then rotate the carry through the higher ones
+        code.extend_from_slice(&[0xE0, 0xA0]).unwrap();
+        for i in 0xA1..0xA8 {
+            code.extend_from_slice(&[0xE2, i]).unwrap();
+        }
+        for i in 0xA0..0xA8 {
+            code.extend_from_slice(&[4, i, 8, 255]).unwrap();
+        }
+
+        let mut output = String::<32>::new();
+        alu(code, [], &mut output).unwrap();
+        let shifted = (u64::from_le_bytes(buffer) << 1).to_le_bytes();
+        assert!(output.chars().map(|c| c as u32).eq(shifted.map(u32::from)));
+    }
+
+    #[test]
+    fn shift_memory_right_and_carry() {
+        // 0x01 >> 1 sets the overflow, which is rotated into 0x00 as the top bit
+        let code = [0, 0x01, 8, 0x20, 0xE1, 0x20, 0xE3, 0x21, 4, 0x21, 8, 255];
+        let mut output = String::<4>::new();
+        alu(code, [], &mut output).unwrap();
+        assert!(output.chars().eq(['\u{80}']));
+    }
+
+    #[test]
+    fn shift_memory_bypasses_output() {
+        // The shift itself writes nothing, only the following store to 255 does
+        let code = [0, b'A', 8, 255, 0xE0, 255, 4, 255, 8, 255];
+        let mut output = String::<4>::new();
+        alu(code, [], &mut output).unwrap();
+        assert!(output.chars().eq(['A', char::from(b'A' << 1)]));
+    }
+
+    #[test]
+    fn echo() {
+        // Copies the input to the output until a 0 is read
+        let code = [
+            4, 254, // loop: r0 = input
+            0xC4, 8, // jump to end if r0 is zero
+            8, 255, // print r0
+            0xC0, 0, // jump to loop
+        ];
+        let mut output = String::<8>::new();
+        assert_eq!(
+            alu(code, *b"echo", &mut output),
+            Ok(StopReason::EndOfProgram)
+        );
+        assert_eq!(output, "echo");
     }
 }
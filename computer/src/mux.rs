@@ -2,3 +2,4 @@
 
 pub mod bit;
 pub mod byte;
+pub mod word;
@@ -1,111 +1,191 @@
 //! Muxes interacting on bits
 
-use core::array;
+use crate::bit::{gate, Bit};
 
-use crate::bit::Bit;
-
-/// Returns the left bit if `select` is `Bit::Low`, returns right bit otherwise
-pub const fn mux(left: Bit, right: Bit, select: Bit) -> Bit {
-    left.and(select.not()).or(right.and(select))
+gate! {
+    /// Returns the left bit if `select` is `Bit::Low`, returns right bit otherwise
+    pub fn mux(left: Bit, right: Bit, select: Bit) -> Bit {
+        left.and(select.not()).or(right.and(select))
+    }
 }
 
-/// `select[0]` adds 1 to the index if `Bit::High`.
-/// `select[1]` adds 2 to the index if `Bit::High`.
+/// Every select bit adds (1 << index) if `Bit::High`.
 /// Returns the bit at the resulting index.
-pub const fn mux4(input: [Bit; 4], select: [Bit; 2]) -> Bit {
-    mux(
-        mux(input[0], input[1], select[0]),
-        mux(input[2], input[3], select[0]),
-        select[1],
-    )
+///
+/// A tree of 2-way muxes: `select[0]` picks between the neighbouring inputs, every next select bit
+/// between the neighbouring outputs of the layer before. `N` has to be `2^S`.
+pub fn mux_n<const N: usize, const S: usize>(input: [Bit; N], select: [Bit; S]) -> Bit {
+    const { assert!(N == 1 << S, "a mux needs 2^S inputs") };
+    let mut layer = input;
+    let mut length = N;
+    for select in select {
+        length /= 2;
+        for i in 0..length {
+            layer[i] = mux(layer[2 * i], layer[2 * i + 1], select);
+        }
+    }
+    layer[0]
+}
+
+gate! {
+    /// `select[0]` adds 1 to the index if `Bit::High`.
+    /// `select[1]` adds 2 to the index if `Bit::High`.
+    /// Returns the bit at the resulting index.
+    pub fn mux4(input: [Bit; 4], select: [Bit; 2]) -> Bit {
+        mux(
+            mux(input[0], input[1], select[0]),
+            mux(input[2], input[3], select[0]),
+            select[1],
+        )
+    }
+}
+
+gate! {
+    /// `select[0]` adds 1 to the index, `select[1]` adds 2 and `select[2]` adds 4 if `Bit::High`.
+    /// Returns the bit at the resulting index, the 4-way muxes pick in both halves and `select[2]`
+    /// between them.
+    pub fn mux8(input: [Bit; 8], select: [Bit; 3]) -> Bit {
+        let [i0, i1, i2, i3, i4, i5, i6, i7] = input;
+        let [s0, s1, s2] = select;
+        mux(
+            mux4([i0, i1, i2, i3], [s0, s1]),
+            mux4([i4, i5, i6, i7], [s0, s1]),
+            s2,
+        )
+    }
 }
 
 /// Every select bit adds (1 << index) if `Bit::High`.
 /// Returns the bit at the resulting index
-#[expect(clippy::missing_panics_doc)]
 pub fn mux16(input: [Bit; 16], select: [Bit; 4]) -> Bit {
-    mux4(
-        array::from_fn(|i| {
-            mux4(
-                input[i * 4..i * 4 + 4].try_into().unwrap(),
-                select[..2].try_into().unwrap(),
-            )
-        }),
-        select[2..4].try_into().unwrap(),
-    )
+    mux_n(input, select)
 }
 
 /// Every select bit adds (1 << index) if `Bit::High`.
 /// Returns the bit at the resulting index
-#[expect(clippy::missing_panics_doc)]
 pub fn mux256(input: [Bit; 256], select: [Bit; 8]) -> Bit {
-    mux16(
-        array::from_fn(|i| {
-            mux16(
-                input[i * 16..i * 16 + 16].try_into().unwrap(),
-                select[..4].try_into().unwrap(),
-            )
-        }),
-        select[4..8].try_into().unwrap(),
-    )
+    mux_n(input, select)
 }
 
-/// Returns input bit as left bit, if select is `Bit::Low`, returns input bit as right bit
-/// otherwise. Other bit will be `Bit::Low`.
-pub const fn dmux(input: Bit, select: Bit) -> (Bit, Bit) {
-    (input.and(select.not()), input.and(select))
+gate! {
+    /// Returns the left bit if `select` is `Bit::Low` and the right bit otherwise while `enable`
+    /// is `Bit::High`, returns `Bit::Low` while it is `Bit::Low`
+    pub fn mux_en(left: Bit, right: Bit, select: Bit, enable: Bit) -> Bit {
+        mux(left, right, select).and(enable)
+    }
+}
+
+gate! {
+    /// Returns input bit as left bit, if select is `Bit::Low`, returns input bit as right bit
+    /// otherwise. Other bit will be `Bit::Low`.
+    pub fn dmux(input: Bit, select: Bit) -> (Bit, Bit) {
+        (input.and(select.not()), input.and(select))
+    }
+}
+
+/// Returns input bit as selected bit.
+/// Other bits will be `Bit::Low`.
+/// select[0] is 1, every next index is twice as high as the previous.
+///
+/// A tree of 2-way dmuxes: the highest select bit splits the input in two, every lower one splits
+/// each output of the layer before. `N` has to be `2^S`.
+pub fn dmux_n<const N: usize, const S: usize>(input: Bit, select: [Bit; S]) -> [Bit; N] {
+    const { assert!(N == 1 << S, "a dmux needs 2^S outputs") };
+    let mut outputs = [Bit::Low; N];
+    outputs[0] = input;
+    for (length, select) in (0..S).map(|level| 1 << level).zip(select.into_iter().rev()) {
+        // From the end, so every output is split before it is overwritten
+        for i in (0..length).rev() {
+            (outputs[2 * i], outputs[2 * i + 1]) = dmux(outputs[i], select);
+        }
+    }
+    outputs
 }
 
 /// Returns input bit as selected bit.
 /// Other bits will be `Bit::Low`.
 /// select[0] is 1, every next index is twice as high as the previous.
 pub fn dmux4(input: Bit, select: [Bit; 2]) -> [Bit; 4] {
-    array::from_fn(|i| {
-        Bit::from(
-            select
-                .iter()
-                .enumerate()
-                .all(|(j, bit)| &Bit::from((i >> j) & 1 == 1) == bit),
-        )
-        .and(input)
-    })
+    dmux_n(input, select)
+}
+
+/// Returns input bit as selected bit.
+/// Other bits will be `Bit::Low`.
+/// select[0] is 1, every next index is twice as high as the previous.
+pub fn dmux8(input: Bit, select: [Bit; 3]) -> [Bit; 8] {
+    dmux_n(input, select)
 }
 
 /// Returns input bit as selected bit.
 /// Other bits will be `Bit::Low`.
 /// select[0] is 1, every next index is twice as high as the previous.
 pub fn dmux16(input: Bit, select: [Bit; 4]) -> [Bit; 16] {
-    array::from_fn(|i| {
-        Bit::from(
-            select
-                .iter()
-                .enumerate()
-                .all(|(j, bit)| &Bit::from((i >> j) & 1 == 1) == bit),
-        )
-        .and(input)
-    })
+    dmux_n(input, select)
 }
 
 /// Returns input bit as selected bit.
 /// Other bits will be `Bit::Low`.
 /// select[0] is 1, every next index is twice as high as the previous.
 pub fn dmux256(input: Bit, select: [Bit; 8]) -> [Bit; 256] {
-    array::from_fn(|i| {
-        Bit::from(
-            select
-                .iter()
-                .enumerate()
-                .all(|(j, bit)| &Bit::from((i >> j) & 1 == 1) == bit),
-        )
-        .and(input)
-    })
+    dmux_n(input, select)
+}
+
+/// Returns input bit as selected bit like [`dmux4`] while `enable` is `Bit::High`, every bit is
+/// `Bit::Low` while it is `Bit::Low`
+pub fn dmux4_en(input: Bit, select: [Bit; 2], enable: Bit) -> [Bit; 4] {
+    dmux4(input.and(enable), select)
+}
+
+/// Returns input bit as selected bit like [`dmux16`] while `enable` is `Bit::High`, every bit is
+/// `Bit::Low` while it is `Bit::Low`
+pub fn dmux16_en(input: Bit, select: [Bit; 4], enable: Bit) -> [Bit; 16] {
+    dmux16(input.and(enable), select)
+}
+
+/// Returns input bit as selected bit like [`dmux256`] while `enable` is `Bit::High`, every bit is
+/// `Bit::Low` while it is `Bit::Low`
+pub fn dmux256_en(input: Bit, select: [Bit; 8], enable: Bit) -> [Bit; 256] {
+    dmux256(input.and(enable), select)
+}
+
+/// Returns the select bits as a one-hot output, only the selected bit is `Bit::High`.
+/// select[0] is 1, select[1] is 2.
+pub fn decoder2(select: [Bit; 2]) -> [Bit; 4] {
+    dmux4(Bit::High, select)
+}
+
+/// Returns the select bits as a one-hot output, only the selected bit is `Bit::High`.
+/// select[0] is 1, every next index is twice as high as the previous.
+pub fn decoder3(select: [Bit; 3]) -> [Bit; 8] {
+    dmux8(Bit::High, select)
+}
+
+gate! {
+    /// Returns the index of the highest bit that is `Bit::High`, with bit 0 of the index first, and
+    /// whether any bit is high. The index is 0 when none is.
+    pub fn priority_encoder8(input: [Bit; 8]) -> ([Bit; 3], Bit) {
+        let [i0, i1, i2, i3, i4, i5, i6, i7] = input;
+        let (upper, lower) = (i4.or(i5).or(i6.or(i7)), i0.or(i1).or(i2.or(i3)));
+        // Every index bit is high when a high bit with it in its index isn't masked by a higher one
+        let index = [
+            i7.or(i6.not().and(i5))
+                .or(i6.nor(i4).and(i3))
+                .or(i6.nor(i4).and(i2.not()).and(i1)),
+            i7.or(i6).or(i5.nor(i4).and(i3.or(i2))),
+            upper,
+        ];
+        (index, upper.or(lower))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use core::array;
 
-    use super::{dmux4, mux4};
+    use super::{
+        decoder2, decoder3, dmux16, dmux16_en, dmux256, dmux256_en, dmux4, dmux4_en, dmux8, dmux_n,
+        mux16, mux256, mux4, mux8, mux_en, mux_n, priority_encoder8,
+    };
     use crate::{
         bit::Bit,
         mux::bit::{dmux, mux},
@@ -135,6 +215,31 @@ mod tests {
         }
     }
 
+    /// The gates are only `const fn` without the counter of the `gate-count` feature
+    #[cfg(not(feature = "gate-count"))]
+    #[test]
+    fn const_mux4() {
+        const SELECTED: [Bit; 4] = [
+            mux4(
+                [Bit::High, Bit::Low, Bit::Low, Bit::Low],
+                [Bit::Low, Bit::Low],
+            ),
+            mux4(
+                [Bit::Low, Bit::High, Bit::Low, Bit::Low],
+                [Bit::High, Bit::Low],
+            ),
+            mux4(
+                [Bit::Low, Bit::Low, Bit::High, Bit::Low],
+                [Bit::Low, Bit::High],
+            ),
+            mux4(
+                [Bit::High, Bit::High, Bit::High, Bit::Low],
+                [Bit::High, Bit::High],
+            ),
+        ];
+        assert_eq!(SELECTED, [Bit::High, Bit::High, Bit::High, Bit::Low]);
+    }
+
     #[test]
     fn dmux_test() {
         assert_eq!(dmux(Bit::Low, Bit::Low), (Bit::Low, Bit::Low));
@@ -160,4 +265,175 @@ mod tests {
             }
         }
     }
+
+    /// Returns the bits of the index, bit 0 first
+    fn bits<const N: usize>(index: usize) -> [Bit; N] {
+        array::from_fn(|j| Bit::from((index >> j) & 1 == 1))
+    }
+
+    #[test]
+    fn mux8_truth_table() {
+        for input in 0..256 {
+            for select in 0..8 {
+                assert_eq!(
+                    mux8(bits(input), bits(select)),
+                    Bit::from((input >> select) & 1 == 1),
+                    "{input:#010b}, {select}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn dmux8_truth_table() {
+        for select in 0..8 {
+            for input in [Bit::Low, Bit::High] {
+                let output = dmux8(input, bits(select));
+                assert_eq!(
+                    output,
+                    array::from_fn(|i| input.and(Bit::from(i == select)))
+                );
+                assert_eq!(mux8(output, bits(select)), input);
+            }
+        }
+    }
+
+    #[test]
+    fn mux16_test() {
+        for select in 0..16 {
+            // Only the selected input differs from the others
+            let one_hot = array::from_fn(|i| Bit::from(i == select));
+            assert_eq!(mux16(one_hot, bits(select)), Bit::High);
+            let one_cold = array::from_fn(|i| Bit::from(i != select));
+            assert_eq!(mux16(one_cold, bits(select)), Bit::Low);
+        }
+    }
+
+    #[test]
+    fn dmux16_test() {
+        for select in 0..16 {
+            let output = dmux16(Bit::High, bits(select));
+            assert_eq!(output, array::from_fn(|i| Bit::from(i == select)));
+            assert_eq!(dmux16(Bit::Low, bits(select)), [Bit::Low; 16]);
+        }
+    }
+
+    #[test]
+    fn mux256_every_index() {
+        for select in 0..256 {
+            let one_hot = array::from_fn(|i| Bit::from(i == select));
+            assert_eq!(mux256(one_hot, bits(select)), Bit::High, "{select}");
+            let one_cold = array::from_fn(|i| Bit::from(i != select));
+            assert_eq!(mux256(one_cold, bits(select)), Bit::Low, "{select}");
+        }
+    }
+
+    #[test]
+    fn dmux256_every_index() {
+        for select in 0..256 {
+            let output = dmux256(Bit::High, bits(select));
+            assert_eq!(
+                output,
+                array::from_fn(|i| Bit::from(i == select)),
+                "{select}"
+            );
+            // The demultiplexed bit goes back through the mux
+            assert_eq!(mux256(output, bits(select)), Bit::High);
+            assert_eq!(dmux256(Bit::Low, bits(select)), [Bit::Low; 256]);
+        }
+    }
+
+    /// Checks the generic mux and dmux with every select value at the size
+    fn every_select<const N: usize, const S: usize>() {
+        for select in 0..N {
+            let one_hot: [Bit; N] = array::from_fn(|i| Bit::from(i == select));
+            assert_eq!(
+                mux_n(one_hot, bits::<S>(select)),
+                Bit::High,
+                "{N}: {select}"
+            );
+            let one_cold: [Bit; N] = array::from_fn(|i| Bit::from(i != select));
+            assert_eq!(
+                mux_n(one_cold, bits::<S>(select)),
+                Bit::Low,
+                "{N}: {select}"
+            );
+            assert_eq!(dmux_n::<N, S>(Bit::High, bits(select)), one_hot, "{N}");
+            assert_eq!(dmux_n::<N, S>(Bit::Low, bits(select)), [Bit::Low; N]);
+        }
+    }
+
+    #[test]
+    fn generic_sizes() {
+        every_select::<2, 1>();
+        every_select::<8, 3>();
+        every_select::<32, 5>();
+        every_select::<64, 6>();
+    }
+
+    #[test]
+    fn enable() {
+        for input in 0..8 {
+            let [left, right, select] = bits(input);
+            assert_eq!(
+                mux_en(left, right, select, Bit::High),
+                mux(left, right, select)
+            );
+            assert_eq!(mux_en(left, right, select, Bit::Low), Bit::Low);
+        }
+        for select in 0..256 {
+            assert_eq!(
+                dmux256_en(Bit::High, bits(select), Bit::Low),
+                [Bit::Low; 256]
+            );
+            assert_eq!(
+                dmux256_en(Bit::High, bits(select), Bit::High),
+                dmux256(Bit::High, bits(select))
+            );
+            if select < 16 {
+                assert_eq!(dmux16_en(Bit::High, bits(select), Bit::Low), [Bit::Low; 16]);
+                assert_eq!(
+                    dmux16_en(Bit::High, bits(select), Bit::High),
+                    dmux16(Bit::High, bits(select))
+                );
+            }
+            if select < 4 {
+                assert_eq!(dmux4_en(Bit::High, bits(select), Bit::Low), [Bit::Low; 4]);
+                assert_eq!(
+                    dmux4_en(Bit::High, bits(select), Bit::High),
+                    dmux4(Bit::High, bits(select))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decoders() {
+        for select in 0..4 {
+            assert_eq!(
+                decoder2(bits(select)),
+                array::from_fn(|i| Bit::from(i == select))
+            );
+        }
+        for select in 0..8 {
+            assert_eq!(
+                decoder3(bits(select)),
+                array::from_fn(|i| Bit::from(i == select))
+            );
+        }
+    }
+
+    #[test]
+    fn priority_encoder() {
+        for input in 0_u8..=255 {
+            let (index, valid) = priority_encoder8(bits(usize::from(input)));
+            let highest = input.checked_ilog2().unwrap_or(0);
+            assert_eq!(
+                index,
+                bits(usize::try_from(highest).unwrap()),
+                "{input:#010b}"
+            );
+            assert_eq!(valid, Bit::from(input != 0));
+        }
+    }
 }
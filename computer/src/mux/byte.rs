@@ -1,8 +1,16 @@
 //! Circuits interacting on bytes
 
-use core::array;
+use core::{
+    array,
+    fmt::{self, Debug, Display, Formatter},
+};
 
-use crate::{bit::Bit, byte::Byte};
+use crate::{
+    bit::{gate, Bit},
+    byte::Byte,
+    fast,
+    seq::RegisterCell,
+};
 
 use super::bit::{self, mux as bit_mux};
 
@@ -13,45 +21,58 @@ pub fn mux(left: Byte, right: Byte, select: Bit) -> Byte {
     Byte::from(array::from_fn(|i| bit_mux(left[i], right[i], select)))
 }
 
+/// Every select bit adds (1 << index) if `Bit::High`.
+/// Returns the byte at the resulting index.
+///
+/// A tree of 2-way muxes like [`bit::mux_n`], `N` has to be `2^S`.
+pub fn mux_n<const N: usize, const S: usize>(input: [Byte; N], select: [Bit; S]) -> Byte {
+    const { assert!(N == 1 << S, "a mux needs 2^S inputs") };
+    let mut layer = input;
+    let mut length = N;
+    for select in select {
+        length /= 2;
+        for i in 0..length {
+            layer[i] = mux(layer[2 * i], layer[2 * i + 1], select);
+        }
+    }
+    layer[0]
+}
+
+/// Returns the left byte if `select` is `Bit::Low` and the right byte otherwise while `enable` is
+/// `Bit::High`, returns 0 while it is `Bit::Low`
+pub fn mux_en(left: Byte, right: Byte, select: Bit, enable: Bit) -> Byte {
+    enabled(mux(left, right, select), enable)
+}
+
+/// Returns the byte while `enable` is `Bit::High` and 0 otherwise, an and gate per bit
+fn enabled(input: Byte, enable: Bit) -> Byte {
+    let input: [Bit; 8] = input.into();
+    Byte::from(input.map(|bit| bit.and(enable)))
+}
+
 /// `select[0]` adds 1 to the index if `Bit::High`.
 /// `select[1]` adds 2 to the index if `Bit::High`.
 /// Returns the byte at the resulting index.
 pub fn mux4(input: [Byte; 4], select: [Bit; 2]) -> Byte {
-    mux(
-        mux(input[0], input[1], select[0]),
-        mux(input[2], input[3], select[0]),
-        select[1],
-    )
+    mux_n(input, select)
 }
 
 /// Every select bit adds (1 << index) if `Bit::High`.
 /// Returns the byte at the resulting index
-#[expect(clippy::missing_panics_doc)]
 pub fn mux16(input: [Byte; 16], select: [Bit; 4]) -> Byte {
-    mux4(
-        array::from_fn(|i| {
-            mux4(
-                input[i * 4..i * 4 + 4].try_into().unwrap(),
-                select[..2].try_into().unwrap(),
-            )
-        }),
-        select[2..4].try_into().unwrap(),
-    )
+    mux_n(input, select)
 }
 
 /// Every select bit adds (1 << index) if `Bit::High`.
 /// Returns the byte at the resulting index
-#[expect(clippy::missing_panics_doc)]
 pub fn mux256(input: [Byte; 256], select: [Bit; 8]) -> Byte {
-    mux16(
-        array::from_fn(|i| {
-            mux16(
-                input[i * 16..i * 16 + 16].try_into().unwrap(),
-                select[..4].try_into().unwrap(),
-            )
-        }),
-        select[4..8].try_into().unwrap(),
-    )
+    mux_n(input, select)
+}
+
+/// `select[0]` adds 1 to the index, `select[1]` adds 2 and `select[2]` adds 4 if `Bit::High`.
+/// Returns the byte at the resulting index.
+pub fn mux8(input: [Byte; 8], select: [Bit; 3]) -> Byte {
+    mux_n(input, select)
 }
 
 /// Returns input bit as left bit, if select is `Bit::Low`, returns input bit as right bit
@@ -64,61 +85,366 @@ pub fn dmux(input: Byte, select: Bit) -> (Byte, Byte) {
     )
 }
 
+/// Returns input byte as selected byte.
+/// Other bytes will be 0.
+/// select[0] is 1, every next index is twice as high as the previous.
+///
+/// A tree of 2-way dmuxes like [`bit::dmux_n`], `N` has to be `2^S`.
+pub fn dmux_n<const N: usize, const S: usize>(input: Byte, select: [Bit; S]) -> [Byte; N] {
+    const { assert!(N == 1 << S, "a dmux needs 2^S outputs") };
+    let mut outputs = [Byte::from(0); N];
+    outputs[0] = input;
+    for (length, select) in (0..S).map(|level| 1 << level).zip(select.into_iter().rev()) {
+        // From the end, so every output is split before it is overwritten
+        for i in (0..length).rev() {
+            (outputs[2 * i], outputs[2 * i + 1]) = dmux(outputs[i], select);
+        }
+    }
+    outputs
+}
+
 /// Returns input bit as selected bit.
 /// Other bits will be `Bit::Low`.
 /// select[0] is 1, every next index is twice as high as the previous.
 pub fn dmux4(input: Byte, select: [Bit; 2]) -> [Byte; 4] {
-    let input: [Bit; 8] = input.into();
-    array::from_fn(|i| {
-        Byte::from(array::from_fn(|j| {
-            Bit::from(
-                select
-                    .iter()
-                    .enumerate()
-                    .all(|(k, bit)| &Bit::from((i >> k) & 1 == 1) == bit),
-            )
-            .and(input[j])
-        }))
-    })
+    dmux_n(input, select)
+}
+
+/// Returns input byte as selected byte.
+/// Other bytes will be 0.
+/// select[0] is 1, every next index is twice as high as the previous.
+pub fn dmux8(input: Byte, select: [Bit; 3]) -> [Byte; 8] {
+    dmux_n(input, select)
 }
 
 /// Returns input bit as selected bit.
 /// Other bits will be `Bit::Low`.
 /// select[0] is 1, every next index is twice as high as the previous.
 pub fn dmux16(input: Byte, select: [Bit; 4]) -> [Byte; 16] {
-    let input: [Bit; 8] = input.into();
-    array::from_fn(|i| {
-        Byte::from(array::from_fn(|j| {
-            Bit::from(
-                select
-                    .iter()
-                    .enumerate()
-                    .all(|(k, bit)| &Bit::from((i >> k) & 1 == 1) == bit),
-            )
-            .and(input[j])
-        }))
-    })
+    dmux_n(input, select)
 }
 
 /// Returns input bit as selected bit.
 /// Other bits will be `Bit::Low`.
 /// select[0] is 1, every next index is twice as high as the previous.
 pub fn dmux256(input: Byte, select: [Bit; 8]) -> [Byte; 256] {
-    let input: [Bit; 8] = input.into();
-    array::from_fn(|i| {
-        Byte::from(array::from_fn(|j| {
-            Bit::from(
-                select
-                    .iter()
-                    .enumerate()
-                    .all(|(k, bit)| &Bit::from((i >> k) & 1 == 1) == bit),
-            )
-            .and(input[j])
-        }))
+    dmux_n(input, select)
+}
+
+/// Returns input byte as selected byte like [`dmux4`] while `enable` is `Bit::High`, every byte is
+/// 0 while it is `Bit::Low`
+pub fn dmux4_en(input: Byte, select: [Bit; 2], enable: Bit) -> [Byte; 4] {
+    dmux4(enabled(input, enable), select)
+}
+
+/// Returns input byte as selected byte like [`dmux16`] while `enable` is `Bit::High`, every byte
+/// is 0 while it is `Bit::Low`
+pub fn dmux16_en(input: Byte, select: [Bit; 4], enable: Bit) -> [Byte; 16] {
+    dmux16(enabled(input, enable), select)
+}
+
+/// Returns input byte as selected byte like [`dmux256`] while `enable` is `Bit::High`, every byte
+/// is 0 while it is `Bit::Low`
+pub fn dmux256_en(input: Byte, select: [Bit; 8], enable: Bit) -> [Byte; 256] {
+    dmux256(enabled(input, enable), select)
+}
+
+/// Returns `Bit::High` if all bits of the byte are low
+pub fn is_zero(input: Byte) -> Bit {
+    let bits: [Bit; 8] = input.into();
+    let pairs: [Bit; 4] = array::from_fn(|i| bits[i * 2].or(bits[i * 2 + 1]));
+    pairs[0].or(pairs[1]).or(pairs[2].or(pairs[3])).not()
+}
+
+/// Adds the right byte and the carry to the left one, or subtracts the right byte and the carry
+/// as a borrow from it if `subtract` is `Bit::High`.
+///
+/// A single adder like the data path of an ALU: the right byte is xored with `subtract` and the
+/// carry in is `carry` xor `subtract`. Returns the result and the carry out, which is `Bit::High`
+/// for a subtraction that doesn't borrow.
+pub fn add_sub(left: Byte, right: Byte, carry: Bit, subtract: Bit) -> (Byte, Bit) {
+    let right: [Bit; 8] = right.into();
+    left.add_with_carry(
+        Byte::from(right.map(|bit| bit.xor(subtract))),
+        carry.xor(subtract),
+    )
+}
+
+/// The function of the ALU, every output is computed and `op` selects one like a data path does.
+///
+/// Returns the result and the carry out of [`add_sub`] for the arithmetic functions, `Bit::Low`
+/// for the others.
+///
+/// `op` | function
+/// -----|---------
+/// 0    | and
+/// 1    | or
+/// 2    | xor
+/// 3    | nand
+/// 4    | nor
+/// 5    | xnor
+/// 6    | add with the carry
+/// 7    | sub with the carry as a borrow
+pub fn alu_op(left: Byte, right: Byte, carry: Bit, op: [Bit; 3]) -> (Byte, Bit) {
+    let [subtract, arithmetic_low, arithmetic_high] = op;
+    let (sum, carry) = add_sub(left, right, carry, subtract);
+    let results = [
+        left & right,
+        left | right,
+        left ^ right,
+        left.nand(&right),
+        left.nor(&right),
+        left.xnor(&right),
+        sum,
+        sum,
+    ];
+    (
+        mux8(results, op),
+        carry.and(arithmetic_low.and(arithmetic_high)),
+    )
+}
+
+/// Returns the carry out of bit 3 of [`add_sub`], the half carry a decimal adjust corrects the low
+/// digit with
+pub fn half_carry(left: Byte, right: Byte, carry: Bit, subtract: Bit) -> Bit {
+    let (left, right): ([Bit; 8], [Bit; 8]) = (left.into(), right.into());
+    let mut carry = carry.xor(subtract);
+    for (left, right) in left.into_iter().zip(right).take(4) {
+        (_, carry) = left.full_adder(right.xor(subtract), carry);
+    }
+    carry
+}
+
+gate! {
+    /// Returns `Bit::High` if the 4 bits hold a number above 9, which isn't a decimal digit
+    fn above_nine([_, b1, b2, b3]: [Bit; 4]) -> Bit {
+        b3.and(b2.or(b1))
+    }
+}
+
+/// Corrects the binary sum of two packed BCD bytes to their decimal sum, given the carry and the
+/// half carry of the addition.
+///
+/// A digit above 9 or one that carried out gets 6 added, skipping the 6 values that aren't digits.
+/// Returns the corrected byte and the decimal carry out of the high digit.
+pub fn decimal_adjust(input: Byte, carry: Bit, half_carry: Bit) -> (Byte, Bit) {
+    let [b0, b1, b2, b3, b4, b5, b6, b7]: [Bit; 8] = input.into();
+    let low_above_nine = above_nine([b0, b1, b2, b3]);
+    let low = half_carry.or(low_above_nine);
+    // Correcting the low digit carries into a high digit of 9
+    let high_nine = b7.and(b6.not()).and(b5.not()).and(b4);
+    let high = carry
+        .or(above_nine([b4, b5, b6, b7]))
+        .or(high_nine.and(low_above_nine));
+    let correction = Byte::from([Bit::Low, low, low, Bit::Low, Bit::Low, high, high, Bit::Low]);
+    (input.add_with_carry(correction, Bit::Low).0, high)
+}
+
+/// Adds two packed BCD bytes, two decimal digits each, and the carry. The binary sum is corrected
+/// by [`decimal_adjust`], returns the decimal sum and the carry out of the high digit.
+pub fn bcd_add(left: Byte, right: Byte, carry: Bit) -> (Byte, Bit) {
+    let (sum, carry_out) = left.add_with_carry(right, carry);
+    decimal_adjust(sum, carry_out, half_carry(left, right, carry, Bit::Low))
+}
+
+/// Adds one with the ripple chain of half adders of [`Byte::increment`], which takes fewer gates
+/// than the adder with a constant. Returns the result and whether it wrapped from 255 to 0.
+pub fn increment(input: Byte) -> (Byte, Bit) {
+    input.increment()
+}
+
+/// Subtracts one with the ripple chain of half subtractors of [`Byte::decrement`]. Returns the
+/// result and whether it wrapped from 0 to 255.
+pub fn decrement(input: Byte) -> (Byte, Bit) {
+    input.decrement()
+}
+
+/// Multiplies 2 bytes with an array multiplier, returns the low and the high byte of the product.
+///
+/// Row `i` of the array ands the left byte with bit `i` of the right one into a partial product,
+/// which an adder adds to the product bits from bit `i` up.
+pub fn multiply(left: Byte, right: Byte) -> (Byte, Byte) {
+    if fast::enabled() {
+        fast::multiply(left, right)
+    } else {
+        array_multiplier(left, right)
+    }
+}
+
+/// The circuit of [`multiply`], which the `fast` feature skips
+fn array_multiplier(left: Byte, right: Byte) -> (Byte, Byte) {
+    let right: [Bit; 8] = right.into();
+    let mut product = [Bit::Low; 16];
+    for (row, select) in right.into_iter().enumerate() {
+        let partial = left & Byte::from([select; 8]);
+        let window: [Bit; 8] = array::from_fn(|i| product[row + i]);
+        let (sum, carry) = Byte::from(window).add_with_carry_circuit(partial, Bit::Low);
+        product[row..row + 8].copy_from_slice(&<[Bit; 8]>::from(sum));
+        product[row + 8] = carry;
+    }
+    (
+        Byte::from(array::from_fn(|i| product[i])),
+        Byte::from(array::from_fn(|i| product[i + 8])),
+    )
+}
+
+/// Multiplies 2 bytes with [`multiply`], returns the low byte of the product and `Bit::High` if
+/// the high byte isn't 0, which the low byte lost
+pub fn multiply_low(left: Byte, right: Byte) -> (Byte, Bit) {
+    let (low, high) = multiply(left, right);
+    (low, is_zero(high).not())
+}
+
+/// Divides by the divisor with an 8 step restoring division, returns the quotient and the
+/// remainder, or `None` if the divisor is 0.
+///
+/// Every step shifts the next bit of the dividend into the remainder from the top and subtracts
+/// the divisor with the subtractor. The quotient bit is set if that didn't borrow, and a mux keeps
+/// the difference as the remainder then, or restores the remainder otherwise.
+pub fn divide(dividend: Byte, divisor: Byte) -> Option<(Byte, Byte)> {
+    if bool::from(is_zero(divisor)) {
+        return None;
+    }
+    Some(if fast::enabled() {
+        fast::divide(dividend, divisor)
+    } else {
+        restoring_divider(dividend, divisor)
+    })
+}
+
+/// The circuit of [`divide`], which the `fast` feature skips
+fn restoring_divider(dividend: Byte, divisor: Byte) -> (Byte, Byte) {
+    let bits: [Bit; 8] = dividend.into();
+    let mut remainder = Byte::from(0);
+    let mut quotient = [Bit::Low; 8];
+    for i in (0..8).rev() {
+        // The bit shifted out is the ninth bit of the remainder
+        let out;
+        (remainder, out) = remainder.rotate_left_through_carry(bits[i]);
+        let (difference, no_borrow) = add_sub(remainder, divisor, Bit::Low, Bit::High);
+        quotient[i] = out.or(no_borrow);
+        remainder = mux(remainder, difference, quotient[i]);
+    }
+    (Byte::from(quotient), remainder)
+}
+
+/// A barrel shifter of 3 stages, moving the bits by 1, 2 and 4 places if the matching bit of the
+/// amount is `Bit::High`. `source` returns the bit moving into place `index` at a stage moving
+/// `distance` places.
+fn barrel(input: Byte, amount: [Bit; 3], source: impl Fn(&[Bit; 8], usize, usize) -> Bit) -> Byte {
+    let mut bits: [Bit; 8] = input.into();
+    for (stage, enable) in amount.into_iter().enumerate() {
+        let distance = 1 << stage;
+        bits = array::from_fn(|i| bit_mux(bits[i], source(&bits, i, distance), enable));
+    }
+    Byte::from(bits)
+}
+
+/// Shifts the byte up by the amount, filling the low bits with `Bit::Low`
+pub fn shift_left(input: Byte, amount: [Bit; 3]) -> Byte {
+    barrel(input, amount, |bits, i, distance| {
+        i.checked_sub(distance).map_or(Bit::Low, |i| bits[i])
     })
 }
 
-/// Simple 256 byte RAM memory
+/// Shifts the byte down by the amount, filling the high bits with `Bit::Low`
+pub fn shift_right_logical(input: Byte, amount: [Bit; 3]) -> Byte {
+    barrel(input, amount, |bits, i, distance| {
+        bits.get(i + distance).copied().unwrap_or(Bit::Low)
+    })
+}
+
+/// Shifts the byte down by the amount, filling the high bits with the sign bit
+pub fn shift_right_arithmetic(input: Byte, amount: [Bit; 3]) -> Byte {
+    barrel(input, amount, |bits, i, distance| {
+        bits.get(i + distance).copied().unwrap_or(bits[7])
+    })
+}
+
+/// Rotates the byte up by the amount, the bits moving out at the top come back in at the bottom
+pub fn rotate_left(input: Byte, amount: [Bit; 3]) -> Byte {
+    barrel(input, amount, |bits, i, distance| {
+        bits[(i + 8 - distance) % 8]
+    })
+}
+
+/// Rotates the byte down by the amount, the bits moving out at the bottom come back in at the top
+pub fn rotate_right(input: Byte, amount: [Bit; 3]) -> Byte {
+    barrel(input, amount, |bits, i, distance| bits[(i + distance) % 8])
+}
+
+/// Returns bit 7, which is `Bit::High` if the byte is negative as a two's complement number, see
+/// [`Byte::is_negative`]
+pub const fn sign(input: Byte) -> Bit {
+    input.is_negative()
+}
+
+/// Returns `Bit::High` if an odd number of bits of the byte are high, the xor tree of the bits
+pub fn parity(input: Byte) -> Bit {
+    let bits: [Bit; 8] = input.into();
+    let pairs: [Bit; 4] = array::from_fn(|i| bits[i * 2].xor(bits[i * 2 + 1]));
+    pairs[0].xor(pairs[1]).xor(pairs[2].xor(pairs[3]))
+}
+
+/// Encodes the byte as Gray code, the xor of the byte with itself shifted down by one, so
+/// consecutive values differ in a single bit
+pub fn to_gray(input: Byte) -> Byte {
+    input ^ shift_right_logical(input, [Bit::High, Bit::Low, Bit::Low])
+}
+
+/// Decodes a Gray code byte, every bit is the xor of the Gray code bits from it upwards
+pub fn from_gray(input: Byte) -> Byte {
+    let gray: [Bit; 8] = input.into();
+    let mut bits = gray;
+    for i in (0..7).rev() {
+        bits[i] = bits[i + 1].xor(gray[i]);
+    }
+    Byte::from(bits)
+}
+
+/// Returns `Bit::High` if the bytes are equal, the and tree of the xnor of every bit pair
+pub fn equals(left: Byte, right: Byte) -> Bit {
+    let (left, right): ([Bit; 8], [Bit; 8]) = (left.into(), right.into());
+    let same: [Bit; 8] = array::from_fn(|i| left[i].xnor(right[i]));
+    let pairs: [Bit; 4] = array::from_fn(|i| same[i * 2].and(same[i * 2 + 1]));
+    pairs[0].and(pairs[1]).and(pairs[2].and(pairs[3]))
+}
+
+/// Returns `Bit::High` if the left byte is below the right one as unsigned numbers, the borrow of
+/// the subtractor
+pub fn less_than(left: Byte, right: Byte) -> Bit {
+    // The subtractor adds the inverted right byte with a carry in, which carries unless it borrows
+    let (_, carry) = left - right;
+    carry.not()
+}
+
+/// Compares the bytes as unsigned numbers, returns whether the left byte is less than, equal to
+/// and greater than the right one. Exactly one of the bits is `Bit::High`.
+pub fn compare(left: Byte, right: Byte) -> (Bit, Bit, Bit) {
+    let (less, equal) = (less_than(left, right), equals(left, right));
+    (less, equal, less.nor(equal))
+}
+
+/// Inverts bit 7, which maps the signed order onto the unsigned one
+fn flip_sign(input: Byte) -> Byte {
+    let mut bits: [Bit; 8] = input.into();
+    bits[7] = bits[7].not();
+    Byte::from(bits)
+}
+
+/// Returns `Bit::High` if the left byte is below the right one as two's complement numbers
+pub fn less_than_signed(left: Byte, right: Byte) -> Bit {
+    less_than(flip_sign(left), flip_sign(right))
+}
+
+/// Compares the bytes as two's complement numbers like [`compare`]
+pub fn compare_signed(left: Byte, right: Byte) -> (Bit, Bit, Bit) {
+    compare(flip_sign(left), flip_sign(right))
+}
+
+/// Simple 256 byte RAM memory.
+/// Its debug format is a hexdump of 16 bytes per line, with runs of zero lines elided.
+#[derive(PartialEq, Eq)]
 pub struct Ram {
     data: [Byte; 256],
 }
@@ -139,22 +465,332 @@ impl Ram {
 
     /// Loads a byte from memory
     pub fn load(&self, address: Byte) -> Byte {
-        mux256(self.data, address.into())
+        if fast::enabled() {
+            self.data[fast::index(address.into())]
+        } else {
+            self.load_circuit(address)
+        }
     }
 
     /// Stores the new byte in memory
     pub fn store(&mut self, address: Byte, value: Byte) {
+        if fast::enabled() {
+            self.data[fast::index(address.into())] = value;
+        } else {
+            self.store_circuit(address, value);
+        }
+    }
+
+    /// Loads a byte through the address decoder, which the `fast` feature skips
+    pub(crate) fn load_circuit(&self, address: Byte) -> Byte {
+        mux256(self.data, address.into())
+    }
+
+    /// Stores a byte through the address decoder, which the `fast` feature skips
+    pub(crate) fn store_circuit(&mut self, address: Byte, value: Byte) {
         let new_value = dmux256(value, address.into());
         let select = bit::dmux256(Bit::High, address.into());
         for ((target, value), select) in self.data.iter_mut().zip(new_value).zip(select) {
             *target = mux(*target, value, select);
         }
     }
+
+    /// Creates memory with the given contents
+    pub fn from_bytes(bytes: &[u8; 256]) -> Self {
+        Self {
+            data: bytes.map(Byte::from),
+        }
+    }
+
+    /// Creates memory starting with the given bytes, the rest of memory is cleared
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 256 bytes.
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut ram = Self::new();
+        ram.load_slice(0, bytes);
+        ram
+    }
+
+    /// Copies the bytes into memory starting at `offset`, wrapping around to address 0 at the end
+    /// of memory like the addresses do
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 256 bytes, as they would overwrite each other.
+    pub fn load_slice(&mut self, offset: u8, bytes: &[u8]) {
+        assert!(
+            bytes.len() <= self.data.len(),
+            "{} bytes don't fit in memory",
+            bytes.len()
+        );
+        for (address, &byte) in (0..=u8::MAX).cycle().skip(offset.into()).zip(bytes) {
+            self.data[usize::from(address)] = Byte::from(byte);
+        }
+    }
+
+    /// Returns the contents of memory as bytes, indexed by address
+    pub fn dump(&self) -> [u8; 256] {
+        self.data.map(u8::from)
+    }
+
+    /// Iterates over the addresses and values of the cells that aren't zero
+    pub fn iter(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        (0..=u8::MAX)
+            .zip(self.data)
+            .map(|(address, byte)| (address, u8::from(byte)))
+            .filter(|&(_, byte)| byte != 0)
+    }
+
+    /// Returns the contents as a Rust constant named `name`, to embed it in source code
+    pub fn to_rust_const<'a>(&self, name: &'a str) -> RustConst<'a, 256> {
+        RustConst {
+            name,
+            bytes: self.dump(),
+        }
+    }
+
+    /// Creates memory with the given contents, without going through the address decoder
+    pub(crate) const fn from_cells(data: &[Byte; 256]) -> Self {
+        Self { data: *data }
+    }
+
+    /// Returns the contents of memory, without going through the address decoder
+    pub(crate) const fn cells(&self) -> [Byte; 256] {
+        self.data
+    }
+}
+
+/// Read-only memory holding up to 256 bytes from address 0, the addresses past them read 0.
+/// Its debug format is the hexdump of [`Ram`].
+#[derive(PartialEq, Eq)]
+pub struct Rom {
+    data: [Byte; 256],
+    length: usize,
+}
+
+impl Rom {
+    /// Creates a ROM holding the bytes
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 256 bytes.
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let ram = Ram::from_slice(bytes);
+        Self {
+            data: ram.data,
+            length: bytes.len(),
+        }
+    }
+
+    /// Loads a byte, like [`Ram::load`]
+    pub fn load(&self, address: Byte) -> Byte {
+        if fast::enabled() {
+            self.data[fast::index(address.into())]
+        } else {
+            mux256(self.data, address.into())
+        }
+    }
+
+    /// Returns the number of bytes the ROM was created with
+    pub const fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns whether the ROM was created without bytes
+    pub const fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns whether the address holds one of the bytes the ROM was created with
+    pub fn contains(&self, address: Byte) -> bool {
+        usize::from(u8::from(address)) < self.length
+    }
+
+    /// Returns the contents as bytes, indexed by address
+    pub fn dump(&self) -> [u8; 256] {
+        self.data.map(u8::from)
+    }
+
+    /// Creates a ROM holding the first `length` cells, without going through the address decoder
+    pub(crate) const fn from_cells(data: &[Byte; 256], length: usize) -> Self {
+        Self {
+            data: *data,
+            length,
+        }
+    }
+}
+
+/// 4 KiB of memory in 16 banks of [`Ram`], addressed by 12 bits: bits 0-7 select the cell in a
+/// bank and bits 8-11 select the bank
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Ram4k {
+    banks: [Ram; 16],
+}
+
+impl Ram4k {
+    /// Initializes memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a byte from memory
+    pub fn load(&self, address: [Bit; 12]) -> Byte {
+        let (cell, bank) = split_address(address);
+        if fast::enabled() {
+            self.banks[fast::index(bank)].load(cell)
+        } else {
+            mux16(self.banks.each_ref().map(|ram| ram.load(cell)), bank)
+        }
+    }
+
+    /// Stores the new byte in memory, the other banks store back the byte they hold
+    pub fn store(&mut self, address: [Bit; 12], value: Byte) {
+        self.store_enabled(address, value, Bit::High);
+    }
+
+    /// Stores the new byte in memory while the write line `enable` is `Bit::High`, otherwise every
+    /// bank stores back the byte it holds
+    pub fn store_enabled(&mut self, address: [Bit; 12], value: Byte, enable: Bit) {
+        let (cell, bank) = split_address(address);
+        if fast::enabled() {
+            if bool::from(enable) {
+                self.banks[fast::index(bank)].store(cell, value);
+            }
+        } else {
+            let new_value = dmux16_en(value, bank, enable);
+            let select = bit::dmux16_en(Bit::High, bank, enable);
+            for ((ram, value), select) in self.banks.iter_mut().zip(new_value).zip(select) {
+                ram.store(cell, mux(ram.load(cell), value, select));
+            }
+        }
+    }
+
+    /// Returns the banks, indexed by bits 8-11 of the address
+    pub const fn banks(&self) -> &[Ram; 16] {
+        &self.banks
+    }
+}
+
+/// Splits a 12 bit address into the cell in the bank and the bank
+fn split_address(address: [Bit; 12]) -> (Byte, [Bit; 4]) {
+    (
+        Byte::from(array::from_fn(|i| address[i])),
+        array::from_fn(|i| address[i + 8]),
+    )
+}
+
+/// The number of registers in the register file
+pub const REGISTER_COUNT: usize = 8;
+
+/// The type representing a register index.
+///
+/// `R0` to `R3` are encoded in the opcodes, `R4` to `R7` need a register prefix, see the
+/// [`opcode`](crate::opcode) module.
+#[expect(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    R0,
+    R1,
+    R2,
+    R3,
+    R4,
+    R5,
+    R6,
+    R7,
+}
+
+impl Register {
+    /// The registers, in the order of their numbers
+    pub const ALL: [Self; REGISTER_COUNT] = [
+        Self::R0,
+        Self::R1,
+        Self::R2,
+        Self::R3,
+        Self::R4,
+        Self::R5,
+        Self::R6,
+        Self::R7,
+    ];
+
+    /// Returns the register with the number, only the lowest 3 bits are used
+    pub const fn from_number(number: u8) -> Self {
+        Self::ALL[(number & 0b111) as usize]
+    }
+
+    /// Returns whether the register needs a register prefix to be encoded
+    pub const fn is_extended(self) -> bool {
+        matches!(self, Self::R4 | Self::R5 | Self::R6 | Self::R7)
+    }
+}
+
+impl From<Register> for u8 {
+    fn from(value: Register) -> Self {
+        match value {
+            Register::R0 => 0,
+            Register::R1 => 1,
+            Register::R2 => 2,
+            Register::R3 => 3,
+            Register::R4 => 4,
+            Register::R5 => 5,
+            Register::R6 => 6,
+            Register::R7 => 7,
+        }
+    }
+}
+
+/// The select bits of the register in the register file, bit 0 of its number first
+impl From<Register> for [Bit; 3] {
+    fn from(register: Register) -> Self {
+        let index = u8::from(register);
+        array::from_fn(|i| Bit::from((index >> i) & 1 == 1))
+    }
+}
+
+/// The register the select bits pick, every combination is a register so this can't fail and
+/// `TryFrom` is available through it
+impl From<[Bit; 3]> for Register {
+    fn from(select: [Bit; 3]) -> Self {
+        let number = select
+            .into_iter()
+            .rev()
+            .fold(0, |number, bit| number << 1 | u8::from(bool::from(bit)));
+        Self::from_number(number)
+    }
 }
 
-/// A simple set of registers
+/// The register is one of `R4` to `R7`, which 2 select bits can't pick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedRegister(pub Register);
+
+/// The 2 select bits of the register in an opcode, bit 0 of its number first
+impl TryFrom<Register> for [Bit; 2] {
+    type Error = ExtendedRegister;
+
+    fn try_from(register: Register) -> Result<Self, Self::Error> {
+        if register.is_extended() {
+            return Err(ExtendedRegister(register));
+        }
+        let [low, high, _] = <[Bit; 3]>::from(register);
+        Ok([low, high])
+    }
+}
+
+/// One of `R0` to `R3`, the registers 2 select bits pick
+impl From<[Bit; 2]> for Register {
+    fn from([low, high]: [Bit; 2]) -> Self {
+        Self::from([low, high, Bit::Low])
+    }
+}
+
+/// A set of registers built from register cells, debug formatted as a map from register name to
+/// value.
+///
+/// A store is a clock tick of every cell, with the load line of the selected one high.
+#[derive(PartialEq, Eq)]
 pub struct Registers {
-    data: [Byte; 4],
+    data: [RegisterCell; REGISTER_COUNT],
 }
 
 impl Default for Registers {
@@ -165,23 +801,749 @@ impl Default for Registers {
 
 impl Registers {
     /// Initializes the registers
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
-            data: [Byte::from(0); 4],
+            data: [RegisterCell::new(); REGISTER_COUNT],
         }
     }
 
     /// Loads the value of a register
-    pub fn load(&self, select: [Bit; 2]) -> Byte {
-        mux4(self.data, select)
+    pub fn load(&self, select: [Bit; 3]) -> Byte {
+        if fast::enabled() {
+            self.data[fast::index(select)].output()
+        } else {
+            self.load_circuit(select)
+        }
     }
 
     /// Stores the new byte in a register
-    pub fn store(&mut self, select: [Bit; 2], value: Byte) {
-        let new_value = dmux4(value, select);
-        let select = bit::dmux4(Bit::High, select);
-        for ((target, value), select) in self.data.iter_mut().zip(new_value).zip(select) {
-            *target = mux(*target, value, select);
+    pub fn store(&mut self, select: [Bit; 3], value: Byte) {
+        if fast::enabled() {
+            self.data[fast::index(select)].tick(value, Bit::High);
+        } else {
+            self.store_circuit(select, value);
+        }
+    }
+
+    /// Loads the value of the register, like [`Registers::load`] with its select bits
+    pub fn load_reg(&self, register: Register) -> Byte {
+        self.load(register.into())
+    }
+
+    /// Stores the new byte in the register, like [`Registers::store`] with its select bits
+    pub fn store_reg(&mut self, register: Register, value: Byte) {
+        self.store(register.into(), value);
+    }
+
+    /// Loads a register through the mux, which the `fast` feature skips
+    pub(crate) fn load_circuit(&self, select: [Bit; 3]) -> Byte {
+        mux8(self.data.map(RegisterCell::output), select)
+    }
+
+    /// Stores a register through the load lines of the demux, which the `fast` feature skips
+    pub(crate) fn store_circuit(&mut self, select: [Bit; 3], value: Byte) {
+        let load = bit::dmux8(Bit::High, select);
+        for (cell, load) in self.data.iter_mut().zip(load) {
+            cell.tick(value, load);
+        }
+    }
+
+    /// Creates registers with the given values, indexed by register number
+    pub fn from_bytes(bytes: &[u8; REGISTER_COUNT]) -> Self {
+        let mut registers = Self::new();
+        registers.set_all(*bytes);
+        registers
+    }
+
+    /// Returns the values of the registers, indexed by register number
+    pub fn as_bytes(&self) -> [u8; REGISTER_COUNT] {
+        self.data.map(|cell| u8::from(cell.output()))
+    }
+
+    /// Sets all registers at once in one clock tick, indexed by register number
+    pub fn set_all(&mut self, bytes: [u8; REGISTER_COUNT]) {
+        for (cell, value) in self.data.iter_mut().zip(bytes) {
+            cell.tick(Byte::from(value), Bit::High);
+        }
+    }
+
+    /// Clears all registers
+    pub const fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Returns the values as a Rust constant named `name`, to embed them in source code
+    pub fn to_rust_const<'a>(&self, name: &'a str) -> RustConst<'a, REGISTER_COUNT> {
+        RustConst {
+            name,
+            bytes: self.as_bytes(),
+        }
+    }
+}
+
+impl Debug for Ram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        hexdump(&self.data, f)
+    }
+}
+
+impl Debug for Rom {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        hexdump(&self.data, f)
+    }
+}
+
+/// Formats the cells as 16 bytes per line, collapsing runs of zero lines into one
+fn hexdump(data: &[Byte; 256], f: &mut Formatter<'_>) -> fmt::Result {
+    let is_zero = |line: &[Byte]| line.iter().all(|&byte| u8::from(byte) == 0);
+    let mut set = f.debug_set();
+    let mut lines = data.chunks(16).enumerate().peekable();
+    while let Some((start, line)) = lines.next() {
+        if is_zero(line) {
+            let mut end = start + 1;
+            while lines.next_if(|(_, line)| is_zero(line)).is_some() {
+                end += 1;
+            }
+            set.entry(&format_args!(
+                "{:#04X}..{:#04X}: 00 x {}",
+                start * 16,
+                end * 16,
+                (end - start) * 16
+            ));
+        } else {
+            set.entry(&HexLine {
+                offset: start * 16,
+                bytes: line,
+            });
+        }
+    }
+    set.finish()
+}
+
+/// A line of the memory hexdump
+struct HexLine<'a> {
+    offset: usize,
+    bytes: &'a [Byte],
+}
+
+impl Debug for HexLine<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#04X}:", self.offset)?;
+        for &byte in self.bytes {
+            write!(f, " {:02X}", u8::from(byte))?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Registers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (register, cell) in self.data.iter().enumerate() {
+            map.key(&format_args!("r{register}")).value(&cell.output());
+        }
+        map.finish()
+    }
+}
+
+/// Stored as the 256 byte values of the cells
+#[cfg(feature = "serde")]
+impl serde::Serialize for Ram {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        crate::serialize::memory::serialize(&self.dump(), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Ram {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        crate::serialize::memory::deserialize(deserializer).map(|bytes| Self::from_bytes(&bytes))
+    }
+}
+
+/// Stored as the 8 byte values, indexed by register number
+#[cfg(feature = "serde")]
+impl serde::Serialize for Registers {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.as_bytes(), serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Registers {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        serde::Deserialize::deserialize(deserializer)
+            .map(|bytes: [u8; REGISTER_COUNT]| Self::from_bytes(&bytes))
+    }
+}
+
+/// Formats bytes as a `pub const` array declaration, 16 bytes per line
+pub struct RustConst<'a, const N: usize> {
+    name: &'a str,
+    bytes: [u8; N],
+}
+
+impl<const N: usize> Display for RustConst<'_, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "pub const {}: [u8; {N}] = [", self.name)?;
+        for line in self.bytes.chunks(16) {
+            f.write_str("   ")?;
+            for byte in line {
+                write!(f, " {byte:#04x},")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "];")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{array, fmt::Write};
+
+    use heapless::String;
+
+    use crate::{bit::Bit, byte::Byte, equivalence::RandomStates};
+
+    use super::{
+        add_sub, alu_op, array_multiplier, bcd_add, compare, compare_signed, decrement, divide,
+        dmux16, dmux16_en, dmux256, dmux256_en, dmux4, dmux4_en, dmux8, dmux_n, equals, from_gray,
+        half_carry, increment, is_zero, less_than, less_than_signed, multiply, multiply_low, mux,
+        mux8, mux_en, mux_n, parity, restoring_divider, rotate_left, rotate_right, shift_left,
+        shift_right_arithmetic, shift_right_logical, sign, to_gray, ExtendedRegister, Ram, Ram4k,
+        Register, Registers, Rom,
+    };
+
+    mod generated {
+        include!("golden_table.rs");
+    }
+
+    /// Returns a generator of pseudo random bytes, the xorshift generator of the equivalence
+    /// checker
+    fn random_bytes(seed: u32) -> impl FnMut() -> u8 {
+        let mut random = RandomStates::new(seed);
+        move || random.next_byte()
+    }
+
+    #[test]
+    fn is_zero_test() {
+        for byte in 0..=u8::MAX {
+            assert_eq!(is_zero(Byte::from(byte)), Bit::from(byte == 0));
+        }
+    }
+
+    #[test]
+    fn adder_subtractor() {
+        for left in 0..=u8::MAX {
+            for right in 0..=u8::MAX {
+                let (a, b) = (Byte::from(left), Byte::from(right));
+                assert_eq!(add_sub(a, b, Bit::Low, Bit::Low), a + b);
+                assert_eq!(add_sub(a, b, Bit::Low, Bit::High), a - b);
+                for carry in [Bit::Low, Bit::High] {
+                    assert_eq!(add_sub(a, b, carry, Bit::Low), a.add_with_carry(b, carry));
+                    let (result, no_borrow) = add_sub(a, b, carry, Bit::High);
+                    assert_eq!(result, a.sub_with_carry(b, carry).0);
+                    let borrow_in = u16::from(carry == Bit::High);
+                    assert_eq!(
+                        no_borrow,
+                        Bit::from(u16::from(left) >= u16::from(right) + borrow_in)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn alu_functions() {
+        for left in 0..=u8::MAX {
+            for right in 0..=u8::MAX {
+                let (a, b) = (Byte::from(left), Byte::from(right));
+                let carry = Bit::from((left ^ right) & 1 == 1);
+                let expected = [
+                    (a & b, Bit::Low),
+                    (a | b, Bit::Low),
+                    (a ^ b, Bit::Low),
+                    (a.nand(&b), Bit::Low),
+                    (a.nor(&b), Bit::Low),
+                    (a.xnor(&b), Bit::Low),
+                    a.add_with_carry(b, carry),
+                    add_sub(a, b, carry, Bit::High),
+                ];
+                for (op, expected) in expected.into_iter().enumerate() {
+                    let select = array::from_fn(|bit| Bit::from((op >> bit) & 1 == 1));
+                    assert_eq!(alu_op(a, b, carry, select), expected, "{op}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bcd_adder() {
+        let bcd = |value: u8| Byte::from(((value / 10) << 4) | (value % 10));
+        for left in 0..100 {
+            for right in 0..100 {
+                for carry in [false, true] {
+                    let sum = left + right + u8::from(carry);
+                    assert_eq!(
+                        bcd_add(bcd(left), bcd(right), Bit::from(carry)),
+                        (bcd(sum % 100), Bit::from(sum >= 100)),
+                        "{left} + {right} + {carry}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn half_carries() {
+        for left in 0..=u8::MAX {
+            for right in 0..=u8::MAX {
+                let carry = (left ^ right) & 1;
+                let (a, b, c) = (Byte::from(left), Byte::from(right), Bit::from(carry == 1));
+                assert_eq!(
+                    half_carry(a, b, c, Bit::Low),
+                    Bit::from((left & 0x0F) + (right & 0x0F) + carry > 0x0F)
+                );
+                // A subtraction carries out of bit 3 if the low digit doesn't borrow
+                assert_eq!(
+                    half_carry(a, b, c, Bit::High),
+                    Bit::from((left & 0x0F) >= (right & 0x0F) + carry)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn multiplier() {
+        for left in 0..=u8::MAX {
+            for right in 0..=u8::MAX {
+                let (a, b) = (Byte::from(left), Byte::from(right));
+                let [low, high] = (u16::from(left) * u16::from(right)).to_le_bytes();
+                let expected = (Byte::from(low), Byte::from(high));
+                assert_eq!(multiply(a, b), expected, "{left} * {right}");
+                assert_eq!(array_multiplier(a, b), expected, "{left} * {right}");
+                assert_eq!(multiply_low(a, b), (expected.0, Bit::from(high != 0)));
+            }
+        }
+    }
+
+    #[test]
+    fn divider() {
+        for dividend in 0..=u8::MAX {
+            for divisor in 1..=u8::MAX {
+                let (a, b) = (Byte::from(dividend), Byte::from(divisor));
+                let expected = (
+                    Byte::from(dividend / divisor),
+                    Byte::from(dividend % divisor),
+                );
+                assert_eq!(divide(a, b), Some(expected), "{dividend} / {divisor}");
+                assert_eq!(restoring_divider(a, b), expected, "{dividend} / {divisor}");
+            }
+            assert_eq!(divide(Byte::from(dividend), Byte::from(0)), None);
+        }
+    }
+
+    #[test]
+    fn increment_decrement() {
+        for byte in 0..=u8::MAX {
+            let (result, carry) = increment(Byte::from(byte));
+            assert_eq!(
+                (u8::from(result), carry),
+                (byte.wrapping_add(1), Bit::from(byte == u8::MAX))
+            );
+            let (result, borrow) = decrement(Byte::from(byte));
+            assert_eq!(
+                (u8::from(result), borrow),
+                (byte.wrapping_sub(1), Bit::from(byte == 0))
+            );
+        }
+    }
+
+    #[test]
+    fn barrel_shifter() {
+        for byte in 0..=u8::MAX {
+            for amount in 0..8 {
+                let (input, bits) = (
+                    Byte::from(byte),
+                    array::from_fn(|bit| Bit::from((amount >> bit) & 1 == 1)),
+                );
+                assert_eq!(u8::from(shift_left(input, bits)), byte << amount);
+                assert_eq!(u8::from(shift_right_logical(input, bits)), byte >> amount);
+                assert_eq!(
+                    u8::from(shift_right_arithmetic(input, bits)),
+                    (byte.cast_signed() >> amount).cast_unsigned()
+                );
+                assert_eq!(u8::from(rotate_left(input, bits)), byte.rotate_left(amount));
+                assert_eq!(
+                    u8::from(rotate_right(input, bits)),
+                    byte.rotate_right(amount)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sign_and_parity() {
+        for byte in 0..=u8::MAX {
+            assert_eq!(sign(Byte::from(byte)), Bit::from(byte.cast_signed() < 0));
+            assert_eq!(
+                parity(Byte::from(byte)),
+                Bit::from(byte.count_ones() % 2 == 1)
+            );
+        }
+    }
+
+    #[test]
+    fn gray_code() {
+        for byte in 0..=u8::MAX {
+            let gray = to_gray(Byte::from(byte));
+            assert_eq!(u8::from(gray), byte ^ (byte >> 1));
+            assert_eq!(u8::from(from_gray(gray)), byte);
+            // Counting up changes a single bit, wrapping around too
+            let next = to_gray(Byte::from(byte.wrapping_add(1)));
+            assert_eq!((u8::from(gray) ^ u8::from(next)).count_ones(), 1);
+        }
+    }
+
+    #[test]
+    fn comparators() {
+        for left in 0..=u8::MAX {
+            for right in 0..=u8::MAX {
+                let (a, b) = (Byte::from(left), Byte::from(right));
+                assert_eq!(equals(a, b), Bit::from(left == right));
+                assert_eq!(less_than(a, b), Bit::from(left < right));
+                assert_eq!(
+                    compare(a, b),
+                    (
+                        Bit::from(left < right),
+                        Bit::from(left == right),
+                        Bit::from(left > right)
+                    )
+                );
+                let (left, right) = (left.cast_signed(), right.cast_signed());
+                assert_eq!(less_than_signed(a, b), Bit::from(left < right));
+                assert_eq!(
+                    compare_signed(a, b),
+                    (
+                        Bit::from(left < right),
+                        Bit::from(left == right),
+                        Bit::from(left > right)
+                    )
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn register_select() {
+        let selects: [[Bit; 3]; 8] =
+            array::from_fn(|index| array::from_fn(|bit| Bit::from((index >> bit) & 1 == 1)));
+        for (index, (register, select)) in Register::ALL.into_iter().zip(selects).enumerate() {
+            assert_eq!(usize::from(u8::from(register)), index);
+            assert_eq!(<[Bit; 3]>::from(register), select);
+            assert_eq!(Register::from(select), register);
+            assert_eq!(Register::from_number(u8::from(register) | 8), register);
+            // The classic registers are the ones 2 select bits can pick
+            let classic = <[Bit; 2]>::try_from(register);
+            assert_eq!(classic.is_ok(), index < 4);
+            assert_eq!(
+                classic.map(Register::from),
+                if register.is_extended() {
+                    Err(ExtendedRegister(register))
+                } else {
+                    Ok(register)
+                }
+            );
+        }
+
+        let mut registers = Registers::new();
+        let values = [1, 0x20, 0xAB, 0xFF, 4, 5, 0x60, 0x77];
+        for (register, value) in Register::ALL.into_iter().zip(values) {
+            registers.store_reg(register, Byte::from(value));
+        }
+        for (register, select) in Register::ALL.into_iter().zip(selects) {
+            let value = u8::from(registers.load(select));
+            assert_eq!(u8::from(registers.load_reg(register)), value);
+            assert_eq!(u8::from(registers.load_circuit(select)), value);
+        }
+        assert_eq!(u8::from(registers.load_reg(Register::R2)), 0xAB);
+        registers.store_reg(Register::R2, Byte::from(7));
+        registers.store_circuit(selects[6], Byte::from(6));
+        assert_eq!(
+            Register::ALL.map(|register| u8::from(registers.load_reg(register))),
+            [1, 0x20, 7, 0xFF, 4, 5, 6, 0x77]
+        );
+        assert_eq!(registers.as_bytes(), [1, 0x20, 7, 0xFF, 4, 5, 6, 0x77]);
+        registers.set_all([8; 8]);
+        assert_eq!(u8::from(registers.load_circuit(selects[3])), 8);
+    }
+
+    #[test]
+    fn mux8_dmux8() {
+        let input = array::from_fn(|i| Byte::from(u8::try_from(i).unwrap() * 3));
+        for index in 0..8 {
+            let select = array::from_fn(|bit| Bit::from((index >> bit) & 1 == 1));
+            assert_eq!(
+                u8::from(mux8(input, select)),
+                u8::try_from(index).unwrap() * 3
+            );
+            let output = dmux8(Byte::from(0xA5), select).map(u8::from);
+            assert!(output
+                .iter()
+                .enumerate()
+                .all(|(i, &byte)| byte == if i == index { 0xA5 } else { 0 }));
+        }
+    }
+
+    #[test]
+    fn mux8_dmux8_random() {
+        let mut next = random_bytes(0x8765_4321);
+        for _ in 0..32 {
+            let input: [u8; 8] = array::from_fn(|_| next());
+            let value = next();
+            for index in 0..8 {
+                let select = array::from_fn(|bit| Bit::from((index >> bit) & 1 == 1));
+                assert_eq!(u8::from(mux8(input.map(Byte::from), select)), input[index]);
+                let output = dmux8(Byte::from(value), select);
+                assert_eq!(
+                    output.map(u8::from),
+                    array::from_fn(|i| if i == index { value } else { 0 })
+                );
+                assert_eq!(u8::from(mux8(output, select)), value);
+            }
+        }
+    }
+
+    /// Checks the generic mux and dmux with every select value at the size
+    fn every_select<const N: usize, const S: usize>() {
+        let input: [Byte; N] = array::from_fn(|i| Byte::from(u8::try_from(i * 37 % 256).unwrap()));
+        for index in 0..N {
+            let select = array::from_fn(|bit| Bit::from((index >> bit) & 1 == 1));
+            assert_eq!(mux_n::<N, S>(input, select), input[index], "{N}: {index}");
+            let output = dmux_n::<N, S>(Byte::from(0xA5), select).map(u8::from);
+            assert_eq!(
+                output,
+                array::from_fn(|i| if i == index { 0xA5 } else { 0 }),
+                "{N}: {index}"
+            );
         }
     }
+
+    #[test]
+    fn generic_sizes() {
+        every_select::<2, 1>();
+        every_select::<8, 3>();
+        every_select::<32, 5>();
+        every_select::<64, 6>();
+    }
+
+    #[test]
+    fn rust_const() {
+        let bytes = array::from_fn(|i| u8::try_from(i * 7 % 256).unwrap());
+        let ram = Ram::from_bytes(&bytes);
+        let mut text = String::<2048>::new();
+        write!(text, "{}", ram.to_rust_const("TABLE")).unwrap();
+        assert_eq!(text, include_str!("golden_table.rs"));
+        assert_eq!(generated::TABLE, bytes);
+        assert_eq!(Ram::from_bytes(&generated::TABLE).dump(), bytes);
+
+        let registers = Registers::from_bytes(&[1, 0x20, 0xAB, 0xFF, 0, 0, 0, 7]);
+        text.clear();
+        write!(text, "{}", registers.to_rust_const("STATE")).unwrap();
+        assert_eq!(
+            text,
+            "pub const STATE: [u8; 8] = [\n    0x01, 0x20, 0xab, 0xff, 0x00, 0x00, 0x00, 0x07,\n];\n"
+        );
+    }
+
+    #[test]
+    fn debug_format() {
+        let mut bytes = [0; 256];
+        bytes[..5].copy_from_slice(b"Hello");
+        bytes[0xF5] = 0xFF;
+        let ram = Ram::from_bytes(&bytes);
+        let mut text = String::<256>::new();
+        write!(text, "{ram:?}").unwrap();
+        assert_eq!(
+            text,
+            "{0x00: 48 65 6C 6C 6F 00 00 00 00 00 00 00 00 00 00 00, \
+             0x10..0xF0: 00 x 224, \
+             0xF0: 00 00 00 00 00 FF 00 00 00 00 00 00 00 00 00 00}"
+        );
+        text.clear();
+        write!(text, "{:?}", Ram::new()).unwrap();
+        assert_eq!(text, "{0x00..0x100: 00 x 256}");
+
+        let registers = Registers::from_bytes(&[0x48, 0, 0, 0, 0, 0, 0, 0xFF]);
+        text.clear();
+        write!(text, "{registers:?}").unwrap();
+        assert_eq!(
+            text,
+            "{r0: 0x48, r1: 0x00, r2: 0x00, r3: 0x00, r4: 0x00, r5: 0x00, r6: 0x00, r7: 0xFF}"
+        );
+    }
+
+    #[test]
+    fn slices() {
+        let bytes: [u8; 256] = array::from_fn(|i| u8::try_from(i * 7 % 256).unwrap());
+        assert_eq!(Ram::from_slice(&bytes).dump(), bytes);
+        assert_eq!(Ram::from_slice(&bytes), Ram::from_bytes(&bytes));
+
+        let mut ram = Ram::from_slice(b"Hi");
+        ram.load_slice(0x80, &[1, 2, 3]);
+        let mut cells = heapless::Vec::<(u8, u8), 8>::new();
+        cells.extend(ram.iter());
+        assert_eq!(
+            cells,
+            [(0, b'H'), (1, b'i'), (0x80, 1), (0x81, 2), (0x82, 3)]
+        );
+        assert_eq!(u8::from(ram.load(Byte::from(0x81))), 2);
+
+        // Loading past the end of memory wraps around to address 0
+        ram.load_slice(0xFE, &[4, 5, 6]);
+        assert_eq!(ram.dump()[0xFE..], [4, 5]);
+        assert_eq!(ram.dump()[..2], [6, b'i']);
+        let mut ram = Ram::new();
+        ram.load_slice(0x10, &bytes);
+        assert_eq!(ram.dump()[0x10], 0);
+        assert_eq!(ram.dump()[0x0F], bytes[0xFF]);
+    }
+
+    #[test]
+    #[should_panic = "257 bytes don't fit in memory"]
+    fn slice_too_long() {
+        let _ = Ram::from_slice(&[0; 257]);
+    }
+
+    #[test]
+    fn rom() {
+        let rom = Rom::from_slice(b"Hello");
+        let ram = Ram::from_slice(b"Hello");
+        for address in 0..=u8::MAX {
+            let address = Byte::from(address);
+            assert_eq!(rom.load(address), ram.load(address));
+            assert_eq!(rom.contains(address), u8::from(address) < 5);
+        }
+        assert_eq!((rom.len(), rom.is_empty()), (5, false));
+        assert_eq!(rom.dump(), ram.dump());
+        assert!(Rom::from_slice(&[]).is_empty());
+        let mut text = String::<64>::new();
+        write!(text, "{:?}", Rom::from_slice(&[0; 256])).unwrap();
+        assert_eq!(text, "{0x00..0x100: 00 x 256}");
+    }
+
+    /// Returns the 12 bit address of the value, ignoring the bits above
+    fn address(value: u16) -> [Bit; 12] {
+        array::from_fn(|i| Bit::from((value >> i) & 1 == 1))
+    }
+
+    #[test]
+    fn ram4k() {
+        let mut ram = Ram4k::new();
+        // The last cell of the first bank, the first of the second and the last address
+        for (addr, value) in [(0x0FF, 1), (0x100, 2), (0xFFF, 3)] {
+            ram.store(address(addr), Byte::from(value));
+        }
+        assert_eq!(u8::from(ram.load(address(0x0FF))), 1);
+        assert_eq!(u8::from(ram.load(address(0x100))), 2);
+        assert_eq!(u8::from(ram.load(address(0xFFF))), 3);
+        assert_eq!(u8::from(ram.load(address(0x000))), 0);
+        assert_eq!(u8::from(ram.load(address(0x1FF))), 0);
+        assert_eq!(ram.banks()[0].dump()[0xFF], 1);
+        assert_eq!(ram.banks()[1].dump()[0], 2);
+        assert_eq!(ram.banks()[15].dump()[0xFF], 3);
+
+        // Random accesses agree with an array
+        let mut model = [0; 4096];
+        model[0x0FF] = 1;
+        model[0x100] = 2;
+        model[0xFFF] = 3;
+        let mut next = random_bytes(0x1234_5678);
+        for _ in 0..200 {
+            let [low, high, value, read] = array::from_fn(|_| next());
+            let addr = u16::from_le_bytes([low, high]) & 0xFFF;
+            ram.store(address(addr), Byte::from(value));
+            model[usize::from(addr)] = value;
+            let addr = u16::from(read) << 4 | u16::from(low & 0xF);
+            assert_eq!(u8::from(ram.load(address(addr))), model[usize::from(addr)]);
+        }
+        for (bank, ram) in ram.banks().iter().enumerate() {
+            assert_eq!(ram.dump(), model[bank * 256..][..256]);
+        }
+
+        // Without the write line nothing is stored
+        ram.store_enabled(address(0x100), Byte::from(0xAA), Bit::Low);
+        assert_eq!(u8::from(ram.load(address(0x100))), model[0x100]);
+        ram.store_enabled(address(0x100), Byte::from(0xAA), Bit::High);
+        assert_eq!(u8::from(ram.load(address(0x100))), 0xAA);
+    }
+
+    #[test]
+    fn enable() {
+        let (left, right) = (Byte::from(0x12), Byte::from(0xEF));
+        for select in [Bit::Low, Bit::High] {
+            assert_eq!(
+                mux_en(left, right, select, Bit::High),
+                mux(left, right, select)
+            );
+            assert_eq!(u8::from(mux_en(left, right, select, Bit::Low)), 0);
+        }
+        let input = Byte::from(0xA5);
+        for index in 0..256 {
+            let select: [Bit; 8] = array::from_fn(|bit| Bit::from((index >> bit) & 1 == 1));
+            let all_zero = |bytes: &[Byte]| bytes.iter().all(|&byte| u8::from(byte) == 0);
+            assert!(all_zero(&dmux256_en(input, select, Bit::Low)));
+            assert_eq!(dmux256_en(input, select, Bit::High), dmux256(input, select));
+            let select16 = array::from_fn(|bit| select[bit]);
+            assert!(all_zero(&dmux16_en(input, select16, Bit::Low)));
+            assert_eq!(
+                dmux16_en(input, select16, Bit::High),
+                dmux16(input, select16)
+            );
+            let select4 = array::from_fn(|bit| select[bit]);
+            assert!(all_zero(&dmux4_en(input, select4, Bit::Low)));
+            assert_eq!(dmux4_en(input, select4, Bit::High), dmux4(input, select4));
+        }
+    }
+
+    #[test]
+    fn equality() {
+        let mut ram = Ram::new();
+        assert_eq!(ram, Ram::default());
+        ram.store(Byte::from(0x20), Byte::from(1));
+        assert_ne!(ram, Ram::new());
+        ram.store(Byte::from(0x20), Byte::from(0));
+        assert_eq!(ram, Ram::new());
+
+        let mut registers = Registers::new();
+        registers.store_reg(Register::R5, Byte::from(3));
+        assert_eq!(registers, Registers::from_bytes(&[0, 0, 0, 0, 0, 3, 0, 0]));
+        assert_ne!(registers, Registers::new());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let bytes: [u8; 256] = array::from_fn(|i| u8::try_from(i * 7 % 256).unwrap());
+        let json = serde_json::to_string(&Ram::from_bytes(&bytes)).unwrap();
+        let ram: Ram = serde_json::from_str(&json).unwrap();
+        assert_eq!(ram.dump(), bytes);
+        // The memory needs all of its cells
+        assert!(serde_json::from_str::<Ram>("[1, 2, 3]").is_err());
+
+        let bytes = [1, 0x20, 0xAB, 0xFF, 0, 0, 0, 7];
+        let json = serde_json::to_string(&Registers::from_bytes(&bytes)).unwrap();
+        assert_eq!(json, "[1,32,171,255,0,0,0,7]");
+        let registers: Registers = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            u8::from(registers.load([Bit::High, Bit::High, Bit::Low])),
+            0xFF
+        );
+        assert_eq!(u8::from(registers.load([Bit::High; 3])), 7);
+        assert_eq!(serde_json::to_string(&Byte::from(7)).unwrap(), "7");
+        assert_eq!(
+            serde_json::from_str::<Bit>(&serde_json::to_string(&Bit::High).unwrap()).unwrap(),
+            Bit::High
+        );
+    }
 }
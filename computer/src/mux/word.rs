@@ -0,0 +1,169 @@
+//! Muxes and adders on words, the register file of words, and the addressing of the hierarchical
+//! RAM with a word.
+
+use core::{
+    array,
+    fmt::{self, Debug, Formatter},
+};
+
+use crate::{bit::Bit, byte::Byte, word::Word};
+
+use super::byte::{self, Registers, REGISTER_COUNT};
+
+/// Returns the left word if `select` is `Bit::Low`, returns right word otherwise
+pub fn mux(left: Word, right: Word, select: Bit) -> Word {
+    Word::from((
+        byte::mux(left.low(), right.low(), select),
+        byte::mux(left.high(), right.high(), select),
+    ))
+}
+
+/// Adds 2 words and the carry with the chain of two byte adders of [`Word::add_with_carry`].
+/// Returns the sum and the carry out of the high byte.
+pub fn word_add_with_carry(left: Word, right: Word, carry: Bit) -> (Word, Bit) {
+    left.add_with_carry(right, carry)
+}
+
+/// The register file of a 16 bit machine, one [`Registers`] for the low bytes and one for the
+/// high bytes sharing the select lines. Debug formatted with the word values.
+#[derive(Default)]
+pub struct WordRegisters {
+    low: Registers,
+    high: Registers,
+}
+
+impl WordRegisters {
+    /// Initializes the registers
+    pub const fn new() -> Self {
+        Self {
+            low: Registers::new(),
+            high: Registers::new(),
+        }
+    }
+
+    /// Loads the value of a register
+    pub fn load(&self, select: [Bit; 3]) -> Word {
+        Word::from((self.low.load(select), self.high.load(select)))
+    }
+
+    /// Stores the new word in a register, both bytes in the same clock tick
+    pub fn store(&mut self, select: [Bit; 3], value: Word) {
+        self.low.store(select, value.low());
+        self.high.store(select, value.high());
+    }
+
+    /// Creates registers with the given values, indexed by register number
+    pub fn from_values(values: [u16; REGISTER_COUNT]) -> Self {
+        Self {
+            low: Registers::from_bytes(&values.map(|value| value.to_le_bytes()[0])),
+            high: Registers::from_bytes(&values.map(|value| value.to_le_bytes()[1])),
+        }
+    }
+
+    /// Returns the values of the registers, indexed by register number
+    pub fn values(&self) -> [u16; REGISTER_COUNT] {
+        array::from_fn(|index| {
+            u16::from(self.load(array::from_fn(|i| Bit::from((index >> i) & 1 == 1))))
+        })
+    }
+}
+
+impl Debug for WordRegisters {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (register, value) in self.values().into_iter().enumerate() {
+            map.key(&format_args!("r{register}"))
+                .value(&Word::from(value));
+        }
+        map.finish()
+    }
+}
+
+/// Splits a word address into the byte offset in the bank and the bank select bits of a
+/// [`Ram4k`](byte::Ram4k), bits 0-7 and bits 8-11. The top 4 bits are outside the 4 KiB and
+/// ignored.
+pub fn split_address(address: Word) -> (Byte, [Bit; 4]) {
+    let [b8, b9, b10, b11, ..]: [Bit; 8] = address.high().into();
+    (address.low(), [b8, b9, b10, b11])
+}
+
+/// Returns the 12 bit address of a [`Ram4k`](byte::Ram4k) for a word address, see
+/// [`split_address`]
+pub fn ram4k_address(address: Word) -> [Bit; 12] {
+    let (offset, bank) = split_address(address);
+    let offset: [Bit; 8] = offset.into();
+    array::from_fn(|i| if i < 8 { offset[i] } else { bank[i - 8] })
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{array, fmt::Write};
+
+    use heapless::String;
+
+    use super::{mux, ram4k_address, split_address, word_add_with_carry, WordRegisters};
+    use crate::{
+        bit::Bit,
+        byte::Byte,
+        mux::byte::{Ram4k, Register},
+        word::{tests::values, Word},
+    };
+
+    #[test]
+    fn mux_test() {
+        for left in values() {
+            for right in values() {
+                let (a, b) = (Word::from(left), Word::from(right));
+                assert_eq!(u16::from(mux(a, b, Bit::Low)), left);
+                assert_eq!(u16::from(mux(a, b, Bit::High)), right);
+            }
+        }
+    }
+
+    #[test]
+    fn adder() {
+        for left in values() {
+            for right in values() {
+                let (a, b) = (Word::from(left), Word::from(right));
+                for carry in [false, true] {
+                    let (sum, carry_out) = word_add_with_carry(a, b, Bit::from(carry));
+                    let (expected, wrapped) = left.carrying_add(right, carry);
+                    assert_eq!((u16::from(sum), carry_out), (expected, Bit::from(wrapped)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn word_registers() {
+        let mut registers = WordRegisters::from_values([1, 0x1234, 0, 0, 0, 0, 0, 0xFFFF]);
+        assert_eq!(u16::from(registers.load(Register::R1.into())), 0x1234);
+        registers.store(Register::R5.into(), Word::from(0xABCD));
+        assert_eq!(registers.values(), [1, 0x1234, 0, 0, 0, 0xABCD, 0, 0xFFFF]);
+        let mut text = String::<128>::new();
+        write!(text, "{registers:?}").unwrap();
+        assert!(text.starts_with("{r0: 0x0001, r1: 0x1234, r2: 0x0000,"));
+        let cleared = WordRegisters::new();
+        assert_eq!(cleared.values(), array::from_fn(|_| 0));
+    }
+
+    #[test]
+    fn ram_addressing() {
+        for address in values() {
+            let (offset, bank) = split_address(Word::from(address));
+            assert_eq!(u8::from(offset), address.to_le_bytes()[0]);
+            let bank = bank
+                .into_iter()
+                .enumerate()
+                .fold(0, |bank, (i, bit)| bank | u16::from(bool::from(bit)) << i);
+            assert_eq!(bank, (address >> 8) & 0x0F);
+        }
+
+        // Addresses 4 KiB apart select the same cell
+        let mut ram = Ram4k::new();
+        ram.store(ram4k_address(Word::from(0x1A2B)), Byte::from(0x48));
+        assert_eq!(u8::from(ram.load(ram4k_address(Word::from(0x0A2B)))), 0x48);
+        assert_eq!(u8::from(ram.load(ram4k_address(Word::from(0x0A2C)))), 0);
+        assert_eq!(u8::from(ram.banks()[0x0A].load(Byte::from(0x2B))), 0x48);
+    }
+}
@@ -0,0 +1,585 @@
+//! The decoding of opcode bytes, shared by the computer and the assembler.
+//!
+//! Every consumer of opcode bytes goes through [`decode_opcode`], so the computer and the
+//! disassembler can't disagree about which bytes are instructions and how long they are.
+//!
+//! The register fields of the opcodes and register bytes are 2 bits wide and select `r0` to `r3`.
+//! A [`RegisterPrefix`] byte before an opcode adds 4 to them, selecting `r4` to `r7`. The prefix
+//! bytes weren't opcodes before, so programs without them decode as they always did.
+
+use core::fmt::{self, Display, Formatter};
+
+use crate::{
+    error::{ErrorKind, McError},
+    mux::byte::Register,
+};
+
+/// An operation decoded from its opcode byte, with the registers encoded in the opcode.
+///
+/// Operands following the opcode byte aren't part of it, [`DecodedOp::operand`] tells which one
+/// follows.
+#[expect(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodedOp {
+    LoadConstant(Register),
+    LoadMemory(Register),
+    StoreMemory(Register),
+    Not(Register),
+    Move(Register, Register),
+    Nand(Register, Register),
+    And(Register, Register),
+    Nor(Register, Register),
+    Or(Register, Register),
+    Xnor(Register, Register),
+    Xor(Register, Register),
+    Add(Register, Register),
+    AddOverflow(Register, Register),
+    Sub(Register, Register),
+    SubOverflow(Register, Register),
+    Jump,
+    JumpIfOverflow,
+    Call,
+    Return,
+    JumpIfZero(Register),
+    Push(Register),
+    Pop(Register),
+    ShiftLeft(Register),
+    ShiftRight(Register),
+    RotateLeft(Register),
+    RotateRight(Register),
+    ShiftLeftMemory,
+    ShiftRightMemory,
+    RotateLeftMemory,
+    RotateRightMemory,
+    Inc(Register),
+    Dec(Register),
+    SetBank(Register),
+    SetCarry,
+    ClearCarry,
+    LoadIndirect,
+    StoreIndirect,
+    Mul,
+    Div,
+    Rem,
+    Copy,
+    DecimalAdjust,
+    Halt,
+}
+
+/// The byte following an opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// The instruction is only the opcode byte
+    None,
+
+    /// A constant or an address
+    Byte,
+
+    /// A register byte, selecting registers in bits 2-3 and bits 0-1
+    Registers,
+}
+
+impl DecodedOp {
+    /// Returns the operand following the opcode byte
+    pub const fn operand(self) -> Operand {
+        match self {
+            Self::LoadConstant(_)
+            | Self::LoadMemory(_)
+            | Self::StoreMemory(_)
+            | Self::Jump
+            | Self::JumpIfOverflow
+            | Self::Call
+            | Self::JumpIfZero(_)
+            | Self::ShiftLeftMemory
+            | Self::ShiftRightMemory
+            | Self::RotateLeftMemory
+            | Self::RotateRightMemory => Operand::Byte,
+            Self::LoadIndirect
+            | Self::StoreIndirect
+            | Self::Mul
+            | Self::Div
+            | Self::Rem
+            | Self::Copy
+            | Self::DecimalAdjust => Operand::Registers,
+            _ => Operand::None,
+        }
+    }
+
+    /// Returns the length of the instruction in bytes, including the operand
+    pub const fn length(self) -> usize {
+        match self.operand() {
+            Operand::None => 1,
+            Operand::Byte | Operand::Registers => 2,
+        }
+    }
+}
+
+/// The 16 groups of related instructions a [`Profile`](crate::profile::Profile) counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpcodeGroup {
+    /// Load constant
+    LoadConstant,
+
+    /// Load memory
+    LoadMemory,
+
+    /// Store memory
+    StoreMemory,
+
+    /// Load and store indirect and copy
+    Indirect,
+
+    /// Move between registers
+    Move,
+
+    /// Not and the logic instructions on 2 registers
+    Logic,
+
+    /// Add, add with overflow and decimal adjust
+    Add,
+
+    /// Sub and sub with overflow
+    Sub,
+
+    /// Increment and decrement
+    IncDec,
+
+    /// Multiply, divide and remainder
+    MulDiv,
+
+    /// The register shifts and rotates
+    Shift,
+
+    /// The memory shifts and rotates
+    ShiftMemory,
+
+    /// Jump, jump if overflow and jump if zero
+    Jump,
+
+    /// Call and return
+    CallReturn,
+
+    /// Push and pop
+    Stack,
+
+    /// Set carry, clear carry, set bank and halt
+    Control,
+}
+
+impl OpcodeGroup {
+    /// The groups in the order of their index
+    pub const ALL: [Self; 16] = [
+        Self::LoadConstant,
+        Self::LoadMemory,
+        Self::StoreMemory,
+        Self::Indirect,
+        Self::Move,
+        Self::Logic,
+        Self::Add,
+        Self::Sub,
+        Self::IncDec,
+        Self::MulDiv,
+        Self::Shift,
+        Self::ShiftMemory,
+        Self::Jump,
+        Self::CallReturn,
+        Self::Stack,
+        Self::Control,
+    ];
+
+    /// Returns the index of the group in [`OpcodeGroup::ALL`]
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Returns the name of the group for tables
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::LoadConstant => "load constant",
+            Self::LoadMemory => "load memory",
+            Self::StoreMemory => "store memory",
+            Self::Indirect => "indirect",
+            Self::Move => "move",
+            Self::Logic => "logic",
+            Self::Add => "add",
+            Self::Sub => "sub",
+            Self::IncDec => "inc/dec",
+            Self::MulDiv => "mul/div",
+            Self::Shift => "shift",
+            Self::ShiftMemory => "shift memory",
+            Self::Jump => "jump",
+            Self::CallReturn => "call/return",
+            Self::Stack => "stack",
+            Self::Control => "control",
+        }
+    }
+}
+
+impl DecodedOp {
+    /// Returns the group the operation is counted in by a profile
+    pub const fn group(self) -> OpcodeGroup {
+        match self {
+            Self::LoadConstant(_) => OpcodeGroup::LoadConstant,
+            Self::LoadMemory(_) => OpcodeGroup::LoadMemory,
+            Self::StoreMemory(_) => OpcodeGroup::StoreMemory,
+            Self::LoadIndirect | Self::StoreIndirect | Self::Copy => OpcodeGroup::Indirect,
+            Self::Move(..) => OpcodeGroup::Move,
+            Self::Not(_)
+            | Self::Nand(..)
+            | Self::And(..)
+            | Self::Nor(..)
+            | Self::Or(..)
+            | Self::Xnor(..)
+            | Self::Xor(..) => OpcodeGroup::Logic,
+            Self::Add(..) | Self::AddOverflow(..) | Self::DecimalAdjust => OpcodeGroup::Add,
+            Self::Sub(..) | Self::SubOverflow(..) => OpcodeGroup::Sub,
+            Self::Inc(_) | Self::Dec(_) => OpcodeGroup::IncDec,
+            Self::Mul | Self::Div | Self::Rem => OpcodeGroup::MulDiv,
+            Self::ShiftLeft(_)
+            | Self::ShiftRight(_)
+            | Self::RotateLeft(_)
+            | Self::RotateRight(_) => OpcodeGroup::Shift,
+            Self::ShiftLeftMemory
+            | Self::ShiftRightMemory
+            | Self::RotateLeftMemory
+            | Self::RotateRightMemory => OpcodeGroup::ShiftMemory,
+            Self::Jump | Self::JumpIfOverflow | Self::JumpIfZero(_) => OpcodeGroup::Jump,
+            Self::Call | Self::Return => OpcodeGroup::CallReturn,
+            Self::Push(_) | Self::Pop(_) => OpcodeGroup::Stack,
+            Self::SetBank(_) | Self::SetCarry | Self::ClearCarry | Self::Halt => {
+                OpcodeGroup::Control
+            }
+        }
+    }
+}
+
+/// The byte `1111 10HL` before an opcode, extending its register fields to `r4` to `r7`.
+///
+/// `H` adds 4 to the register in bits 2-3 and `L` adds 4 to the register in bits 0-1, of the
+/// opcode or of the register byte following it. A prefix extending a field the instruction
+/// doesn't have is invalid, so every instruction has a single encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterPrefix {
+    /// Whether the register in bits 2-3 is one of `r4` to `r7`
+    pub high: bool,
+
+    /// Whether the register in bits 0-1 is one of `r4` to `r7`
+    pub low: bool,
+}
+
+impl RegisterPrefix {
+    /// Returns the prefix byte
+    pub const fn byte(self) -> u8 {
+        0b1111_1000 | (self.high as u8) << 1 | self.low as u8
+    }
+}
+
+/// Decodes a register prefix byte, `0b1111_1000` is no prefix as it wouldn't extend a register
+pub const fn decode_prefix(byte: u8) -> Option<RegisterPrefix> {
+    match byte {
+        0b1111_1001..=0b1111_1011 => Some(RegisterPrefix {
+            high: byte & 0b10 != 0,
+            low: byte & 1 != 0,
+        }),
+        _ => None,
+    }
+}
+
+/// The byte isn't the opcode of an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    /// The invalid byte
+    pub byte: u8,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#04X} isn't an opcode", self.byte)
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+impl McError for DecodeError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Execution
+    }
+}
+
+/// Returns the register selected by the lowest 2 bits, plus 4 if it's extended
+const fn register(bits: u8, extended: bool) -> Register {
+    Register::from_number(bits & 0b11 | (extended as u8) << 2)
+}
+
+impl DecodedOp {
+    /// Returns whether the instruction has a register in bits 2-3 and in bits 0-1, of the opcode
+    /// or of its register byte
+    pub const fn register_fields(self) -> (bool, bool) {
+        match self {
+            Self::Move(..)
+            | Self::Nand(..)
+            | Self::And(..)
+            | Self::Nor(..)
+            | Self::Or(..)
+            | Self::Xnor(..)
+            | Self::Xor(..)
+            | Self::Add(..)
+            | Self::AddOverflow(..)
+            | Self::Sub(..)
+            | Self::SubOverflow(..) => (true, true),
+            Self::LoadConstant(_)
+            | Self::LoadMemory(_)
+            | Self::StoreMemory(_)
+            | Self::Not(_)
+            | Self::JumpIfZero(_)
+            | Self::Push(_)
+            | Self::Pop(_)
+            | Self::ShiftLeft(_)
+            | Self::ShiftRight(_)
+            | Self::RotateLeft(_)
+            | Self::RotateRight(_)
+            | Self::Inc(_)
+            | Self::Dec(_)
+            | Self::SetBank(_)
+            | Self::DecimalAdjust => (false, true),
+            _ => match self.operand() {
+                Operand::Registers => (true, true),
+                Operand::None | Operand::Byte => (false, false),
+            },
+        }
+    }
+}
+
+/// Decodes an opcode byte, see the instruction table in the Readme
+///
+/// # Errors
+/// Returns an error if the byte isn't the opcode of an instruction
+pub const fn decode_opcode(byte: u8) -> Result<DecodedOp, DecodeError> {
+    decode(
+        byte,
+        RegisterPrefix {
+            high: false,
+            low: false,
+        },
+    )
+}
+
+/// Decodes the opcode byte following a register prefix. The registers of a register byte are
+/// extended by [`Computer`](crate::Computer) when it reads the byte.
+///
+/// # Errors
+/// Returns an error with the opcode if it isn't the opcode of an instruction, or with the prefix
+/// if it extends a register field the instruction doesn't have
+pub const fn decode_prefixed(prefix: RegisterPrefix, byte: u8) -> Result<DecodedOp, DecodeError> {
+    let op = match decode(byte, prefix) {
+        Ok(op) => op,
+        Err(error) => return Err(error),
+    };
+    let (high, low) = op.register_fields();
+    if (prefix.high && !high) || (prefix.low && !low) {
+        return Err(DecodeError {
+            byte: prefix.byte(),
+        });
+    }
+    Ok(op)
+}
+
+/// Decodes an opcode byte with its register fields extended by the prefix
+const fn decode(byte: u8, prefix: RegisterPrefix) -> Result<DecodedOp, DecodeError> {
+    let (high, low) = (register(byte >> 2, prefix.high), register(byte, prefix.low));
+    Ok(match byte {
+        0b0000_0000..=0b0000_0011 => DecodedOp::LoadConstant(low),
+        0b0000_0100..=0b0000_0111 => DecodedOp::LoadMemory(low),
+        0b0000_1000..=0b0000_1011 => DecodedOp::StoreMemory(low),
+        0b0000_1100..=0b0000_1111 => DecodedOp::Not(low),
+        0b0001_0000..=0b0001_1111 => DecodedOp::Move(high, low),
+        0b0010_0000..=0b0010_1111 => DecodedOp::Nand(high, low),
+        0b0011_0000..=0b0011_1111 => DecodedOp::And(high, low),
+        0b0100_0000..=0b0100_1111 => DecodedOp::Nor(high, low),
+        0b0101_0000..=0b0101_1111 => DecodedOp::Or(high, low),
+        0b0110_0000..=0b0110_1111 => DecodedOp::Xnor(high, low),
+        0b0111_0000..=0b0111_1111 => DecodedOp::Xor(high, low),
+        0b1000_0000..=0b1000_1111 => DecodedOp::Add(high, low),
+        0b1001_0000..=0b1001_1111 => DecodedOp::AddOverflow(high, low),
+        0b1010_0000..=0b1010_1111 => DecodedOp::Sub(high, low),
+        0b1011_0000..=0b1011_1111 => DecodedOp::SubOverflow(high, low),
+        0b1100_0000 => DecodedOp::Jump,
+        0b1100_0001 => DecodedOp::JumpIfOverflow,
+        0b1100_0010 => DecodedOp::Call,
+        0b1100_0011 => DecodedOp::Return,
+        0b1100_0100..=0b1100_0111 => DecodedOp::JumpIfZero(low),
+        0b1100_1000..=0b1100_1011 => DecodedOp::Push(low),
+        0b1100_1100..=0b1100_1111 => DecodedOp::Pop(low),
+        0b1101_0000..=0b1101_0011 => DecodedOp::ShiftLeft(low),
+        0b1101_0100..=0b1101_0111 => DecodedOp::ShiftRight(low),
+        0b1101_1000..=0b1101_1011 => DecodedOp::RotateLeft(low),
+        0b1101_1100..=0b1101_1111 => DecodedOp::RotateRight(low),
+        0b1110_0000 => DecodedOp::ShiftLeftMemory,
+        0b1110_0001 => DecodedOp::ShiftRightMemory,
+        0b1110_0010 => DecodedOp::RotateLeftMemory,
+        0b1110_0011 => DecodedOp::RotateRightMemory,
+        0b1110_0100..=0b1110_0111 => DecodedOp::Inc(low),
+        0b1110_1000..=0b1110_1011 => DecodedOp::Dec(low),
+        0b1110_1100..=0b1110_1111 => DecodedOp::SetBank(low),
+        0b1111_0000 => DecodedOp::SetCarry,
+        0b1111_0001 => DecodedOp::ClearCarry,
+        0b1111_0010 => DecodedOp::LoadIndirect,
+        0b1111_0011 => DecodedOp::StoreIndirect,
+        0b1111_0100 => DecodedOp::Mul,
+        0b1111_0101 => DecodedOp::Div,
+        0b1111_0110 => DecodedOp::Rem,
+        0b1111_0111 => DecodedOp::Copy,
+        0b1111_1100 => DecodedOp::DecimalAdjust,
+        0b1111_1111 => DecodedOp::Halt,
+        _ => return Err(DecodeError { byte }),
+    })
+}
+
+/// Returns the number of cycles the instruction with the opcode takes, or 0 if the byte isn't an
+/// opcode.
+///
+/// Every byte of the instruction takes a cycle to fetch, so register instructions take 1 cycle
+/// and instructions with an operand take 2. Multiply, divide and remainder take 8 more, one for
+/// every bit their circuits step through. A copy moves its block like a DMA transfer, without
+/// taking cycles for the bytes. A [`RegisterPrefix`] takes 1 more
+/// cycle to fetch.
+pub const fn cycles(opcode: u8) -> u8 {
+    let Ok(op) = decode_opcode(opcode) else {
+        return 0;
+    };
+    let fetch = match op.operand() {
+        Operand::None => 1,
+        Operand::Byte | Operand::Registers => 2,
+    };
+    match op {
+        DecodedOp::Mul | DecodedOp::Div | DecodedOp::Rem => fetch + 8,
+        _ => fetch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        cycles, decode_opcode, decode_prefix, decode_prefixed, DecodeError, DecodedOp, OpcodeGroup,
+        Operand,
+    };
+    use crate::{bus::Bus, mux::byte::Register, Computer, ExecutionError};
+
+    #[test]
+    fn invalid_opcodes() {
+        let invalid = (0..=u8::MAX).filter(|&byte| decode_opcode(byte).is_err());
+        assert!(invalid.eq([0xF8, 0xF9, 0xFA, 0xFB, 0xFD, 0xFE]));
+        assert!((0xF9..=0xFB).all(|byte| decode_prefix(byte).is_some()));
+        assert_eq!(decode_opcode(0xFE), Err(DecodeError { byte: 0xFE }));
+    }
+
+    #[test]
+    fn register_fields() {
+        assert_eq!(
+            decode_opcode(0x9E),
+            Ok(DecodedOp::AddOverflow(Register::R3, Register::R2))
+        );
+        assert_eq!(decode_opcode(0xC6), Ok(DecodedOp::JumpIfZero(Register::R2)));
+        assert_eq!(
+            decode_opcode(0xF4).map(DecodedOp::operand),
+            Ok(Operand::Registers)
+        );
+        assert_eq!(decode_opcode(0xC3).map(DecodedOp::length), Ok(1));
+    }
+
+    #[test]
+    fn register_prefixes() {
+        let prefixes = [0xF9, 0xFA, 0xFB].map(|byte| decode_prefix(byte).unwrap());
+        assert!(prefixes
+            .iter()
+            .all(|prefix| decode_prefix(prefix.byte()) == Some(*prefix)));
+        assert_eq!(decode_prefix(0xF8), None);
+        assert_eq!(decode_prefix(0xFF), None);
+        assert_eq!(
+            decode_prefixed(prefixes[2], 0x9E),
+            Ok(DecodedOp::AddOverflow(Register::R7, Register::R6))
+        );
+        assert_eq!(
+            decode_prefixed(prefixes[0], 0x9E),
+            Ok(DecodedOp::AddOverflow(Register::R3, Register::R6))
+        );
+        assert_eq!(
+            decode_prefixed(prefixes[0], 0xC6),
+            Ok(DecodedOp::JumpIfZero(Register::R6))
+        );
+        assert_eq!(decode_prefixed(prefixes[2], 0xF4), Ok(DecodedOp::Mul));
+        // Single register instructions only have the low field, others have none
+        assert_eq!(
+            decode_prefixed(prefixes[1], 0xC6),
+            Err(DecodeError { byte: 0xFA })
+        );
+        assert_eq!(
+            decode_prefixed(prefixes[0], 0xFF),
+            Err(DecodeError { byte: 0xF9 })
+        );
+        assert_eq!(
+            decode_prefixed(prefixes[0], 0xF9),
+            Err(DecodeError { byte: 0xF9 })
+        );
+    }
+
+    #[test]
+    fn cycle_costs() {
+        assert_eq!(cycles(0x0D), 1);
+        assert_eq!(cycles(0x00), 2);
+        assert_eq!(cycles(0xF2), 2);
+        assert_eq!(cycles(0xF5), 10);
+        assert_eq!(cycles(0xFF), 1);
+        assert_eq!(cycles(0xFE), 0);
+    }
+
+    #[test]
+    fn computer_agrees() {
+        for byte in 0..=u8::MAX {
+            // The operand selects r0 for every register byte
+            let mut computer = Computer::new([byte, 0], Bus::new()).unwrap();
+            let result = computer.step();
+            let invalid = result == Err(ExecutionError::InvalidInstruction { offset: 0, byte });
+            // A prefix is decoded with the opcode after it, the computer tests cover them
+            if decode_prefix(byte).is_some() {
+                continue;
+            }
+            assert_eq!(invalid, decode_opcode(byte).is_err(), "{byte:#04X}");
+            if let (Ok(op), Ok(_)) = (decode_opcode(byte), result) {
+                // Jumps move the program counter elsewhere
+                if !matches!(
+                    op,
+                    DecodedOp::Jump | DecodedOp::JumpIfZero(_) | DecodedOp::Call
+                ) {
+                    assert_eq!(
+                        usize::from(computer.program_counter()),
+                        op.length(),
+                        "{byte:#04X}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn groups() {
+        assert!(OpcodeGroup::ALL
+            .iter()
+            .enumerate()
+            .all(|(index, group)| group.index() == index));
+        // Every group has opcodes, and the groups of an opcode agree with the Readme table
+        let mut used = [false; 16];
+        for op in (0..=u8::MAX).filter_map(|byte| decode_opcode(byte).ok()) {
+            used[op.group().index()] = true;
+        }
+        assert_eq!(used, [true; 16]);
+        assert_eq!(
+            decode_opcode(0x0C).map(DecodedOp::group),
+            Ok(OpcodeGroup::Logic)
+        );
+        assert_eq!(
+            decode_opcode(0xFF).map(DecodedOp::group),
+            Ok(OpcodeGroup::Control)
+        );
+    }
+}
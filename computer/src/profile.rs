@@ -0,0 +1,129 @@
+//! Counting how often the groups of instructions execute and how many cycles they take.
+//!
+//! A computer only profiles its execution when it was created with
+//! [`Computer::with_profiler`], otherwise it doesn't keep the counters at all.
+//!
+//! [`Computer::with_profiler`]: crate::Computer::with_profiler
+
+use core::{
+    cmp::Reverse,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::opcode::OpcodeGroup;
+
+/// The executions and cycles of every [`OpcodeGroup`], indexed by [`OpcodeGroup::index`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Profile {
+    /// The number of executed instructions of every group
+    pub counts: [u64; 16],
+
+    /// The number of cycles the instructions of every group took
+    pub cycles: [u64; 16],
+}
+
+impl Profile {
+    /// Creates a profile without any executed instruction
+    pub const fn new() -> Self {
+        Self {
+            counts: [0; 16],
+            cycles: [0; 16],
+        }
+    }
+
+    /// Returns the number of executed instructions of the group
+    pub const fn count(&self, group: OpcodeGroup) -> u64 {
+        self.counts[group.index()]
+    }
+
+    /// Returns the number of cycles the instructions of the group took
+    pub const fn cycles(&self, group: OpcodeGroup) -> u64 {
+        self.cycles[group.index()]
+    }
+
+    /// Counts an executed instruction
+    pub(crate) fn record(&mut self, group: OpcodeGroup, cycles: u8) {
+        self.counts[group.index()] += 1;
+        self.cycles[group.index()] += u64::from(cycles);
+    }
+}
+
+/// Prints the groups that executed as a table, the most cycles first
+impl Display for Profile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut groups = OpcodeGroup::ALL;
+        groups.sort_unstable_by_key(|&group| (Reverse(self.cycles(group)), group.index()));
+        writeln!(f, "{:<13} {:>8} {:>8}", "group", "count", "cycles")?;
+        for group in groups.into_iter().filter(|&group| self.count(group) > 0) {
+            writeln!(
+                f,
+                "{:<13} {:>8} {:>8}",
+                group.name(),
+                self.count(group),
+                self.cycles(group)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{array, fmt::Write};
+
+    use heapless::String;
+
+    use super::Profile;
+    use crate::{
+        bus::{Bus, ConsoleDevice},
+        opcode::OpcodeGroup,
+        Computer, StopReason,
+    };
+
+    #[test]
+    fn hello_world() {
+        let expected = b"Hello, world!";
+        let code: [u8; 52] = array::from_fn(|i| match i % 4 {
+            0 => 0,
+            1 => expected[i / 4],
+            2 => 8,
+            _ => 255,
+        });
+        let mut console = ConsoleDevice::new(String::<16>::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let mut computer = Computer::new(code, bus).unwrap().with_profiler();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        let profile = *computer.profile().unwrap();
+        assert_eq!(profile.count(OpcodeGroup::LoadConstant), 13);
+        assert_eq!(profile.count(OpcodeGroup::StoreMemory), 13);
+        assert_eq!(profile.counts.iter().sum::<u64>(), computer.executed());
+        assert_eq!(profile.cycles.iter().sum::<u64>(), computer.cycles());
+
+        let mut table = String::<128>::new();
+        write!(table, "{profile}").unwrap();
+        assert_eq!(
+            table,
+            "group            count   cycles\n\
+             load constant       13       26\n\
+             store memory        13       26\n"
+        );
+        assert_eq!(console.output(), "Hello, world!");
+    }
+
+    #[test]
+    fn sorted_by_cost() {
+        let mut profile = Profile::new();
+        profile.record(OpcodeGroup::Move, 1);
+        profile.record(OpcodeGroup::MulDiv, 10);
+        profile.record(OpcodeGroup::Jump, 2);
+        profile.record(OpcodeGroup::Jump, 2);
+        let mut table = String::<128>::new();
+        write!(table, "{profile}").unwrap();
+        let groups = table.lines().skip(1).map(|line| &line[..13]);
+        assert!(groups.eq(["mul/div      ", "jump         ", "move         "]));
+
+        // Without the profiler there are no counters
+        let computer = Computer::new([0xFF], Bus::new()).unwrap();
+        assert_eq!(computer.profile(), None);
+    }
+}
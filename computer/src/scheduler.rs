@@ -0,0 +1,156 @@
+//! Time slicing between several computers.
+//!
+//! Each computer has its own bus, so its memory is isolated from the others. Devices are isolated
+//! as well, unless the same device is attached to several buses through a
+//! [`SharedDevice`](crate::bus::SharedDevice).
+
+use crate::{Computer, ExecutionError, StopReason};
+
+/// Runs several computers in turns, giving each of them a slice of fuel.
+///
+/// The computers take turns in order, skipping the ones that stopped, until all of them stopped.
+pub struct Scheduler<'d, const N: usize> {
+    computers: [Computer<'d>; N],
+    slice: u64,
+    switches: u64,
+}
+
+impl<'d, const N: usize> Scheduler<'d, N> {
+    /// Creates a scheduler giving every computer `slice` instructions per turn.
+    ///
+    /// # Panics
+    /// Panics if the slice is empty
+    pub fn new(computers: [Computer<'d>; N], slice: u64) -> Self {
+        assert!(slice > 0, "the slice must allow at least one instruction");
+        Self {
+            computers,
+            slice,
+            switches: 0,
+        }
+    }
+
+    /// Runs all computers until each of them stopped for another reason than running out of fuel.
+    ///
+    /// The fuel the computers were given is replaced, they are left without a limit once they
+    /// stopped. Returns the result of every computer.
+    pub fn run(&mut self) -> [Result<StopReason, ExecutionError>; N] {
+        // Every result is replaced once its computer stopped
+        let mut results = [Ok(StopReason::EndOfProgram); N];
+        let mut stopped = [false; N];
+        let mut last = None;
+        while stopped.contains(&false) {
+            for (index, computer) in self.computers.iter_mut().enumerate() {
+                if stopped[index] {
+                    continue;
+                }
+                if last.is_some_and(|last| last != index) {
+                    self.switches += 1;
+                }
+                last = Some(index);
+                computer.set_fuel(Some(self.slice));
+                match computer.run() {
+                    Ok(StopReason::OutOfFuel { .. }) => {}
+                    result => {
+                        computer.set_fuel(None);
+                        results[index] = result;
+                        stopped[index] = true;
+                    }
+                }
+            }
+        }
+        results
+    }
+
+    /// Returns the number of times the scheduler switched from one computer to another
+    pub const fn switches(&self) -> u64 {
+        self.switches
+    }
+
+    /// Returns the computers, in the order they were given
+    pub const fn computers(&self) -> &[Computer<'d>; N] {
+        &self.computers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use heapless::String;
+
+    use super::Scheduler;
+    use crate::{
+        bus::{Bus, ConsoleDevice, SharedDevice},
+        Computer, StopReason,
+    };
+
+    /// Prints 3 consecutive characters starting with `first`, a print every 5 instructions
+    const fn counter(first: u8) -> [u8; 14] {
+        [
+            0, first, // ldi r0, first
+            1, 1, // ldi r1, 1
+            2, 3, // ldi r2, 3
+            8, 255,  // st r0, [0xff]
+            0x81, // add r0, r1
+            0xA9, // sub r2, r1
+            0xC6, 14, // jz r2, 14
+            0xC0, 6, // jmp 6
+        ]
+    }
+
+    /// Runs both counters sharing a console, returns the output and the number of switches
+    fn interleave(slice: u64) -> (String<8>, u64) {
+        let console = RefCell::new(ConsoleDevice::new(String::new()));
+        let (mut first, mut second) = (SharedDevice::new(&console), SharedDevice::new(&console));
+        let computers = [
+            Computer::new(counter(b'0'), Bus::new().with_device(255..=255, &mut first)).unwrap(),
+            Computer::new(
+                counter(b'a'),
+                Bus::new().with_device(255..=255, &mut second),
+            )
+            .unwrap(),
+        ];
+        let mut scheduler = Scheduler::new(computers, slice);
+        assert_eq!(scheduler.run(), [Ok(StopReason::EndOfProgram); 2]);
+        for computer in scheduler.computers() {
+            assert_eq!(computer.executed(), 17);
+            assert_eq!(computer.fuel(), None);
+        }
+        let switches = scheduler.switches();
+        (console.into_inner().into_output(), switches)
+    }
+
+    #[test]
+    fn shared_console() {
+        assert_eq!(interleave(3), ("0a1b2c".try_into().unwrap(), 11));
+        assert_eq!(interleave(5), ("0a1b2c".try_into().unwrap(), 7));
+        assert_eq!(interleave(7), ("0a12bc".try_into().unwrap(), 5));
+        assert_eq!(interleave(20), ("012abc".try_into().unwrap(), 1));
+    }
+
+    #[test]
+    fn isolated_consoles() {
+        let mut first = ConsoleDevice::new(String::<4>::new());
+        let mut second = ConsoleDevice::new(String::<4>::new());
+        let computers = [
+            Computer::new(counter(b'0'), Bus::new().with_device(255..=255, &mut first)).unwrap(),
+            Computer::new(
+                counter(b'a'),
+                Bus::new().with_device(255..=255, &mut second),
+            )
+            .unwrap(),
+            Computer::new([0xFF], Bus::new()).unwrap(),
+        ];
+        let mut scheduler = Scheduler::new(computers, 4);
+        let [first_result, second_result, halt] = scheduler.run();
+        assert_eq!(first_result, Ok(StopReason::EndOfProgram));
+        assert_eq!(second_result, Ok(StopReason::EndOfProgram));
+        assert_eq!(halt, Ok(StopReason::Halted));
+        // The memory of every computer is its own
+        assert_eq!(scheduler.computers()[0].memory(255), b'2');
+        assert_eq!(scheduler.computers()[1].memory(255), b'c');
+        assert_eq!(scheduler.computers()[2].memory(255), 0);
+        assert_eq!(first.output(), "012");
+        assert_eq!(second.output(), "abc");
+    }
+}
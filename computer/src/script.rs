@@ -0,0 +1,329 @@
+//! A device following a script of the accesses a program is expected to make.
+//!
+//! Tests can check the exact order in which a program talks to a device without writing a
+//! [`Device`] for every scenario:
+//!
+//! ```
+//! use michael_computer::{bus::Bus, script::ScriptedDevice, Computer};
+//!
+//! let mut device = ScriptedDevice::new().with_read(0xF8, 5).with_write(0xF9, 6);
+//! // r0 = [0xF8], inc r0, [0xF9] = r0
+//! let code = [0x04, 0xF8, 0xE4, 0x08, 0xF9];
+//! let bus = Bus::new().with_device(0xF8..=0xF9, &mut device);
+//! Computer::new(code, bus).unwrap().run().unwrap();
+//! assert_eq!(device.finish(), Ok(()));
+//! ```
+
+use core::fmt::{self, Display, Formatter};
+
+use crate::{
+    bus::Device,
+    byte::Byte,
+    error::{ErrorKind, McError},
+};
+
+/// The number of accesses a script can hold
+pub const MAX_ACCESSES: usize = 32;
+
+/// An access of the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// A load from the address, which reads the value
+    Read {
+        /// The address that is read
+        address: u8,
+
+        /// The value returned by the device
+        value: u8,
+    },
+
+    /// A store of the value at the address
+    Write {
+        /// The address that is written
+        address: u8,
+
+        /// The value that is written
+        value: u8,
+    },
+}
+
+impl Display for Access {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Read { address, value } => {
+                write!(f, "a read of {value:#04X} at {address:#04X}")
+            }
+            Self::Write { address, value } => {
+                write!(f, "a write of {value:#04X} at {address:#04X}")
+            }
+        }
+    }
+}
+
+/// The program didn't access the device as the script expected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptError {
+    /// The position of the access in the script
+    pub index: usize,
+
+    /// The access in the script, `None` if the script already ended
+    pub expected: Option<Access>,
+
+    /// The access the program made, `None` if the program ended before it. Reads have the value
+    /// the script expected to return, or 0 after the end of the script.
+    pub actual: Option<Access>,
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "access {}: expected ", self.index)?;
+        match self.expected {
+            Some(access) => write!(f, "{access}")?,
+            None => write!(f, "no more accesses")?,
+        }
+        match self.actual {
+            Some(access) => write!(f, ", got {access}"),
+            None => write!(f, ", the program ended"),
+        }
+    }
+}
+
+impl core::error::Error for ScriptError {}
+
+impl McError for ScriptError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Device
+    }
+}
+
+/// A device that expects the accesses of the script, in order.
+///
+/// Reads in the script return their value, writes in the script must store exactly their value.
+/// The first access that doesn't match is recorded and returned by [`ScriptedDevice::finish`], a
+/// write that doesn't match also fails the device, which stops the program with an output error.
+/// After a mismatch every read goes to the memory cell and every write succeeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptedDevice {
+    script: [Option<Access>; MAX_ACCESSES],
+    next: usize,
+    error: Option<ScriptError>,
+}
+
+impl Default for ScriptedDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScriptedDevice {
+    /// Creates a device that expects no accesses
+    pub const fn new() -> Self {
+        Self {
+            script: [None; MAX_ACCESSES],
+            next: 0,
+            error: None,
+        }
+    }
+
+    /// Appends the access to the script
+    ///
+    /// # Panics
+    /// Panics if the script already holds [`MAX_ACCESSES`] accesses
+    pub const fn with_access(mut self, access: Access) -> Self {
+        let mut index = 0;
+        while self.script[index].is_some() {
+            index += 1;
+            assert!(
+                index < MAX_ACCESSES,
+                "no room for another access in the script"
+            );
+        }
+        self.script[index] = Some(access);
+        self
+    }
+
+    /// Appends a read at the address to the script, the device returns the value
+    ///
+    /// # Panics
+    /// Panics if the script already holds [`MAX_ACCESSES`] accesses
+    pub const fn with_read(self, address: u8, value: u8) -> Self {
+        self.with_access(Access::Read { address, value })
+    }
+
+    /// Appends a write of the value at the address to the script
+    ///
+    /// # Panics
+    /// Panics if the script already holds [`MAX_ACCESSES`] accesses
+    pub const fn with_write(self, address: u8, value: u8) -> Self {
+        self.with_access(Access::Write { address, value })
+    }
+
+    /// Returns the number of accesses that matched the script so far
+    pub const fn completed(&self) -> usize {
+        self.next
+    }
+
+    /// Checks that the program made every access of the script and nothing else
+    ///
+    /// # Errors
+    /// Returns the first access that didn't match, or the first access of the script the program
+    /// didn't make
+    pub fn finish(&self) -> Result<(), ScriptError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.expected().map_or(Ok(()), |expected| {
+            Err(ScriptError {
+                index: self.next,
+                expected: Some(expected),
+                actual: None,
+            })
+        })
+    }
+
+    /// Returns the next access of the script
+    fn expected(&self) -> Option<Access> {
+        self.script.get(self.next).copied().flatten()
+    }
+
+    /// Matches the access against the next one in the script, returns whether it matched.
+    /// Every access matches once a mismatch was recorded.
+    fn check(&mut self, actual: Access) -> bool {
+        if self.error.is_some() {
+            return true;
+        }
+        let expected = self.expected();
+        if expected == Some(actual) {
+            self.next += 1;
+            return true;
+        }
+        self.error = Some(ScriptError {
+            index: self.next,
+            expected,
+            actual: Some(actual),
+        });
+        false
+    }
+}
+
+impl Device for ScriptedDevice {
+    fn read(&mut self, address: Byte) -> Option<Byte> {
+        let address = u8::from(address);
+        // A read matches on the address, the script decides the value
+        let value = match self.expected() {
+            Some(Access::Read {
+                address: expected,
+                value,
+            }) if expected == address => value,
+            _ => 0,
+        };
+        let matched = self.check(Access::Read { address, value }) && self.error.is_none();
+        matched.then(|| Byte::from(value))
+    }
+
+    fn write(&mut self, address: Byte, value: Byte) -> bool {
+        self.check(Access::Write {
+            address: address.into(),
+            value: value.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use heapless::String;
+
+    use super::{Access, ScriptError, ScriptedDevice};
+    use crate::{bus::Bus, Computer, ExecutionError, StopReason};
+
+    /// Reads 2 bytes from 0xF8, writes their sum to 0xF9, then writes 0xFF to 0xFA
+    const CODE: [u8; 12] = [
+        0x04, 0xF8, 0x05, 0xF8, 0x81, 0x08, 0xF9, // r0 = [0xF8] + [0xF8], [0xF9] = r0
+        0x01, 0xFF, 0x09, 0xFA, 0xFF, // r1 = 0xFF, [0xFA] = r1, halt
+    ];
+
+    #[test]
+    fn interaction_order() {
+        let mut device = ScriptedDevice::new()
+            .with_read(0xF8, 3)
+            .with_read(0xF8, 4)
+            .with_write(0xF9, 7)
+            .with_write(0xFA, 0xFF);
+        let bus = Bus::new().with_device(0xF8..=0xFA, &mut device);
+        let mut computer = Computer::new(CODE, bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(device.completed(), 4);
+        assert_eq!(device.finish(), Ok(()));
+    }
+
+    #[test]
+    fn unexpected_write() {
+        let mut device = ScriptedDevice::new()
+            .with_read(0xF8, 3)
+            .with_read(0xF8, 4)
+            .with_write(0xF9, 8)
+            .with_write(0xFA, 0xFF);
+        let bus = Bus::new().with_device(0xF8..=0xFA, &mut device);
+        let mut computer = Computer::new(CODE, bus).unwrap();
+        assert_eq!(
+            computer.run(),
+            Err(ExecutionError::OutputError { offset: 5 })
+        );
+        let error = device.finish().unwrap_err();
+        assert_eq!(
+            error,
+            ScriptError {
+                index: 2,
+                expected: Some(Access::Write {
+                    address: 0xF9,
+                    value: 8
+                }),
+                actual: Some(Access::Write {
+                    address: 0xF9,
+                    value: 7
+                }),
+            }
+        );
+        let mut text = String::<80>::new();
+        write!(text, "{error}").unwrap();
+        assert_eq!(
+            text,
+            "access 2: expected a write of 0x08 at 0xF9, got a write of 0x07 at 0xF9"
+        );
+    }
+
+    #[test]
+    fn unfinished_and_extra_accesses() {
+        // The program ends before the last write
+        let mut device = ScriptedDevice::new()
+            .with_read(0xF8, 1)
+            .with_read(0xF8, 1)
+            .with_write(0xF9, 2)
+            .with_write(0xFA, 0xFF)
+            .with_write(0xFB, 0);
+        let bus = Bus::new().with_device(0xF8..=0xFB, &mut device);
+        Computer::new(CODE, bus).unwrap().run().unwrap();
+        let error = device.finish().unwrap_err();
+        assert_eq!((error.index, error.actual), (4, None));
+
+        // A read the script doesn't expect reads the memory cell
+        let mut device = ScriptedDevice::new();
+        let bus = Bus::new().with_device(0xF8..=0xFA, &mut device);
+        let mut computer = Computer::new(CODE, bus).unwrap();
+        // Only writes fail the device
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(
+            device.finish().unwrap_err(),
+            ScriptError {
+                index: 0,
+                expected: None,
+                actual: Some(Access::Read {
+                    address: 0xF8,
+                    value: 0
+                }),
+            }
+        );
+    }
+}
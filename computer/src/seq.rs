@@ -0,0 +1,302 @@
+//! Sequential logic, circuits whose output depends on what they latched on earlier clock ticks.
+//!
+//! The other modules only contain combinational circuits, which compute their output from their
+//! inputs alone. A [`Dff`] remembers one bit from one tick to the next, and a [`RegisterCell`]
+//! remembers a byte, which the [`Registers`](crate::mux::byte::Registers) are built from. A
+//! [`ShiftRegister`] moves its bits along by one on every tick, a [`RingCounter`] circulates a
+//! single high bit through one, and a [`ClockDivider`] slows a clock down by a power of two.
+//!
+//! ```
+//! use michael_computer::{bit::Bit, byte::Byte, seq::RegisterCell};
+//!
+//! let mut cell = RegisterCell::new();
+//! // The value is latched on the tick, the output follows one tick later
+//! assert_eq!(u8::from(cell.tick(Byte::from(0x48), Bit::High)), 0);
+//! assert_eq!(u8::from(cell.tick(Byte::from(0xFF), Bit::Low)), 0x48);
+//! assert_eq!(u8::from(cell.output()), 0x48);
+//! ```
+
+use crate::{bit::Bit, byte::Byte, mux};
+
+/// A D flip-flop, latching its input on every clock tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dff {
+    latched: Bit,
+}
+
+impl Default for Dff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dff {
+    /// Creates a flip-flop holding `Bit::Low`
+    pub const fn new() -> Self {
+        Self { latched: Bit::Low }
+    }
+
+    /// Latches the input, returns the bit latched on the tick before
+    pub const fn tick(&mut self, input: Bit) -> Bit {
+        let output = self.latched;
+        self.latched = input;
+        output
+    }
+
+    /// Returns the latched bit, without a clock tick
+    pub const fn output(self) -> Bit {
+        self.latched
+    }
+}
+
+/// A byte of 8 flip-flops with a load line, keeping its value on the ticks the line is low
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RegisterCell {
+    bits: [Dff; 8],
+}
+
+impl RegisterCell {
+    /// Creates a cell holding 0
+    pub const fn new() -> Self {
+        Self {
+            bits: [Dff::new(); 8],
+        }
+    }
+
+    /// Latches the input if `load` is high and the old value otherwise, returns the value latched
+    /// on the tick before
+    pub fn tick(&mut self, input: Byte, load: Bit) -> Byte {
+        let output = self.output();
+        let input: [Bit; 8] = input.into();
+        for (bit, input) in self.bits.iter_mut().zip(input) {
+            bit.tick(mux::bit::mux(bit.output(), input, load));
+        }
+        output
+    }
+
+    /// Returns the latched value, without a clock tick
+    pub fn output(self) -> Byte {
+        Byte::from(self.bits.map(Dff::output))
+    }
+}
+
+/// A serial in, parallel out shift register of 8 flip-flops with a shift enable line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShiftRegister {
+    bits: [Dff; 8],
+}
+
+impl ShiftRegister {
+    /// Creates a shift register holding 0
+    pub const fn new() -> Self {
+        Self {
+            bits: [Dff::new(); 8],
+        }
+    }
+
+    /// Shifts the bits one place up with the input at bit 0 if `shift` is high and keeps them
+    /// otherwise, returns the value latched on the tick before
+    pub fn tick(&mut self, input: Bit, shift: Bit) -> Byte {
+        let output = self.output();
+        let mut previous = input;
+        for bit in &mut self.bits {
+            // Every flip-flop latches the bit the one below it held before the tick
+            previous = bit.tick(mux::bit::mux(bit.output(), previous, shift));
+        }
+        output
+    }
+
+    /// Returns the latched bits, bit 0 being the last one shifted in
+    pub fn bits(self) -> [Bit; 8] {
+        self.bits.map(Dff::output)
+    }
+
+    /// Returns the latched bits as a byte
+    pub fn output(self) -> Byte {
+        Byte::from(self.bits())
+    }
+}
+
+/// A ring counter, a shift register feeding its highest bit back into bit 0, starting with only
+/// bit 0 high
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingCounter {
+    register: ShiftRegister,
+}
+
+impl Default for RingCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RingCounter {
+    /// Creates a ring counter with bit 0 high, like after a reset
+    pub fn new() -> Self {
+        let mut register = ShiftRegister::new();
+        register.tick(Bit::High, Bit::High);
+        Self { register }
+    }
+
+    /// Moves the high bit one place up, from bit 7 back to bit 0, returns the value before
+    pub fn tick(&mut self) -> Byte {
+        let [.., last] = self.register.bits();
+        self.register.tick(last, Bit::High)
+    }
+
+    /// Returns the bits of the counter, exactly one of them is high
+    pub fn bits(self) -> [Bit; 8] {
+        self.register.bits()
+    }
+
+    /// Returns the bits of the counter as a byte
+    pub fn output(self) -> Byte {
+        self.register.output()
+    }
+}
+
+/// Divides a clock by a power of two, a counter of 8 flip-flops with half adders between them.
+///
+/// Bit `n` of the counter toggles every 2^n ticks, the divisor selects which bit is the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockDivider {
+    stages: [Dff; 8],
+    divisor: [Bit; 3],
+}
+
+impl Default for ClockDivider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockDivider {
+    /// Creates a divider with the counter at 0, toggling on every tick
+    pub const fn new() -> Self {
+        Self {
+            stages: [Dff::new(); 8],
+            divisor: [Bit::Low; 3],
+        }
+    }
+
+    /// Makes the output toggle every 2^n ticks, with n given by the select bits, keeping the count
+    pub const fn set_divisor(&mut self, divisor: [Bit; 3]) {
+        self.divisor = divisor;
+    }
+
+    /// Counts the tick, returns the divided clock after it
+    pub fn tick(&mut self) -> Bit {
+        let mut carry = Bit::High;
+        for stage in &mut self.stages {
+            let (sum, carry_out) = stage.output().half_adder(carry);
+            stage.tick(sum);
+            carry = carry_out;
+        }
+        self.output()
+    }
+
+    /// Returns the divided clock, without a tick
+    pub fn output(self) -> Bit {
+        let [low @ .., high] = self.divisor;
+        let [b0, b1, b2, b3, b4, b5, b6, b7] = self.stages.map(Dff::output);
+        mux::bit::mux(
+            mux::bit::mux4([b0, b1, b2, b3], low),
+            mux::bit::mux4([b4, b5, b6, b7], low),
+            high,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bit::Bit, byte::Byte};
+
+    use super::{ClockDivider, Dff, RegisterCell, RingCounter, ShiftRegister};
+
+    #[test]
+    fn dff() {
+        let mut dff = Dff::new();
+        let inputs = [Bit::High, Bit::High, Bit::Low, Bit::High, Bit::Low];
+        let outputs = inputs.map(|input| dff.tick(input));
+        assert_eq!(
+            outputs,
+            [Bit::Low, Bit::High, Bit::High, Bit::Low, Bit::High]
+        );
+        assert_eq!(dff.output(), Bit::Low);
+    }
+
+    #[test]
+    fn register_cell() {
+        let mut cell = RegisterCell::new();
+        let ticks = [
+            (0x48, Bit::High, 0),
+            (0xFF, Bit::Low, 0x48),
+            (0x01, Bit::Low, 0x48),
+            (0xA5, Bit::High, 0x48),
+            (0x5A, Bit::High, 0xA5),
+            (0x00, Bit::Low, 0x5A),
+        ];
+        for (input, load, output) in ticks {
+            assert_eq!(u8::from(cell.tick(Byte::from(input), load)), output);
+        }
+        assert_eq!(u8::from(cell.output()), 0x5A);
+        assert_eq!(cell, {
+            let mut other = RegisterCell::default();
+            other.tick(Byte::from(0x5A), Bit::High);
+            other
+        });
+    }
+
+    #[test]
+    fn shift_register() {
+        let mut register = ShiftRegister::new();
+        let mut model = 0_u8;
+        let pattern = [1, 0, 1, 1, 0, 0, 1, 0, 1, 1, 1, 0];
+        for (tick, input) in pattern.into_iter().enumerate() {
+            // Every third tick keeps the bits
+            let shift = tick % 3 != 2;
+            let output = register.tick(Bit::from(input == 1), Bit::from(shift));
+            assert_eq!(u8::from(output), model);
+            if shift {
+                model = model << 1 | input;
+            }
+            assert_eq!(u8::from(register.output()), model);
+            assert_eq!(Byte::from(register.bits()), register.output());
+        }
+    }
+
+    #[test]
+    fn ring_counter() {
+        let mut counter = RingCounter::new();
+        for tick in 0..20 {
+            assert_eq!(u8::from(counter.output()), 1 << (tick % 8));
+            assert_eq!(
+                counter
+                    .bits()
+                    .iter()
+                    .filter(|&&bit| bit == Bit::High)
+                    .count(),
+                1
+            );
+            assert_eq!(u8::from(counter.tick()), 1 << (tick % 8));
+        }
+    }
+
+    #[test]
+    fn clock_divider() {
+        for n in 0..8 {
+            let mut divider = ClockDivider::new();
+            divider.set_divisor([0, 1, 2].map(|bit| Bit::from((n >> bit) & 1 == 1)));
+            let mut previous = divider.output();
+            for tick in 1..=64 {
+                let output = divider.tick();
+                assert_eq!(
+                    output != previous,
+                    tick % (1 << n) == 0,
+                    "n = {n}, tick {tick}"
+                );
+                previous = output;
+            }
+        }
+    }
+}
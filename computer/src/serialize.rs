@@ -0,0 +1,51 @@
+//! Serde support for the memory, which serde can't derive for an array of 256 bytes.
+//!
+//! The memory is stored as a sequence of its 256 byte values, used by [`Ram`] and the memory of
+//! [`MachineState`] with `#[serde(with = "crate::serialize::memory")]`.
+//!
+//! [`Ram`]: crate::mux::byte::Ram
+//! [`MachineState`]: crate::state::MachineState
+
+pub mod memory {
+    use core::fmt::{self, Formatter};
+
+    use serde::{
+        de::{Error, SeqAccess, Visitor},
+        ser::SerializeTuple,
+        Deserializer, Serializer,
+    };
+
+    /// Serializes the bytes as a tuple of 256 elements
+    pub fn serialize<S: Serializer>(memory: &[u8; 256], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(memory.len())?;
+        for byte in memory {
+            tuple.serialize_element(byte)?;
+        }
+        tuple.end()
+    }
+
+    /// Deserializes exactly 256 bytes
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 256], D::Error> {
+        deserializer.deserialize_tuple(256, MemoryVisitor)
+    }
+
+    struct MemoryVisitor;
+
+    impl<'de> Visitor<'de> for MemoryVisitor {
+        type Value = [u8; 256];
+
+        fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+            f.write_str("an array of 256 bytes")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut memory = [0; 256];
+            for (index, cell) in memory.iter_mut().enumerate() {
+                *cell = seq
+                    .next_element()?
+                    .ok_or_else(|| Error::invalid_length(index, &self))?;
+            }
+            Ok(memory)
+        }
+    }
+}
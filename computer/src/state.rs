@@ -0,0 +1,414 @@
+//! Machine state that can be passed in and out of the computer.
+
+use core::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use crate::{
+    computer::STACK_BASE,
+    error::{ErrorKind, McError},
+    mux::byte::{Register, REGISTER_COUNT},
+};
+
+/// The state of the machine before the first instruction is executed.
+///
+/// The state is applied after the program is loaded. Setting the same memory cell twice keeps the
+/// last value. Setting a cell that holds a byte of the program is an error, unless
+/// [`InitialState::allow_overlap`] is set and the state overrides the program byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InitialState {
+    /// The values of the registers
+    pub registers: [u8; REGISTER_COUNT],
+
+    /// The overflow flag
+    pub overflow: bool,
+
+    /// The memory cells to set, `None` cells are left at 0
+    pub memory: [Option<u8>; 256],
+
+    /// The stack pointer, pops below it fail
+    pub stack_pointer: u8,
+
+    /// Whether memory cells may override the bytes of the program
+    pub allow_overlap: bool,
+}
+
+impl Default for InitialState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InitialState {
+    /// Creates an initial state with all registers, memory and the overflow flag cleared and the
+    /// stack pointer at [`STACK_BASE`]
+    pub const fn new() -> Self {
+        Self {
+            registers: [0; REGISTER_COUNT],
+            overflow: false,
+            memory: [None; 256],
+            stack_pointer: STACK_BASE,
+            allow_overlap: false,
+        }
+    }
+
+    /// Sets the values of the first registers, the others are left as they are. Programs for
+    /// `r0` to `r3` only set those.
+    ///
+    /// # Panics
+    /// Panics if there are more values than registers
+    pub const fn with_registers<const N: usize>(mut self, registers: [u8; N]) -> Self {
+        assert!(N <= REGISTER_COUNT, "there are only 8 registers");
+        let mut i = 0;
+        while i < N {
+            self.registers[i] = registers[i];
+            i += 1;
+        }
+        self
+    }
+
+    /// Sets the overflow flag
+    pub const fn with_overflow(mut self, overflow: bool) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets the stack pointer, the start of the stack
+    pub const fn with_stack_pointer(mut self, stack_pointer: u8) -> Self {
+        self.stack_pointer = stack_pointer;
+        self
+    }
+
+    /// Sets the value of a memory cell
+    pub const fn with_memory(mut self, address: u8, value: u8) -> Self {
+        self.memory[address as usize] = Some(value);
+        self
+    }
+
+    /// Sets whether memory cells may override the bytes of the program
+    pub const fn with_allow_overlap(mut self, allow_overlap: bool) -> Self {
+        self.allow_overlap = allow_overlap;
+        self
+    }
+
+    /// Sets the target of the assignment to its value
+    pub const fn with_assignment(self, assignment: Assignment) -> Self {
+        let value = assignment.value;
+        match assignment.target {
+            Target::Register(register) => {
+                let mut state = self;
+                state.registers[register as usize] = value;
+                state
+            }
+            Target::Memory(address) => self.with_memory(address, value),
+            Target::Overflow => self.with_overflow(value != 0),
+            Target::StackPointer => self.with_stack_pointer(value),
+        }
+    }
+}
+
+/// A part of the machine state a value can be assigned to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// A register, written `r0` to `r7`
+    Register(Register),
+
+    /// A memory cell, written `mem[address]`
+    Memory(u8),
+
+    /// The overflow flag, written `overflow`, with the value 0 or 1
+    Overflow,
+
+    /// The stack pointer, written `sp`
+    StackPointer,
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Register(register) => write!(f, "r{}", u8::from(*register)),
+            Self::Memory(address) => write!(f, "mem[{address:#04x}]"),
+            Self::Overflow => write!(f, "overflow"),
+            Self::StackPointer => write!(f, "sp"),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = AssignmentError;
+
+    /// Parses the target case insensitively, the address of a memory cell is a number like the
+    /// value of an [`Assignment`]
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let memory = text
+            .get(..4)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("mem["))
+            .and_then(|_| text[4..].strip_suffix(']'));
+        if let Some(address) = memory {
+            return parse_number(address).map(Self::Memory);
+        }
+        if text.eq_ignore_ascii_case("overflow") {
+            return Ok(Self::Overflow);
+        }
+        if text.eq_ignore_ascii_case("sp") {
+            return Ok(Self::StackPointer);
+        }
+        match text.as_bytes() {
+            [b'r' | b'R', number @ b'0'..=b'7'] => {
+                Ok(Self::Register(Register::from_number(number - b'0')))
+            }
+            _ => Err(AssignmentError::Target),
+        }
+    }
+}
+
+/// A value for a part of the machine state, written `target=value` like `r0=5` or
+/// `mem[0x10]=0xFF`.
+///
+/// Numbers are decimal, or hexadecimal and binary with a `0x` and `0b` prefix. Test vectors and the
+/// `--set` option of `michael-run` use the same syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Assignment {
+    /// The part of the state that is assigned
+    pub target: Target,
+
+    /// The value it is assigned
+    pub value: u8,
+}
+
+impl Display for Assignment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={:#04x}", self.target, self.value)
+    }
+}
+
+impl FromStr for Assignment {
+    type Err = AssignmentError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (target, value) = text.split_once('=').ok_or(AssignmentError::MissingValue)?;
+        let target = target.parse()?;
+        let value = parse_number(value)?;
+        if target == Target::Overflow && value > 1 {
+            return Err(AssignmentError::Value);
+        }
+        Ok(Self { target, value })
+    }
+}
+
+/// Parses a byte written in decimal, or in hexadecimal or binary with a `0x` or `0b` prefix
+fn parse_number(text: &str) -> Result<u8, AssignmentError> {
+    let text = text.trim();
+    let (digits, radix) = match text.get(..2) {
+        Some("0x" | "0X") => (&text[2..], 16),
+        Some("0b" | "0B") => (&text[2..], 2),
+        _ => (text, 10),
+    };
+    u8::from_str_radix(digits, radix).map_err(|_| AssignmentError::Value)
+}
+
+/// Why a text isn't a valid [`Assignment`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssignmentError {
+    /// There is no `=` between the target and the value
+    MissingValue,
+
+    /// The target isn't a register, a memory cell, `overflow` or `sp`
+    Target,
+
+    /// The value or the address isn't a byte, or the overflow flag isn't 0 or 1
+    Value,
+}
+
+impl Display for AssignmentError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::MissingValue => "expected `target=value`",
+            Self::Target => "expected a register, `mem[address]`, `overflow` or `sp`",
+            Self::Value => "the value isn't a byte",
+        })
+    }
+}
+
+impl core::error::Error for AssignmentError {}
+
+impl McError for AssignmentError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Parse
+    }
+}
+
+/// The state of the machine after a program ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[expect(clippy::struct_excessive_bools)]
+pub struct MachineState {
+    /// The values of the registers
+    pub registers: [u8; REGISTER_COUNT],
+
+    /// The contents of memory, as stored by the program
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::memory"))]
+    pub memory: [u8; 256],
+
+    /// The overflow flag
+    pub overflow: bool,
+
+    /// Whether the result of the last logic or arithmetic instruction was 0
+    pub zero: bool,
+
+    /// Whether bit 7 of the result of the last logic or arithmetic instruction was set
+    pub negative: bool,
+
+    /// Whether the last addition, subtraction, increment or decrement carried out of bit 3
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub half_carry: bool,
+
+    /// The stack pointer
+    pub stack_pointer: u8,
+}
+
+impl MachineState {
+    /// Returns the state as a Rust constant named `name`, to embed it in source code
+    pub const fn to_rust_const<'a>(&'a self, name: &'a str) -> StateConst<'a> {
+        StateConst { name, state: self }
+    }
+}
+
+/// Formats a machine state as a `pub const` declaration
+pub struct StateConst<'a> {
+    name: &'a str,
+    state: &'a MachineState,
+}
+
+impl Display for StateConst<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "pub const {}: MachineState = MachineState {{", self.name)?;
+        f.write_str("    registers: [")?;
+        for (i, register) in self.state.registers.iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            write!(f, "{separator}{register:#04x}")?;
+        }
+        writeln!(f, "],")?;
+        writeln!(f, "    memory: [")?;
+        for line in self.state.memory.chunks(16) {
+            f.write_str("       ")?;
+            for byte in line {
+                write!(f, " {byte:#04x},")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "    ],")?;
+        writeln!(f, "    overflow: {},", self.state.overflow)?;
+        writeln!(f, "    zero: {},", self.state.zero)?;
+        writeln!(f, "    negative: {},", self.state.negative)?;
+        writeln!(f, "    half_carry: {},", self.state.half_carry)?;
+        writeln!(f, "    stack_pointer: {:#04x},", self.state.stack_pointer)?;
+        writeln!(f, "}};")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use heapless::String;
+
+    use super::{Assignment, AssignmentError, InitialState, MachineState, Target};
+    use crate::mux::byte::Register;
+
+    #[test]
+    fn last_memory_value_wins() {
+        let state = InitialState::new()
+            .with_memory(0x10, 1)
+            .with_memory(0x20, 2)
+            .with_memory(0x10, 3);
+        assert_eq!(state.memory[0x10], Some(3));
+        assert_eq!(state.memory[0x20], Some(2));
+        assert_eq!(state.memory.iter().flatten().count(), 2);
+    }
+
+    #[test]
+    fn assignments() {
+        let parse = |text: &str| text.parse::<Assignment>();
+        let assignment = |target, value| Ok(Assignment { target, value });
+        assert_eq!(parse("R0=5"), assignment(Target::Register(Register::R0), 5));
+        assert_eq!(
+            parse("r7 = 0b101"),
+            assignment(Target::Register(Register::R7), 5)
+        );
+        assert_eq!(
+            parse("mem[0x10]=0xFF"),
+            assignment(Target::Memory(0x10), 0xFF)
+        );
+        assert_eq!(parse("MEM[255]=0X0a"), assignment(Target::Memory(0xFF), 10));
+        assert_eq!(parse("overflow=1"), assignment(Target::Overflow, 1));
+        assert_eq!(parse("sp=0xE0"), assignment(Target::StackPointer, 0xE0));
+
+        assert_eq!(parse("r0"), Err(AssignmentError::MissingValue));
+        assert_eq!(parse("r8=1"), Err(AssignmentError::Target));
+        assert_eq!(parse("mem[0x100]=1"), Err(AssignmentError::Value));
+        assert_eq!(parse("r0=256"), Err(AssignmentError::Value));
+        assert_eq!(parse("overflow=2"), Err(AssignmentError::Value));
+
+        let mut text = String::<16>::new();
+        write!(text, "{}", parse("MEM[16]=255").unwrap()).unwrap();
+        assert_eq!(text, "mem[0x10]=0xff");
+        assert_eq!(text.parse(), parse("MEM[16]=255"));
+
+        let state = ["r1=2", "mem[3]=4", "overflow=1", "sp=0xE0", "r1=5"]
+            .into_iter()
+            .fold(InitialState::new(), |state, text| {
+                state.with_assignment(parse(text).unwrap())
+            });
+        assert_eq!(
+            state,
+            InitialState::new()
+                .with_registers([0, 5])
+                .with_memory(3, 4)
+                .with_overflow(true)
+                .with_stack_pointer(0xE0)
+        );
+    }
+
+    #[test]
+    fn rust_const() {
+        let mut memory = [0; 256];
+        memory[0xFF] = b'A';
+        let state = MachineState {
+            registers: [0x41, 1, 0, 0xFF, 0, 0, 0, 7],
+            memory,
+            overflow: true,
+            zero: false,
+            negative: true,
+            half_carry: false,
+            stack_pointer: 0xF2,
+        };
+        let mut text = String::<2048>::new();
+        write!(text, "{}", state.to_rust_const("DONE")).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(
+            lines.next(),
+            Some("pub const DONE: MachineState = MachineState {")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("    registers: [0x41, 0x01, 0x00, 0xff, 0x00, 0x00, 0x00, 0x07],")
+        );
+        assert_eq!(lines.next(), Some("    memory: ["));
+        assert!(lines.nth(15).unwrap().ends_with(" 0x00, 0x41,"));
+        assert_eq!(
+            lines.collect::<heapless::Vec<_, 7>>(),
+            [
+                "    ],",
+                "    overflow: true,",
+                "    zero: false,",
+                "    negative: true,",
+                "    half_carry: false,",
+                "    stack_pointer: 0xf2,",
+                "};"
+            ]
+        );
+    }
+}
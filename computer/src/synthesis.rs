@@ -0,0 +1,298 @@
+//! Synthesis of nand circuits from truth tables.
+//!
+//! A function of up to 4 inputs is given by its [`TruthTable`], [`synthesize`] builds a sum of
+//! products from nand gates only: every row with a high output becomes a product of the inputs or
+//! their inversions, and the products are combined with an or.
+//!
+//! ```
+//! use michael_computer::{
+//!     bit::Bit,
+//!     synthesis::{synthesize, TruthTable},
+//! };
+//!
+//! // Rows 1 and 2 are high: a xor b
+//! let table = TruthTable::new(2, 0b0110).unwrap();
+//! let xor = synthesize(&table);
+//! assert!(xor.matches(&table));
+//! assert_eq!(xor.eval([Bit::High, Bit::Low, Bit::Low, Bit::Low]), Bit::High);
+//! ```
+
+use core::array;
+
+use crate::{bit::Bit, circuit::CircuitError};
+
+/// The number of inputs a truth table can have
+pub const MAX_INPUTS: usize = 4;
+
+/// The number of gates a synthesized circuit can hold, enough for every table of 4 inputs
+pub const MAX_GATES: usize = 128;
+
+/// The outputs of a function for every combination of its inputs.
+///
+/// Row `i` holds the output for the inputs that are the bits of `i`, input 0 is the lowest bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TruthTable {
+    inputs: u8,
+    outputs: u16,
+}
+
+impl TruthTable {
+    /// Creates a table of the inputs, bit `i` of the outputs is the output of row `i`. Bits past
+    /// the last row are ignored.
+    ///
+    /// # Errors
+    /// Returns an error if there are no inputs or more than [`MAX_INPUTS`]
+    pub const fn new(inputs: u8, outputs: u16) -> Result<Self, CircuitError> {
+        if inputs == 0 || inputs as usize > MAX_INPUTS {
+            return Err(CircuitError::InputCount(inputs));
+        }
+        Ok(Self {
+            inputs,
+            outputs: outputs & (u16::MAX >> (16 - (1 << inputs))),
+        })
+    }
+
+    /// Creates a table by evaluating the function for every row, the unused inputs are low
+    ///
+    /// # Errors
+    /// Returns an error if there are no inputs or more than [`MAX_INPUTS`]
+    pub fn from_fn(
+        inputs: u8,
+        function: impl Fn([Bit; MAX_INPUTS]) -> Bit,
+    ) -> Result<Self, CircuitError> {
+        let table = Self::new(inputs, 0)?;
+        let outputs = (0..table.rows())
+            .filter(|&row| bool::from(function(row_inputs(row))))
+            .fold(0, |outputs, row| outputs | (1 << row));
+        Ok(Self { outputs, ..table })
+    }
+
+    /// Returns the number of inputs
+    pub const fn inputs(&self) -> u8 {
+        self.inputs
+    }
+
+    /// Returns the number of rows, one for every combination of the inputs
+    pub const fn rows(&self) -> u8 {
+        1 << self.inputs
+    }
+
+    /// Returns the output of the row
+    pub fn output(&self, row: u8) -> Bit {
+        Bit::from((self.outputs >> row) & 1 == 1)
+    }
+}
+
+/// Returns the inputs of a row, input 0 is the lowest bit
+fn row_inputs(row: u8) -> [Bit; MAX_INPUTS] {
+    array::from_fn(|i| Bit::from((row >> i) & 1 == 1))
+}
+
+/// A wire in a synthesized circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// The input with the index
+    Input(u8),
+
+    /// The output of the gate with the index in the netlist
+    Gate(u8),
+}
+
+/// A nand gate of 2 signals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gate {
+    /// The first input of the gate
+    pub left: Signal,
+
+    /// The second input of the gate
+    pub right: Signal,
+}
+
+/// A circuit of nand gates, every gate only reads the inputs and earlier gates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SynthesizedCircuit {
+    inputs: u8,
+    gates: [Gate; MAX_GATES],
+    length: usize,
+    output: Signal,
+}
+
+impl SynthesizedCircuit {
+    /// Returns the netlist, the gates in the order they are evaluated
+    pub fn gates(&self) -> &[Gate] {
+        &self.gates[..self.length]
+    }
+
+    /// Returns the signal that is the output of the circuit
+    pub const fn output(&self) -> Signal {
+        self.output
+    }
+
+    /// Evaluates the gates of the netlist on the inputs, the inputs past the table's are ignored
+    pub fn eval(&self, inputs: [Bit; MAX_INPUTS]) -> Bit {
+        let mut values = [Bit::Low; MAX_GATES];
+        let value = |values: &[Bit], signal| match signal {
+            Signal::Input(index) => inputs[usize::from(index)],
+            Signal::Gate(index) => values[usize::from(index)],
+        };
+        for (i, gate) in self.gates().iter().enumerate() {
+            values[i] = value(&values, gate.left).nand(value(&values, gate.right));
+        }
+        value(&values, self.output)
+    }
+
+    /// Returns the circuit as a closure
+    pub fn as_fn(&self) -> impl Fn([Bit; MAX_INPUTS]) -> Bit + '_ {
+        |inputs| self.eval(inputs)
+    }
+
+    /// Returns whether the circuit computes the table for every row
+    pub fn matches(&self, table: &TruthTable) -> bool {
+        self.inputs == table.inputs
+            && (0..table.rows()).all(|row| self.eval(row_inputs(row)) == table.output(row))
+    }
+
+    /// Appends a nand gate of the signals, returns its output
+    ///
+    /// # Panics
+    /// Panics if the circuit already holds [`MAX_GATES`] gates
+    fn nand(&mut self, left: Signal, right: Signal) -> Signal {
+        self.gates[self.length] = Gate { left, right };
+        self.length += 1;
+        Signal::Gate(u8::try_from(self.length - 1).expect("gate indices fit in a byte"))
+    }
+
+    /// Appends gates computing the inversion of the and of all signals
+    fn nand_all(&mut self, signals: &[Signal]) -> Signal {
+        let Some((&last, rest)) = signals.split_last() else {
+            unreachable!("no signals to combine")
+        };
+        let and = rest.iter().copied().reduce(|left, right| {
+            let nand = self.nand(left, right);
+            self.nand(nand, nand)
+        });
+        match and {
+            Some(and) => self.nand(and, last),
+            None => self.nand(last, last),
+        }
+    }
+}
+
+/// Builds a sum of products from nand gates computing the table
+///
+/// Every product is the nand of the inputs or their inversions for a high row, the nand of all
+/// products is the or of the rows. A table without high rows nands an input with its inversion
+/// and inverts the result, so the output is low.
+pub fn synthesize(table: &TruthTable) -> SynthesizedCircuit {
+    let mut circuit = SynthesizedCircuit {
+        inputs: table.inputs,
+        gates: [Gate {
+            left: Signal::Input(0),
+            right: Signal::Input(0),
+        }; MAX_GATES],
+        length: 0,
+        output: Signal::Input(0),
+    };
+    let mut inverted = [Signal::Input(0); MAX_INPUTS];
+    for (index, inverted) in (0..table.inputs).zip(&mut inverted) {
+        *inverted = circuit.nand(Signal::Input(index), Signal::Input(index));
+    }
+
+    let mut products = [Signal::Input(0); 1 << MAX_INPUTS];
+    let mut count = 0;
+    for row in (0..table.rows()).filter(|&row| table.output(row) == Bit::High) {
+        let mut literals = [Signal::Input(0); MAX_INPUTS];
+        for (index, literal) in (0..table.inputs).zip(&mut literals) {
+            *literal = if (row >> index) & 1 == 1 {
+                Signal::Input(index)
+            } else {
+                inverted[usize::from(index)]
+            };
+        }
+        products[count] = circuit.nand_all(&literals[..usize::from(table.inputs)]);
+        count += 1;
+    }
+
+    circuit.output = if count == 0 {
+        let high = circuit.nand(Signal::Input(0), inverted[0]);
+        circuit.nand(high, high)
+    } else {
+        circuit.nand_all(&products[..count])
+    };
+    debug_assert!(circuit.matches(table));
+    circuit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{synthesize, Signal, SynthesizedCircuit, TruthTable, MAX_INPUTS};
+    use crate::{bit::Bit, circuit::CircuitError};
+
+    /// Checks that every gate only reads the inputs of the table and earlier gates
+    fn well_formed(circuit: &SynthesizedCircuit, inputs: u8) -> bool {
+        let valid = |signal, gate: usize| match signal {
+            Signal::Input(index) => index < inputs,
+            Signal::Gate(index) => usize::from(index) < gate,
+        };
+        circuit
+            .gates()
+            .iter()
+            .enumerate()
+            .all(|(i, gate)| valid(gate.left, i) && valid(gate.right, i))
+            && valid(circuit.output(), circuit.gates().len())
+    }
+
+    #[test]
+    fn xor() {
+        let table = TruthTable::from_fn(2, |[a, b, ..]| a.xor(b)).unwrap();
+        assert_eq!(table, TruthTable::new(2, 0b0110).unwrap());
+        let circuit = synthesize(&table);
+        assert!(circuit.matches(&table));
+        assert!(well_formed(&circuit, 2));
+        // 2 inverters, 2 products and the or
+        assert_eq!(circuit.gates().len(), 5);
+        let xor = circuit.as_fn();
+        assert_eq!(xor([Bit::High, Bit::High, Bit::Low, Bit::Low]), Bit::Low);
+        assert_eq!(xor([Bit::Low, Bit::High, Bit::Low, Bit::Low]), Bit::High);
+    }
+
+    #[test]
+    fn majority() {
+        let majority = |[a, b, c, _]: [Bit; MAX_INPUTS]| a.and(b).or(a.and(c)).or(b.and(c));
+        let table = TruthTable::from_fn(3, majority).unwrap();
+        let circuit = synthesize(&table);
+        assert!(circuit.matches(&table));
+        assert!(well_formed(&circuit, 3));
+        for row in 0..8 {
+            let inputs = super::row_inputs(row);
+            assert_eq!(circuit.eval(inputs), majority(inputs));
+        }
+    }
+
+    #[test]
+    fn every_table_of_4_inputs() {
+        // A fixed pseudorandom table, and every other table as well
+        let random = TruthTable::new(4, 0xB4E1).unwrap();
+        assert!(synthesize(&random).matches(&random));
+        for outputs in 0..=u16::MAX {
+            let table = TruthTable::new(4, outputs).unwrap();
+            let circuit = synthesize(&table);
+            assert!(circuit.matches(&table), "{outputs:#06x}");
+            assert!(well_formed(&circuit, 4), "{outputs:#06x}");
+        }
+    }
+
+    #[test]
+    fn constants_and_input_counts() {
+        let low = TruthTable::new(1, 0).unwrap();
+        assert!(synthesize(&low).matches(&low));
+        // Bits past the last row are ignored
+        let high = TruthTable::new(1, 0xFF).unwrap();
+        assert_eq!(high, TruthTable::new(1, 0b11).unwrap());
+        assert!(synthesize(&high).matches(&high));
+        assert!(!synthesize(&high).matches(&low));
+
+        assert_eq!(TruthTable::new(0, 0), Err(CircuitError::InputCount(0)));
+        assert_eq!(TruthTable::new(5, 0), Err(CircuitError::InputCount(5)));
+    }
+}
@@ -0,0 +1,503 @@
+//! Time as seen by devices, derived from the execution instead of the host clock.
+//!
+//! Devices that depend on time read it from a [`TimeSource`], so a simulation behaves the same on
+//! every run. The [`CycleClock`] given to [`Computer::with_clock`] counts the executed
+//! instructions or cycles, [`MockTime`] is advanced by hand in tests. A host clock can implement
+//! the trait outside of this `no_std` crate.
+//!
+//! ```
+//! use michael_computer::{bus::Bus, time::{CounterDevice, CycleClock}, Computer};
+//!
+//! let clock = CycleClock::new();
+//! let mut counter = CounterDevice::new(&clock);
+//! // r0 = [0xF8], r1 = [0xF8]
+//! let code = [0x04, 0xF8, 0x05, 0xF8];
+//! let bus = Bus::new().with_device(0xF8..=0xF8, &mut counter);
+//! let mut computer = Computer::new(code, bus).unwrap().with_clock(&clock);
+//! computer.run().unwrap();
+//! assert_eq!(computer.registers(), [1, 2, 0, 0, 0, 0, 0, 0]);
+//! ```
+//!
+//! A [`TimerInterrupt`] given to [`Computer::with_interrupt`] interrupts the program every period
+//! of its time source, at the first instruction starting once the period passed.
+//!
+//! [`Computer::with_clock`]: crate::Computer::with_clock
+//! [`Computer::with_interrupt`]: crate::Computer::with_interrupt
+
+use core::cell::Cell;
+
+use crate::{bus::Device, byte::Byte};
+
+/// A clock devices read the time from, in ticks
+pub trait TimeSource {
+    /// Returns the number of ticks since the clock started
+    fn now(&self) -> u64;
+}
+
+impl<T: TimeSource + ?Sized> TimeSource for &T {
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+}
+
+/// The number of instructions executed by the computer the clock is given to, or the number of
+/// cycles they took.
+///
+/// An instruction is counted when it starts, the devices it accesses see it included.
+#[derive(Debug, Default)]
+pub struct CycleClock {
+    cycles: Cell<u64>,
+    counts_cycles: bool,
+}
+
+impl CycleClock {
+    /// Creates a clock at 0, ticking once per executed instruction
+    pub const fn new() -> Self {
+        Self {
+            cycles: Cell::new(0),
+            counts_cycles: false,
+        }
+    }
+
+    /// Creates a clock at 0, ticking once per cycle as counted by [`Computer::cycles`]
+    ///
+    /// [`Computer::cycles`]: crate::Computer::cycles
+    pub const fn counting_cycles() -> Self {
+        Self {
+            cycles: Cell::new(0),
+            counts_cycles: true,
+        }
+    }
+
+    /// Moves the clock to the number of executed instructions or cycles
+    pub(crate) fn set(&self, executed: u64, cycles: u64) {
+        self.cycles
+            .set(if self.counts_cycles { cycles } else { executed });
+    }
+}
+
+impl TimeSource for CycleClock {
+    fn now(&self) -> u64 {
+        self.cycles.get()
+    }
+}
+
+/// A clock that only moves when it is advanced, for tests
+#[derive(Debug, Default)]
+pub struct MockTime {
+    now: Cell<u64>,
+}
+
+impl MockTime {
+    /// Creates a clock at 0
+    pub const fn new() -> Self {
+        Self { now: Cell::new(0) }
+    }
+
+    /// Moves the clock forward by the ticks
+    pub fn advance(&self, ticks: u64) {
+        self.now.set(self.now.get() + ticks);
+    }
+
+    /// Moves the clock to the time
+    pub fn set(&self, now: u64) {
+        self.now.set(now);
+    }
+}
+
+impl TimeSource for MockTime {
+    fn now(&self) -> u64 {
+        self.now.get()
+    }
+}
+
+/// Requests interrupts from the computer it is given to with [`Computer::with_interrupt`]
+///
+/// [`Computer::with_interrupt`]: crate::Computer::with_interrupt
+pub trait InterruptSource {
+    /// Returns whether an interrupt is requested, acknowledging it. Asked before every
+    /// instruction outside of the interrupt handler.
+    fn take(&self) -> bool;
+}
+
+/// Requests an interrupt every `period` ticks of the time source.
+///
+/// An interrupt is due once the time reached the next multiple of the period since the source was
+/// created. Periods that pass while the handler runs or the clock jumps ahead are dropped, only
+/// one interrupt is taken then and the next one is due at the following multiple.
+#[derive(Debug)]
+pub struct TimerInterrupt<T> {
+    time: T,
+    period: u64,
+    next: Cell<u64>,
+}
+
+impl<T: TimeSource> TimerInterrupt<T> {
+    /// Creates a source interrupting every `period` ticks from now
+    ///
+    /// # Panics
+    /// Panics if the period is 0
+    pub fn new(time: T, period: u64) -> Self {
+        assert!(period > 0, "the period of a timer interrupt can't be 0");
+        let next = Cell::new(time.now() + period);
+        Self { time, period, next }
+    }
+
+    /// Returns the time the next interrupt is due at
+    pub const fn next(&self) -> u64 {
+        self.next.get()
+    }
+}
+
+impl<T: TimeSource> InterruptSource for TimerInterrupt<T> {
+    fn take(&self) -> bool {
+        let (now, next) = (self.time.now(), self.next.get());
+        if now < next {
+            return false;
+        }
+        self.next
+            .set(now - (now - next) % self.period + self.period);
+        true
+    }
+}
+
+/// A device reading the low byte of the time on every load, writes are ignored
+pub struct CounterDevice<T> {
+    time: T,
+}
+
+impl<T: TimeSource> CounterDevice<T> {
+    /// Creates a counter of the time source
+    pub const fn new(time: T) -> Self {
+        Self { time }
+    }
+}
+
+impl<T: TimeSource> Device for CounterDevice<T> {
+    fn read(&mut self, _: Byte) -> Option<Byte> {
+        Some(Byte::from(self.time.now().to_le_bytes()[0]))
+    }
+
+    fn write(&mut self, _: Byte, _: Byte) -> bool {
+        true
+    }
+}
+
+/// A free-running timer mapped at 2 addresses, counting the ticks of its time source.
+///
+/// Reading the base address returns the counter, which the gate-level incrementer of [`Byte`]
+/// moves once per tick, wrapping from 255 to 0. Writes to it are ignored. Writing the reload
+/// register at the base address + 1 resets the counter to the written value, reading it returns
+/// that value.
+pub struct TimerDevice<T> {
+    time: T,
+    base: u8,
+    counter: Byte,
+    reload: Byte,
+    seen: u64,
+}
+
+/// The state of a [`TimerDevice`], saved next to a [`Snapshot`] to continue with the same counter.
+///
+/// Devices aren't part of a snapshot, and a restored [`CycleClock`] moves back in time, so the
+/// timer has to be restored as well to read the same values again.
+///
+/// [`Snapshot`]: crate::Snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimerState {
+    /// The value of the counter
+    pub counter: u8,
+
+    /// The value last written to the reload register
+    pub reload: u8,
+
+    /// The time the counter was last brought up to date
+    pub seen: u64,
+}
+
+impl<T: TimeSource> TimerDevice<T> {
+    /// Creates a timer at 0 with the counter at `base` and the reload register at `base + 1`
+    ///
+    /// # Panics
+    /// Panics if `base` is 255, leaving no address for the reload register
+    pub fn new(time: T, base: u8) -> Self {
+        assert!(base < u8::MAX, "no address for the reload register");
+        let seen = time.now();
+        Self {
+            time,
+            base,
+            counter: Byte::from(0),
+            reload: Byte::from(0),
+            seen,
+        }
+    }
+
+    /// Returns the current value of the counter
+    pub fn counter(&mut self) -> u8 {
+        self.update();
+        self.counter.into()
+    }
+
+    /// Returns the state of the timer, brought up to date with the time source
+    pub fn state(&mut self) -> TimerState {
+        self.update();
+        TimerState {
+            counter: self.counter.into(),
+            reload: self.reload.into(),
+            seen: self.seen,
+        }
+    }
+
+    /// Puts the timer back into a saved state
+    pub fn restore(&mut self, state: &TimerState) {
+        self.counter = state.counter.into();
+        self.reload = state.reload.into();
+        self.seen = state.seen;
+    }
+
+    /// Increments the counter for the ticks since it was last updated
+    fn update(&mut self) {
+        let now = self.time.now();
+        // Only the last 256 ticks change the counter, a clock moving back doesn't change it
+        for _ in 0..now.saturating_sub(self.seen) % 256 {
+            self.counter = self.counter.increment().0;
+        }
+        self.seen = now;
+    }
+}
+
+impl<T: TimeSource> Device for TimerDevice<T> {
+    fn read(&mut self, address: Byte) -> Option<Byte> {
+        match u8::from(address).wrapping_sub(self.base) {
+            0 => {
+                self.update();
+                Some(self.counter)
+            }
+            1 => Some(self.reload),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: Byte, value: Byte) -> bool {
+        if u8::from(address).wrapping_sub(self.base) == 1 {
+            self.reload = value;
+            self.counter = value;
+            self.seen = self.time.now();
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use heapless::String;
+
+    use super::{
+        CounterDevice, CycleClock, MockTime, TimeSource, TimerDevice, TimerInterrupt, TimerState,
+    };
+    use crate::{
+        bus::{Bus, ConsoleDevice, SharedDevice},
+        mux::byte::Register,
+        Computer, StepOutcome, StopReason,
+    };
+
+    /// Loads the counter at 0xF8 into r0, then into r1 after 2 other instructions
+    const CODE: [u8; 8] = [0x04, 0xF8, 0x0F, 0x0F, 0x05, 0xF8, 0x0E, 0xFF];
+
+    #[test]
+    fn cycle_clock() {
+        let clock = CycleClock::new();
+        let mut counter = CounterDevice::new(&clock);
+        let bus = Bus::new().with_device(0xF8..=0xF8, &mut counter);
+        let mut computer = Computer::new(CODE, bus).unwrap().with_clock(&clock);
+        computer.run().unwrap();
+        assert_eq!(computer.register(Register::R0), 1);
+        assert_eq!(computer.register(Register::R1), 4);
+        assert_eq!(clock.now(), computer.executed());
+
+        // The same program sees the same time on every run
+        let again = CycleClock::new();
+        let mut counter = CounterDevice::new(&again);
+        let bus = Bus::new().with_device(0xF8..=0xF8, &mut counter);
+        let mut second = Computer::new(CODE, bus).unwrap().with_clock(&again);
+        second.run().unwrap();
+        assert_eq!(second.registers(), computer.registers());
+    }
+
+    #[test]
+    fn mock_time() {
+        let time = MockTime::new();
+        let mut counter = CounterDevice::new(&time);
+        let bus = Bus::new().with_device(0xF8..=0xF8, &mut counter);
+        let mut computer = Computer::new(CODE, bus).unwrap();
+        time.set(7);
+        assert_eq!(computer.step(), Ok(StepOutcome::Executed));
+        // Executing doesn't move the mock, only advancing it does
+        time.advance(0x1FE);
+        for _ in 0..3 {
+            computer.step().unwrap();
+        }
+        assert_eq!(computer.registers()[..2], [7, 5]);
+        assert_eq!(time.now(), 0x205);
+    }
+
+    /// Resets the timer at 0xF8 to 100, waits until it reaches 120 and prints `T`
+    const WAIT: [u8; 18] = [
+        0x00, 100, 0x08, 0xF9, // reset the timer to 100
+        0x01, 120, 0x04, 0xF8, 0xA1, 0xC1, 13, 0xC0, 6, // loop until r0 >= 120
+        0x00, b'T', 0x08, 0xFF, 0xFF,
+    ];
+
+    #[test]
+    fn timer_busy_wait() {
+        let clock = CycleClock::new();
+        let mut timer = TimerDevice::new(&clock, 0xF8);
+        let mut console = ConsoleDevice::new(String::<4>::new());
+        let bus = Bus::new()
+            .with_device(0xF8..=0xF9, &mut timer)
+            .with_device(255..=255, &mut console);
+        let mut computer = Computer::new(WAIT, bus).unwrap().with_clock(&clock);
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        // The reset ran as instruction 2, each round of the loop takes 4 and it stops at 122
+        assert_eq!(computer.executed(), 29);
+        assert_eq!(console.output(), "T");
+        assert_eq!(timer.counter(), 127);
+        assert_eq!(timer.state().reload, 100);
+
+        // Counting cycles a round takes 7 and the loop stops at 125
+        let clock = CycleClock::counting_cycles();
+        let mut timer = TimerDevice::new(&clock, 0xF8);
+        let bus = Bus::new().with_device(0xF8..=0xF9, &mut timer);
+        let mut computer = Computer::new(WAIT, bus).unwrap().with_clock(&clock);
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!(computer.executed(), 21);
+    }
+
+    #[test]
+    fn timer_wraps_and_restores() {
+        let time = MockTime::new();
+        let mut timer = TimerDevice::new(&time, 0x10);
+        time.advance(255);
+        assert_eq!(timer.counter(), 255);
+        time.advance(1);
+        assert_eq!(timer.counter(), 0);
+        time.advance(0x203);
+        assert_eq!(timer.counter(), 3);
+        let saved = timer.state();
+        assert_eq!(
+            saved,
+            TimerState {
+                counter: 3,
+                reload: 0,
+                seen: 0x303
+            }
+        );
+
+        time.advance(10);
+        assert_eq!(timer.counter(), 13);
+        // The time moving back leaves the counter, restoring brings it back
+        time.set(0x303);
+        assert_eq!(timer.counter(), 13);
+        timer.restore(&saved);
+        time.advance(1);
+        assert_eq!(timer.counter(), 4);
+    }
+
+    #[test]
+    fn timer_with_snapshots() {
+        let clock = CycleClock::new();
+        let timer = RefCell::new(TimerDevice::new(&clock, 0xF8));
+        let mut device = SharedDevice::new(&timer);
+        let bus = Bus::new().with_device(0xF8..=0xF9, &mut device);
+        let mut computer = Computer::new(WAIT, bus).unwrap().with_clock(&clock);
+        for _ in 0..10 {
+            computer.step().unwrap();
+        }
+        let snapshot = (computer.snapshot(), timer.borrow_mut().state());
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        let first = (computer.snapshot(), timer.borrow_mut().state());
+
+        computer.restore(&snapshot.0);
+        timer.borrow_mut().restore(&snapshot.1);
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert_eq!((computer.snapshot(), timer.borrow_mut().state()), first);
+
+        // Without restoring the timer too, the loop sees the later counter and ends early
+        computer.restore(&snapshot.0);
+        assert_eq!(computer.run(), Ok(StopReason::Halted));
+        assert!(computer.executed() < first.0.executed);
+    }
+
+    /// Increments r0 in a loop, the interrupt handler at 3 increments r1
+    const COUNT: [u8; 5] = [0xE4, 0xC0, 0x00, 0xE5, 0xC3];
+
+    #[test]
+    fn mock_time_interrupts() {
+        let time = MockTime::new();
+        let interrupt = TimerInterrupt::new(&time, 10);
+        let mut computer = Computer::new(COUNT, Bus::new())
+            .unwrap()
+            .with_interrupt(&interrupt, 3);
+        time.advance(9);
+        computer.step().unwrap();
+        assert!(!computer.in_interrupt());
+        assert_eq!(computer.program_counter(), 1);
+
+        // The step after the period passed runs the first instruction of the handler
+        time.advance(1);
+        computer.step().unwrap();
+        assert!(computer.in_interrupt());
+        assert_eq!(computer.registers()[..2], [1, 1]);
+        assert_eq!(interrupt.next(), 20);
+        // The handler isn't interrupted, returning continues at the jump
+        time.advance(10);
+        computer.step().unwrap();
+        assert!(!computer.in_interrupt());
+        assert_eq!(computer.program_counter(), 1);
+        assert_eq!(computer.state().stack_pointer, 0xF0);
+        computer.step().unwrap();
+        assert_eq!(computer.program_counter(), 4);
+
+        // Jumping ahead by several periods takes a single interrupt
+        computer.step().unwrap();
+        time.advance(35);
+        for _ in 0..4 {
+            computer.step().unwrap();
+        }
+        assert_eq!(computer.register(Register::R1), 3);
+        assert_eq!(interrupt.next(), 60);
+    }
+
+    #[test]
+    fn cycle_clock_interrupts() {
+        let clock = CycleClock::new();
+        let interrupt = TimerInterrupt::new(&clock, 6);
+        let mut computer = Computer::new(COUNT, Bus::new())
+            .unwrap()
+            .with_clock(&clock)
+            .with_interrupt(&interrupt, 3);
+        let mut entered = heapless::Vec::<u64, 8>::new();
+        for _ in 0..25 {
+            let outside = !computer.in_interrupt();
+            computer.step().unwrap();
+            if outside && computer.in_interrupt() {
+                entered.push(computer.executed()).unwrap();
+            }
+        }
+        // The handler starts with the instruction after every 6th, returning takes another one,
+        // so the loop ran 6 instructions and then 4 between the interrupts
+        assert_eq!(entered, [7, 13, 19, 25]);
+        assert_eq!(computer.register(Register::R1), 4);
+        assert_eq!(computer.register(Register::R0), 3 + 2 + 2 + 2);
+
+        // A snapshot inside the handler returns from it after restoring
+        let snapshot = computer.snapshot();
+        computer.step().unwrap();
+        assert!(!computer.in_interrupt());
+        computer.restore(&snapshot);
+        assert!(computer.in_interrupt());
+    }
+}
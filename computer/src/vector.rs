@@ -0,0 +1,323 @@
+//! Test vectors: the initial state a program starts from and the results it is expected to leave.
+//!
+//! A test vector is a text with one statement per line. `set` assigns the initial state like
+//! [`InitialState::with_assignment`], `expect` the value a register, memory cell or flag must have
+//! after the program stopped, and `output` the text the program must write. Empty lines and lines
+//! starting with `#` are skipped.
+//!
+//! ```text
+//! # adds two numbers from memory
+//! set mem[0x80]=5
+//! set mem[0x81]=0x10
+//! expect r0=0x15
+//! expect overflow=0
+//! output Done
+//! ```
+//!
+//! The text after `output ` is compared with the output as it is, so it can't contain line breaks.
+
+use core::fmt::{self, Display, Formatter};
+
+use crate::{
+    error::{ErrorKind, McError},
+    state::{Assignment, AssignmentError, InitialState, MachineState, Target},
+};
+
+/// The number of expectations a test vector can hold
+pub const MAX_EXPECTATIONS: usize = 32;
+
+/// A parsed test vector, borrowing the expected output from the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestVector<'a> {
+    /// The state the program starts from
+    pub initial: InitialState,
+
+    /// The values the program must leave, checked in order
+    expectations: [Option<Assignment>; MAX_EXPECTATIONS],
+
+    /// The text the program must write, if it is checked
+    pub output: Option<&'a str>,
+}
+
+impl<'a> TestVector<'a> {
+    /// Parses the statements of a test vector
+    ///
+    /// # Errors
+    /// Returns the line of the first statement that isn't valid
+    pub fn parse(text: &'a str) -> Result<Self, VectorError> {
+        let mut vector = Self {
+            initial: InitialState::new(),
+            expectations: [None; MAX_EXPECTATIONS],
+            output: None,
+        };
+        let mut expectations = 0;
+        for (line, statement) in (1..).zip(text.lines()) {
+            let statement = statement.trim_end_matches('\r');
+            let invalid = |error| VectorError::Assignment { line, error };
+            if statement.trim().is_empty() || statement.trim_start().starts_with('#') {
+                continue;
+            }
+            if let Some(assignment) = statement.strip_prefix("set ") {
+                vector.initial = vector
+                    .initial
+                    .with_assignment(assignment.parse().map_err(invalid)?);
+            } else if let Some(assignment) = statement.strip_prefix("expect ") {
+                let slot = vector
+                    .expectations
+                    .get_mut(expectations)
+                    .ok_or(VectorError::TooManyExpectations { line })?;
+                *slot = Some(assignment.parse().map_err(invalid)?);
+                expectations += 1;
+            } else if let Some(output) = statement.strip_prefix("output ") {
+                vector.output = Some(output);
+            } else {
+                return Err(VectorError::Statement { line });
+            }
+        }
+        Ok(vector)
+    }
+
+    /// Returns the values the program must leave
+    pub fn expectations(&self) -> impl Iterator<Item = Assignment> + '_ {
+        self.expectations.iter().flatten().copied()
+    }
+
+    /// Compares the state the program left and its output with the expectations.
+    /// The output bytes are compared with the code points of the expected text, the way the
+    /// console writes them.
+    ///
+    /// # Errors
+    /// Returns the first expectation the state or output doesn't meet
+    pub fn check(&self, state: &MachineState, output: &[u8]) -> Result<(), Mismatch> {
+        for expected in self.expectations() {
+            let found = match expected.target {
+                Target::Register(register) => state.registers[usize::from(u8::from(register))],
+                Target::Memory(address) => state.memory[usize::from(address)],
+                Target::Overflow => u8::from(state.overflow),
+                Target::StackPointer => state.stack_pointer,
+            };
+            if found != expected.value {
+                return Err(Mismatch::Value { expected, found });
+            }
+        }
+        if let Some(text) = self.output {
+            let position = output
+                .iter()
+                .map(|&byte| Some(char::from(byte)))
+                .chain([None])
+                .zip(text.chars().map(Some).chain([None]))
+                .position(|(found, expected)| found != expected);
+            if let Some(position) = position {
+                return Err(Mismatch::Output { position });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An expectation of a test vector the program didn't meet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mismatch {
+    /// A register, memory cell or flag has another value
+    Value {
+        /// The expectation with the value the program should have left
+        expected: Assignment,
+
+        /// The value the program left
+        found: u8,
+    },
+
+    /// The output differs from the expected text at the position, or one of them ends there
+    Output {
+        /// The position of the first differing character
+        position: usize,
+    },
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value { expected, found } => {
+                write!(f, "expected {expected}, found {found:#04x}")
+            }
+            Self::Output { position } => write!(f, "output differs at position {position}"),
+        }
+    }
+}
+
+/// Why a text isn't a valid [`TestVector`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorError {
+    /// The line doesn't start with `set`, `expect` or `output`
+    Statement {
+        /// The number of the line, starting at 1
+        line: usize,
+    },
+
+    /// The assignment on the line isn't valid
+    Assignment {
+        /// The number of the line, starting at 1
+        line: usize,
+
+        /// What is wrong with the assignment
+        error: AssignmentError,
+    },
+
+    /// The line has more than [`MAX_EXPECTATIONS`] expectations before it
+    TooManyExpectations {
+        /// The number of the line, starting at 1
+        line: usize,
+    },
+}
+
+impl Display for VectorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Statement { line } => {
+                write!(f, "line {line}: expected `set`, `expect` or `output`")
+            }
+            Self::Assignment { line, error } => write!(f, "line {line}: {error}"),
+            Self::TooManyExpectations { line } => {
+                write!(f, "line {line}: more than {MAX_EXPECTATIONS} expectations")
+            }
+        }
+    }
+}
+
+impl core::error::Error for VectorError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Assignment { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl McError for VectorError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Parse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::{Mismatch, TestVector, VectorError, MAX_EXPECTATIONS};
+    use crate::{
+        bus::{Bus, ConsoleDevice},
+        mux::byte::Register,
+        state::{Assignment, AssignmentError, InitialState, Target},
+        Computer, StopReason,
+    };
+
+    /// Adds the bytes at 0x80 and 0x81 and prints `!`
+    const ADD: [u8; 9] = [
+        0x04, 0x80, // ld r0, [0x80]
+        0x05, 0x81, // ld r1, [0x81]
+        0x81, // add r0, r1
+        0x02, b'!', // ldi r2, '!'
+        0x0A, 0xFF, // st r2, [255]
+    ];
+
+    const VECTOR: &str = "\
+# adds two numbers from memory
+set mem[0x80]=5
+set mem[0x81]=0x10
+
+expect r0=0x15
+expect overflow=0
+output !
+";
+
+    /// Runs the program from the initial state of the vector and checks its expectations
+    fn check(vector: &TestVector) -> Result<(), Mismatch> {
+        let mut console = ConsoleDevice::new(heapless::String::<8>::new());
+        let bus = Bus::new().with_device(255..=255, &mut console);
+        let mut computer = Computer::with_initial_state(ADD, &vector.initial, bus).unwrap();
+        assert_eq!(computer.run(), Ok(StopReason::EndOfProgram));
+        let state = computer.state();
+        let output: Vec<u8, 8> = console
+            .output()
+            .chars()
+            .map(|c| u8::try_from(c).unwrap())
+            .collect();
+        vector.check(&state, &output)
+    }
+
+    #[test]
+    fn parse_and_check() {
+        let vector = TestVector::parse(VECTOR).unwrap();
+        assert_eq!(
+            vector.initial,
+            InitialState::new()
+                .with_memory(0x80, 5)
+                .with_memory(0x81, 0x10)
+        );
+        assert_eq!(vector.expectations().count(), 2);
+        assert_eq!(vector.output, Some("!"));
+        assert_eq!(check(&vector), Ok(()));
+    }
+
+    #[test]
+    fn mismatches() {
+        let vector = TestVector::parse(
+            "set mem[0x80]=0xF0\nset mem[0x81]=0x20\nexpect r0=0x10\nexpect overflow=0",
+        )
+        .unwrap();
+        assert_eq!(
+            check(&vector),
+            Err(Mismatch::Value {
+                expected: Assignment {
+                    target: Target::Overflow,
+                    value: 0
+                },
+                found: 1
+            })
+        );
+        let vector = TestVector::parse("expect r1=1").unwrap();
+        assert_eq!(
+            check(&vector),
+            Err(Mismatch::Value {
+                expected: Assignment {
+                    target: Target::Register(Register::R1),
+                    value: 1
+                },
+                found: 0
+            })
+        );
+        assert_eq!(
+            check(&TestVector::parse("output !!").unwrap()),
+            Err(Mismatch::Output { position: 1 })
+        );
+        assert_eq!(
+            check(&TestVector::parse("output ?").unwrap()),
+            Err(Mismatch::Output { position: 0 })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            TestVector::parse("set r0=1\nrun"),
+            Err(VectorError::Statement { line: 2 })
+        );
+        assert_eq!(
+            TestVector::parse("# comment\nexpect r9=1"),
+            Err(VectorError::Assignment {
+                line: 2,
+                error: AssignmentError::Target
+            })
+        );
+        let mut text = heapless::String::<512>::new();
+        for _ in 0..=MAX_EXPECTATIONS {
+            text.push_str("expect r0=0\n").unwrap();
+        }
+        assert_eq!(
+            TestVector::parse(&text),
+            Err(VectorError::TooManyExpectations {
+                line: MAX_EXPECTATIONS + 1
+            })
+        );
+    }
+}
@@ -0,0 +1,630 @@
+//! The 16 bit word datatype, a low and a high byte for arithmetic on more than one byte, and the
+//! [`WordType`] a [`Computer`](crate::Computer) computes with.
+
+use core::{
+    array,
+    fmt::{self, Debug, Formatter},
+    ops::{Add, Not},
+};
+
+use crate::{
+    bit::Bit,
+    byte::Byte,
+    mux::{
+        self,
+        byte::{
+            multiply, rotate_left, rotate_right, shift_left, shift_right_logical, Register,
+            Registers, REGISTER_COUNT,
+        },
+        word::WordRegisters,
+    },
+};
+
+/// A word of 16 bits, stored as its low and its high byte.
+/// Words are equal when their values are, and are debug formatted as their value in hexadecimal.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "u16", into = "u16")
+)]
+pub struct Word {
+    low: Byte,
+    high: Byte,
+}
+
+impl Word {
+    /// Returns the low byte, bits 0 to 7
+    pub const fn low(self) -> Byte {
+        self.low
+    }
+
+    /// Returns the high byte, bits 8 to 15
+    pub const fn high(self) -> Byte {
+        self.high
+    }
+
+    /// Adds 2 words and the carry bit, the carry out of the low byte adder is the carry in of the
+    /// high byte adder. Returns the sum and the carry out.
+    pub fn add_with_carry(self, right: Self, carry: Bit) -> (Self, Bit) {
+        let (low, carry) = self.low.add_with_carry(right.low, carry);
+        let (high, carry) = self.high.add_with_carry(right.high, carry);
+        (Self { low, high }, carry)
+    }
+
+    /// Subtracts the right word and the borrow bit, by adding the inverted word with the inverted
+    /// borrow. Returns the difference and `Bit::High` if the subtraction borrowed.
+    pub fn sub_with_borrow(self, right: Self, borrow: Bit) -> (Self, Bit) {
+        let (difference, carry) = self.add_with_carry(!right, borrow.not());
+        (difference, carry.not())
+    }
+}
+
+/// Extends the byte to a word with its value as a two's complement number kept, the high byte is
+/// the sign bit repeated
+pub fn sign_extend(input: Byte) -> Word {
+    let bits: [Bit; 8] = input.into();
+    Word {
+        low: input,
+        high: Byte::from([bits[7]; 8]),
+    }
+}
+
+impl Debug for Word {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#06X}", u16::from(*self))
+    }
+}
+
+impl From<u16> for Word {
+    fn from(value: u16) -> Self {
+        let [low, high] = value.to_le_bytes();
+        Self {
+            low: low.into(),
+            high: high.into(),
+        }
+    }
+}
+
+impl From<Word> for u16 {
+    fn from(value: Word) -> Self {
+        Self::from_le_bytes([value.low.into(), value.high.into()])
+    }
+}
+
+/// Joins the low and the high byte, in that order
+impl From<(Byte, Byte)> for Word {
+    fn from((low, high): (Byte, Byte)) -> Self {
+        Self { low, high }
+    }
+}
+
+impl From<Word> for (Byte, Byte) {
+    fn from(value: Word) -> Self {
+        (value.low, value.high)
+    }
+}
+
+impl Add for Word {
+    type Output = (Self, Bit);
+
+    /// Adds 2 words without carry bit
+    fn add(self, rhs: Self) -> Self::Output {
+        self.add_with_carry(rhs, Bit::Low)
+    }
+}
+
+impl Not for Word {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        Self {
+            low: !self.low,
+            high: !self.high,
+        }
+    }
+}
+
+/// Returns the bits of the word, bit 0 first
+fn bits(word: Word) -> [Bit; 16] {
+    let (low, high): ([Bit; 8], [Bit; 8]) = (word.low.into(), word.high.into());
+    array::from_fn(|i| if i < 8 { low[i] } else { high[i - 8] })
+}
+
+/// Joins the bits into a word, bit 0 first
+fn from_bits(bits: [Bit; 16]) -> Word {
+    Word {
+        low: Byte::from(array::from_fn(|i| bits[i])),
+        high: Byte::from(array::from_fn(|i| bits[i + 8])),
+    }
+}
+
+/// The width of the registers and the ALU of a [`Computer`](crate::Computer), [`Byte`] for the
+/// 8 bit machine and [`Word`] for the 16 bit one.
+///
+/// The address bus, the memory, the stack and the instruction set are 8 bits wide with either
+/// width, so programs are encoded the same way. On the 16 bit machine these instructions change:
+///
+/// - `ldi`, `ld` and the indirect `ld` load a byte into the low byte of the register and clear the
+///   high byte, `st` and the indirect `st` store the low byte
+/// - the logic and arithmetic instructions, `inc`, `dec`, the register shifts and rotates, `mul`,
+///   `div` and `rem` compute with all 16 bits. The overflow is the carry out of bit 15 or the bit
+///   shifted out, and `jz` tests all 16 bits. Dividing by 0 stores 0xFFFF as the quotient.
+/// - the zero flag tests all 16 bits of a result and the negative flag is bit 15
+/// - `push` stores the low and then the high byte and `pop` loads them back, so a register takes 2
+///   bytes of the stack
+/// - the address the indirect `ld` and `st` take from a register is its low byte
+///
+/// The jumps, calls, returns and the memory shifts and rotates work on bytes and don't change.
+/// The [`MachineState`](crate::state::MachineState), the initial registers, the
+/// [`Snapshot`](crate::Snapshot) and the [`TraceEvent`](crate::TraceEvent) hold bytes, so only the
+/// 8 bit machine reports its state and traces and takes snapshots, and the initial register values
+/// are zero extended.
+pub trait WordType: Copy + Debug {
+    /// The value of a register on the host, `u8` or `u16`
+    type Value: Copy + Eq + Debug + From<u8>;
+
+    /// The register file of [`REGISTER_COUNT`] registers of the width
+    type Registers: RegisterFile<Self>;
+
+    /// The number of bytes a push stores and a pop loads
+    const BYTES: usize;
+
+    /// Whether `DecimalAdjust` is an instruction of the machine
+    const DECIMAL_ADJUST: bool;
+
+    /// Extends the byte with the high bits cleared
+    fn zero_extend(byte: Byte) -> Self;
+
+    /// Returns the byte at the index, below [`WordType::BYTES`] with the low byte first
+    fn byte(self, index: usize) -> Byte;
+
+    /// Returns the value with the byte at the index replaced, see [`WordType::byte`]
+    fn with_byte(self, index: usize, byte: Byte) -> Self;
+
+    /// Returns the low byte, bits 0 to 7
+    fn low(self) -> Byte {
+        self.byte(0)
+    }
+
+    /// Returns the value on the host
+    fn value(self) -> Self::Value;
+
+    /// Returns the left value if `select` is `Bit::Low`, returns the right value otherwise
+    fn mux(left: Self, right: Self, select: Bit) -> Self;
+
+    /// The function of the ALU, see [`alu_op`](mux::byte::alu_op). Returns the result and the
+    /// carry out of the top bit for the arithmetic functions.
+    fn alu_op(left: Self, right: Self, carry: Bit, op: [Bit; 3]) -> (Self, Bit);
+
+    /// Returns `Bit::High` if all bits are low
+    fn is_zero(self) -> Bit;
+
+    /// Returns the top bit, `Bit::High` if the value is negative as a two's complement number
+    fn sign(self) -> Bit;
+
+    /// Adds one, returns the result and whether it wrapped to 0
+    fn increment(self) -> (Self, Bit);
+
+    /// Subtracts one, returns the result and whether it wrapped from 0
+    fn decrement(self) -> (Self, Bit);
+
+    /// Shifts or rotates by one bit, toward the top or toward the bottom if `right` is
+    /// `Bit::High`. A shift feeds in 0 and a rotate the bit moving out. Returns the result and the
+    /// bit moving out.
+    fn shift(self, right: Bit, rotate: Bit) -> (Self, Bit);
+
+    /// Multiplies, returns the low half of the product and `Bit::High` if the high half isn't 0
+    fn multiply_low(self, right: Self) -> (Self, Bit);
+
+    /// Divides by the divisor, returns the quotient and the remainder. Dividing by 0 sets every
+    /// bit of the quotient and leaves the value as the remainder.
+    fn divide(self, divisor: Self) -> (Self, Self);
+}
+
+/// The [`REGISTER_COUNT`] registers of a [`WordType`]. The loads and stores take the select bits
+/// of the register, bit 0 of its number first.
+pub trait RegisterFile<W: WordType>: Debug {
+    /// Creates registers with the bytes as their values, indexed by register number
+    fn from_bytes(bytes: &[u8; REGISTER_COUNT]) -> Self;
+
+    /// Loads the value of a register
+    fn load(&self, select: [Bit; 3]) -> W;
+
+    /// Stores the new value in a register
+    fn store(&mut self, select: [Bit; 3], value: W);
+
+    /// Returns the values of the registers, indexed by register number
+    fn values(&self) -> [W::Value; REGISTER_COUNT] {
+        Register::ALL.map(|register| self.load_reg(register).value())
+    }
+
+    /// Loads the value of the register, like [`RegisterFile::load`] with its select bits
+    fn load_reg(&self, register: Register) -> W {
+        self.load(register.into())
+    }
+
+    /// Stores the new value in the register, like [`RegisterFile::store`] with its select bits
+    fn store_reg(&mut self, register: Register, value: W) {
+        self.store(register.into(), value);
+    }
+}
+
+impl WordType for Byte {
+    type Value = u8;
+    type Registers = Registers;
+    const BYTES: usize = 1;
+    const DECIMAL_ADJUST: bool = true;
+
+    fn zero_extend(byte: Byte) -> Self {
+        byte
+    }
+
+    fn byte(self, _: usize) -> Byte {
+        self
+    }
+
+    fn with_byte(self, _: usize, byte: Byte) -> Self {
+        byte
+    }
+
+    fn value(self) -> u8 {
+        self.into()
+    }
+
+    fn mux(left: Self, right: Self, select: Bit) -> Self {
+        mux::byte::mux(left, right, select)
+    }
+
+    fn alu_op(left: Self, right: Self, carry: Bit, op: [Bit; 3]) -> (Self, Bit) {
+        mux::byte::alu_op(left, right, carry, op)
+    }
+
+    fn is_zero(self) -> Bit {
+        mux::byte::is_zero(self)
+    }
+
+    fn sign(self) -> Bit {
+        mux::byte::sign(self)
+    }
+
+    fn increment(self) -> (Self, Bit) {
+        mux::byte::increment(self)
+    }
+
+    fn decrement(self) -> (Self, Bit) {
+        mux::byte::decrement(self)
+    }
+
+    /// Moves the bits through the barrel shifters
+    fn shift(self, right: Bit, rotate: Bit) -> (Self, Bit) {
+        const ONE: [Bit; 3] = [Bit::High, Bit::Low, Bit::Low];
+        let bits: [Bit; 8] = self.into();
+        let left_value = mux::byte::mux(shift_left(self, ONE), rotate_left(self, ONE), rotate);
+        let right_value = mux::byte::mux(
+            shift_right_logical(self, ONE),
+            rotate_right(self, ONE),
+            rotate,
+        );
+        (
+            mux::byte::mux(left_value, right_value, right),
+            mux::bit::mux(bits[7], bits[0], right),
+        )
+    }
+
+    fn multiply_low(self, right: Self) -> (Self, Bit) {
+        mux::byte::multiply_low(self, right)
+    }
+
+    /// Runs the divider circuit, see [`divide`](mux::byte::divide)
+    fn divide(self, divisor: Self) -> (Self, Self) {
+        mux::byte::divide(self, divisor).unwrap_or_else(|| (Self::from(0xFF), self))
+    }
+}
+
+impl WordType for Word {
+    type Value = u16;
+    type Registers = WordRegisters;
+    const BYTES: usize = 2;
+    const DECIMAL_ADJUST: bool = false;
+
+    fn zero_extend(byte: Byte) -> Self {
+        Self {
+            low: byte,
+            high: Byte::from(0),
+        }
+    }
+
+    fn byte(self, index: usize) -> Byte {
+        if index == 0 {
+            self.low
+        } else {
+            self.high
+        }
+    }
+
+    fn with_byte(self, index: usize, byte: Byte) -> Self {
+        if index == 0 {
+            Self { low: byte, ..self }
+        } else {
+            Self { high: byte, ..self }
+        }
+    }
+
+    fn value(self) -> u16 {
+        self.into()
+    }
+
+    fn mux(left: Self, right: Self, select: Bit) -> Self {
+        mux::word::mux(left, right, select)
+    }
+
+    /// Two byte ALUs with the same function, the carry of the adder of the low one is the carry
+    /// of the adder of the high one
+    fn alu_op(left: Self, right: Self, carry: Bit, op: [Bit; 3]) -> (Self, Bit) {
+        let [subtract, ..] = op;
+        let (low, carry) = mux::byte::alu_op(left.low, right.low, carry, op);
+        // The ALU inverts the carry in of a subtraction, which the low one already did
+        let (high, carry) = mux::byte::alu_op(left.high, right.high, carry.xor(subtract), op);
+        (Self { low, high }, carry)
+    }
+
+    fn is_zero(self) -> Bit {
+        mux::byte::is_zero(self.low).and(mux::byte::is_zero(self.high))
+    }
+
+    fn sign(self) -> Bit {
+        mux::byte::sign(self.high)
+    }
+
+    fn increment(self) -> (Self, Bit) {
+        self.add_with_carry(Self::from(0), Bit::High)
+    }
+
+    fn decrement(self) -> (Self, Bit) {
+        self.sub_with_borrow(Self::from(0), Bit::High)
+    }
+
+    fn shift(self, right: Bit, rotate: Bit) -> (Self, Bit) {
+        let bits = bits(self);
+        let (top, bottom) = (bits[15], bits[0]);
+        let left_value = from_bits(array::from_fn(|i| {
+            if i == 0 {
+                top.and(rotate)
+            } else {
+                bits[i - 1]
+            }
+        }));
+        let right_value = from_bits(array::from_fn(|i| {
+            if i == 15 {
+                bottom.and(rotate)
+            } else {
+                bits[i + 1]
+            }
+        }));
+        (
+            mux::word::mux(left_value, right_value, right),
+            mux::bit::mux(top, bottom, right),
+        )
+    }
+
+    /// Adds the products of the bytes with the byte multipliers. The product of the high bytes
+    /// and the high bytes of the other products are all above bit 15.
+    fn multiply_low(self, right: Self) -> (Self, Bit) {
+        let (low, high) = multiply(self.low, right.low);
+        let (cross, cross_high) = multiply(self.low, right.high);
+        let (other, other_high) = multiply(self.high, right.low);
+        let (top, top_high) = multiply(self.high, right.high);
+        let (high, first) = high.add_with_carry(cross, Bit::Low);
+        let (high, second) = high.add_with_carry(other, Bit::Low);
+        let lost = [cross_high, other_high, top, top_high]
+            .into_iter()
+            .fold(first.or(second), |lost, byte| {
+                lost.or(mux::byte::is_zero(byte).not())
+            });
+        (Self { low, high }, lost)
+    }
+
+    /// A 16 step restoring division like [`divide`](mux::byte::divide), with the word
+    /// subtractor
+    fn divide(self, divisor: Self) -> (Self, Self) {
+        let dividend = bits(self);
+        let mut remainder = Self::zero_extend(Byte::from(0));
+        let mut quotient = [Bit::Low; 16];
+        for i in (0..16).rev() {
+            // The bit shifted out is the seventeenth bit of the remainder
+            let mut shifted = bits(remainder);
+            let out = shifted[15];
+            shifted.rotate_right(1);
+            shifted[0] = dividend[i];
+            remainder = from_bits(shifted);
+            let (difference, borrow) = remainder.sub_with_borrow(divisor, Bit::Low);
+            quotient[i] = out.or(borrow.not());
+            remainder = mux::word::mux(remainder, difference, quotient[i]);
+        }
+        (from_bits(quotient), remainder)
+    }
+}
+
+impl RegisterFile<Byte> for Registers {
+    fn from_bytes(bytes: &[u8; REGISTER_COUNT]) -> Self {
+        Self::from_bytes(bytes)
+    }
+
+    fn load(&self, select: [Bit; 3]) -> Byte {
+        self.load(select)
+    }
+
+    fn store(&mut self, select: [Bit; 3], value: Byte) {
+        self.store(select, value);
+    }
+}
+
+impl RegisterFile<Word> for WordRegisters {
+    fn from_bytes(bytes: &[u8; REGISTER_COUNT]) -> Self {
+        Self::from_values(bytes.map(u16::from))
+    }
+
+    fn load(&self, select: [Bit; 3]) -> Word {
+        self.load(select)
+    }
+
+    fn store(&mut self, select: [Bit; 3], value: Word) {
+        self.store(select, value);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use core::fmt::Write;
+
+    use heapless::String;
+
+    use super::{sign_extend, Word, WordType};
+    use crate::{bit::Bit, byte::Byte};
+
+    /// The values around the byte and word boundaries, and random values
+    pub fn values() -> impl Iterator<Item = u16> {
+        let boundaries = [
+            0, 1, 0x7F, 0x80, 0xFF, 0x100, 0x7FFF, 0x8000, 0xFFFE, 0xFFFF,
+        ];
+        let mut state = 0x1234_u16;
+        let random = (0..32).map(move |_| {
+            // A 16 bit xorshift
+            state ^= state << 7;
+            state ^= state >> 9;
+            state ^= state << 8;
+            state
+        });
+        boundaries.into_iter().chain(random)
+    }
+
+    #[test]
+    fn conversion() {
+        for value in values() {
+            let word = Word::from(value);
+            assert_eq!(u16::from(word), value);
+            assert_eq!(
+                [u8::from(word.low()), u8::from(word.high())],
+                value.to_le_bytes()
+            );
+            assert_eq!(Word::from(<(Byte, Byte)>::from(word)), word);
+        }
+        let mut text = String::<16>::new();
+        write!(text, "{:?}", Word::from(0x1A2B)).unwrap();
+        assert_eq!(text, "0x1A2B");
+    }
+
+    #[test]
+    fn arithmetic() {
+        for left in values() {
+            assert_eq!(u16::from(!Word::from(left)), !left);
+            for right in values() {
+                let (a, b) = (Word::from(left), Word::from(right));
+                for carry in [false, true] {
+                    let (sum, carry_out) = a.add_with_carry(b, Bit::from(carry));
+                    let (expected, wrapped) = left.carrying_add(right, carry);
+                    assert_eq!((u16::from(sum), carry_out), (expected, Bit::from(wrapped)));
+
+                    let (difference, borrow) = a.sub_with_borrow(b, Bit::from(carry));
+                    let (expected, wrapped) = left.borrowing_sub(right, carry);
+                    assert_eq!(
+                        (u16::from(difference), borrow),
+                        (expected, Bit::from(wrapped))
+                    );
+                }
+                assert_eq!((a + b).0, Word::from(left.wrapping_add(right)));
+            }
+        }
+    }
+
+    #[test]
+    fn sign_extension() {
+        for byte in 0..=u8::MAX {
+            let word = sign_extend(Byte::from(byte));
+            assert_eq!(u16::from(word).cast_signed(), i16::from(byte.cast_signed()));
+        }
+    }
+
+    #[test]
+    fn word_type_arithmetic() {
+        for left in values() {
+            for right in values() {
+                let (a, b) = (Word::from(left), Word::from(right));
+                for carry in [false, true] {
+                    let logic = [
+                        left & right,
+                        left | right,
+                        left ^ right,
+                        !(left & right),
+                        !(left | right),
+                        !(left ^ right),
+                    ];
+                    for (function, expected) in (0..).zip(logic) {
+                        let op = [0, 1, 2].map(|bit| Bit::from((function >> bit) & 1 == 1));
+                        let (result, carry_out) = Word::alu_op(a, b, Bit::from(carry), op);
+                        assert_eq!((u16::from(result), carry_out), (expected, Bit::Low));
+                    }
+                    let (sum, carried) = left.carrying_add(right, carry);
+                    let add = [Bit::Low, Bit::High, Bit::High];
+                    let (result, carry_out) = Word::alu_op(a, b, Bit::from(carry), add);
+                    assert_eq!((u16::from(result), carry_out), (sum, Bit::from(carried)));
+                    let (difference, borrowed) = left.borrowing_sub(right, carry);
+                    let sub = [Bit::High; 3];
+                    let (result, carry_out) = Word::alu_op(a, b, Bit::from(carry), sub);
+                    assert_eq!(
+                        (u16::from(result), carry_out),
+                        (difference, Bit::from(!borrowed))
+                    );
+                }
+                let division = right
+                    .checked_div(left)
+                    .zip(right.checked_rem(left))
+                    .unwrap_or((u16::MAX, right));
+                let (quotient, remainder) = b.divide(a);
+                assert_eq!((u16::from(quotient), u16::from(remainder)), division);
+                let (product, lost) = a.multiply_low(b);
+                let (expected, overflowed) = left.overflowing_mul(right);
+                assert_eq!(
+                    (u16::from(product), lost),
+                    (expected, Bit::from(overflowed))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn word_type_shifts() {
+        for value in values() {
+            let word = Word::from(value);
+            assert_eq!(word.is_zero(), Bit::from(value == 0));
+            assert_eq!(word.sign(), Bit::from(value >= 0x8000));
+            let (incremented, wrapped) = word.increment();
+            assert_eq!(
+                (u16::from(incremented), wrapped),
+                (value.wrapping_add(1), Bit::from(value == u16::MAX))
+            );
+            let (decremented, wrapped) = word.decrement();
+            assert_eq!(
+                (u16::from(decremented), wrapped),
+                (value.wrapping_sub(1), Bit::from(value == 0))
+            );
+            for rotate in [false, true] {
+                let (top, bottom) = (value >> 15, value & 1);
+                let (shifted, out) = word.shift(Bit::Low, Bit::from(rotate));
+                let expected = value << 1 | if rotate { top } else { 0 };
+                assert_eq!((u16::from(shifted), out), (expected, Bit::from(top == 1)));
+                let (shifted, out) = word.shift(Bit::High, Bit::from(rotate));
+                let expected = value >> 1 | if rotate { bottom << 15 } else { 0 };
+                assert_eq!(
+                    (u16::from(shifted), out),
+                    (expected, Bit::from(bottom == 1))
+                );
+            }
+            // The byte shifts match the low byte of the word
+            let byte = Byte::from(value.to_le_bytes()[0]);
+            let (shifted, _) = byte.shift(Bit::High, Bit::Low);
+            assert_eq!(u8::from(shifted), value.to_le_bytes()[0] >> 1);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+#![no_std]
+#![warn(
+    clippy::pedantic,
+    clippy::nursery,
+    clippy::missing_const_for_fn,
+    missing_docs
+)]
+
+//! This library runs programs built from assembler instructions on the computer.
+//!
+//! The instructions are encoded with [`Instruction::encode_iter`], the same encoding the text
+//! assembler emits and the computer decodes, so there is no second table of opcodes:
+//!
+//! ```
+//! use assembler::{Instruction, Register};
+//! use emulator::run_program;
+//!
+//! let mut output = String::new();
+//! let program = [
+//!     Instruction::LoadConstant(Register::R0, b'!'),
+//!     Instruction::StoreMemory(Register::R0, 255),
+//!     Instruction::Halt,
+//! ];
+//! let state = run_program(program, &mut output).unwrap();
+//! assert_eq!(output, "!");
+//! assert_eq!(state.registers, [b'!', 0, 0, 0, 0, 0, 0, 0]);
+//! ```
+
+use core::fmt::Write;
+
+use assembler::Instruction;
+use michael_computer::{alu_with_state, state::MachineState, ExecutionError};
+
+/// Encodes the instructions and runs them like [`michael_computer::alu`] without input, returning
+/// the state of the machine once it stopped.
+///
+/// # Errors
+/// Returns an error if the program ended unexpectedly, writing the output failed, or the program
+/// is larger than 256 bytes
+pub fn run_program(
+    instructions: impl IntoIterator<Item = Instruction>,
+    out: &mut impl Write,
+) -> Result<MachineState, ExecutionError> {
+    alu_with_state(Instruction::encode_iter(instructions), [], out)
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::String;
+
+    use assembler::{Instruction, Register};
+    use michael_computer::ExecutionError;
+
+    use super::run_program;
+
+    #[test]
+    fn hello_world() {
+        let mut output = String::<20>::new();
+        let expected = b"Hello, world!";
+        let program = expected.iter().flat_map(|&character| {
+            [
+                Instruction::LoadConstant(Register::R0, character),
+                Instruction::StoreMemory(Register::R0, 255),
+            ]
+        });
+        let state = run_program(program, &mut output).unwrap();
+        assert_eq!(output.as_bytes(), expected);
+        assert_eq!(state.registers, [b'!', 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(state.memory[..4], [0x00, b'H', 0x08, 0xFF]);
+    }
+
+    #[test]
+    fn extended_registers() {
+        let mut output = String::<4>::new();
+        let program = [
+            Instruction::LoadConstant(Register::R7, b'A'),
+            Instruction::LoadConstant(Register::R4, 2),
+            Instruction::Add(Register::R7, Register::R4),
+            Instruction::StoreMemory(Register::R7, 255),
+            Instruction::Move(Register::R1, Register::R7),
+            Instruction::Halt,
+        ];
+        let state = run_program(program, &mut output).unwrap();
+        assert_eq!(output, "C");
+        assert_eq!(state.registers, [0, b'C', 0, 0, 2, 0, 0, b'C']);
+    }
+
+    #[test]
+    fn errors() {
+        let mut output = String::<4>::new();
+        assert_eq!(
+            run_program([Instruction::Return], &mut output),
+            Err(ExecutionError::StackUnderflow { offset: 0 })
+        );
+        let program = [Instruction::LoadConstant(Register::R0, 0); 129];
+        assert_eq!(
+            run_program(program, &mut output),
+            Err(ExecutionError::ProgramTooLarge)
+        );
+    }
+}